@@ -0,0 +1,101 @@
+use glam::*;
+use crate::cloth::Constraint;
+
+// How many frames an edge stays in the alarm color after it crosses a bound.
+const FLASH_FRAMES : u32 = 20;
+
+// One constraint crossing a strain bound.
+pub struct AlarmEvent
+{
+    pub step : i32,
+    pub constraint : usize,
+    pub strain : f32,
+}
+
+// Watches the strain of every distance constraint, its length over its rest length less one,
+// and raises an alarm on the step one leaves [min_strain, max_strain]. A constraint that stays
+// outside only counts again after it has come back in.
+pub struct StrainAlarm
+{
+    pub enabled : bool,
+    pub min_strain : f32,
+    pub max_strain : f32,
+    pub pause_on_alarm : bool,
+    pub count : u32,
+    // Frames left to flash, and whether it was out of bounds at the last check, per constraint.
+    flash : Vec<u32>,
+    outside : Vec<bool>,
+    events : Vec<AlarmEvent>,
+}
+
+impl StrainAlarm {
+    pub fn new() -> StrainAlarm
+    {
+        StrainAlarm {
+            enabled : false,
+            min_strain : -0.1,
+            max_strain : 0.1,
+            pause_on_alarm : false,
+            count : 0,
+            flash : vec![],
+            outside : vec![],
+            events : vec![],
+        }
+    }
+
+    // Constraint indices don't survive a rebuild, so the events logged against them go too.
+    pub fn reset(&mut self, num_constraints : usize)
+    {
+        self.count = 0;
+        self.flash = vec![0; num_constraints];
+        self.outside = vec![false; num_constraints];
+        self.events.clear();
+    }
+
+    // Checks every constraint after a step. Returns whether any crossed a bound.
+    pub fn check(&mut self, step : i32, positions : &[Vec3], constraints : &[Constraint]) -> bool
+    {
+        if !self.enabled {
+            return false;
+        }
+        if self.flash.len() != constraints.len() {
+            self.reset(constraints.len());
+        }
+
+        let mut tripped = false;
+        for (i, c) in constraints.iter().enumerate() {
+            let strain = (positions[c.p0] - positions[c.p1]).length() / c.length - 1.0;
+            let outside = strain < self.min_strain || strain > self.max_strain;
+            if outside && !self.outside[i] {
+                self.flash[i] = FLASH_FRAMES;
+                self.count += 1;
+                self.events.push(AlarmEvent { step : step, constraint : i, strain : strain });
+                tripped = true;
+            }
+            self.outside[i] = outside;
+        }
+        tripped
+    }
+
+    // Counts down the flashes, once per drawn frame.
+    pub fn fade(&mut self)
+    {
+        self.flash.iter_mut().for_each(|f| *f = f.saturating_sub(1));
+    }
+
+    pub fn flashing(&self) -> Vec<usize>
+    {
+        (0..self.flash.len()).filter(|&i| self.flash[i] > 0).collect()
+    }
+
+    // The events as CSV, with the bounds and the settings they were raised under as leading comments.
+    pub fn to_csv(&self, settings : &str) -> String
+    {
+        let mut csv = format!("# min_strain {}, max_strain {}\n# {}\n", self.min_strain, self.max_strain, settings);
+        csv.push_str("step,constraint,strain\n");
+        for e in self.events.iter() {
+            csv.push_str(&format!("{},{},{}\n", e.step, e.constraint, e.strain));
+        }
+        csv
+    }
+}
@@ -3,8 +3,11 @@ precision mediump float;
 
 attribute vec2 a_position;
 uniform float u_aspect_ratio;
+uniform vec2 u_view_center;
+uniform float u_view_scale;
 
 void main() {
     gl_PointSize = 5.0;
-    gl_Position = vec4( a_position.x / u_aspect_ratio, a_position.y, 0.0, 1.0);
+    vec2 p = (a_position - u_view_center) * u_view_scale;
+    gl_Position = vec4( p.x / u_aspect_ratio, p.y, 0.0, 1.0);
 }
@@ -0,0 +1,192 @@
+use glam::*;
+use web_sys::Performance;
+use crate::cloth::{build_cloth, BendModel, ClothBuild, Connectivity, Scene};
+use crate::params::{Params, ParamsDelta};
+use crate::rail::PinMode;
+use crate::solver::{self, ClothState, Scratch, Solver, SolverParams};
+
+// Starting settings picked to suit the device, from a benchmark on the first load.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum QualityTier
+{
+    Low,
+    Medium,
+    High,
+}
+
+pub const QUALITY_TIERS : [QualityTier; 3] = [QualityTier::Low, QualityTier::Medium, QualityTier::High];
+
+impl QualityTier {
+    pub fn name(&self) -> &'static str
+    {
+        match self {
+            QualityTier::Low => "Low",
+            QualityTier::Medium => "Medium",
+            QualityTier::High => "High",
+        }
+    }
+
+    // Medium is the settings the page has always started with.
+    pub fn delta(&self) -> ParamsDelta
+    {
+        let (size, iterations) = match self {
+            QualityTier::Low => (8, 2),
+            QualityTier::Medium => (10, 2),
+            QualityTier::High => (24, 4),
+        };
+        ParamsDelta {
+            num_iterations : Some(iterations),
+            num_particles_x : Some(size),
+            num_particles_y : Some(size),
+            ..Default::default()
+        }
+    }
+
+    // The tier for a measured cost in nanoseconds per constraint per iteration.
+    pub fn for_cost(ns_per_constraint : f64) -> QualityTier
+    {
+        if ns_per_constraint < HIGH_TIER_MAX_NS {
+            QualityTier::High
+        } else if ns_per_constraint < MEDIUM_TIER_MAX_NS {
+            QualityTier::Medium
+        } else {
+            QualityTier::Low
+        }
+    }
+}
+
+const HIGH_TIER_MAX_NS : f64 = 40.0;
+const MEDIUM_TIER_MAX_NS : f64 = 150.0;
+
+const STORAGE_KEY : &str = "warmstart.quality_tier";
+
+fn storage() -> Option<web_sys::Storage>
+{
+    web_sys::window()?.local_storage().ok()?
+}
+
+// The tier picked or chosen in an earlier session. None means the benchmark hasn't run yet.
+pub fn saved_tier() -> Option<QualityTier>
+{
+    let name = storage()?.get_item(STORAGE_KEY).ok()??;
+    QUALITY_TIERS.iter().copied().find(|t| t.name() == name)
+}
+
+pub fn save_tier(tier : QualityTier)
+{
+    if let Some(s) = storage() {
+        let _ = s.set_item(STORAGE_KEY, tier.name());
+    }
+}
+
+const GRID_SIZE : i32 = 30;
+const ITERATIONS_PER_SOLVE : i32 = 10;
+const NUM_CHUNKS : usize = 2;
+// Each chunk stops at whichever of these it reaches first.
+const CHUNK_MS : f64 = 25.0;
+const CHUNK_ITERATIONS : i32 = 150;
+
+// Times the real solver on a scratch cloth, a chunk per frame so the first frames stay responsive.
+pub struct Benchmark
+{
+    cloth : ClothBuild,
+    previous_positions : Vec<Vec3>,
+    pin_modes : Vec<PinMode>,
+    inverse_masses : Vec<f32>,
+    solver : Box<dyn Solver>,
+    scratch : Scratch,
+    params : SolverParams,
+    elapsed_ms : f64,
+    constraint_iterations : f64,
+    num_chunks_run : usize,
+}
+
+impl Benchmark {
+    // Uses the solver and settings the page starts with, on a larger grid than the default.
+    pub fn new(params : &Params) -> Benchmark
+    {
+        let cloth = build_cloth(&Scene::Hanging, Connectivity::Eight, false, GRID_SIZE, GRID_SIZE);
+        let mut scratch = Scratch::default();
+        scratch.resize(cloth.positions.len());
+        let mut inverse_masses = vec![];
+        solver::fill_inverse_masses(&mut inverse_masses, &cloth.is_fixed, &[], &[]);
+        Benchmark {
+            previous_positions : cloth.positions.clone(),
+            inverse_masses : inverse_masses,
+            pin_modes : cloth.is_fixed.iter().map(|&fixed| if fixed {PinMode::Fixed} else {PinMode::Free}).collect(),
+            cloth : cloth,
+            solver : solver::registry().swap_remove(params.solver_index),
+            scratch : scratch,
+            params : SolverParams {
+                dt : params.dt,
+                num_iterations : ITERATIONS_PER_SOLVE,
+                sheet_iterations : vec![ITERATIONS_PER_SOLVE],
+                sheet_warm_start : vec![params.warm_start],
+                warm_start : params.warm_start,
+                eta : params.eta,
+                adaptive_eta : params.adaptive_eta,
+                lambda_limit : None,
+                stiffness : params.stiffness,
+                tension_only : params.tension_only,
+                use_area_constraints : false,
+                area_stiffness : params.area_stiffness,
+                bend_model : BendModel::None,
+                bend_stiffness : params.bend_stiffness,
+                pin_stiffness : params.pin_stiffness,
+            },
+            elapsed_ms : 0.0,
+            constraint_iterations : 0.0,
+            num_chunks_run : 0,
+        }
+    }
+
+    pub fn is_done(&self) -> bool
+    {
+        self.num_chunks_run >= NUM_CHUNKS
+    }
+
+    pub fn run_chunk(&mut self, performance : &Performance)
+    {
+        let start = performance.now();
+        let mut iterations = 0;
+        while iterations < CHUNK_ITERATIONS && performance.now() - start < CHUNK_MS {
+            // Pull the cloth down a little each solve so the constraints have work to do.
+            for (p, &fixed) in self.cloth.positions.iter_mut().zip(self.cloth.is_fixed.iter()) {
+                if !fixed {
+                    p.y -= 1e-3;
+                }
+            }
+            let mut state = ClothState {
+                positions : &mut self.cloth.positions,
+                previous_positions : &mut self.previous_positions,
+                is_fixed : &self.cloth.is_fixed,
+                inverse_masses : &self.inverse_masses,
+                pin_modes : &self.pin_modes,
+                curves : &[],
+                sheet_of : &self.cloth.sheet_of,
+                constraints : &mut self.cloth.constraints,
+                active_constraints : None,
+                area_constraints : &mut [],
+                bend_constraints : &mut [],
+                dihedral_constraints : &mut [],
+                contacts : &mut [],
+                contact_distance : 0.0,
+                collider_contacts : &mut [],
+                colliders : None,
+                weight : None,
+                anchors : &mut [],
+                observers : &mut [],
+            };
+            self.solver.solve(&mut state, &self.params, &mut self.scratch);
+            iterations += ITERATIONS_PER_SOLVE;
+        }
+        self.elapsed_ms += performance.now() - start;
+        self.constraint_iterations += (iterations as usize * self.cloth.constraints.len()) as f64;
+        self.num_chunks_run += 1;
+    }
+
+    pub fn ns_per_constraint(&self) -> f64
+    {
+        self.elapsed_ms * 1e6 / self.constraint_iterations.max(1.0)
+    }
+}
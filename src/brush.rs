@@ -0,0 +1,59 @@
+use glam::*;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum BrushMode
+{
+    Attract,
+    Repel,
+}
+
+// A radial force around the cursor, added to gravity during integration for every particle within
+// the radius. It adds no constraints, so it works the same whatever the solver.
+pub struct ForceBrush
+{
+    pub mode : BrushMode,
+    // Acceleration at the centre as a multiple of gravity's.
+    pub strength : f32,
+    pub radius : f32,
+    // Set while ctrl-dragging.
+    pub active : bool,
+}
+
+impl ForceBrush {
+    pub fn new() -> ForceBrush
+    {
+        ForceBrush {
+            mode : BrushMode::Attract,
+            strength : 3.0,
+            radius : 0.15,
+            active : false,
+        }
+    }
+
+    // The acceleration of a particle at p from the brush at centre, in the plane of the view. It
+    // falls off along a smoothstep to nothing at the rim.
+    pub fn acceleration(&self, centre : Vec2, p : Vec3) -> Vec3
+    {
+        let offset = centre - vec2(p.x, p.y);
+        let distance = offset.length();
+        if distance >= self.radius || distance < 1e-6 {
+            return vec3(0.0, 0.0, 0.0);
+        }
+        let t = 1.0 - distance / self.radius;
+        let falloff = t * t * (3.0 - 2.0 * t);
+        let direction = match self.mode {
+            BrushMode::Attract => offset / distance,
+            BrushMode::Repel => -offset / distance,
+        };
+        vec3(direction.x, direction.y, 0.0) * self.strength * falloff
+    }
+
+    // The rim as a flat list of x, y pairs ready to upload for GL::LINE_LOOP.
+    pub fn outline(&self, centre : Vec2, num_segments : usize) -> Vec<f32>
+    {
+        (0..num_segments).flat_map(|k| {
+            let angle = k as f32 / num_segments as f32 * std::f32::consts::PI * 2.0;
+            vec![centre.x + self.radius * angle.cos(), centre.y + self.radius * angle.sin()]
+        }).collect()
+    }
+}
@@ -0,0 +1,338 @@
+use web_sys::{WebGlUniformLocation, WebGlRenderingContext as GL};
+use crate::color_scale::{ColorScales, ScaleUse};
+use crate::gpu_buffers::GpuBuffers;
+use crate::palette::Palette;
+
+// Scrolling plots of values taken once a step, drawn over the canvas in a corner of their own.
+// Each series keeps a fixed number of the latest values, so a chart's memory doesn't grow however
+// long it runs.
+
+// Values below this are left out of a log scale, which has nowhere to put zero.
+const LOG_FLOOR : f32 = 1e-12;
+
+// Values kept per series, ten seconds of steps at the default time step.
+pub const DEFAULT_CAPACITY : usize = 600;
+
+// A chart's size on the canvas in pixels, and its distance from the canvas edges.
+pub const CHART_WIDTH : i32 = 240;
+pub const CHART_HEIGHT : i32 = 100;
+pub const CHART_MARGIN : i32 = 10;
+
+// The latest values pushed, up to a fixed capacity, oldest first.
+pub struct RingBuffer
+{
+    values : Vec<f32>,
+    capacity : usize,
+    // Where the next value goes once the buffer is full.
+    next : usize,
+}
+
+impl RingBuffer {
+    pub fn new(capacity : usize) -> RingBuffer
+    {
+        RingBuffer { values : Vec::with_capacity(capacity), capacity : capacity.max(1), next : 0 }
+    }
+
+    pub fn push(&mut self, value : f32)
+    {
+        if self.values.len() < self.capacity {
+            self.values.push(value);
+        } else {
+            self.values[self.next] = value;
+            self.next = (self.next + 1) % self.capacity;
+        }
+    }
+
+    pub fn len(&self) -> usize
+    {
+        self.values.len()
+    }
+
+    pub fn clear(&mut self)
+    {
+        self.values.clear();
+        self.next = 0;
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = f32> + '_
+    {
+        self.values[self.next..].iter().chain(self.values[..self.next].iter()).cloned()
+    }
+
+    pub fn latest(&self) -> Option<f32>
+    {
+        match self.values.len() {
+            0 => None,
+            n if n < self.capacity => Some(self.values[n - 1]),
+            _ => Some(self.values[(self.next + self.capacity - 1) % self.capacity]),
+        }
+    }
+}
+
+// Where a chart is drawn, in canvas pixels from the top left like the DOM's.
+#[derive(Clone, Copy)]
+pub struct ChartRect
+{
+    pub x : i32,
+    pub y : i32,
+    pub width : i32,
+    pub height : i32,
+}
+
+// The bottom of a chart's vertical range and its height, on the chart's scale.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChartRange
+{
+    pub min : f32,
+    pub span : f32,
+}
+
+pub struct Series
+{
+    pub name : &'static str,
+    pub values : RingBuffer,
+}
+
+pub struct TimeSeriesChart
+{
+    // Names its GL buffers in the registry.
+    pub id : &'static str,
+    pub title : &'static str,
+    pub series : Vec<Series>,
+    pub log_scale : bool,
+}
+
+impl TimeSeriesChart {
+    pub fn new(id : &'static str, title : &'static str, names : &[&'static str], capacity : usize, log_scale : bool) -> TimeSeriesChart
+    {
+        TimeSeriesChart {
+            id : id,
+            title : title,
+            series : names.iter().map(|&name| Series { name : name, values : RingBuffer::new(capacity) }).collect(),
+            log_scale : log_scale,
+        }
+    }
+
+    // One value per series, in the order they were named.
+    pub fn push(&mut self, values : &[f32])
+    {
+        for (series, &value) in self.series.iter_mut().zip(values.iter()) {
+            series.values.push(value);
+        }
+    }
+
+    pub fn clear(&mut self)
+    {
+        for series in self.series.iter_mut() {
+            series.values.clear();
+        }
+    }
+
+    // A value where the chart's scale puts it, or None for values a log scale can't show.
+    fn scaled(&self, value : f32) -> Option<f32>
+    {
+        match (self.log_scale, value.is_finite()) {
+            (_, false) => None,
+            (true, _) if value < LOG_FLOOR => None,
+            (true, _) => Some(value.log10()),
+            (false, _) => Some(value),
+        }
+    }
+
+    // The smallest and largest of every series' values, on the chart's scale. A flat line gets a
+    // little room either side so it sits in the middle.
+    pub fn range(&self) -> Option<ChartRange>
+    {
+        let (min, max) = self.series.iter()
+            .flat_map(|s| s.values.iter())
+            .filter_map(|v| self.scaled(v))
+            .fold((f32::MAX, f32::MIN), |(min, max), v| (min.min(v), max.max(v)));
+        if min > max {
+            return None;
+        }
+        let span = max - min;
+        if span > 1e-6 * max.abs().max(min.abs()).max(1e-30) {
+            Some(ChartRange { min : min, span : span })
+        } else {
+            let pad = if self.log_scale {0.5} else {(0.1 * min.abs()).max(1e-6)};
+            Some(ChartRange { min : min - pad, span : 2.0 * pad })
+        }
+    }
+
+    // A series as a strip in clip space, newest at the right edge. Capacity spans the width, so
+    // the line scrolls left as values come in.
+    pub fn line_strip(&self, series : &Series, range : ChartRange) -> Vec<f32>
+    {
+        let capacity = series.values.capacity;
+        let offset = capacity - series.values.len();
+        let mut strip = Vec::with_capacity(2 * series.values.len());
+        for (k, v) in series.values.iter().enumerate() {
+            if let Some(v) = self.scaled(v) {
+                strip.push(-1.0 + 2.0 * (offset + k) as f32 / (capacity - 1).max(1) as f32);
+                strip.push(-0.9 + 1.8 * (v - range.min) / range.span);
+            }
+        }
+        strip
+    }
+
+    // The latest value of every series with the color it is drawn in.
+    pub fn labels(&self, palette : &Palette, scales : &ColorScales) -> Vec<(String, [f32; 3])>
+    {
+        self.series.iter().enumerate().filter_map(|(k, s)| {
+            s.values.latest().map(|v| (format!("{} {:.3e}", s.name, v), series_color(palette, scales, k, self.series.len())))
+        }).collect()
+    }
+
+    // Draws into rect with the line program already in use, leaving the viewport and the view
+    // uniforms set for the chart. The caller puts them back once its charts are drawn.
+    pub fn render(&self, gl : &GL, buffers : &mut GpuBuffers, uniforms : &ChartUniforms, position : u32, rect : ChartRect, canvas_height : i32, palette : &Palette, scales : &ColorScales)
+    {
+        let bottom = canvas_height - rect.y - rect.height;
+        gl.viewport(rect.x, bottom, rect.width, rect.height);
+        gl.enable(GL::SCISSOR_TEST);
+        gl.scissor(rect.x, bottom, rect.width, rect.height);
+        gl.clear_color(palette.clear[0], palette.clear[1], palette.clear[2], palette.clear[3]);
+        gl.clear(GL::COLOR_BUFFER_BIT);
+
+        gl.uniform1f(uniforms.aspect_ratio.as_ref(), 1.0);
+        gl.uniform2f(uniforms.view_center.as_ref(), 0.0, 0.0);
+        gl.uniform1f(uniforms.view_scale.as_ref(), 1.0);
+
+        let mut draw = |name : String, vertices : &[f32], mode : u32, color : [f32; 3]| {
+            let array = js_sys::Float32Array::from(vertices);
+            let buffer = buffers.get_or_create(gl, &name, vertices.len() * 4);
+            gl.bind_buffer(GL::ARRAY_BUFFER, Some(&buffer));
+            gl.buffer_data_with_array_buffer_view(GL::ARRAY_BUFFER, &array, GL::STATIC_DRAW);
+            gl.vertex_attrib_pointer_with_i32(position, 2, GL::FLOAT, false, 0, 0);
+            gl.uniform3f(uniforms.color.as_ref(), color[0], color[1], color[2]);
+            gl.draw_arrays(mode, 0, vertices.len() as i32 / 2);
+        };
+
+        draw(format!("chart_{}_frame", self.id), &[-0.995, -0.99, 0.995, -0.99, 0.995, 0.99, -0.995, 0.99], GL::LINE_LOOP, palette.ramp[1]);
+        if let Some(range) = self.range() {
+            for (k, series) in self.series.iter().enumerate() {
+                let strip = self.line_strip(series, range);
+                if strip.len() >= 4 {
+                    draw(format!("chart_{}_{}", self.id, k), &strip, GL::LINE_STRIP, series_color(palette, scales, k, self.series.len()));
+                }
+            }
+        }
+        gl.disable(GL::SCISSOR_TEST);
+    }
+}
+
+// The line program's uniforms a chart sets.
+pub struct ChartUniforms
+{
+    pub aspect_ratio : Option<WebGlUniformLocation>,
+    pub view_center : Option<WebGlUniformLocation>,
+    pub view_scale : Option<WebGlUniformLocation>,
+    pub color : Option<WebGlUniformLocation>,
+}
+
+// Series colors spread along the charts' color scale, which is the palette's ramp unless edited.
+pub fn series_color(palette : &Palette, scales : &ColorScales, k : usize, num_series : usize) -> [f32; 3]
+{
+    scales.color(ScaleUse::Charts, palette, k as f32 / (num_series.max(2) - 1) as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contents(buffer : &RingBuffer) -> Vec<f32>
+    {
+        buffer.iter().collect()
+    }
+
+    #[test]
+    fn ring_buffer_keeps_the_latest_oldest_first()
+    {
+        let mut buffer = RingBuffer::new(3);
+        assert_eq!(buffer.latest(), None);
+        buffer.push(1.0);
+        buffer.push(2.0);
+        assert_eq!(contents(&buffer), vec![1.0, 2.0]);
+        assert_eq!(buffer.latest(), Some(2.0));
+        for v in 3..=7 {
+            buffer.push(v as f32);
+            assert_eq!(buffer.latest(), Some(v as f32));
+        }
+        assert_eq!(buffer.len(), 3);
+        assert_eq!(contents(&buffer), vec![5.0, 6.0, 7.0]);
+    }
+
+    #[test]
+    fn ring_buffer_refills_after_a_clear()
+    {
+        let mut buffer = RingBuffer::new(2);
+        for v in 0..5 {
+            buffer.push(v as f32);
+        }
+        buffer.clear();
+        assert_eq!(buffer.len(), 0);
+        assert_eq!(buffer.latest(), None);
+        for v in 10..13 {
+            buffer.push(v as f32);
+        }
+        assert_eq!(contents(&buffer), vec![11.0, 12.0]);
+    }
+
+    #[test]
+    fn zero_capacity_holds_one()
+    {
+        let mut buffer = RingBuffer::new(0);
+        buffer.push(1.0);
+        buffer.push(2.0);
+        assert_eq!(contents(&buffer), vec![2.0]);
+    }
+
+    fn chart(log_scale : bool, a : &[f32], b : &[f32]) -> TimeSeriesChart
+    {
+        let mut chart = TimeSeriesChart::new("test", "Test", &["a", "b"], 8, log_scale);
+        for (&x, &y) in a.iter().zip(b.iter()) {
+            chart.push(&[x, y]);
+        }
+        chart
+    }
+
+    #[test]
+    fn linear_range_covers_every_series()
+    {
+        let chart = chart(false, &[1.0, 4.0, 2.0], &[-3.0, 0.0, 5.0]);
+        assert_eq!(chart.range(), Some(ChartRange { min : -3.0, span : 8.0 }));
+    }
+
+    #[test]
+    fn log_range_skips_what_it_cant_show()
+    {
+        let chart = chart(true, &[1e-3, 0.0, 10.0], &[-1.0, f32::NAN, 1e-1]);
+        let range = chart.range().unwrap();
+        assert!((range.min + 3.0).abs() < 1e-5 && (range.span - 4.0).abs() < 1e-5, "{:?}", range);
+    }
+
+    #[test]
+    fn flat_lines_get_room_either_side()
+    {
+        let range = chart(false, &[2.0, 2.0], &[2.0, 2.0]).range().unwrap();
+        assert!(range.min < 2.0 && range.min + range.span > 2.0);
+        assert!((range.min + range.span / 2.0 - 2.0).abs() < 1e-6);
+        let range = chart(true, &[100.0], &[100.0]).range().unwrap();
+        assert_eq!(range, ChartRange { min : 1.5, span : 1.0 });
+        assert_eq!(chart(false, &[], &[]).range(), None);
+        assert_eq!(chart(true, &[0.0], &[-1.0]).range(), None);
+    }
+
+    #[test]
+    fn line_strip_ends_at_the_right_edge_within_the_range()
+    {
+        let chart = chart(false, &[0.0, 5.0, 10.0], &[0.0, 0.0, 0.0]);
+        let range = chart.range().unwrap();
+        let strip = chart.line_strip(&chart.series[0], range);
+        assert_eq!(strip.len(), 6);
+        // Three of eight values, so the line starts five slots in from the left.
+        assert!((strip[0] - (-1.0 + 2.0 * 5.0 / 7.0)).abs() < 1e-6);
+        assert!((strip[4] - 1.0).abs() < 1e-6);
+        assert!((strip[1] + 0.9).abs() < 1e-6 && (strip[5] - 0.9).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,1028 @@
+use glam::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use crate::obj::ObjMesh;
+
+#[derive(Clone)]
+pub struct Constraint
+{
+    pub p0 : usize,
+    pub p1 : usize,
+    pub length: f32,
+    pub lambda : Vec3,
+    // Multiplies the global stiffness, for the stiffened perimeter.
+    pub stiffness_scale : f32,
+    // Closes a wrapped sheet across its seam. Solved like any other, but it would cut across the
+    // whole sheet in a view that doesn't show depth.
+    pub wraps : bool,
+}
+
+impl Constraint {
+    pub fn new(p0 : usize, p1 : usize, positions : &[Vec3]) -> Constraint
+    {
+        Constraint {
+            p0 : p0,
+            p1 : p1,
+            length : (positions[p0] - positions[p1]).length(),
+            lambda : vec3(0.0,0.0,0.0),
+            stiffness_scale : 1.0,
+            wraps : false,
+        }
+    }
+
+    fn wrapping(p0 : usize, p1 : usize, positions : &[Vec3]) -> Constraint
+    {
+        Constraint { wraps : true, ..Constraint::new(p0, p1, positions) }
+    }
+}
+
+// Keeps the area of one grid quad, measured as half the 2D cross product of its diagonals
+// projected onto the XY plane, so quads can't shear down into slivers. Unlike the distance
+// constraints it involves four particles and has a scalar lambda.
+pub struct AreaConstraint
+{
+    pub particles : [usize; 4],
+    pub area : f32,
+    pub lambda : f32,
+}
+
+impl AreaConstraint {
+    pub fn new(particles : [usize; 4], positions : &[Vec3]) -> AreaConstraint
+    {
+        let mut c = AreaConstraint {
+            particles : particles,
+            area : 0.0,
+            lambda : 0.0,
+        };
+        c.area = c.current_area(positions);
+        c
+    }
+
+    fn diagonals(&self, positions : &[Vec3]) -> (Vec2, Vec2)
+    {
+        let [a, b, c, d] = self.particles;
+        let d0 = positions[c] - positions[a];
+        let d1 = positions[d] - positions[b];
+        (vec2(d0.x, d0.y), vec2(d1.x, d1.y))
+    }
+
+    pub fn current_area(&self, positions : &[Vec3]) -> f32
+    {
+        let (d0, d1) = self.diagonals(positions);
+        0.5 * (d0.x * d1.y - d0.y * d1.x)
+    }
+
+    // Gradient of the area with respect to each of the four particles, in the same order.
+    pub fn gradients(&self, positions : &[Vec3]) -> [Vec3; 4]
+    {
+        let (d0, d1) = self.diagonals(positions);
+        let g0 = 0.5 * vec3(d1.y, -d1.x, 0.0);
+        let g1 = 0.5 * vec3(-d0.y, d0.x, 0.0);
+        [-g0, -g1, g0, g1]
+    }
+}
+
+// Keeps the angle between the two triangles that share an interior edge, so bending resistance
+// doesn't depend on how far apart the particles either side of the fold are. particles[0] and
+// particles[1] are the shared edge, particles[2] and particles[3] the far corners of each triangle.
+pub struct DihedralConstraint
+{
+    pub particles : [usize; 4],
+    pub angle : f32,
+    pub lambda : f32,
+}
+
+impl DihedralConstraint {
+    pub fn new(particles : [usize; 4], positions : &[Vec3]) -> DihedralConstraint
+    {
+        let mut c = DihedralConstraint {
+            particles : particles,
+            angle : 0.0,
+            lambda : 0.0,
+        };
+        // A constraint that starts out degenerate rests flat.
+        c.angle = c.angle_and_gradients(positions).map_or(std::f32::consts::PI, |(angle, _)| angle);
+        c
+    }
+
+    // The angle between the triangle normals, which is pi for a flat pair, and its gradient with
+    // respect to each particle, following the appendix of Müller et al., "Position Based
+    // Dynamics". None when either triangle has collapsed. Within a hair of flat the gradient is
+    // a ratio of two vanishing terms and only noise, so it is returned as zero there.
+    pub fn angle_and_gradients(&self, positions : &[Vec3]) -> Option<(f32, [Vec3; 4])>
+    {
+        let [a, b, c, d] = self.particles;
+        let p2 = positions[b] - positions[a];
+        let p3 = positions[c] - positions[a];
+        let p4 = positions[d] - positions[a];
+
+        let c23 = p2.cross(p3);
+        let c24 = p2.cross(p4);
+        let l23 = c23.length();
+        let l24 = c24.length();
+        if l23 < 1e-8 || l24 < 1e-8 {
+            return None;
+        }
+        let n1 = c23 / l23;
+        let n2 = c24 / l24;
+
+        let cos = n1.dot(n2).max(-1.0).min(1.0);
+        let angle = cos.acos();
+        let sin = (1.0 - cos * cos).sqrt();
+        if sin < 1e-3 {
+            return Some((angle, [vec3(0.0, 0.0, 0.0); 4]));
+        }
+
+        let q3 = (p2.cross(n2) + n1.cross(p2) * cos) / l23;
+        let q4 = (p2.cross(n1) + n2.cross(p2) * cos) / l24;
+        let q2 = -(p3.cross(n2) + n1.cross(p3) * cos) / l23 - (p4.cross(n1) + n2.cross(p4) * cos) / l24;
+        let q1 = -q2 - q3 - q4;
+
+        // The q terms as the appendix gives them are the derivatives of -cos, not cos, so with
+        // d(acos x)/dx = -1/sqrt(1 - x^2) the two minus signs cancel.
+        let scale = 1.0 / sin;
+        Some((angle, [q1 * scale, q2 * scale, q3 * scale, q4 * scale]))
+    }
+}
+
+// How the cloth resists folding. Without bending constraints it folds freely along any edge.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum BendModel
+{
+    None,
+    // Distance constraints between particles two apart along each grid line.
+    Distance,
+    // Angle constraints across every interior triangle edge.
+    Dihedral,
+}
+
+// Which neighbours distance constraints join each particle to.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Connectivity
+{
+    // Grid lines only.
+    Four,
+    // Grid lines and one diagonal per quad, alternating direction like a triangulated mesh.
+    Six,
+    // Grid lines and both diagonals of every quad.
+    Eight,
+}
+
+impl Connectivity {
+    pub fn name(&self) -> &'static str
+    {
+        match self {
+            Connectivity::Four => "4-connected",
+            Connectivity::Six => "6-connected",
+            Connectivity::Eight => "8-connected",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Scene
+{
+    // A single sheet hanging from its two top corners.
+    Hanging,
+    // A trampoline pinned at all four corners with a smaller free sheet dropped onto it.
+    Stacked,
+    // A flat sheet clamped at one corner, sticking out level so only its bending holds it up.
+    Cantilever,
+}
+
+// Where the cantilever's sheet is built, so its droop can be measured from it.
+pub const CANTILEVER_HEIGHT : f32 = 0.3;
+
+// How far a cantilevered sheet's far corner has dropped below where it was built.
+pub fn tip_droop(grid : &SheetGrid, positions : &[Vec3]) -> Option<f32>
+{
+    let tip = grid.particle(grid.num_particles_x - 1, grid.num_particles_y - 1);
+    positions.get(tip).map(|p| CANTILEVER_HEIGHT - p.y)
+}
+
+// Where one sheet's particles and constraints sit in the cloth's arrays. add_sheet lays them out
+// in a fixed order, so any particle or constraint can be found from its grid coordinates. The seam
+// constraints of a wrapped sheet come after all of these and have no grid lookup.
+#[derive(Clone, Copy)]
+pub struct SheetGrid
+{
+    pub particle_base : usize,
+    pub constraint_base : usize,
+    pub area_base : usize,
+    pub num_particles_x : i32,
+    pub num_particles_y : i32,
+    pub connectivity : Connectivity,
+    // Whether column num_particles_x-1 is joined back to column 0.
+    pub wrap_x : bool,
+}
+
+// The four distance constraint families of a sheet, in the order add_sheet creates them.
+#[derive(Clone, Copy, PartialEq)]
+pub enum EdgeKind
+{
+    Vertical,
+    Horizontal,
+    Diagonal,
+    AntiDiagonal,
+}
+
+impl EdgeKind {
+    pub fn name(&self) -> &'static str
+    {
+        match self {
+            EdgeKind::Vertical => "vertical",
+            EdgeKind::Horizontal => "horizontal",
+            EdgeKind::Diagonal => "diagonal",
+            EdgeKind::AntiDiagonal => "anti-diagonal",
+        }
+    }
+}
+
+impl SheetGrid {
+    pub fn particle(&self, i : i32, j : i32) -> usize
+    {
+        self.particle_base + (i * self.num_particles_y + j) as usize
+    }
+
+    // The family a constraint between particles a and b of this sheet belongs to, or None if
+    // they aren't grid neighbours.
+    pub fn edge_kind(&self, a : usize, b : usize) -> Option<EdgeKind>
+    {
+        let ny = self.num_particles_y as i64;
+        let coords = |p : usize| ((p - self.particle_base) as i64 / ny, (p - self.particle_base) as i64 % ny);
+        let ((ai, aj), (bi, bj)) = (coords(a), coords(b));
+        let mut di = bi - ai;
+        if self.wrap_x && di.abs() == self.num_particles_x as i64 - 1 {
+            di = -di.signum();
+        }
+        match (di, bj - aj) {
+            (0, 1) | (0, -1) => Some(EdgeKind::Vertical),
+            (1, 0) | (-1, 0) => Some(EdgeKind::Horizontal),
+            (1, 1) | (-1, -1) => Some(EdgeKind::Diagonal),
+            (1, -1) | (-1, 1) => Some(EdgeKind::AntiDiagonal),
+            _ => None,
+        }
+    }
+
+    // Size of the lattice of constraints of a kind, and the grid position of the constraint at
+    // lattice cell (0, 0), which is the midpoint of its two particles.
+    pub fn lattice(&self, kind : EdgeKind) -> (i32, i32, Vec2)
+    {
+        let (nx, ny) = (self.num_particles_x, self.num_particles_y);
+        match kind {
+            EdgeKind::Vertical => (nx, ny - 1, vec2(0.0, 0.5)),
+            EdgeKind::Horizontal => (nx - 1, ny, vec2(0.5, 0.0)),
+            EdgeKind::Diagonal | EdgeKind::AntiDiagonal => (nx - 1, ny - 1, vec2(0.5, 0.5)),
+        }
+    }
+
+    // Whether every cell of the kind's lattice has a constraint. Diagonals of a 6-connected sheet
+    // alternate from quad to quad, so neither family fills its lattice.
+    pub fn has_full_lattice(&self, kind : EdgeKind) -> bool
+    {
+        match kind {
+            EdgeKind::Vertical | EdgeKind::Horizontal => true,
+            EdgeKind::Diagonal | EdgeKind::AntiDiagonal => self.connectivity == Connectivity::Eight,
+        }
+    }
+
+    // The constraint at lattice cell (i, j) of a kind, or None if the sheet's connectivity leaves
+    // that edge out.
+    pub fn constraint(&self, kind : EdgeKind, i : i32, j : i32) -> Option<usize>
+    {
+        let (nx, ny) = (self.num_particles_x, self.num_particles_y);
+        let vertical = nx * (ny - 1);
+        let horizontal = (nx - 1) * ny;
+        let quad = i * (ny - 1) + j;
+        let even = (i + j) % 2 == 0;
+        let local = match (kind, self.connectivity) {
+            (EdgeKind::Vertical, _) => i * (ny - 1) + j,
+            (EdgeKind::Horizontal, _) => vertical + i * ny + j,
+            (_, Connectivity::Four) => return None,
+            (EdgeKind::Diagonal, Connectivity::Six) if even => vertical + horizontal + quad,
+            (EdgeKind::AntiDiagonal, Connectivity::Six) if !even => vertical + horizontal + quad,
+            (_, Connectivity::Six) => return None,
+            (EdgeKind::Diagonal, Connectivity::Eight) => vertical + horizontal + 2 * quad,
+            (EdgeKind::AntiDiagonal, Connectivity::Eight) => vertical + horizontal + 2 * quad + 1,
+        };
+        Some(self.constraint_base + local as usize)
+    }
+
+    pub fn area_constraint(&self, i : i32, j : i32) -> usize
+    {
+        self.area_base + (i * (self.num_particles_y - 1) + j) as usize
+    }
+}
+
+// Starts a hanging sheet's vertical constraints from the lambdas they settle to, taking each to
+// carry the load of every particle below it in its column. load(p) is what the weight at particle
+// p adds to its displacement each step, which the constraint above has to take back out. The
+// estimate only holds for a sheet hung from its whole top row, so any other pin pattern, such as
+// the flat sheet's two corners, leaves the sheet alone and returns false.
+pub fn seed_hanging_lambdas(grid : &SheetGrid, constraints : &mut [Constraint], positions : &[Vec3], pinned : impl Fn(usize) -> bool, load : impl Fn(usize) -> f32) -> bool
+{
+    let (nx, ny) = (grid.num_particles_x, grid.num_particles_y);
+    if !(0..nx).all(|i| (0..ny).all(|j| pinned(grid.particle(i, j)) == (j == 0))) {
+        return false;
+    }
+
+    for i in 0..nx {
+        // Walk up the column from the bottom, adding each particle's load as it is passed.
+        let mut below = 0.0;
+        for j in (0..ny - 1).rev() {
+            below += load(grid.particle(i, j + 1));
+            // The lower end is free, so the distance pass moves it by the whole impulse.
+            let c = &mut constraints[grid.constraint(EdgeKind::Vertical, i, j).unwrap()];
+            let normal = (positions[c.p0] - positions[c.p1]).normalize();
+            c.lambda = -normal * below;
+        }
+    }
+    true
+}
+
+// Positions, pins and constraints for a fresh cloth, built separately from the model so a reset
+// can prepare the new layout before swapping it in. A cloth may be made of several sheets,
+// numbered in the order they were added.
+pub struct ClothBuild
+{
+    pub positions : Vec<Vec3>,
+    pub is_fixed : Vec<bool>,
+    pub sheet_of : Vec<usize>,
+    pub constraints : Vec<Constraint>,
+    pub area_constraints : Vec<AreaConstraint>,
+    // Both bending families are always built so the bend model can change without a reset.
+    pub bend_constraints : Vec<Constraint>,
+    pub dihedral_constraints : Vec<DihedralConstraint>,
+    // Indices into constraints solved at low detail: every structural constraint but only one
+    // diagonal per quad.
+    pub lod_constraints : Vec<usize>,
+    pub sheet_grids : Vec<SheetGrid>,
+    pub num_sheets : usize,
+    // Separation kept between particles of different sheets, zero when there is only one.
+    pub contact_distance : f32,
+}
+
+impl ClothBuild {
+    fn new() -> ClothBuild
+    {
+        ClothBuild {
+            positions : vec![],
+            is_fixed : vec![],
+            sheet_of : vec![],
+            constraints : vec![],
+            area_constraints : vec![],
+            bend_constraints : vec![],
+            dihedral_constraints : vec![],
+            lod_constraints : vec![],
+            sheet_grids : vec![],
+            num_sheets : 0,
+            contact_distance : 0.0,
+        }
+    }
+
+    // Appends a num_particles_x by num_particles_y grid with structural, area and bending
+    // constraints, and shear constraints as the connectivity asks. Particle (i, j) of the sheet
+    // gets index base + i*num_particles_y + j. With wrap_x the last column is joined to the first
+    // by a column of quads whose constraints are marked as wrapping.
+    fn add_sheet(&mut self, connectivity : Connectivity, num_particles_x : i32, num_particles_y : i32, wrap_x : bool, position : impl Fn(i32, i32) -> Vec3, pinned : impl Fn(i32, i32) -> bool)
+    {
+        let sheet = self.num_sheets;
+        let base = self.positions.len();
+        self.sheet_grids.push(SheetGrid {
+            particle_base : base,
+            constraint_base : self.constraints.len(),
+            area_base : self.area_constraints.len(),
+            num_particles_x : num_particles_x,
+            num_particles_y : num_particles_y,
+            connectivity : connectivity,
+            wrap_x : wrap_x,
+        });
+        let index = |i : i32, j : i32| base + (i*num_particles_y + j) as usize;
+        let positions = &mut self.positions;
+        let constraints = &mut self.constraints;
+        let lod_constraints = &mut self.lod_constraints;
+
+        for i in 0..num_particles_x
+        {
+            for j in 0..num_particles_y
+            {
+                positions.push(position(i, j));
+                self.is_fixed.push(pinned(i, j));
+                self.sheet_of.push(sheet);
+            }
+        }
+
+        for i in 0..num_particles_x
+        {
+            for j in 0..num_particles_y-1
+            {
+                lod_constraints.push(constraints.len());
+                constraints.push(Constraint::new(index(i, j), index(i, j + 1), positions));
+            }
+        }
+
+        for i in 0..num_particles_x -1
+        {
+            for j in 0..num_particles_y
+            {
+                lod_constraints.push(constraints.len());
+                constraints.push(Constraint::new(index(i, j), index(i + 1, j), positions));
+            }
+        }
+
+        for i in 0..num_particles_x -1
+        {
+            for j in 0..num_particles_y - 1
+            {
+                // Alternate which diagonal survives so the decimated grid has no preferred
+                // shear direction. A 6-connected sheet keeps the same ones at full detail.
+                let diagonal = Constraint::new(index(i, j), index(i + 1, j + 1), positions);
+                let anti_diagonal = Constraint::new(index(i + 1, j), index(i, j + 1), positions);
+                match connectivity {
+                    Connectivity::Four => {}
+                    Connectivity::Six => {
+                        lod_constraints.push(constraints.len());
+                        constraints.push(if (i + j) % 2 == 0 {diagonal} else {anti_diagonal});
+                    }
+                    Connectivity::Eight => {
+                        lod_constraints.push(constraints.len() + ((i + j) % 2) as usize);
+                        constraints.push(diagonal);
+                        constraints.push(anti_diagonal);
+                    }
+                }
+
+                let quad = [index(i, j), index(i + 1, j), index(i + 1, j + 1), index(i, j + 1)];
+                self.area_constraints.push(AreaConstraint::new(quad, positions));
+            }
+        }
+
+        // The seam is one more column of quads, between i = num_particles_x-1 and i = 0, with
+        // its diagonals alternating on from the last column's.
+        let last = num_particles_x - 1;
+        if wrap_x {
+            for j in 0..num_particles_y
+            {
+                lod_constraints.push(constraints.len());
+                constraints.push(Constraint::wrapping(index(last, j), index(0, j), positions));
+            }
+            for j in 0..num_particles_y - 1
+            {
+                let diagonal = Constraint::wrapping(index(last, j), index(0, j + 1), positions);
+                let anti_diagonal = Constraint::wrapping(index(0, j), index(last, j + 1), positions);
+                match connectivity {
+                    Connectivity::Four => {}
+                    Connectivity::Six => {
+                        lod_constraints.push(constraints.len());
+                        constraints.push(if (last + j) % 2 == 0 {diagonal} else {anti_diagonal});
+                    }
+                    Connectivity::Eight => {
+                        lod_constraints.push(constraints.len() + ((last + j) % 2) as usize);
+                        constraints.push(diagonal);
+                        constraints.push(anti_diagonal);
+                    }
+                }
+            }
+        }
+
+        for i in 0..num_particles_x
+        {
+            for j in 0..num_particles_y - 2
+            {
+                self.bend_constraints.push(Constraint::new(index(i, j), index(i, j + 2), positions));
+            }
+        }
+
+        let bend_columns = if wrap_x {num_particles_x} else {num_particles_x - 2};
+        for i in 0..bend_columns
+        {
+            for j in 0..num_particles_y
+            {
+                let (p0, p1) = (index(i, j), index((i + 2) % num_particles_x, j));
+                self.bend_constraints.push(if i + 2 < num_particles_x {Constraint::new(p0, p1, positions)} else {Constraint::wrapping(p0, p1, positions)});
+            }
+        }
+
+        // Each quad is split into two triangles along a diagonal it has a constraint on, the
+        // (i, j)-(i+1, j+1) one when it has both. A 4-connected sheet has no triangles.
+        let grid = self.sheet_grids[sheet];
+        let mut triangles = vec![];
+        for i in 0..num_particles_x - 1
+        {
+            for j in 0..num_particles_y - 1
+            {
+                let (a, b, c, d) = (index(i, j), index(i + 1, j), index(i + 1, j + 1), index(i, j + 1));
+                if grid.constraint(EdgeKind::Diagonal, i, j).is_some() {
+                    triangles.push([a, b, c]);
+                    triangles.push([a, c, d]);
+                } else if grid.constraint(EdgeKind::AntiDiagonal, i, j).is_some() {
+                    triangles.push([a, b, d]);
+                    triangles.push([b, c, d]);
+                }
+            }
+        }
+        if wrap_x {
+            for j in 0..num_particles_y - 1
+            {
+                let (a, b, c, d) = (index(last, j), index(0, j), index(0, j + 1), index(last, j + 1));
+                match connectivity {
+                    Connectivity::Four => {}
+                    Connectivity::Six if (last + j) % 2 != 0 => {
+                        triangles.push([a, b, d]);
+                        triangles.push([b, c, d]);
+                    }
+                    _ => {
+                        triangles.push([a, b, c]);
+                        triangles.push([a, c, d]);
+                    }
+                }
+            }
+        }
+
+        // Every edge shared by two triangles gets one dihedral constraint.
+        for hinge in hinges(&triangles) {
+            self.dihedral_constraints.push(DihedralConstraint::new(hinge, positions));
+        }
+
+        self.num_sheets += 1;
+    }
+
+    // Appends an imported mesh as a sheet with no grid, nothing pinned. Every edge of its
+    // triangulated faces is a distance constraint, so a polygon's fan diagonals stand in for
+    // shear. Bending comes from the edges shared by two triangles, as a distance constraint
+    // between the two far vertices and a dihedral constraint, and quad faces keep their area.
+    fn add_mesh(&mut self, mesh : &ObjMesh, position : impl Fn(Vec3) -> Vec3)
+    {
+        let sheet = self.num_sheets;
+        let base = self.positions.len();
+        for &p in mesh.positions.iter() {
+            self.positions.push(position(p));
+            self.is_fixed.push(false);
+            self.sheet_of.push(sheet);
+        }
+        let positions = &self.positions;
+
+        for (a, b) in mesh.edges() {
+            self.lod_constraints.push(self.constraints.len());
+            self.constraints.push(Constraint::new(base + a, base + b, positions));
+        }
+
+        let triangles : Vec<[usize; 3]> = mesh.triangles().iter().map(|t| [base + t[0], base + t[1], base + t[2]]).collect();
+        for hinge in hinges(&triangles) {
+            self.bend_constraints.push(Constraint::new(hinge[2], hinge[3], positions));
+            self.dihedral_constraints.push(DihedralConstraint::new(hinge, positions));
+        }
+
+        for f in mesh.faces.iter().filter(|f| f.len() == 4) {
+            self.area_constraints.push(AreaConstraint::new([base + f[0], base + f[1], base + f[2], base + f[3]], positions));
+        }
+
+        self.num_sheets += 1;
+    }
+}
+
+// Each edge shared by two triangles, as its two ends followed by the far vertex of each triangle.
+fn hinges(triangles : &[[usize; 3]]) -> Vec<[usize; 4]>
+{
+    let mut hinges = vec![];
+    let mut open_edges : HashMap<(usize, usize), usize> = HashMap::new();
+    for t in triangles.iter() {
+        for k in 0..3 {
+            let (p, q, far) = (t[k], t[(k + 1) % 3], t[(k + 2) % 3]);
+            let edge = (p.min(q), p.max(q));
+            match open_edges.remove(&edge) {
+                Some(other) => hinges.push([edge.0, edge.1, other, far]),
+                None => { open_edges.insert(edge, far); }
+            }
+        }
+    }
+    hinges
+}
+
+// The cloth for an imported mesh, centred and scaled so its longest side spans the unit the grid
+// scenes fill. Nothing is pinned; pins are picked on the cloth afterwards.
+pub fn build_mesh_cloth(mesh : &ObjMesh) -> ClothBuild
+{
+    let mut cloth = ClothBuild::new();
+    let min = mesh.positions.iter().fold(Vec3::splat(f32::MAX), |m, &p| m.min(p));
+    let max = mesh.positions.iter().fold(Vec3::splat(f32::MIN), |m, &p| m.max(p));
+    let extent = max - min;
+    let scale = 1.0 / extent.x.max(extent.y).max(extent.z).max(1e-6);
+    let centre = (min + max) * 0.5;
+    cloth.add_mesh(mesh, |p| (p - centre) * scale);
+    cloth
+}
+
+pub fn build_cloth(scene : &Scene, connectivity : Connectivity, wrap_x : bool, num_particles_x : i32, num_particles_y : i32) -> ClothBuild
+{
+    build_cloth_rows(scene, connectivity, wrap_x, num_particles_x, num_particles_y, num_particles_y)
+}
+
+// The cloth for a num_particles_x by num_particles_y grid with only its first rows rows built,
+// laid out and pinned exactly as they are in the whole cloth. wrap_x rolls the hanging sheet into
+// a tube; the stacked sheets lie flat and never wrap.
+pub fn build_cloth_rows(scene : &Scene, connectivity : Connectivity, wrap_x : bool, num_particles_x : i32, num_particles_y : i32, rows : i32) -> ClothBuild
+{
+    let mut cloth = ClothBuild::new();
+    let nx = num_particles_x as f32;
+    let ny = num_particles_y as f32;
+
+    match scene {
+        // Two columns would make the seam a second copy of the only column of quads.
+        Scene::Hanging if wrap_x && num_particles_x >= 3 => {
+            // A tube as wide around as the flat sheet, hung from its whole top ring since its
+            // corners are next to each other. The seam is at the back.
+            let radius = 0.5 / std::f32::consts::PI;
+            cloth.add_sheet(connectivity, num_particles_x, rows, true, |i, j| {
+                let angle = i as f32 / nx * std::f32::consts::PI * 2.0;
+                let ypos = j as f32 / ny - 0.5f32;
+                vec3(-radius * angle.sin(), -ypos, -radius * angle.cos())
+            }, |_, j| j == 0);
+
+            // Quad areas are measured in the XY plane, which the tube's sides are edge-on to.
+            cloth.area_constraints.clear();
+        }
+        Scene::Hanging => {
+            cloth.add_sheet(connectivity, num_particles_x, rows, false, |i, j| {
+                let xpos = i as f32 / nx - 0.5f32;
+                let ypos = j as f32 / ny - 0.5f32;
+                vec3(xpos, -ypos, xpos * 0.01f32)
+            }, |i, j| j == 0 && (i == 0 || i == num_particles_x-1));
+        }
+        Scene::Stacked => {
+            // Both sheets lie flat in the XZ plane.
+            let is_corner = |i : i32, j : i32| (i == 0 || i == num_particles_x-1) && (j == 0 || j == num_particles_y-1);
+            cloth.add_sheet(connectivity, num_particles_x, rows, false, |i, j| {
+                vec3(i as f32 / nx - 0.5, -0.3, j as f32 / ny - 0.5)
+            }, is_corner);
+            cloth.add_sheet(connectivity, num_particles_x, rows, false, |i, j| {
+                vec3(0.5 * (i as f32 / nx - 0.5), 0.1, 0.5 * (j as f32 / ny - 0.5))
+            }, |_, _| false);
+
+            // Wide enough that the upper sheet can't slip through the middle of a lower quad.
+            cloth.contact_distance = 0.75 * (1.0 / nx).max(1.0 / ny);
+
+            // Quad areas are measured in the XY plane, which these sheets are edge-on to.
+            cloth.area_constraints.clear();
+        }
+        Scene::Cantilever => {
+            // A single particle would be a hinge, so the corner's two by two block is held to
+            // take the sheet's moment.
+            cloth.add_sheet(connectivity, num_particles_x, rows, false, |i, j| {
+                vec3(i as f32 / nx - 0.5, CANTILEVER_HEIGHT, j as f32 / ny - 0.5)
+            }, |i, j| i <= 1 && j <= 1);
+
+            // Quad areas are measured in the XY plane, which this sheet is edge-on to.
+            cloth.area_constraints.clear();
+        }
+    }
+
+    cloth
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::Params;
+    use crate::rail::PinMode;
+    use crate::solver::{self, ClothState, Scratch, SolverParams};
+    use std::collections::HashSet;
+
+    const DT : f32 = 1.0 / 60.0;
+    const GRAVITY : f32 = 0.98;
+
+    const CONNECTIVITIES : [Connectivity; 3] = [Connectivity::Four, Connectivity::Six, Connectivity::Eight];
+
+    fn edges(cloth : &ClothBuild) -> HashSet<(usize, usize)>
+    {
+        cloth.constraints.iter().map(|c| (c.p0.min(c.p1), c.p0.max(c.p1))).collect()
+    }
+
+    #[test]
+    fn constraint_counts_for_each_connectivity()
+    {
+        // 5 by 4 particles have 31 grid edges and 12 quads.
+        for (&connectivity, &expected) in CONNECTIVITIES.iter().zip([31, 43, 55].iter()) {
+            let cloth = build_cloth(&Scene::Hanging, connectivity, false, 5, 4);
+            assert_eq!(cloth.positions.len(), 20);
+            assert_eq!(cloth.constraints.len(), expected, "{:?}", connectivity);
+            assert_eq!(edges(&cloth).len(), expected, "{:?} has a repeated edge", connectivity);
+            assert_eq!(cloth.area_constraints.len(), 12);
+            // Two bend constraints across each of 5 columns of 4 and 3 rows of 4.
+            assert_eq!(cloth.bend_constraints.len(), 5 * 2 + 3 * 4);
+        }
+    }
+
+    #[test]
+    fn wrapped_constraint_counts_for_each_connectivity()
+    {
+        // Around a tube of 5 columns the seam adds a column of 4 edges and 3 quads.
+        for (&connectivity, &expected) in CONNECTIVITIES.iter().zip([35, 50, 65].iter()) {
+            let cloth = build_cloth(&Scene::Hanging, connectivity, true, 5, 4);
+            assert_eq!(cloth.constraints.len(), expected, "{:?}", connectivity);
+            assert_eq!(edges(&cloth).len(), expected, "{:?} has a repeated edge", connectivity);
+        }
+    }
+
+    #[test]
+    fn low_detail_keeps_the_grid_and_one_diagonal_per_quad()
+    {
+        for &connectivity in CONNECTIVITIES.iter() {
+            let cloth = build_cloth(&Scene::Hanging, connectivity, false, 5, 4);
+            let expected = if connectivity == Connectivity::Four {31} else {43};
+            assert_eq!(cloth.lod_constraints.len(), expected, "{:?}", connectivity);
+        }
+    }
+
+    #[test]
+    fn edge_kinds_match_the_connectivity()
+    {
+        for &connectivity in CONNECTIVITIES.iter() {
+            let cloth = build_cloth(&Scene::Hanging, connectivity, false, 5, 4);
+            let grid = &cloth.sheet_grids[0];
+            let mut diagonals = 0;
+            for c in cloth.constraints.iter() {
+                match grid.edge_kind(c.p0, c.p1) {
+                    Some(EdgeKind::Diagonal) | Some(EdgeKind::AntiDiagonal) => diagonals += 1,
+                    Some(_) => {}
+                    None => panic!("{:?}: {}-{} is not a grid edge", connectivity, c.p0, c.p1),
+                }
+            }
+            assert_eq!(diagonals, cloth.constraints.len() - 31, "{:?}", connectivity);
+        }
+    }
+
+    // The render and dihedral triangles come from the constraints, so every hinge edge is one.
+    #[test]
+    fn hinges_run_along_constraints()
+    {
+        for &connectivity in CONNECTIVITIES.iter() {
+            let cloth = build_cloth(&Scene::Hanging, connectivity, false, 5, 4);
+            let edges = edges(&cloth);
+            for hinge in cloth.dihedral_constraints.iter() {
+                let [a, b, _, _] = hinge.particles;
+                assert!(edges.contains(&(a.min(b), a.max(b))), "{:?}: hinge {}-{} has no constraint", connectivity, a, b);
+            }
+            // Every quad diagonal plus the 17 grid edges off the boundary, or none without triangles.
+            let expected = if connectivity == Connectivity::Four {0} else {12 + 17};
+            assert_eq!(cloth.dihedral_constraints.len(), expected, "{:?}", connectivity);
+        }
+    }
+
+    // A hinge along the y axis with one wing out along x and the other out along x or, folded a
+    // quarter turn, along z.
+    fn hinge(folded : bool) -> (DihedralConstraint, Vec<Vec3>)
+    {
+        let flat = vec![vec3(0.0, 0.0, 0.0), vec3(0.0, 1.0, 0.0), vec3(1.0, 0.5, 0.0), vec3(-1.0, 0.5, 0.0)];
+        let constraint = DihedralConstraint::new([0, 1, 2, 3], &flat);
+        let mut positions = flat;
+        if folded {
+            positions[3] = vec3(0.0, 0.5, 1.0);
+        }
+        (constraint, positions)
+    }
+
+    #[test]
+    fn a_flat_hinge_rests_at_pi()
+    {
+        let (constraint, _) = hinge(false);
+        assert!((constraint.angle - std::f32::consts::PI).abs() < 1e-6);
+    }
+
+    // Each particle's share of the correction, taken alone, opens the fold back towards flat, and
+    // the gradients match the angle's finite differences.
+    #[test]
+    fn a_right_angle_fold_is_pushed_back_open_at_every_particle()
+    {
+        let (constraint, positions) = hinge(true);
+        let (angle, gradients) = constraint.angle_and_gradients(&positions).unwrap();
+        assert!((angle - std::f32::consts::FRAC_PI_2).abs() < 1e-6);
+
+        let residual = angle - constraint.angle;
+        let denominator : f32 = gradients.iter().map(|g| g.length_squared()).sum();
+        for i in 0..4 {
+            let correction = gradients[i] * (-residual / denominator);
+            assert!(correction.length() > 1e-3, "particle {} has no correction", i);
+            let mut moved = positions.clone();
+            moved[i] += correction * 0.01;
+            let (opened, _) = constraint.angle_and_gradients(&moved).unwrap();
+            assert!(opened > angle, "particle {}'s correction folds the hinge further", i);
+
+            for axis in 0..3 {
+                let mut step = Vec3::zero();
+                step[axis] = 1e-3;
+                let mut ahead = positions.clone();
+                let mut behind = positions.clone();
+                ahead[i] += step;
+                behind[i] -= step;
+                let slope = (constraint.angle_and_gradients(&ahead).unwrap().0 - constraint.angle_and_gradients(&behind).unwrap().0) / 2e-3;
+                assert!((slope - gradients[i][axis]).abs() < 1e-2, "particle {} axis {}: {} against {}", i, axis, gradients[i][axis], slope);
+            }
+        }
+        // Moving the whole hinge doesn't change its angle.
+        let total = gradients.iter().fold(Vec3::zero(), |sum, &g| sum + g);
+        assert!(total.length() < 1e-5);
+        // The wings swing apart: the folded one back towards -x, the other down out of the plane.
+        assert!(gradients[3].x < 0.0 && gradients[2].z < 0.0);
+    }
+
+    #[test]
+    fn a_collapsed_triangle_has_no_angle()
+    {
+        let (constraint, mut positions) = hinge(true);
+        positions[2] = vec3(0.0, 0.5, 0.0);
+        assert!(constraint.angle_and_gradients(&positions).is_none());
+        positions[2] = vec3(1.0, 0.5, 0.0);
+        positions[3] = vec3(0.0, 2.0, 0.0);
+        assert!(constraint.angle_and_gradients(&positions).is_none());
+        // Nor does one whose shared edge has collapsed.
+        positions = hinge(true).1;
+        positions[1] = positions[0];
+        assert!(constraint.angle_and_gradients(&positions).is_none());
+    }
+
+    #[test]
+    fn a_nearly_flat_hinge_has_zero_gradients()
+    {
+        for &lift in &[0.0, 1e-5, -1e-5, 1e-4] {
+            let (constraint, mut positions) = hinge(false);
+            positions[3].z = lift;
+            let (angle, gradients) = constraint.angle_and_gradients(&positions).unwrap();
+            assert!((angle - std::f32::consts::PI).abs() < 1e-3, "lifted {}: {}", lift, angle);
+            assert_eq!(gradients, [Vec3::zero(); 4], "lifted {}", lift);
+        }
+        // A clear bend is past the cutoff.
+        let (constraint, mut positions) = hinge(false);
+        positions[3].z = 0.01;
+        assert!(constraint.angle_and_gradients(&positions).unwrap().1.iter().all(|g| g.length() > 0.0));
+    }
+
+    // The centre particle's height after each step of a 10 by 10 tube hung from its top ring,
+    // under the default solver settings, seeded or not.
+    fn tube_centre_heights(seeded : bool, steps : usize) -> Vec<f32>
+    {
+        let params = SolverParams {
+            dt : DT,
+            num_iterations : 2,
+            sheet_iterations : vec![2],
+            sheet_warm_start : vec![true],
+            warm_start : true,
+            eta : 1.0,
+            adaptive_eta : 0.0,
+            stiffness : 5000.0,
+            tension_only : false,
+            use_area_constraints : false,
+            area_stiffness : 5000.0,
+            bend_model : BendModel::None,
+            bend_stiffness : 100.0,
+            pin_stiffness : 1e6,
+            lambda_limit : None,
+        };
+        let mut cloth = build_cloth(&Scene::Hanging, Connectivity::Eight, true, 10, 10);
+        let grid = cloth.sheet_grids[0];
+        if seeded {
+            let is_fixed = cloth.is_fixed.clone();
+            assert!(seed_hanging_lambdas(&grid, &mut cloth.constraints, &cloth.positions, |p| is_fixed[p], |_| GRAVITY * DT));
+        }
+        let centre = grid.particle(5, 5);
+        let mut heights = vec![];
+        step_cloth(&mut cloth, &params, steps, |positions| heights.push(positions[centre].y));
+        heights
+    }
+
+    // Steps a cloth under gravity with the default solver, calling back with the positions after
+    // each step.
+    fn step_cloth(cloth : &mut ClothBuild, params : &SolverParams, steps : usize, mut each : impl FnMut(&[Vec3]))
+    {
+        let mut previous_positions = cloth.positions.clone();
+        let mut inverse_masses = vec![];
+        solver::fill_inverse_masses(&mut inverse_masses, &cloth.is_fixed, &[], &[]);
+        let pin_modes : Vec<PinMode> = cloth.is_fixed.iter().map(|&fixed| if fixed {PinMode::Fixed} else {PinMode::Free}).collect();
+        let mut solver = solver::registry().swap_remove(Params::default().solver_index);
+        let mut scratch = Scratch::default();
+        for _ in 0..steps {
+            for i in 0..cloth.positions.len() {
+                solver::integrate_particle(&mut cloth.positions[i], &mut previous_positions[i], inverse_masses[i], vec3(0.0, -GRAVITY, 0.0), 0.6, DT);
+            }
+            scratch.resize(cloth.positions.len());
+            let mut state = ClothState {
+                positions : &mut cloth.positions,
+                previous_positions : &mut previous_positions,
+                is_fixed : &cloth.is_fixed,
+                inverse_masses : &inverse_masses,
+                pin_modes : &pin_modes,
+                curves : &[],
+                sheet_of : &cloth.sheet_of,
+                constraints : &mut cloth.constraints,
+                active_constraints : None,
+                area_constraints : &mut cloth.area_constraints,
+                bend_constraints : &mut cloth.bend_constraints,
+                dihedral_constraints : &mut cloth.dihedral_constraints,
+                contacts : &mut [],
+                contact_distance : 0.0,
+                collider_contacts : &mut [],
+                colliders : None,
+                weight : None,
+                anchors : &mut [],
+                observers : &mut [],
+            };
+            solver.solve(&mut state, params, &mut scratch);
+            each(&cloth.positions);
+        }
+    }
+
+    // The stiff sheet preset on top of the defaults, as the solver is given it.
+    fn stiff_sheet_params(num_iterations : i32) -> SolverParams
+    {
+        let mut params = Params::default();
+        params.apply(&crate::params::stiff_sheet());
+        SolverParams {
+            dt : params.dt,
+            num_iterations : num_iterations,
+            sheet_iterations : vec![num_iterations],
+            sheet_warm_start : vec![params.warm_start],
+            warm_start : params.warm_start,
+            eta : params.eta,
+            adaptive_eta : params.adaptive_eta,
+            stiffness : params.stiffness,
+            tension_only : params.tension_only,
+            use_area_constraints : params.use_area_constraints,
+            area_stiffness : params.area_stiffness,
+            bend_model : params.bend_model,
+            bend_stiffness : params.bend_stiffness,
+            pin_stiffness : params.pin_stiffness,
+            lambda_limit : None,
+        }
+    }
+
+    // The stiff sheet's droop once it has stopped moving, which the Stats panel shows to judge
+    // convergence by. The warm start is off, as it doesn't let a sheet this stiff settle.
+    fn settled_droop(num_iterations : i32) -> f32
+    {
+        let defaults = Params::default();
+        let mut cloth = build_cloth(&Scene::Cantilever, defaults.connectivity, false, defaults.num_particles_x, defaults.num_particles_y);
+        let grid = cloth.sheet_grids[0];
+        let params = SolverParams { warm_start : false, sheet_warm_start : vec![false], ..stiff_sheet_params(num_iterations) };
+        let mut droops = vec![];
+        step_cloth(&mut cloth, &params, 600, |positions| droops.push(tip_droop(&grid, positions).unwrap()));
+        let last = droops[droops.len() - 100..].iter();
+        let (low, high) = last.fold((f32::INFINITY, f32::NEG_INFINITY), |(low, high), &d| (low.min(d), high.max(d)));
+        assert!(high - low < 1e-3, "at {} iterations the droop still moves between {} and {}", num_iterations, low, high);
+        high
+    }
+
+    #[test]
+    fn the_stiff_sheet_droops_less_with_more_iterations()
+    {
+        let bands = [(2, 1.44), (10, 1.08), (50, 0.72)];
+        for &(num_iterations, expected) in bands.iter() {
+            let droop = settled_droop(num_iterations);
+            assert!((droop - expected).abs() < 0.02, "at {} iterations the tip droops {}, not about {}", num_iterations, droop, expected);
+        }
+    }
+
+    #[test]
+    fn seeding_cuts_the_sag_overshoot_of_a_sheet_hung_from_its_top_row()
+    {
+        let settled = *tube_centre_heights(false, 3000).last().unwrap();
+        let overshoot = |heights : Vec<f32>| heights.iter().map(|&y| settled - y).fold(0.0f32, f32::max);
+        let cold = overshoot(tube_centre_heights(false, 60));
+        let seeded = overshoot(tube_centre_heights(true, 60));
+        assert!(cold > 0.0, "the cold start never sagged past {}", settled);
+        assert!(seeded <= 0.7 * cold, "seeding only cut the overshoot from {} to {}", cold, seeded);
+    }
+
+    #[test]
+    fn seeding_leaves_other_pin_patterns_alone()
+    {
+        let tube = || build_cloth(&Scene::Hanging, Connectivity::Eight, true, 6, 5);
+        let grid = tube().sheet_grids[0];
+        let mut extra_pin = tube().is_fixed;
+        extra_pin[grid.particle(3, 2)] = true;
+        let mut missing_pin = tube().is_fixed;
+        missing_pin[grid.particle(4, 0)] = false;
+        let flat = build_cloth(&Scene::Hanging, Connectivity::Eight, false, 6, 5);
+        let cases = vec![
+            ("flat sheet by its corners", flat.is_fixed.clone(), flat),
+            ("tube with an extra pin", extra_pin, tube()),
+            ("tube missing a pin", missing_pin, tube()),
+            ("no pins", vec![false; grid.num_particles_x as usize * grid.num_particles_y as usize], tube()),
+        ];
+        let untouched = vec3(1.0, 2.0, 3.0);
+        for (name, pinned, mut cloth) in cases {
+            for c in cloth.constraints.iter_mut() {
+                c.lambda = untouched;
+            }
+            let grid = cloth.sheet_grids[0];
+            assert!(!seed_hanging_lambdas(&grid, &mut cloth.constraints, &cloth.positions, |p| pinned[p], |_| 1.0), "{} was seeded", name);
+            assert!(cloth.constraints.iter().all(|c| c.lambda == untouched), "{} had its lambdas changed", name);
+        }
+
+        // Neither the trampoline pinned at its four corners nor the free sheet dropped onto it.
+        let mut stacked = build_cloth(&Scene::Stacked, Connectivity::Eight, false, 6, 5);
+        let is_fixed = stacked.is_fixed.clone();
+        for grid in stacked.sheet_grids.clone() {
+            assert!(!seed_hanging_lambdas(&grid, &mut stacked.constraints, &stacked.positions, |p| is_fixed[p], |_| 1.0));
+        }
+        assert!(stacked.constraints.iter().all(|c| c.lambda == Vec3::zero()));
+    }
+}
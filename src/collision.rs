@@ -0,0 +1,190 @@
+use glam::*;
+use serde::Deserialize;
+use crate::sdf::SdfGrid;
+
+// How particles that reach a collider are stopped. The same model is used for every collider.
+#[derive(Clone, Copy, PartialEq)]
+pub enum CollisionResponse
+{
+    // Moved out to the surface after the solve with their normal velocity dropped.
+    Projection,
+    // Moved out as above, with the normal velocity reflected and scaled by the restitution.
+    Reflection,
+    // Solved inside the iterations as unilateral constraints with warm-started impulses.
+    Xpbd,
+}
+
+// Height of the optional floor plane.
+pub const FLOOR_HEIGHT : f32 = -0.9;
+
+// A static sphere, which the 2D view shows as its outline in the XY plane.
+#[derive(Deserialize, Clone, Copy)]
+pub struct Sphere
+{
+    pub center : [f32; 3],
+    pub radius : f32,
+}
+
+impl Sphere {
+    // Signed distance from p to the surface and the outward normal there.
+    pub fn distance(&self, p : Vec3) -> (f32, Vec3)
+    {
+        let offset = p - vec3(self.center[0], self.center[1], self.center[2]);
+        let length = offset.length();
+        let normal = if length > 1e-6 {offset / length} else {vec3(0.0, 1.0, 0.0)};
+        (length - self.radius, normal)
+    }
+
+    // The outline as line segments, a flat list of x, y pairs ready to upload for GL::LINES.
+    pub fn outline(&self, num_segments : usize) -> Vec<f32>
+    {
+        let point = |k : usize| {
+            let angle = k as f32 / num_segments as f32 * std::f32::consts::PI * 2.0;
+            [self.center[0] + self.radius * angle.cos(), self.center[1] + self.radius * angle.sin()]
+        };
+        (0..num_segments).flat_map(|k| {
+            let (a, b) = (point(k), point(k + 1));
+            vec![a[0], a[1], b[0], b[1]]
+        }).collect()
+    }
+}
+
+// A particle near a static collider, linearised at detection into the half-space
+// normal.x >= offset so the solver needn't look the collider up again.
+pub struct ColliderContact
+{
+    pub particle : usize,
+    pub normal : Vec3,
+    pub offset : f32,
+    pub lambda : f32,
+}
+
+// Where to put a particle that is inside a collider, and the previous position that gives it
+// the velocity the response model leaves it with. distance is its distance from the surface
+// less the thickness, so negative, and normal points out of the collider.
+pub fn respond(response : CollisionResponse, restitution : f32, position : Vec3, previous_position : Vec3, distance : f32, normal : Vec3) -> (Vec3, Vec3)
+{
+    let projected = position - normal * distance;
+    let velocity = position - previous_position;
+    let normal_speed = velocity.dot(normal);
+    if normal_speed >= 0.0 {
+        // Already leaving, so there is no approach to take out.
+        return (projected, projected - velocity);
+    }
+
+    let velocity = match response {
+        CollisionResponse::Reflection => velocity - normal * ((1.0 + restitution) * normal_speed),
+        _ => velocity - normal * normal_speed,
+    };
+    (projected, projected - velocity)
+}
+
+// When the positional responses push particles out, relative to the constraint iterations.
+// XPBD contacts are constraints themselves and are solved in every iteration whatever this says.
+#[derive(Clone, Copy, PartialEq)]
+pub enum CollisionOrder
+{
+    // Once after the last iteration, so the constraints never undo it within the step.
+    AfterConstraints,
+    // After every iteration, so the constraints and the colliders settle together.
+    Interleaved,
+    // Once before the first iteration, from where integration left the particles.
+    BeforeConstraints,
+}
+
+pub const COLLISION_ORDERS : [CollisionOrder; 3] = [CollisionOrder::AfterConstraints, CollisionOrder::Interleaved, CollisionOrder::BeforeConstraints];
+
+impl CollisionOrder {
+    pub fn name(&self) -> &'static str
+    {
+        match self {
+            CollisionOrder::AfterConstraints => "constraints_then_collisions",
+            CollisionOrder::Interleaved => "interleaved",
+            CollisionOrder::BeforeConstraints => "collisions_first",
+        }
+    }
+
+    pub fn label(&self) -> &'static str
+    {
+        match self {
+            CollisionOrder::AfterConstraints => "Constraints Then Collisions",
+            CollisionOrder::Interleaved => "Interleaved",
+            CollisionOrder::BeforeConstraints => "Collisions First",
+        }
+    }
+}
+
+// The static colliders and the response that keeps particles out of them, borrowed for a step.
+#[derive(Clone, Copy)]
+pub struct Colliders<'a>
+{
+    pub sdf : Option<&'a SdfGrid>,
+    pub spheres : &'a [Sphere],
+    pub floor : bool,
+    pub thickness : f32,
+    pub response : CollisionResponse,
+    pub restitution : f32,
+}
+
+impl Colliders<'_> {
+    pub fn is_empty(&self) -> bool
+    {
+        self.sdf.is_none() && self.spheres.is_empty() && !self.floor
+    }
+
+    // Distance from p to the nearest collider surface and the outward normal there, if there
+    // are any colliders.
+    pub fn nearest(&self, p : Vec3) -> Option<(f32, Vec3)>
+    {
+        let mut nearest = None;
+        if let Some(sdf) = self.sdf {
+            let gradient = sdf.gradient(vec2(p.x, p.y));
+            let len = gradient.length();
+            if len > 1e-6 {
+                nearest = Some((sdf.sample(vec2(p.x, p.y)), vec3(gradient.x, gradient.y, 0.0) / len));
+            }
+        }
+        for sphere in self.spheres.iter() {
+            let (distance, normal) = sphere.distance(p);
+            if nearest.map_or(true, |(d, _)| distance < d) {
+                nearest = Some((distance, normal));
+            }
+        }
+        if self.floor {
+            let distance = p.y - FLOOR_HEIGHT;
+            if nearest.map_or(true, |(d, _)| distance < d) {
+                nearest = Some((distance, vec3(0.0, 1.0, 0.0)));
+            }
+        }
+        nearest
+    }
+
+    // Pushes every free particle that is closer than the cloth thickness back out along the
+    // collider normal.
+    pub fn push_out(&self, positions : &mut [Vec3], previous_positions : &mut [Vec3], is_fixed : &[bool])
+    {
+        for i in 0..positions.len() {
+            if is_fixed[i] {
+                continue;
+            }
+            if let Some((distance, normal)) = self.nearest(positions[i]) {
+                if distance < self.thickness {
+                    let (position, previous_position) = respond(self.response, self.restitution,
+                        positions[i], previous_positions[i], distance - self.thickness, normal);
+                    positions[i] = position;
+                    previous_positions[i] = previous_position;
+                }
+            }
+        }
+    }
+
+    // How far the deepest free particle is inside the cloth thickness around a collider, or zero.
+    pub fn max_penetration(&self, positions : &[Vec3], is_fixed : &[bool]) -> f32
+    {
+        positions.iter().zip(is_fixed.iter())
+            .filter(|(_, &fixed)| !fixed)
+            .filter_map(|(&p, _)| self.nearest(p))
+            .map(|(distance, _)| self.thickness - distance)
+            .fold(0.0f32, f32::max)
+    }
+}
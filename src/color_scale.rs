@@ -0,0 +1,346 @@
+use serde::{Deserialize, Serialize};
+use crate::palette::Palette;
+
+// User-edited color scales for the views that color by a value, each used in place of the
+// palette's ramp when it is set. Colors are blended in Oklab, where equal steps look about equally
+// far apart, so a ramp through a light middle doesn't dip muddy on the way. Kept across page loads.
+const STORAGE_KEY : &str = "warmstart.color_scales";
+
+// A scale needs both ends to be a scale.
+pub const MIN_STOPS : usize = 2;
+
+// The editor's bar in pixels, and how close a press must be to a stop to pick it up.
+pub const BAR_WIDTH : i32 = 200;
+pub const GRAB_PIXELS : i32 = 6;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ColorStop
+{
+    pub t : f32,
+    pub color : [f32; 3],
+}
+
+// The views that take their colors from a scale.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ScaleUse
+{
+    Valence,
+    Charts,
+}
+
+pub const SCALE_USES : [ScaleUse; 2] = [ScaleUse::Valence, ScaleUse::Charts];
+
+impl ScaleUse {
+    pub fn name(&self) -> &'static str
+    {
+        match self {
+            ScaleUse::Valence => "Valence",
+            ScaleUse::Charts => "Chart Series",
+        }
+    }
+}
+
+// The color at t in [0, 1] along stops sorted by t. Before the first stop and after the last the
+// end colors hold. Where stops share a t the color steps from the earlier one to the later.
+pub fn evaluate(stops : &[ColorStop], t : f32) -> [f32; 3]
+{
+    let (first, last) = match (stops.first(), stops.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return [0.5, 0.5, 0.5],
+    };
+    let t = if t.is_finite() {t.max(0.0).min(1.0)} else {0.0};
+    if t <= first.t {
+        return first.color;
+    }
+    if t >= last.t {
+        return last.color;
+    }
+    let k = stops.iter().rposition(|s| s.t <= t).unwrap_or(0);
+    let (a, b) = (stops[k], stops[k + 1]);
+    let f = if b.t > a.t {(t - a.t) / (b.t - a.t)} else {1.0};
+    let (la, lb) = (to_oklab(a.color), to_oklab(b.color));
+    from_oklab([la[0] + (lb[0] - la[0]) * f, la[1] + (lb[1] - la[1]) * f, la[2] + (lb[2] - la[2]) * f])
+}
+
+// The palette's three ramp colors as stops at either end and the middle.
+pub fn palette_stops(palette : &Palette) -> Vec<ColorStop>
+{
+    palette.ramp.iter().enumerate().map(|(k, &color)| ColorStop { t : k as f32 * 0.5, color : color }).collect()
+}
+
+// Stops in order of t, which every edit keeps them in. The sort is stable so stops at the same t
+// keep the order they step in.
+pub fn sort_stops(stops : &mut Vec<ColorStop>)
+{
+    for s in stops.iter_mut() {
+        s.t = if s.t.is_finite() {s.t.max(0.0).min(1.0)} else {0.0};
+    }
+    stops.sort_by(|a, b| a.t.partial_cmp(&b.t).unwrap_or(std::cmp::Ordering::Equal));
+}
+
+// A new stop halfway across the widest gap, in the color the scale already has there.
+pub fn add_stop(stops : &mut Vec<ColorStop>) -> usize
+{
+    let gap = (1..stops.len()).max_by(|&i, &j| {
+        (stops[i].t - stops[i - 1].t).partial_cmp(&(stops[j].t - stops[j - 1].t)).unwrap_or(std::cmp::Ordering::Equal)
+    });
+    let t = gap.map_or(0.5, |i| 0.5 * (stops[i - 1].t + stops[i].t));
+    let stop = ColorStop { t : t, color : evaluate(stops, t) };
+    let index = stops.iter().position(|s| s.t > t).unwrap_or(stops.len());
+    stops.insert(index, stop);
+    index
+}
+
+// Moves stop k to t, passing any stops it crosses so the order holds. Returns where it ends up.
+pub fn move_stop(stops : &mut [ColorStop], k : usize, t : f32) -> usize
+{
+    let mut k = k;
+    stops[k].t = if t.is_finite() {t.max(0.0).min(1.0)} else {0.0};
+    while k > 0 && stops[k - 1].t > stops[k].t {
+        stops.swap(k - 1, k);
+        k -= 1;
+    }
+    while k + 1 < stops.len() && stops[k + 1].t < stops[k].t {
+        stops.swap(k, k + 1);
+        k += 1;
+    }
+    k
+}
+
+fn to_linear(c : f32) -> f32
+{
+    if c <= 0.04045 {c / 12.92} else {((c + 0.055) / 1.055).powf(2.4)}
+}
+
+fn from_linear(c : f32) -> f32
+{
+    let c = c.max(0.0).min(1.0);
+    if c <= 0.0031308 {c * 12.92} else {1.055 * c.powf(1.0 / 2.4) - 0.055}
+}
+
+// Björn Ottosson's Oklab, from and to sRGB.
+fn to_oklab(c : [f32; 3]) -> [f32; 3]
+{
+    let (r, g, b) = (to_linear(c[0]), to_linear(c[1]), to_linear(c[2]));
+    let l = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+    let m = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+    let s = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+    [
+        0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+        1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+        0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+    ]
+}
+
+fn from_oklab(c : [f32; 3]) -> [f32; 3]
+{
+    let l = (c[0] + 0.3963377774 * c[1] + 0.2158037573 * c[2]).powi(3);
+    let m = (c[0] - 0.1055613458 * c[1] - 0.0638541728 * c[2]).powi(3);
+    let s = (c[0] - 0.0894841775 * c[1] - 1.2914855480 * c[2]).powi(3);
+    [
+        from_linear(4.0767416621 * l - 3.3077115913 * m + 0.2309699292 * s),
+        from_linear(-1.2684380046 * l + 2.6097574011 * m - 0.3413193965 * s),
+        from_linear(-0.0041960863 * l - 0.7034186147 * m + 1.7076147010 * s),
+    ]
+}
+
+// "#rrggbb", as a color input takes and gives.
+pub fn to_hex(c : [f32; 3]) -> String
+{
+    let byte = |v : f32| (v.max(0.0).min(1.0) * 255.0).round() as u8;
+    format!("#{:02x}{:02x}{:02x}", byte(c[0]), byte(c[1]), byte(c[2]))
+}
+
+pub fn from_hex(text : &str) -> Option<[f32; 3]>
+{
+    let hex = text.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    let channel = |k : usize| u8::from_str_radix(hex.get(2 * k..2 * k + 2)?, 16).ok().map(|v| v as f32 / 255.0);
+    Some([channel(0)?, channel(1)?, channel(2)?])
+}
+
+// The scale each view uses, or None where it follows the palette.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct ColorScales
+{
+    pub valence : Option<Vec<ColorStop>>,
+    pub charts : Option<Vec<ColorStop>>,
+}
+
+impl ColorScales {
+    pub fn get(&self, scale_use : ScaleUse) -> Option<&Vec<ColorStop>>
+    {
+        match scale_use {
+            ScaleUse::Valence => self.valence.as_ref(),
+            ScaleUse::Charts => self.charts.as_ref(),
+        }
+    }
+
+    pub fn get_mut(&mut self, scale_use : ScaleUse) -> &mut Option<Vec<ColorStop>>
+    {
+        match scale_use {
+            ScaleUse::Valence => &mut self.valence,
+            ScaleUse::Charts => &mut self.charts,
+        }
+    }
+
+    // The color at t for a view, from its own scale or else the palette's.
+    pub fn color(&self, scale_use : ScaleUse, palette : &Palette, t : f32) -> [f32; 3]
+    {
+        match self.get(scale_use) {
+            Some(stops) => evaluate(stops, t),
+            None => palette.ramp_color(t),
+        }
+    }
+
+    pub fn saved() -> ColorScales
+    {
+        storage()
+            .and_then(|s| s.get_item(STORAGE_KEY).ok().flatten())
+            .and_then(|json| serde_json::from_str::<ColorScales>(&json).ok())
+            .map(|mut scales| {
+                for scale_use in SCALE_USES.iter() {
+                    let scale = scales.get_mut(*scale_use);
+                    if let Some(stops) = scale {
+                        sort_stops(stops);
+                    }
+                    if scale.as_ref().map_or(false, |stops| stops.len() < MIN_STOPS) {
+                        *scale = None;
+                    }
+                }
+                scales
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self)
+    {
+        if let (Some(s), Ok(json)) = (storage(), serde_json::to_string(self)) {
+            let _ = s.set_item(STORAGE_KEY, &json);
+        }
+    }
+}
+
+fn storage() -> Option<web_sys::Storage>
+{
+    web_sys::window()?.local_storage().ok()?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED : [f32; 3] = [1.0, 0.0, 0.0];
+    const WHITE : [f32; 3] = [1.0, 1.0, 1.0];
+    const BLUE : [f32; 3] = [0.0, 0.0, 1.0];
+
+    fn stop(t : f32, color : [f32; 3]) -> ColorStop
+    {
+        ColorStop { t : t, color : color }
+    }
+
+    fn assert_color(actual : [f32; 3], expected : [f32; 3])
+    {
+        assert!(actual.iter().zip(expected.iter()).all(|(a, e)| (a - e).abs() < 1e-3), "{:?} is not {:?}", actual, expected);
+    }
+
+    #[test]
+    fn stops_evaluate_to_their_own_colors()
+    {
+        let stops = [stop(0.0, BLUE), stop(0.4, WHITE), stop(1.0, RED)];
+        assert_color(evaluate(&stops, 0.0), BLUE);
+        assert_color(evaluate(&stops, 0.4), WHITE);
+        assert_color(evaluate(&stops, 1.0), RED);
+    }
+
+    #[test]
+    fn ends_hold_outside_the_stops_and_t_is_clamped()
+    {
+        let stops = [stop(0.2, BLUE), stop(0.8, RED)];
+        assert_color(evaluate(&stops, 0.1), BLUE);
+        assert_color(evaluate(&stops, 0.9), RED);
+        assert_color(evaluate(&stops, -5.0), BLUE);
+        assert_color(evaluate(&stops, 5.0), RED);
+        assert_color(evaluate(&stops, f32::NAN), BLUE);
+    }
+
+    #[test]
+    fn grays_blend_to_grays_that_brighten_steadily()
+    {
+        let stops = [stop(0.0, [0.0; 3]), stop(1.0, WHITE)];
+        let mut last = -1.0;
+        for k in 0..=10 {
+            let c = evaluate(&stops, k as f32 / 10.0);
+            assert!((c[0] - c[1]).abs() < 1e-3 && (c[1] - c[2]).abs() < 1e-3, "{:?} is not gray", c);
+            assert!(c[0] > last, "{:?} is not brighter than {}", c, last);
+            last = c[0];
+        }
+        // Halfway in Oklab lightness is an eighth of white's luminance, about 0.39 in sRGB.
+        let middle = evaluate(&stops, 0.5)[0];
+        assert!(middle > 0.35 && middle < 0.5, "{}", middle);
+    }
+
+    #[test]
+    fn stops_at_the_same_t_step_between_colors()
+    {
+        let stops = [stop(0.0, BLUE), stop(0.5, BLUE), stop(0.5, RED), stop(1.0, RED)];
+        assert_color(evaluate(&stops, 0.49), BLUE);
+        assert_color(evaluate(&stops, 0.5), RED);
+        assert_color(evaluate(&stops, 0.51), RED);
+    }
+
+    #[test]
+    fn degenerate_scales_still_give_a_color()
+    {
+        assert_color(evaluate(&[], 0.3), [0.5, 0.5, 0.5]);
+        assert_color(evaluate(&[stop(0.5, RED)], 0.1), RED);
+        assert_color(evaluate(&[stop(0.5, RED)], 0.9), RED);
+    }
+
+    #[test]
+    fn sorting_clamps_and_keeps_ties_in_order()
+    {
+        let mut stops = vec![stop(0.7, RED), stop(1.5, WHITE), stop(0.3, BLUE), stop(f32::NAN, WHITE), stop(0.3, RED)];
+        sort_stops(&mut stops);
+        let ts : Vec<f32> = stops.iter().map(|s| s.t).collect();
+        assert_eq!(ts, vec![0.0, 0.3, 0.3, 0.7, 1.0]);
+        assert_eq!(stops[1].color, BLUE);
+        assert_eq!(stops[2].color, RED);
+    }
+
+    #[test]
+    fn added_stops_split_the_widest_gap_without_changing_the_scale()
+    {
+        let mut stops = vec![stop(0.0, BLUE), stop(0.2, WHITE), stop(1.0, RED)];
+        let before = evaluate(&stops, 0.6);
+        let k = add_stop(&mut stops);
+        assert_eq!(k, 2);
+        assert!((stops[2].t - 0.6).abs() < 1e-6);
+        assert_color(stops[2].color, before);
+        assert_color(evaluate(&stops, 0.6), before);
+    }
+
+    #[test]
+    fn moved_stops_pass_the_stops_they_cross()
+    {
+        let mut stops = vec![stop(0.0, BLUE), stop(0.3, WHITE), stop(0.6, RED), stop(1.0, BLUE)];
+        assert_eq!(move_stop(&mut stops, 1, 0.8), 2);
+        assert_eq!(stops[2].color, WHITE);
+        assert_eq!(move_stop(&mut stops, 2, -1.0), 1);
+        assert_eq!(stops[1].t, 0.0);
+        let ts : Vec<f32> = stops.iter().map(|s| s.t).collect();
+        assert!(ts.windows(2).all(|w| w[0] <= w[1]), "{:?}", ts);
+    }
+
+    #[test]
+    fn hex_round_trips()
+    {
+        assert_eq!(to_hex([1.0, 0.5, 0.0]), "#ff8000");
+        assert_eq!(from_hex("#ff8000").map(to_hex), Some("#ff8000".to_string()));
+        assert_eq!(from_hex("ff8000"), None);
+        assert_eq!(from_hex("#ff80"), None);
+        assert_eq!(from_hex("#gg8000"), None);
+    }
+}
@@ -0,0 +1,261 @@
+use glam::*;
+use std::collections::HashMap;
+
+// Buckets particle indices into cubic cells so close pairs can be found without testing every
+// pair. The cell vectors are kept between rebuilds to avoid reallocating every step.
+pub struct SpatialHash
+{
+    cell_size : f32,
+    cells : HashMap<(i32, i32, i32), Vec<usize>>,
+    // The lowest and highest cell anything was put in at the last rebuild.
+    bounds : Option<((i32, i32, i32), (i32, i32, i32))>,
+}
+
+impl SpatialHash {
+    pub fn new(cell_size : f32) -> SpatialHash
+    {
+        SpatialHash {
+            cell_size : cell_size,
+            cells : HashMap::new(),
+            bounds : None,
+        }
+    }
+
+    fn cell_of(&self, p : Vec3) -> (i32, i32, i32)
+    {
+        ((p.x / self.cell_size).floor() as i32, (p.y / self.cell_size).floor() as i32, (p.z / self.cell_size).floor() as i32)
+    }
+
+    pub fn rebuild(&mut self, positions : &[Vec3], cell_size : f32)
+    {
+        self.cell_size = cell_size;
+        for cell in self.cells.values_mut() {
+            cell.clear();
+        }
+        self.bounds = None;
+        for (i, &p) in positions.iter().enumerate() {
+            let cell = self.cell_of(p);
+            self.cells.entry(cell).or_insert_with(Vec::new).push(i);
+            self.bounds = Some(match self.bounds {
+                Some((lo, hi)) => ((lo.0.min(cell.0), lo.1.min(cell.1), lo.2.min(cell.2)), (hi.0.max(cell.0), hi.1.max(cell.1), hi.2.max(cell.2))),
+                None => (cell, cell),
+            });
+        }
+    }
+
+    // The index of the position closest to p, the lowest index on a tie, or None if the hash is
+    // empty or p isn't finite. Searches shells of cells outwards from p's and stops once no cell
+    // further out could hold anything closer. Points far outside everything hashed are checked
+    // against every position instead, as the shells would mostly be empty.
+    pub fn nearest(&self, positions : &[Vec3], p : Vec3) -> Option<usize>
+    {
+        const MAX_SHELLS : i64 = 4;
+        let (lo, hi) = self.bounds?;
+        if !(p.x.is_finite() && p.y.is_finite() && p.z.is_finite()) {
+            return None;
+        }
+        let c = self.cell_of(p);
+        let reach = |c : i32, lo : i32, hi : i32| (c as i64 - lo as i64).abs().max((c as i64 - hi as i64).abs());
+        let reach = reach(c.0, lo.0, hi.0).max(reach(c.1, lo.1, hi.1)).max(reach(c.2, lo.2, hi.2));
+        if reach > MAX_SHELLS {
+            return (0..positions.len()).min_by(|&i, &j| {
+                (positions[i] - p).length_squared().partial_cmp(&(positions[j] - p).length_squared()).unwrap_or(std::cmp::Ordering::Equal)
+            });
+        }
+        let reach = reach as i32;
+        let mut best : Option<(f32, usize)> = None;
+        for r in 0..=reach {
+            for dx in -r..=r {
+                for dy in -r..=r {
+                    for dz in -r..=r {
+                        if dx.abs().max(dy.abs()).max(dz.abs()) != r {
+                            continue;
+                        }
+                        for &j in self.cells.get(&(c.0 + dx, c.1 + dy, c.2 + dz)).into_iter().flatten() {
+                            let d = (positions[j] - p).length_squared();
+                            if best.map_or(true, |(bd, bj)| d < bd || (d == bd && j < bj)) {
+                                best = Some((d, j));
+                            }
+                        }
+                    }
+                }
+            }
+            // Every cell r + 1 shells out is at least r cells away.
+            let cleared = r as f32 * self.cell_size;
+            if best.map_or(false, |(d, _)| d < cleared * cleared) {
+                break;
+            }
+        }
+        best.map(|(_, j)| j)
+    }
+
+    // All pairs (i, j) with i < j closer than the cell size, sorted so callers see the same
+    // order regardless of hash map iteration order.
+    pub fn close_pairs(&self, positions : &[Vec3]) -> Vec<(usize, usize)>
+    {
+        let mut pairs = vec![];
+        let radius_squared = self.cell_size * self.cell_size;
+
+        for (i, &p) in positions.iter().enumerate() {
+            let (cx, cy, cz) = self.cell_of(p);
+            for dx in -1..=1 {
+                for dy in -1..=1 {
+                    for dz in -1..=1 {
+                        if let Some(cell) = self.cells.get(&(cx + dx, cy + dy, cz + dz)) {
+                            for &j in cell.iter() {
+                                if j > i && (positions[j] - p).length_squared() < radius_squared {
+                                    pairs.push((i, j));
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        pairs.sort();
+        pairs
+    }
+}
+
+// Identifies a contact between two particles on different sheets so its lambda can be carried
+// over to the next step while the contact persists.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ContactKey
+{
+    pub sheets : (usize, usize),
+    pub particles : (usize, usize),
+}
+
+// A unilateral constraint keeping two particles at least the contact distance apart. lambda is
+// the accumulated separating impulse and never goes negative.
+pub struct Contact
+{
+    pub key : ContactKey,
+    pub lambda : f32,
+    // Steps since the contact formed, 0 on the step it first appears.
+    pub age : u32,
+}
+
+// Contacts start with no impulse, so at one or two iterations a fresh one takes several frames
+// to catch up with its warm-started neighbours and the sheets sink into each other meanwhile.
+// The boost gives contacts younger than `frames` steps extra passes of their own after the solve,
+// and can seed a fresh contact's lambda from an established one sharing a particle. All contacts
+// share the contact distance, so the neighbour's lambda needs no scaling.
+pub struct NewbornBoost
+{
+    pub enabled : bool,
+    pub frames : u32,
+    pub passes : i32,
+    pub seed_lambda : bool,
+}
+
+impl NewbornBoost {
+    pub fn new() -> NewbornBoost
+    {
+        NewbornBoost {
+            enabled : false,
+            frames : 3,
+            passes : 2,
+            seed_lambda : true,
+        }
+    }
+}
+
+// Gives each contact formed this step the lambda of the oldest established contact on either of
+// its particles. The seed only takes effect through the warm start.
+pub fn seed_newborn_lambdas(contacts : &mut [Contact])
+{
+    let mut oldest : HashMap<usize, (u32, f32)> = HashMap::new();
+    for c in contacts.iter().filter(|c| c.age > 0) {
+        for &p in [c.key.particles.0, c.key.particles.1].iter() {
+            let entry = oldest.entry(p).or_insert((c.age, c.lambda));
+            if c.age > entry.0 {
+                *entry = (c.age, c.lambda);
+            }
+        }
+    }
+    for c in contacts.iter_mut().filter(|c| c.age == 0) {
+        let (a, b) = c.key.particles;
+        let seed = match (oldest.get(&a), oldest.get(&b)) {
+            (Some(&x), Some(&y)) => Some(if x.0 >= y.0 {x.1} else {y.1}),
+            (Some(&x), None) | (None, Some(&x)) => Some(x.1),
+            (None, None) => None,
+        };
+        if let Some(lambda) = seed {
+            c.lambda = lambda;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn brute_force(positions : &[Vec3], p : Vec3) -> usize
+    {
+        let mut best = 0;
+        for (j, &q) in positions.iter().enumerate() {
+            if (q - p).length_squared() < (positions[best] - p).length_squared() {
+                best = j;
+            }
+        }
+        best
+    }
+
+    // A bent 13 by 9 grid with spacing 0.1.
+    fn grid() -> Vec<Vec3>
+    {
+        (0..13 * 9).map(|k| {
+            let (i, j) = ((k / 9) as f32, (k % 9) as f32);
+            vec3(i * 0.1 - 0.6, j * -0.1, (i * 0.4).sin() * 0.2)
+        }).collect()
+    }
+
+    #[test]
+    fn nearest_matches_a_brute_force_search()
+    {
+        let positions = grid();
+        let mut hash = SpatialHash::new(1.0);
+        hash.rebuild(&positions, 0.1);
+        for k in 0..400 {
+            let t = k as f32;
+            // Inside, around and well outside the grid.
+            let p = vec3((t * 0.37).sin() * (1.0 + t / 100.0), (t * 0.53).cos() * (1.0 + t / 100.0) - 0.4, (t * 0.71).sin() * 0.5);
+            let expected = brute_force(&positions, p);
+            let found = hash.nearest(&positions, p).unwrap();
+            assert!((positions[found] - p).length() == (positions[expected] - p).length(), "{}: found {} not {}", p, found, expected);
+        }
+    }
+
+    #[test]
+    fn nearest_takes_the_lowest_index_on_a_tie()
+    {
+        let positions = vec![vec3(1.0, 0.0, 0.0), vec3(-1.0, 0.0, 0.0), vec3(0.0, 1.0, 0.0)];
+        let mut hash = SpatialHash::new(1.0);
+        hash.rebuild(&positions, 0.3);
+        assert_eq!(hash.nearest(&positions, vec3(0.0, 0.0, 0.0)), Some(0));
+        assert_eq!(hash.nearest(&positions, vec3(0.0, 100.0, 0.0)), Some(2));
+    }
+
+    #[test]
+    fn nearest_in_nothing_is_none()
+    {
+        let mut hash = SpatialHash::new(1.0);
+        assert_eq!(hash.nearest(&[], vec3(0.0, 0.0, 0.0)), None);
+        hash.rebuild(&[], 0.1);
+        assert_eq!(hash.nearest(&[], vec3(0.0, 0.0, 0.0)), None);
+        let positions = grid();
+        hash.rebuild(&positions, 0.1);
+        assert_eq!(hash.nearest(&positions, vec3(f32::NAN, 0.0, 0.0)), None);
+    }
+
+    #[test]
+    fn close_pairs_are_those_within_the_cell_size()
+    {
+        let positions = vec![vec3(0.0, 0.0, 0.0), vec3(0.05, 0.0, 0.0), vec3(0.5, 0.0, 0.0), vec3(0.5, 0.09, 0.0)];
+        let mut hash = SpatialHash::new(1.0);
+        hash.rebuild(&positions, 0.1);
+        assert_eq!(hash.close_pairs(&positions), vec![(0, 1), (2, 3)]);
+    }
+}
@@ -0,0 +1,156 @@
+use glam::*;
+use crate::cloth::Constraint;
+
+// A right-click only opens the menu if the cursor moved less than this many pixels between the
+// press and the release, so a right-button drag is left to whatever else wants it.
+pub const DRAG_THRESHOLD_PIXELS : i32 = 4;
+
+// Speed in units per second given to a particle at the centre of a push, falling off to nothing
+// at the brush's rim.
+pub const PUSH_SPEED : f32 = 2.0;
+
+// What each item does, to whatever was under the click.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MenuAction
+{
+    TogglePin,
+    ProbeConstraint,
+    // A one-off radial kick away from the point, over the brush's radius.
+    PushAway,
+    MeasureFrom,
+    FreezeFrom,
+}
+
+// An action resolved against what was under the click, for the app to carry out.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MenuCommand
+{
+    Pin(usize),
+    Unpin(usize),
+    Probe(usize),
+    PushAway(Vec2),
+    // From the click in canvas pixels, as the measure tool's first click.
+    MeasureFrom(i32, i32),
+    FreezeFrom(Vec2),
+}
+
+// The menu as opened: where it was clicked, in canvas pixels and in simulation space, and what
+// was under the click.
+pub struct ContextMenu
+{
+    pub x : i32,
+    pub y : i32,
+    pub point : Vec2,
+    pub particle : usize,
+    pub pinned : bool,
+    pub constraint : Option<usize>,
+}
+
+impl ContextMenu {
+    pub fn items(&self) -> Vec<(String, MenuAction)>
+    {
+        let mut items = vec![];
+        let pin_label = if self.pinned { "Unpin" } else { "Pin" };
+        items.push((format!("{} particle {}", pin_label, self.particle), MenuAction::TogglePin));
+        if let Some(k) = self.constraint {
+            items.push((format!("Probe constraint {}", k), MenuAction::ProbeConstraint));
+        }
+        items.push(("Push particles away".to_string(), MenuAction::PushAway));
+        items.push(("Measure from here".to_string(), MenuAction::MeasureFrom));
+        items.push(("Freeze a region from here".to_string(), MenuAction::FreezeFrom));
+        items
+    }
+
+    // None only for a probe with no constraint to probe, which the menu doesn't offer.
+    pub fn command(&self, action : MenuAction) -> Option<MenuCommand>
+    {
+        match action {
+            MenuAction::TogglePin if self.pinned => Some(MenuCommand::Unpin(self.particle)),
+            MenuAction::TogglePin => Some(MenuCommand::Pin(self.particle)),
+            MenuAction::ProbeConstraint => self.constraint.map(MenuCommand::Probe),
+            MenuAction::PushAway => Some(MenuCommand::PushAway(self.point)),
+            MenuAction::MeasureFrom => Some(MenuCommand::MeasureFrom(self.x, self.y)),
+            MenuAction::FreezeFrom => Some(MenuCommand::FreezeFrom(self.point)),
+        }
+    }
+}
+
+// The constraint whose segment passes closest to p in the plane of the view.
+pub fn nearest_constraint(positions : &[Vec3], constraints : &[Constraint], p : Vec2) -> Option<usize>
+{
+    let mut best = None;
+    let mut best_distance = f32::MAX;
+    for (k, c) in constraints.iter().enumerate() {
+        let a = vec2(positions[c.p0].x, positions[c.p0].y);
+        let b = vec2(positions[c.p1].x, positions[c.p1].y);
+        let ab = b - a;
+        let t = if ab.length_squared() > 0.0 { ((p - a).dot(ab) / ab.length_squared()).max(0.0).min(1.0) } else { 0.0 };
+        let distance = (a + ab * t - p).length_squared();
+        if distance < best_distance {
+            best = Some(k);
+            best_distance = distance;
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn menu(pinned : bool, constraint : Option<usize>) -> ContextMenu
+    {
+        ContextMenu { x : 120, y : 45, point : vec2(0.25, -0.5), particle : 7, pinned : pinned, constraint : constraint }
+    }
+
+    #[test]
+    fn each_item_commands_its_action_on_what_was_clicked()
+    {
+        let commands = |menu : &ContextMenu| -> Vec<(String, MenuCommand)> {
+            menu.items().into_iter().map(|(label, action)| (label, menu.command(action).unwrap())).collect()
+        };
+        assert_eq!(commands(&menu(false, Some(3))), vec![
+            ("Pin particle 7".to_string(), MenuCommand::Pin(7)),
+            ("Probe constraint 3".to_string(), MenuCommand::Probe(3)),
+            ("Push particles away".to_string(), MenuCommand::PushAway(vec2(0.25, -0.5))),
+            ("Measure from here".to_string(), MenuCommand::MeasureFrom(120, 45)),
+            ("Freeze a region from here".to_string(), MenuCommand::FreezeFrom(vec2(0.25, -0.5))),
+        ]);
+        assert_eq!(commands(&menu(true, None))[0], ("Unpin particle 7".to_string(), MenuCommand::Unpin(7)));
+    }
+
+    #[test]
+    fn there_is_no_probe_without_a_constraint()
+    {
+        let menu = menu(false, None);
+        assert!(menu.items().iter().all(|&(_, action)| action != MenuAction::ProbeConstraint));
+        assert_eq!(menu.command(MenuAction::ProbeConstraint), None);
+    }
+
+    #[test]
+    fn the_nearest_constraint_is_measured_to_its_segment_in_the_view()
+    {
+        // A right angle with its corner at the origin, and a far constraint whose ends are
+        // both out of the view's plane.
+        let positions = vec![vec3(0.0, 0.0, 0.0), vec3(1.0, 0.0, 0.0), vec3(0.0, 1.0, 0.0), vec3(3.0, 3.0, 5.0), vec3(3.0, 4.0, -5.0)];
+        let constraints = vec![Constraint::new(0, 1, &positions), Constraint::new(0, 2, &positions), Constraint::new(3, 4, &positions)];
+        let nearest = |x : f32, y : f32| nearest_constraint(&positions, &constraints, vec2(x, y));
+        assert_eq!(nearest(0.5, 0.1), Some(0));
+        assert_eq!(nearest(0.1, 0.5), Some(1));
+        // Past the end of a segment the distance is to its end.
+        assert_eq!(nearest(1.4, -0.1), Some(0));
+        assert_eq!(nearest(-0.1, 1.4), Some(1));
+        // Depth is ignored.
+        assert_eq!(nearest(3.1, 3.5), Some(2));
+        assert_eq!(nearest_constraint(&positions, &[], vec2(0.0, 0.0)), None);
+    }
+
+    #[test]
+    fn a_collapsed_constraint_is_measured_to_its_point()
+    {
+        let positions = vec![vec3(0.0, 0.0, 0.0), vec3(0.0, 0.0, 0.0), vec3(2.0, 0.0, 0.0), vec3(2.0, 2.0, 0.0)];
+        let constraints = vec![Constraint::new(0, 1, &positions), Constraint::new(2, 3, &positions)];
+        assert_eq!(nearest_constraint(&positions, &constraints, vec2(0.2, 0.2)), Some(0));
+        assert_eq!(nearest_constraint(&positions, &constraints, vec2(1.8, 1.0)), Some(1));
+    }
+}
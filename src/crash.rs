@@ -0,0 +1,208 @@
+use glam::*;
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use crate::fuzz;
+use crate::params::Params;
+use crate::session::{self, SavedCloth};
+
+// What a panic leaves behind. The cloth as the last finished step left it and the latest actions
+// are kept here every step, so the panic hook has them without the model, which may be in the
+// middle of anything. The hook writes them to localStorage as a crash report and covers the page
+// with a plain DOM overlay, since the app can't draw anything once it has panicked. The overlay's
+// buttons are inline script for the same reason.
+const STORAGE_KEY : &str = "warmstart.crash";
+
+// The query parameter the overlay reloads with, which picks the saved cloth up again.
+pub const RESTORE_PARAMETER : &str = "restore_crash=1";
+
+// Actions kept for the report, oldest dropped first.
+const MAX_ACTIONS : usize = 100;
+
+// Past this many characters the report keeps everything but the cloth, as a session does.
+const MAX_CHARS : usize = 2_000_000;
+
+// The cloth after the last step to finish, as plain copies that cost a copy a step to keep. It is
+// only encoded when a panic calls for it.
+#[derive(Default)]
+pub struct Snapshot
+{
+    pub time_step : i32,
+    pub seed : u64,
+    // False for a cloth the parameters can't rebuild, such as an imported mesh.
+    pub restorable : bool,
+    pub positions : Vec<Vec3>,
+    pub previous_positions : Vec<Vec3>,
+    pub is_fixed : Vec<bool>,
+    pub pin_weights : Vec<f32>,
+    pub lambdas : Vec<Vec3>,
+    pub bend_lambdas : Vec<Vec3>,
+    pub area_lambdas : Vec<f32>,
+    pub dihedral_lambdas : Vec<f32>,
+    pub anchor_lambdas : Vec<Vec3>,
+}
+
+impl Snapshot {
+    fn saved_cloth(&self) -> Option<SavedCloth>
+    {
+        if !self.restorable || self.positions.is_empty() {
+            return None;
+        }
+        Some(SavedCloth {
+            time_step : self.time_step,
+            positions : session::encode_vec3(&self.positions),
+            previous_positions : session::encode_vec3(&self.previous_positions),
+            is_fixed : self.is_fixed.iter().map(|&f| if f {'1'} else {'0'}).collect(),
+            pin_weights : session::encode(self.pin_weights.iter().cloned()),
+            lambdas : session::encode_vec3(&self.lambdas),
+            bend_lambdas : session::encode_vec3(&self.bend_lambdas),
+            area_lambdas : session::encode(self.area_lambdas.iter().cloned()),
+            dihedral_lambdas : session::encode(self.dihedral_lambdas.iter().cloned()),
+            anchor_lambdas : session::encode_vec3(&self.anchor_lambdas),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CrashReport
+{
+    pub message : String,
+    // Date.now() when it crashed.
+    pub crashed_ms : f64,
+    pub seed : u64,
+    pub params : Option<Params>,
+    // The latest actions, oldest first.
+    pub actions : Vec<String>,
+    // None when the cloth couldn't be kept.
+    pub state : Option<SavedCloth>,
+}
+
+thread_local! {
+    static SNAPSHOT : RefCell<Snapshot> = RefCell::new(Snapshot::default());
+    static PARAMS : RefCell<Option<Params>> = RefCell::new(None);
+    static ACTIONS : RefCell<VecDeque<String>> = RefCell::new(VecDeque::new());
+}
+
+// Updates the snapshot in place, so its lists keep their allocations from step to step.
+pub fn keep(update : impl FnOnce(&mut Snapshot))
+{
+    SNAPSHOT.with(|snapshot| update(&mut snapshot.borrow_mut()));
+}
+
+pub fn keep_params(params : &Params)
+{
+    PARAMS.with(|kept| *kept.borrow_mut() = Some(params.clone()));
+}
+
+pub fn record_action(action : String)
+{
+    ACTIONS.with(|actions| {
+        let mut actions = actions.borrow_mut();
+        if actions.len() >= MAX_ACTIONS {
+            actions.pop_front();
+        }
+        actions.push_back(action);
+    });
+}
+
+// The report from what was last kept. Anything the panic left borrowed is left out rather than
+// risk panicking again inside the hook.
+fn report(message : String) -> CrashReport
+{
+    let (seed, state) = SNAPSHOT.with(|s| s.try_borrow().map(|s| (s.seed, s.saved_cloth())).unwrap_or((0, None)));
+    CrashReport {
+        message : message,
+        crashed_ms : js_sys::Date::now(),
+        seed : seed,
+        params : PARAMS.with(|p| p.try_borrow().ok().and_then(|p| p.clone())),
+        actions : ACTIONS.with(|a| a.try_borrow().map(|a| a.iter().cloned().collect()).unwrap_or_default()),
+        state : state,
+    }
+}
+
+fn storage() -> Option<web_sys::Storage>
+{
+    web_sys::window()?.local_storage().ok()?
+}
+
+// Saves the report, without the cloth if the whole of it won't fit. Returns whether the cloth
+// went in.
+fn save(report : &mut CrashReport) -> bool
+{
+    let storage = match storage() {
+        Some(storage) => storage,
+        None => return false,
+    };
+    if let Ok(text) = serde_json::to_string(report) {
+        if text.len() <= MAX_CHARS && storage.set_item(STORAGE_KEY, &text).is_ok() {
+            return report.state.is_some();
+        }
+    }
+    report.state = None;
+    if let Ok(text) = serde_json::to_string(report) {
+        let _ = storage.set_item(STORAGE_KEY, &text);
+    }
+    false
+}
+
+// The report a crash left for this load to restore, removed so a later reload starts fresh.
+pub fn take_saved() -> Option<CrashReport>
+{
+    let storage = storage()?;
+    let report = storage.get_item(STORAGE_KEY).ok().flatten().and_then(|text| serde_json::from_str::<CrashReport>(&text).ok());
+    let _ = storage.remove_item(STORAGE_KEY);
+    report
+}
+
+pub fn restore_requested() -> bool
+{
+    web_sys::window()
+        .and_then(|w| w.location().search().ok())
+        .map_or(false, |search| search.trim_start_matches('?').split('&').any(|pair| pair == RESTORE_PARAMETER))
+}
+
+fn escape_html(text : &str) -> String
+{
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+// Covers the page with the panic message and the ways on from it.
+fn show_overlay(report : &CrashReport, kept_cloth : bool)
+{
+    let body = match web_sys::window().and_then(|w| w.document()).and_then(|d| d.body()) {
+        Some(body) => body,
+        None => return,
+    };
+    let saved = match (kept_cloth, report.params.is_some()) {
+        (true, _) => format!("The cloth as of step {} was saved, and reloading puts it back.", report.state.as_ref().map_or(0, |s| s.time_step)),
+        (false, true) => "The cloth couldn't be kept, but reloading puts the parameters back.".to_string(),
+        (false, false) => "Nothing could be kept to restore.".to_string(),
+    };
+    let download = format!("var a=document.createElement('a');a.href=URL.createObjectURL(new Blob([localStorage.getItem('{}')],{{type:'application/json'}}));a.download='crash_report.json';a.click();", STORAGE_KEY);
+    let reload = "var s=new URLSearchParams(location.search);s.set('restore_crash','1');location.search=s.toString();";
+    let overlay = format!(r#"<div id="crash_overlay" style="position:fixed; top:0; left:0; right:0; bottom:0; z-index:1000; background-color:rgba(0,0,0,0.5);">
+        <div style="background-color:#EB9696; border-radius:5px; margin:10% auto; max-width:600px; padding:10px;">
+            <b>The simulation crashed.</b>
+            <pre style="white-space:pre-wrap;">{}</pre>
+            <p>{}</p>
+            <button class="button" style="background-color:#5756EB" onclick="{}">Download Crash Report</button>
+            <button class="button" style="background-color:#5756EB" onclick="{}">Reload and Restore</button>
+        </div>
+    </div>"#, escape_html(&report.message), saved, escape_html(&download), escape_html(reload));
+    let _ = body.insert_adjacent_html("beforeend", &overlay);
+}
+
+// Saves the crash report and puts up the overlay before the panic is logged, with the fuzz
+// report when there is one.
+pub fn install_panic_hook()
+{
+    std::panic::set_hook(Box::new(|info| {
+        let mut report = report(info.to_string());
+        let kept_cloth = save(&mut report);
+        show_overlay(&report, kept_cloth);
+        info!("Saved a crash report with {} actions{}", report.actions.len(), if kept_cloth {" and the cloth"} else {""});
+        fuzz::log_panic_report();
+        console_error_panic_hook::hook(info);
+    }));
+}
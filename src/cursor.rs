@@ -0,0 +1,101 @@
+use glam::*;
+use std::collections::VecDeque;
+
+const MAX_SAMPLES : usize = 8;
+// Older samples describe motion the cursor has already finished.
+const MAX_AGE_MS : f64 = 80.0;
+// A cursor that hasn't moved for this long has stopped, so it isn't extrapolated.
+const STOPPED_MS : f64 = 40.0;
+
+// Recent cursor positions in simulation space with the times of their events, for tools that
+// follow the cursor's motion rather than just where it is now. Every pointer event feeds it.
+pub struct CursorHistory
+{
+    samples : VecDeque<(f64, Vec2)>,
+}
+
+impl CursorHistory {
+    pub fn new() -> CursorHistory
+    {
+        CursorHistory { samples : VecDeque::with_capacity(MAX_SAMPLES) }
+    }
+
+    pub fn clear(&mut self)
+    {
+        self.samples.clear();
+    }
+
+    pub fn push(&mut self, time_ms : f64, position : Vec2)
+    {
+        if self.samples.len() == MAX_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((time_ms, position));
+    }
+
+    pub fn latest(&self) -> Option<Vec2>
+    {
+        self.samples.back().map(|&(_, p)| p)
+    }
+
+    // Where the cursor was at time_ms, interpolated between the samples either side and held at the
+    // oldest or latest sample outside them.
+    pub fn at(&self, time_ms : f64) -> Option<Vec2>
+    {
+        let &(latest_time, latest) = self.samples.back()?;
+        if time_ms >= latest_time {
+            return Some(latest);
+        }
+        let mut previous = *self.samples.front()?;
+        if time_ms <= previous.0 {
+            return Some(previous.1);
+        }
+        for &(t, p) in self.samples.iter().skip(1) {
+            if t >= time_ms {
+                let f = if t > previous.0 {((time_ms - previous.0) / (t - previous.0)) as f32} else {1.0};
+                return Some(previous.1 + (p - previous.1) * f);
+            }
+            previous = (t, p);
+        }
+        Some(latest)
+    }
+
+    // Least squares fit over the samples close to the latest, in simulation units per
+    // millisecond. None until there are two distinct event times to fit.
+    pub fn velocity(&self) -> Option<Vec2>
+    {
+        let &(latest_time, _) = self.samples.back()?;
+        let recent : Vec<(f64, Vec2)> = self.samples.iter().copied().filter(|&(t, _)| latest_time - t <= MAX_AGE_MS).collect();
+        let n = recent.len() as f64;
+        let mean_time = recent.iter().map(|&(t, _)| t).sum::<f64>() / n;
+        let mean_position = recent.iter().fold(vec2(0.0, 0.0), |sum, &(_, p)| sum + p) / n as f32;
+        let mut covariance = vec2(0.0, 0.0);
+        let mut variance = 0.0;
+        for &(t, p) in recent.iter() {
+            let dt = t - mean_time;
+            covariance += (p - mean_position) * dt as f32;
+            variance += dt * dt;
+        }
+        if variance < 1e-6 {
+            return None;
+        }
+        Some(covariance / variance as f32)
+    }
+
+    // Where the cursor will be lead_ms after now, carried along the fitted velocity by no more
+    // than max_distance so a sudden stop doesn't fling the target past it.
+    pub fn predict(&self, now_ms : f64, lead_ms : f64, max_distance : f32) -> Option<Vec2>
+    {
+        let &(latest_time, latest) = self.samples.back()?;
+        if now_ms - latest_time > STOPPED_MS {
+            return Some(latest);
+        }
+        let velocity = match self.velocity() {
+            Some(velocity) => velocity,
+            None => return Some(latest),
+        };
+        let offset = velocity * (now_ms + lead_ms - latest_time).max(0.0) as f32;
+        let length = offset.length();
+        Some(if length > max_distance {latest + offset * (max_distance / length)} else {latest + offset})
+    }
+}
@@ -0,0 +1,171 @@
+use log::{error, info};
+use glam::*;
+
+// Checks that the default scenario steps bit for bit the same, run with ?determinism=check in the
+// query string. The cloth is reset and stepped AUDIT_STEPS times, twice, hashing the state after
+// every step; the two runs must agree on every hash. Every CHECKPOINT_INTERVAL steps the hashes so
+// far are folded into a checkpoint, and the checkpoints are compared with CHECKPOINTS below,
+// which ?determinism=record prints from whichever browser took them.
+//
+// To check across browsers by hand: open ?determinism=record in one browser, paste the printed
+// checkpoints into CHECKPOINTS, rebuild, and open ?determinism=check in the other. Both runs use
+// the default parameters with full detail locked, whatever tier the device saved. Nothing here
+// pins the arithmetic; agreement across browsers rests on wasm's f32 semantics.
+//
+// There is no portable determinism mode. The step's sums and lengths are already sequential
+// loops, which the compiler doesn't reorder, so a fixed-order path would change nothing. What is
+// missing is a recording from one browser checked in another: CHECKPOINTS is still None.
+
+pub const AUDIT_STEPS : u32 = 1000;
+pub const CHECKPOINT_INTERVAL : u32 = 100;
+
+// Steps taken a frame, so the page keeps drawing the progress while it runs.
+pub const STEPS_PER_FRAME : u32 = 50;
+
+// As printed by a record run. None until one is taken.
+pub const CHECKPOINTS : Option<[&str; (AUDIT_STEPS / CHECKPOINT_INTERVAL) as usize]> = None;
+
+const FNV_OFFSET : u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME : u64 = 0x0000_0100_0000_01b3;
+
+// FNV-1a over the bits of each value in turn, so equal hashes mean equal bits in the same order.
+pub struct StateHasher
+{
+    hash : u64,
+}
+
+impl StateHasher {
+    pub fn new() -> StateHasher
+    {
+        StateHasher { hash : FNV_OFFSET }
+    }
+
+    pub fn add_u64(&mut self, value : u64)
+    {
+        for byte in value.to_le_bytes().iter() {
+            self.hash = (self.hash ^ *byte as u64).wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    pub fn add_floats(&mut self, values : impl Iterator<Item = f32>)
+    {
+        for v in values {
+            self.add_u64(v.to_bits() as u64);
+        }
+    }
+
+    pub fn add_vec3s(&mut self, values : &[Vec3])
+    {
+        self.add_floats(values.iter().flat_map(|v| vec![v.x, v.y, v.z]));
+    }
+
+    pub fn finish(&self) -> u64
+    {
+        self.hash
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum AuditAction
+{
+    Check,
+    Record,
+}
+
+// The action asked for in the query string, if any.
+pub fn requested() -> Option<AuditAction>
+{
+    let search = web_sys::window()?.location().search().ok()?;
+    search.trim_start_matches('?').split('&').find_map(|pair| match pair {
+        "determinism=check" => Some(AuditAction::Check),
+        "determinism=record" => Some(AuditAction::Record),
+        _ => None,
+    })
+}
+
+// The hashes folded together up to each checkpoint, as hex.
+fn checkpoints(hashes : &[u64]) -> Vec<String>
+{
+    let mut hasher = StateHasher::new();
+    let mut checkpoints = vec![];
+    for (k, &hash) in hashes.iter().enumerate() {
+        hasher.add_u64(hash);
+        if (k as u32 + 1) % CHECKPOINT_INTERVAL == 0 {
+            checkpoints.push(format!("{:016x}", hasher.finish()));
+        }
+    }
+    checkpoints
+}
+
+// An audit in progress: the first run's hashes, then the second's.
+pub struct HashAudit
+{
+    pub action : AuditAction,
+    pub runs : [Vec<u64>; 2],
+    pub passed : Option<bool>,
+}
+
+impl HashAudit {
+    pub fn new(action : AuditAction) -> HashAudit
+    {
+        HashAudit { action : action, runs : [vec![], vec![]], passed : None }
+    }
+
+    // The run still stepping, or None once both are done.
+    pub fn current_run(&self) -> Option<usize>
+    {
+        self.runs.iter().position(|r| r.len() < AUDIT_STEPS as usize)
+    }
+
+    pub fn is_done(&self) -> bool
+    {
+        self.current_run().is_none()
+    }
+
+    pub fn record(&mut self, hash : u64)
+    {
+        if let Some(run) = self.current_run() {
+            self.runs[run].push(hash);
+        }
+        if self.is_done() && self.passed.is_none() {
+            self.finish();
+        }
+    }
+
+    fn finish(&mut self)
+    {
+        let mut passed = true;
+        match self.runs[0].iter().zip(self.runs[1].iter()).position(|(a, b)| a != b) {
+            Some(step) => {
+                error!("Determinism: FAIL, the two runs first differ after step {}", step + 1);
+                passed = false;
+            }
+            None => info!("Determinism: both runs agree on all {} step hashes", AUDIT_STEPS),
+        }
+
+        let taken = checkpoints(&self.runs[0]);
+        match (self.action, CHECKPOINTS) {
+            (AuditAction::Record, _) => info!("Determinism checkpoints: Some([\"{}\"])", taken.join("\", \"")),
+            (AuditAction::Check, None) => info!("Determinism: no recorded checkpoints yet, this run's are {}", taken.join(" ")),
+            (AuditAction::Check, Some(recorded)) => match taken.iter().zip(recorded.iter()).position(|(a, b)| a != b) {
+                Some(k) => {
+                    error!("Determinism: FAIL, the checkpoint at step {} is {} where {} was recorded",
+                        (k as u32 + 1) * CHECKPOINT_INTERVAL, taken[k], recorded[k]);
+                    passed = false;
+                }
+                None => info!("Determinism: all {} checkpoints match the recorded ones", recorded.len()),
+            },
+        }
+        self.passed = Some(passed);
+    }
+
+    pub fn summary(&self) -> String
+    {
+        let steps : usize = self.runs.iter().map(|r| r.len()).sum();
+        match self.passed {
+            None => format!("Determinism audit: {} of {} steps", steps, 2 * AUDIT_STEPS),
+            Some(true) => "Determinism audit: pass".to_string(),
+            Some(false) => "Determinism audit: FAIL, see the log".to_string(),
+        }
+    }
+}
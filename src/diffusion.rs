@@ -0,0 +1,80 @@
+use glam::*;
+use crate::cloth::Constraint;
+
+// An experiment in smoothing the stored distance lambdas across constraints that share a particle
+// before they warm start the next step. When the cloth moves, last step's impulses have moved with
+// it, so a constraint's neighbours may know better than its own lambda what it will carry. Each
+// lambda moves towards its neighbours' mean by the diffusion factor; 0 leaves them alone.
+
+// The constraints sharing a particle with each constraint, in compressed rows: constraint k's are
+// neighbours[starts[k]..starts[k + 1]]. Built once per topology.
+pub struct LambdaAdjacency
+{
+    starts : Vec<usize>,
+    neighbours : Vec<usize>,
+}
+
+impl LambdaAdjacency {
+    pub fn new() -> LambdaAdjacency
+    {
+        LambdaAdjacency { starts : vec![0], neighbours : vec![] }
+    }
+
+    pub fn build(constraints : &[Constraint], num_particles : usize) -> LambdaAdjacency
+    {
+        let mut by_particle = vec![vec![]; num_particles];
+        for (k, c) in constraints.iter().enumerate() {
+            by_particle[c.p0].push(k);
+            by_particle[c.p1].push(k);
+        }
+
+        let mut adjacency = LambdaAdjacency::new();
+        for (k, c) in constraints.iter().enumerate() {
+            let row_start = adjacency.neighbours.len();
+            for &j in by_particle[c.p0].iter().chain(by_particle[c.p1].iter()) {
+                if j != k && !adjacency.neighbours[row_start..].contains(&j) {
+                    adjacency.neighbours.push(j);
+                }
+            }
+            adjacency.starts.push(adjacency.neighbours.len());
+        }
+        adjacency
+    }
+
+    // How many constraints it was built for.
+    pub fn len(&self) -> usize
+    {
+        self.starts.len() - 1
+    }
+
+    fn neighbours(&self, k : usize) -> &[usize]
+    {
+        &self.neighbours[self.starts[k]..self.starts[k + 1]]
+    }
+}
+
+// Moves each lambda factor of the way to its neighbours' mean. The means are taken from a copy of
+// the lambdas as they were, so the result doesn't depend on the constraint order.
+pub fn diffuse(constraints : &mut [Constraint], adjacency : &LambdaAdjacency, factor : f32, scratch : &mut Vec<Vec3>)
+{
+    if factor <= 0.0 || adjacency.len() != constraints.len() {
+        return;
+    }
+    scratch.clear();
+    scratch.extend(constraints.iter().map(|c| c.lambda));
+    for (k, c) in constraints.iter_mut().enumerate() {
+        let neighbours = adjacency.neighbours(k);
+        if neighbours.is_empty() {
+            continue;
+        }
+        let mean = neighbours.iter().fold(vec3(0.0, 0.0, 0.0), |sum, &j| sum + scratch[j]) / neighbours.len() as f32;
+        c.lambda = scratch[k].lerp(mean, factor);
+    }
+}
+
+// The mean distance residual, for the first iteration's, which is what a better warm start lowers.
+pub fn mean_residual(positions : &[Vec3], constraints : &[Constraint]) -> f32
+{
+    let total : f32 = constraints.iter().map(|c| ((positions[c.p0] - positions[c.p1]).length() - c.length).abs()).sum();
+    total / constraints.len().max(1) as f32
+}
@@ -0,0 +1,35 @@
+use wasm_bindgen::JsCast;
+use wasm_bindgen::JsValue;
+use web_sys::{Blob, BlobPropertyBag, HtmlAnchorElement, Url};
+
+// Offers contents to the user as a file download by clicking a temporary object-URL link.
+pub fn download_text(filename : &str, mime_type : &str, contents : &str) -> Result<(), JsValue>
+{
+    let parts = js_sys::Array::of1(&JsValue::from_str(contents));
+    let options = BlobPropertyBag::new();
+    options.set_type(mime_type);
+    let blob = Blob::new_with_str_sequence_and_options(&parts, &options)?;
+    download_blob(filename, &blob)
+}
+
+pub fn download_bytes(filename : &str, mime_type : &str, contents : &[u8]) -> Result<(), JsValue>
+{
+    let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(contents));
+    let options = BlobPropertyBag::new();
+    options.set_type(mime_type);
+    let blob = Blob::new_with_u8_array_sequence_and_options(&parts, &options)?;
+    download_blob(filename, &blob)
+}
+
+fn download_blob(filename : &str, blob : &Blob) -> Result<(), JsValue>
+{
+    let url = Url::create_object_url_with_blob(blob)?;
+
+    let document = web_sys::window().and_then(|w| w.document()).ok_or("no document")?;
+    let link : HtmlAnchorElement = document.create_element("a")?.dyn_into()?;
+    link.set_href(&url);
+    link.set_download(filename);
+    link.click();
+
+    Url::revoke_object_url(&url)
+}
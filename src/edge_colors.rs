@@ -0,0 +1,34 @@
+// Colors drawn over the cloth's base color on some of its edges. Each layer claims a set of
+// edges at a priority, and an edge claimed by several layers takes the color of the highest, so
+// a warning like the strain alarm shows through whatever other coloring is on.
+pub struct EdgeLayer
+{
+    pub priority : u32,
+    pub color : [f32; 3],
+    pub edges : Vec<usize>,
+}
+
+pub const PROBE_PRIORITY : u32 = 50;
+pub const SWEEP_PRIORITY : u32 = 75;
+pub const ALARM_PRIORITY : u32 = 100;
+
+// The edges each layer ends up owning, lowest priority first, leaving out layers that own none.
+pub fn resolve(num_edges : usize, layers : &[EdgeLayer]) -> Vec<([f32; 3], Vec<usize>)>
+{
+    let mut owner : Vec<Option<usize>> = vec![None; num_edges];
+    for (index, layer) in layers.iter().enumerate() {
+        for &edge in layer.edges.iter().filter(|&&e| e < num_edges) {
+            match owner[edge] {
+                Some(other) if layers[other].priority >= layer.priority => {}
+                _ => owner[edge] = Some(index),
+            }
+        }
+    }
+
+    let mut order : Vec<usize> = (0..layers.len()).collect();
+    order.sort_by_key(|&index| layers[index].priority);
+    order.into_iter()
+        .map(|index| (layers[index].color, (0..num_edges).filter(|&e| owner[e] == Some(index)).collect::<Vec<usize>>()))
+        .filter(|(_, edges)| !edges.is_empty())
+        .collect()
+}
@@ -0,0 +1,66 @@
+use glam::*;
+
+// Particles locked in place mid-motion for staging. Frozen particles are held as pins, so the
+// solver gives them no inverse mass, and each keeps the velocity it had for when it is let go.
+pub struct Freeze
+{
+    pub frozen : Vec<bool>,
+    velocities : Vec<(usize, Vec3)>,
+}
+
+impl Freeze {
+    pub fn new() -> Freeze
+    {
+        Freeze { frozen : vec![], velocities : vec![] }
+    }
+
+    // Forgets every frozen particle without touching the cloth, for when it is rebuilt.
+    pub fn reset(&mut self, num_particles : usize)
+    {
+        self.frozen = vec![false; num_particles];
+        self.velocities.clear();
+    }
+
+    pub fn num_frozen(&self) -> usize
+    {
+        self.velocities.len()
+    }
+
+    // Freezes the particles that aren't already held, stopping them where they are. Returns how
+    // many were frozen.
+    pub fn freeze(&mut self, particles : impl Iterator<Item = usize>, positions : &[Vec3], previous_positions : &mut [Vec3], is_fixed : &mut [bool]) -> usize
+    {
+        let mut count = 0;
+        for p in particles {
+            if is_fixed[p] {
+                continue;
+            }
+            self.velocities.push((p, positions[p] - previous_positions[p]));
+            previous_positions[p] = positions[p];
+            is_fixed[p] = true;
+            self.frozen[p] = true;
+            count += 1;
+        }
+        count
+    }
+
+    // Lets one particle go with the velocity it was frozen with. Does nothing if it isn't frozen.
+    pub fn release(&mut self, p : usize, positions : &[Vec3], previous_positions : &mut [Vec3], is_fixed : &mut [bool])
+    {
+        if let Some(k) = self.velocities.iter().position(|&(q, _)| q == p) {
+            let (_, velocity) = self.velocities.swap_remove(k);
+            previous_positions[p] = positions[p] - velocity;
+            is_fixed[p] = false;
+            self.frozen[p] = false;
+        }
+    }
+
+    pub fn unfreeze(&mut self, positions : &[Vec3], previous_positions : &mut [Vec3], is_fixed : &mut [bool])
+    {
+        for (p, velocity) in self.velocities.drain(..) {
+            previous_positions[p] = positions[p] - velocity;
+            is_fixed[p] = false;
+            self.frozen[p] = false;
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use glam::*;
+use log::error;
+use serde_json::{json, Value};
+use std::cell::RefCell;
+use crate::cloth::Constraint;
+use crate::rng::{Pcg32, FUZZ_STREAM};
+use crate::timeline::{TimelineEvent, PARAMETERS};
+
+// Steps between actions are drawn from this range.
+const MIN_ACTION_GAP : i32 = 5;
+const MAX_ACTION_GAP : i32 = 120;
+
+// The largest displacement a pull or nudge gives, in simulation units.
+const MAX_KICK : f64 = 0.3;
+
+thread_local! {
+    // The latest replay of the run in progress, for the panic hook to report.
+    static REPORT : RefCell<Option<String>> = RefCell::new(None);
+}
+
+// The values a timeline parameter is drawn from. Stiffnesses are drawn on a log scale, as their
+// sliders are.
+fn random_value(rng : &mut Pcg32, name : &str) -> f32
+{
+    let switch = |rng : &mut Pcg32| if rng.next_f64() < 0.5 {0.0} else {1.0};
+    match name {
+        "eta" | "nu" => rng.range(0.0, 1.0) as f32,
+        "lambda_diffusion" => rng.range(0.0, 0.5) as f32,
+        "stiffness" | "area_stiffness" => 10f64.powf(rng.range(3.0, 8.0)) as f32,
+        "weight_mass" => rng.range(0.1, 20.0) as f32,
+        "gravity_angle" => rng.range(0.0, 360.0) as f32,
+        "gravity_period" => rng.range(0.0, 60.0) as f32,
+        "num_iterations" => (rng.next_u32() % 11) as f32,
+        _ => switch(rng),
+    }
+}
+
+fn random_offset(rng : &mut Pcg32) -> [f32; 3]
+{
+    let mut axis = || rng.range(-MAX_KICK, MAX_KICK) as f32;
+    [axis(), axis(), axis()]
+}
+
+// One thing the fuzzer did, in the terms of a timeline keyframe so a run can be played back.
+#[derive(Clone)]
+pub enum FuzzAction
+{
+    Param { name : &'static str, value : f32 },
+    Event(TimelineEvent),
+}
+
+// Pokes the simulation with random but valid parameter changes and interactions at random
+// intervals, all drawn from the session seed, and keeps a log of them. Every step the model
+// checks the invariants below; the first that breaks ends the run with the log as a timeline
+// that plays the same actions back.
+pub struct Fuzzer
+{
+    pub seed : u64,
+    rng : Pcg32,
+    // Steps taken since the run started, and the step the next action comes before.
+    pub steps : i32,
+    next_action : i32,
+    // Each action with the step it came before.
+    pub actions : Vec<(i32, FuzzAction)>,
+    // Parameter values when the run started. Runs start from a reset, so with these playback
+    // starts from the same place.
+    initial : Vec<(&'static str, f32)>,
+    dt : f32,
+    pub failure : Option<String>,
+}
+
+impl Fuzzer {
+    pub fn new(seed : u64, initial : Vec<(&'static str, f32)>, dt : f32) -> Fuzzer
+    {
+        let mut fuzzer = Fuzzer {
+            seed : seed,
+            rng : Pcg32::new(seed, FUZZ_STREAM),
+            steps : 0,
+            next_action : 0,
+            actions : vec![],
+            initial : initial,
+            dt : dt,
+            failure : None,
+        };
+        fuzzer.schedule();
+        fuzzer
+    }
+
+    fn schedule(&mut self)
+    {
+        self.next_action = self.steps + MIN_ACTION_GAP + (self.rng.next_u32() % (MAX_ACTION_GAP - MIN_ACTION_GAP + 1) as u32) as i32;
+    }
+
+    // Counts one step, returning the action to take before it if one is due.
+    pub fn next(&mut self, num_particles : usize) -> Option<FuzzAction>
+    {
+        self.steps += 1;
+        if self.failure.is_some() || self.steps < self.next_action || num_particles == 0 {
+            return None;
+        }
+        self.schedule();
+
+        let particle = (self.rng.next_u32() as usize) % num_particles;
+        let action = match self.rng.next_u32() % 10 {
+            0..=4 => {
+                let name = PARAMETERS[self.rng.next_u32() as usize % PARAMETERS.len()].0;
+                FuzzAction::Param { name : name, value : random_value(&mut self.rng, name) }
+            }
+            5 => FuzzAction::Event(TimelineEvent::Reset),
+            6 => FuzzAction::Event(TimelineEvent::Pin { particle : particle }),
+            7 => FuzzAction::Event(TimelineEvent::ReleasePin { particle : Some(particle) }),
+            8 => FuzzAction::Event(TimelineEvent::Pull { particle : particle, offset : random_offset(&mut self.rng) }),
+            _ => FuzzAction::Event(TimelineEvent::Nudge { offset : random_offset(&mut self.rng) }),
+        };
+        self.actions.push((self.steps, action.clone()));
+        REPORT.with(|report| *report.borrow_mut() = Some(self.report()));
+        Some(action)
+    }
+
+    // The run so far as timeline JSON. An action before step n lands between steps n-1 and n, and
+    // a parameter holds its old value up to step n-1 so playback doesn't ease it in.
+    pub fn to_timeline(&self) -> String
+    {
+        let dt = self.dt;
+        let mut current = self.initial.clone();
+        let params = |values : &[(&str, f32)]| values.iter().map(|&(name, value)| (name.to_string(), json!(value))).collect::<serde_json::Map<String, Value>>();
+        let mut keyframes = vec![json!({ "time" : 0.0, "params" : params(&current), "events" : [TimelineEvent::Reset] })];
+        for (step, action) in self.actions.iter() {
+            let before = (*step as f32 - 1.0) * dt;
+            let time = (*step as f32 - 0.5) * dt;
+            match action {
+                FuzzAction::Param { name, value } => {
+                    if let Some(entry) = current.iter_mut().find(|(n, _)| n == name) {
+                        keyframes.push(json!({ "time" : before, "params" : { (*name) : entry.1 } }));
+                        entry.1 = *value;
+                    }
+                    keyframes.push(json!({ "time" : time, "params" : { (*name) : value } }));
+                }
+                FuzzAction::Event(event) => keyframes.push(json!({ "time" : time, "events" : [event] })),
+            }
+        }
+        serde_json::to_string_pretty(&json!({ "keyframes" : keyframes })).unwrap_or_default()
+    }
+
+    fn report(&self) -> String
+    {
+        format!("Fuzzing seed {}, {} actions, replayable as this timeline:\n{}", self.seed, self.actions.len(), self.to_timeline())
+    }
+
+    pub fn fail(&mut self, reason : String)
+    {
+        error!("Fuzzing seed {} failed at step {}: {}", self.seed, self.steps, reason);
+        self.failure = Some(reason);
+    }
+}
+
+// Adds the fuzz report to whatever the console shows for a panic.
+pub fn log_panic_report()
+{
+    REPORT.with(|report| {
+        if let Some(report) = report.borrow().as_ref() {
+            error!("Panicked while fuzzing. {}", report);
+        }
+    });
+}
+
+// What a step must leave true, whatever came before it.
+pub fn check_invariants(positions : &[Vec3], previous_positions : &[Vec3], is_fixed : &[bool], constraints : &[Constraint], bend_constraints : &[Constraint]) -> Result<(), String>
+{
+    let n = positions.len();
+    if previous_positions.len() != n || is_fixed.len() != n {
+        return Err(format!("{} positions but {} previous positions and {} pin flags", n, previous_positions.len(), is_fixed.len()));
+    }
+    if let Some(i) = positions.iter().position(|p| !(p.x.is_finite() && p.y.is_finite() && p.z.is_finite())) {
+        return Err(format!("particle {} is at {:?}", i, positions[i]));
+    }
+    for (kind, list) in [("constraint", constraints), ("bend constraint", bend_constraints)].iter() {
+        if let Some(k) = list.iter().position(|c| c.p0 >= n || c.p1 >= n) {
+            return Err(format!("{} {} joins {} and {} with only {} particles", kind, k, list[k].p0, list[k].p1, n));
+        }
+    }
+    Ok(())
+}
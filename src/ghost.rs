@@ -0,0 +1,74 @@
+use glam::*;
+use crate::recording::{DecodedFrame, Recorder};
+
+// A recorded run drawn as a pale wireframe behind the live cloth, at the recorded frame for the
+// live step. Run the same scenario from a reset and the two overlap exactly while the runs agree,
+// so a changed parameter shows as the wireframes drifting apart.
+
+// Decoded frames kept, enough for the two an interpolated draw blends between.
+const CACHED_FRAMES : usize = 2;
+
+pub struct Ghost
+{
+    pub name : String,
+    recording : Recorder,
+    decoded : DecodedFrame,
+    cache : Vec<(usize, Vec<Vec3>)>,
+    // Added to the live step to find the ghost's frame, so a positive offset runs it ahead.
+    pub offset : i32,
+    pub visible : bool,
+}
+
+impl Ghost {
+    // Only a recording of every particle of a cloth the same size can be laid over it.
+    pub fn new(name : String, recording : Recorder, num_particles : usize) -> Result<Ghost, String>
+    {
+        let particles = recording.particles();
+        if particles.len() != num_particles {
+            return Err(format!("it has {} particles but the cloth has {}", particles.len(), num_particles));
+        }
+        if particles.iter().enumerate().any(|(k, &i)| k != i) {
+            return Err("it kept only some of the particles".to_string());
+        }
+        if recording.num_frames() == 0 {
+            return Err("it has no frames".to_string());
+        }
+        Ok(Ghost { name : name, recording : recording, decoded : DecodedFrame::new(), cache : vec![], offset : 0, visible : true })
+    }
+
+    pub fn num_particles(&self) -> usize
+    {
+        self.recording.num_particles()
+    }
+
+    pub fn num_frames(&self) -> usize
+    {
+        self.recording.num_frames()
+    }
+
+    pub fn frame_at_step(&self, step : i32) -> Option<usize>
+    {
+        self.recording.frame_at_step(step + self.offset)
+    }
+
+    // The positions after the given live step, or None once the ghost has run out of frames.
+    pub fn positions(&mut self, step : i32) -> Option<&[Vec3]>
+    {
+        let k = self.frame_at_step(step)?;
+        let cached = match self.cache.iter().position(|(j, _)| *j == k) {
+            Some(cached) => cached,
+            None => {
+                if !self.recording.decode(k, &mut self.decoded) {
+                    return None;
+                }
+                let positions = self.decoded.values.chunks_exact(3).map(|p| vec3(p[0], p[1], p[2])).collect();
+                if self.cache.len() >= CACHED_FRAMES {
+                    self.cache.remove(0);
+                }
+                self.cache.push((k, positions));
+                self.cache.len() - 1
+            }
+        };
+        Some(&self.cache[cached].1)
+    }
+}
@@ -0,0 +1,172 @@
+use log::{error, info};
+use web_sys::WebGlRenderingContext as GL;
+
+// Golden-image checks for the renderer, run with ?golden=check in the query string. Each
+// scenario resets the cloth, takes a fixed number of steps, draws, and reads the picture back.
+// The picture is reduced to a GRID x GRID block average, and each cell is set when it is darker
+// than the picture's mean, so antialiasing and driver differences move few cells while a missing
+// layer or a shifted cloth moves many. ?golden=update runs the same scenarios and prints the
+// hashes to paste in below.
+
+pub const GRID : usize = 32;
+
+// Golden runs draw at this size whatever the window, so the cells cover the same part of the view.
+pub const CANVAS_SIZE : u32 = 512;
+
+// Cells that may differ before a picture fails.
+pub const TOLERANCE : u32 = 24;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum GoldenMode
+{
+    // The plain edges.
+    Wireframe,
+    // Particles drawn as points colored along the palette ramp by valence.
+    Valence,
+    // Both eyes' pictures, which covers the color masks.
+    Anaglyph,
+}
+
+pub struct GoldenScenario
+{
+    pub name : &'static str,
+    pub mode : GoldenMode,
+    pub steps : i32,
+    // The hash of the accepted picture, as printed by an update run. None until one is taken.
+    pub hash : Option<&'static str>,
+}
+
+pub const SCENARIOS : [GoldenScenario; 3] = [
+    GoldenScenario { name : "wireframe", mode : GoldenMode::Wireframe, steps : 60, hash : None },
+    GoldenScenario { name : "valence_points", mode : GoldenMode::Valence, steps : 60, hash : None },
+    GoldenScenario { name : "anaglyph", mode : GoldenMode::Anaglyph, steps : 60, hash : None },
+];
+
+// Checking compares against the goldens. Updating prints new ones instead.
+#[derive(Clone, Copy, PartialEq)]
+pub enum GoldenAction
+{
+    Check,
+    Update,
+}
+
+// The action asked for in the query string, if any.
+pub fn requested() -> Option<GoldenAction>
+{
+    let search = web_sys::window()?.location().search().ok()?;
+    search.trim_start_matches('?').split('&').find_map(|pair| match pair {
+        "golden=check" => Some(GoldenAction::Check),
+        "golden=update" => Some(GoldenAction::Update),
+        _ => None,
+    })
+}
+
+// The hash of what is in the bound framebuffer, as GRID * GRID bits written out in hex.
+pub fn read_hash(gl : &GL, width : i32, height : i32) -> Option<String>
+{
+    if width < GRID as i32 || height < GRID as i32 {
+        return None;
+    }
+    let mut pixels = vec![0u8; (width * height * 4) as usize];
+    gl.read_pixels_with_opt_u8_array(0, 0, width, height, GL::RGBA, GL::UNSIGNED_BYTE, Some(&mut pixels)).ok()?;
+
+    let (width, height) = (width as usize, height as usize);
+    let mut cells = vec![(0.0f32, 0u32); GRID * GRID];
+    for y in 0..height {
+        for x in 0..width {
+            let p = &pixels[(y * width + x) * 4..];
+            let luminance = 0.299 * p[0] as f32 + 0.587 * p[1] as f32 + 0.114 * p[2] as f32;
+            let cell = &mut cells[(y * GRID / height) * GRID + x * GRID / width];
+            cell.0 += luminance;
+            cell.1 += 1;
+        }
+    }
+    let means : Vec<f32> = cells.iter().map(|&(sum, count)| sum / count.max(1) as f32).collect();
+    let mean = means.iter().sum::<f32>() / means.len() as f32;
+
+    Some(means.chunks(4).map(|nibble| {
+        let bits = nibble.iter().enumerate().fold(0u32, |bits, (k, &m)| if m < mean {bits | 1 << k} else {bits});
+        std::char::from_digit(bits, 16).unwrap()
+    }).collect())
+}
+
+// How many cells two hashes differ in, or None if they aren't both hashes of the same grid.
+pub fn differing_cells(a : &str, b : &str) -> Option<u32>
+{
+    if a.len() != b.len() || a.len() != GRID * GRID / 4 {
+        return None;
+    }
+    a.chars().zip(b.chars())
+        .map(|(x, y)| Some((x.to_digit(16)? ^ y.to_digit(16)?).count_ones()))
+        .sum()
+}
+
+// A golden run in progress, one scenario a frame.
+pub struct GoldenRun
+{
+    pub action : GoldenAction,
+    pub next : usize,
+    pub passed : u32,
+    pub failed : u32,
+    pub missing : u32,
+}
+
+impl GoldenRun {
+    pub fn new(action : GoldenAction) -> GoldenRun
+    {
+        GoldenRun { action : action, next : 0, passed : 0, failed : 0, missing : 0 }
+    }
+
+    pub fn is_done(&self) -> bool
+    {
+        self.next == SCENARIOS.len()
+    }
+
+    // Takes the hash of the scenario just drawn and moves on to the next.
+    pub fn record(&mut self, hash : Option<String>)
+    {
+        let scenario = &SCENARIOS[self.next];
+        self.next += 1;
+        let hash = match hash {
+            Some(hash) => hash,
+            None => {
+                error!("Golden {}: the picture couldn't be read back", scenario.name);
+                self.failed += 1;
+                return;
+            }
+        };
+
+        match (self.action, scenario.hash) {
+            (GoldenAction::Update, _) => {
+                info!("Golden {}: hash : Some(\"{}\")", scenario.name, hash);
+            }
+            (GoldenAction::Check, None) => {
+                info!("Golden {}: no golden yet, this run's is {}", scenario.name, hash);
+                self.missing += 1;
+            }
+            (GoldenAction::Check, Some(golden)) => match differing_cells(golden, &hash) {
+                Some(differing) if differing <= TOLERANCE => {
+                    info!("Golden {}: pass, {} of {} cells differ", scenario.name, differing, GRID * GRID);
+                    self.passed += 1;
+                }
+                differing => {
+                    error!("Golden {}: FAIL, {} cells differ against a tolerance of {}; this run's hash is {}",
+                        scenario.name, differing.map_or("all".to_string(), |d| d.to_string()), TOLERANCE, hash);
+                    self.failed += 1;
+                }
+            },
+        }
+        if self.is_done() {
+            info!("{}", self.summary());
+        }
+    }
+
+    pub fn summary(&self) -> String
+    {
+        match self.action {
+            GoldenAction::Update => format!("Golden update: {} of {} hashes printed to the console", self.next, SCENARIOS.len()),
+            GoldenAction::Check => format!("Golden check: {} passed, {} failed, {} without a golden{}",
+                self.passed, self.failed, self.missing, if self.is_done() {""} else {" so far"}),
+        }
+    }
+}
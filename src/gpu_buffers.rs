@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+use web_sys::{WebGlBuffer, WebGlRenderingContext as GL};
+
+// What the registry needs from a GL context. Only GL implements it outside of tests, which
+// count buffers with a stand-in instead.
+pub trait BufferContext
+{
+    type Buffer : Clone;
+
+    fn new_buffer(&self) -> Self::Buffer;
+    fn free_buffer(&self, buffer : &Self::Buffer);
+}
+
+impl BufferContext for GL {
+    type Buffer = WebGlBuffer;
+
+    fn new_buffer(&self) -> WebGlBuffer
+    {
+        self.create_buffer().expect("Failed to create GL buffer")
+    }
+
+    fn free_buffer(&self, buffer : &WebGlBuffer)
+    {
+        self.delete_buffer(Some(buffer));
+    }
+}
+
+struct GpuBuffer<B>
+{
+    buffer : B,
+    bytes : usize,
+    last_used_frame : u64,
+}
+
+// Owns every GL buffer the draw passes use, keyed by name. Passes ask for their buffers each
+// frame through get_or_create, and anything that hasn't been asked for in a while (because the
+// visualization that wanted it was switched off) gets deleted by collect.
+pub struct GpuBuffers<C : BufferContext = GL>
+{
+    buffers : HashMap<String, GpuBuffer<C::Buffer>>,
+    frame : u64,
+    max_idle_frames : u64,
+}
+
+impl<C : BufferContext> GpuBuffers<C> {
+    pub fn new(max_idle_frames : u64) -> GpuBuffers<C>
+    {
+        GpuBuffers {
+            buffers : HashMap::new(),
+            frame : 0,
+            max_idle_frames : max_idle_frames,
+        }
+    }
+
+    pub fn begin_frame(&mut self)
+    {
+        self.frame += 1;
+    }
+
+    // Returns the buffer registered under name, creating it if needed. size_hint is the number
+    // of bytes the caller is about to upload and is only used for the debug readout.
+    pub fn get_or_create(&mut self, gl : &C, name : &str, size_hint : usize) -> C::Buffer
+    {
+        let frame = self.frame;
+        let entry = self.buffers.entry(name.to_string()).or_insert_with(|| GpuBuffer {
+            buffer : gl.new_buffer(),
+            bytes : 0,
+            last_used_frame : frame,
+        });
+        entry.bytes = size_hint;
+        entry.last_used_frame = frame;
+        entry.buffer.clone()
+    }
+
+    // Deletes every buffer whose name starts with prefix, for use when a feature is disabled.
+    pub fn release_prefix(&mut self, gl : &C, prefix : &str)
+    {
+        self.buffers.retain(|name, b| {
+            let keep = !name.starts_with(prefix);
+            if !keep {
+                gl.free_buffer(&b.buffer);
+            }
+            keep
+        });
+    }
+
+    // Deletes buffers that haven't been used for more than max_idle_frames frames.
+    pub fn collect(&mut self, gl : &C)
+    {
+        let frame = self.frame;
+        let max_idle_frames = self.max_idle_frames;
+        self.buffers.retain(|name, b| {
+            let keep = frame - b.last_used_frame <= max_idle_frames;
+            if !keep {
+                log::debug!("Deleting GL buffer {} after {} idle frames", name, max_idle_frames);
+                gl.free_buffer(&b.buffer);
+            }
+            keep
+        });
+    }
+
+    pub fn live_count(&self) -> usize
+    {
+        self.buffers.len()
+    }
+
+    pub fn total_bytes(&self) -> usize
+    {
+        self.buffers.values().map(|b| b.bytes).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    // Hands out numbered buffers and counts how many are alive.
+    #[derive(Default)]
+    struct Counter
+    {
+        created : Cell<usize>,
+        freed : Cell<usize>,
+    }
+
+    impl Counter {
+        fn live(&self) -> usize
+        {
+            self.created.get() - self.freed.get()
+        }
+    }
+
+    impl BufferContext for Counter {
+        type Buffer = usize;
+
+        fn new_buffer(&self) -> usize
+        {
+            self.created.set(self.created.get() + 1);
+            self.created.get()
+        }
+
+        fn free_buffer(&self, _buffer : &usize)
+        {
+            self.freed.set(self.freed.get() + 1);
+        }
+    }
+
+    const MAX_IDLE_FRAMES : u64 = 60;
+    // Drawn every frame whatever is switched on.
+    const BASELINE : [&str; 2] = ["cloth_vertices", "cloth_indices"];
+    // Buffers of the optional passes, and the prefix released when one is switched off, if any.
+    const VISUALIZATIONS : [(&[&str], Option<&str>); 5] = [
+        (&["ghost_vertices"], None),
+        (&["stereo_vertices"], None),
+        (&["chart_residual_0", "chart_residual_1"], None),
+        (&["sdf_contour"], Some("sdf_")),
+        (&["rail_0", "rail_1"], None),
+    ];
+
+    fn frame(buffers : &mut GpuBuffers<Counter>, gl : &Counter, names : &[&str])
+    {
+        buffers.begin_frame();
+        for name in BASELINE.iter().chain(names.iter()) {
+            buffers.get_or_create(gl, name, 64);
+        }
+        buffers.collect(gl);
+    }
+
+    #[test]
+    fn toggling_every_visualization_returns_to_the_baseline()
+    {
+        let gl = Counter::default();
+        let mut buffers = GpuBuffers::new(MAX_IDLE_FRAMES);
+        frame(&mut buffers, &gl, &[]);
+        let baseline = buffers.live_count();
+        assert_eq!(baseline, BASELINE.len());
+
+        for _ in 0..10 {
+            let on : Vec<&str> = VISUALIZATIONS.iter().flat_map(|(names, _)| names.iter().cloned()).collect();
+            for _ in 0..5 {
+                frame(&mut buffers, &gl, &on);
+            }
+            assert_eq!(buffers.live_count(), baseline + on.len());
+            for (_, prefix) in VISUALIZATIONS.iter() {
+                if let Some(prefix) = prefix {
+                    buffers.release_prefix(&gl, prefix);
+                }
+            }
+            frame(&mut buffers, &gl, &[]);
+        }
+        for _ in 0..=MAX_IDLE_FRAMES {
+            frame(&mut buffers, &gl, &[]);
+        }
+        assert_eq!(buffers.live_count(), baseline);
+        assert_eq!(gl.live(), baseline);
+        assert_eq!(buffers.total_bytes(), 64 * baseline);
+    }
+
+    #[test]
+    fn buffers_in_use_are_reused_not_recreated()
+    {
+        let gl = Counter::default();
+        let mut buffers = GpuBuffers::new(MAX_IDLE_FRAMES);
+        for _ in 0..100 {
+            frame(&mut buffers, &gl, &["ghost_vertices"]);
+        }
+        assert_eq!(gl.created.get(), 3);
+        assert_eq!(gl.freed.get(), 0);
+    }
+
+    #[test]
+    fn idle_buffers_last_exactly_max_idle_frames()
+    {
+        let gl = Counter::default();
+        let mut buffers = GpuBuffers::new(MAX_IDLE_FRAMES);
+        frame(&mut buffers, &gl, &["ghost_vertices"]);
+        for _ in 0..MAX_IDLE_FRAMES {
+            frame(&mut buffers, &gl, &[]);
+        }
+        assert_eq!(buffers.live_count(), 3);
+        frame(&mut buffers, &gl, &[]);
+        assert_eq!(buffers.live_count(), 2);
+        assert_eq!(gl.live(), 2);
+    }
+
+    #[test]
+    fn release_prefix_frees_only_its_buffers()
+    {
+        let gl = Counter::default();
+        let mut buffers = GpuBuffers::new(MAX_IDLE_FRAMES);
+        frame(&mut buffers, &gl, &["sdf_contour", "sdf_field", "stereo_vertices"]);
+        buffers.release_prefix(&gl, "sdf_");
+        assert_eq!(buffers.live_count(), 3);
+        assert_eq!(gl.freed.get(), 2);
+    }
+}
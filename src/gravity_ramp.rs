@@ -0,0 +1,113 @@
+use crate::params::Params;
+
+// The experiment that goes with the gravity ramp scenario: warm start off and on, each at 1 to 6
+// iterations. Every case starts from a fresh cloth with gravity turning once every TURN_SECONDS,
+// and runs for two turns. The mean distance residual is taken over the second, so the cloth's
+// first fall from the rest pose doesn't count against either side.
+
+pub const TURN_SECONDS : f32 = 60.0;
+pub const ITERATIONS : [i32; 6] = [1, 2, 3, 4, 5, 6];
+
+// Milliseconds of stepping a frame, so the page keeps drawing while it runs.
+pub const BUDGET_MS : f64 = 12.0;
+
+#[derive(Clone, Copy)]
+pub struct RampCase
+{
+    pub warm_start : bool,
+    pub iterations : i32,
+}
+
+pub struct RampResult
+{
+    pub case : RampCase,
+    pub mean_residual : f32,
+}
+
+pub struct RampExperiment
+{
+    pub cases : Vec<RampCase>,
+    pub results : Vec<RampResult>,
+    pub steps_per_turn : i32,
+    // Steps taken in the case under way.
+    pub steps_done : i32,
+    residual_sum : f64,
+    pub settings : String,
+    // The parameters from before, put back when the experiment ends.
+    pub saved_params : Params,
+}
+
+impl RampExperiment {
+    pub fn new(params : &Params, settings : String) -> RampExperiment
+    {
+        let cases = [false, true].iter()
+            .flat_map(|&warm_start| ITERATIONS.iter().map(move |&iterations| RampCase { warm_start : warm_start, iterations : iterations }))
+            .collect();
+        RampExperiment {
+            cases : cases,
+            results : vec![],
+            steps_per_turn : (TURN_SECONDS / params.dt).round() as i32,
+            steps_done : 0,
+            residual_sum : 0.0,
+            settings : settings,
+            saved_params : params.clone(),
+        }
+    }
+
+    // The case under way, or None once they have all run.
+    pub fn current(&self) -> Option<RampCase>
+    {
+        self.cases.get(self.results.len()).cloned()
+    }
+
+    // Adds a step's mean residual. The case ends after two turns.
+    pub fn record(&mut self, mean_residual : f32)
+    {
+        let case = match self.current() {
+            Some(case) => case,
+            None => return,
+        };
+        self.steps_done += 1;
+        if self.steps_done > self.steps_per_turn {
+            self.residual_sum += mean_residual as f64;
+        }
+        if self.steps_done >= 2 * self.steps_per_turn {
+            let mean_residual = (self.residual_sum / self.steps_per_turn.max(1) as f64) as f32;
+            self.results.push(RampResult { case : case, mean_residual : mean_residual });
+            self.steps_done = 0;
+            self.residual_sum = 0.0;
+        }
+    }
+
+    pub fn progress(&self) -> String
+    {
+        match self.current() {
+            Some(case) => format!("Gravity ramp: case {} of {} (warm start {}, {} iterations), {:.0}%",
+                self.results.len() + 1, self.cases.len(), if case.warm_start {"on"} else {"off"}, case.iterations,
+                100.0 * self.steps_done as f32 / (2 * self.steps_per_turn).max(1) as f32),
+            None => "Gravity ramp: done".to_string(),
+        }
+    }
+
+    // The fewest warm started iterations that do at least as well as the cold start at the given
+    // count, if any do.
+    pub fn warm_iterations_to_match(&self, iterations : i32) -> Option<i32>
+    {
+        let cold = self.results.iter().find(|r| !r.case.warm_start && r.case.iterations == iterations)?;
+        self.results.iter()
+            .filter(|r| r.case.warm_start && r.mean_residual <= cold.mean_residual)
+            .map(|r| r.case.iterations)
+            .min()
+    }
+
+    pub fn to_csv(&self) -> String
+    {
+        let mut csv = format!("# {}, gravity turning every {}s\n", self.settings, TURN_SECONDS);
+        csv.push_str("warm_start,iterations,mean_residual,warm_iterations_to_match\n");
+        for r in self.results.iter() {
+            let matched = if r.case.warm_start {None} else {self.warm_iterations_to_match(r.case.iterations)};
+            csv.push_str(&format!("{},{},{},{}\n", r.case.warm_start, r.case.iterations, r.mean_residual, matched.map_or(String::new(), |k| k.to_string())));
+        }
+        csv
+    }
+}
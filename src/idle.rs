@@ -0,0 +1,70 @@
+use glam::*;
+
+// Stops stepping a cloth that has come to rest, which would otherwise burn power redrawing the
+// same frame for as long as the tab is left open. Lambdas are untouched while asleep, so the
+// first step after waking warm starts exactly where the last one left off.
+pub struct IdleTracker
+{
+    pub enabled : bool,
+    // The largest distance any particle may move in a step and still count as at rest.
+    pub threshold : f32,
+    // Simulated seconds the cloth must stay at rest, with no input, before it sleeps.
+    pub delay : f32,
+    // Draw once a second rather than every frame while asleep.
+    pub low_frame_rate : bool,
+    still_time : f32,
+    sleeping : bool,
+}
+
+impl IdleTracker {
+    pub fn new() -> IdleTracker
+    {
+        IdleTracker {
+            enabled : true,
+            threshold : 1e-5,
+            delay : 10.0,
+            low_frame_rate : true,
+            still_time : 0.0,
+            sleeping : false,
+        }
+    }
+
+    pub fn is_sleeping(&self) -> bool
+    {
+        self.sleeping
+    }
+
+    // Starts the rest period over. Returns whether it was asleep.
+    pub fn wake(&mut self) -> bool
+    {
+        let was_sleeping = self.sleeping;
+        self.still_time = 0.0;
+        self.sleeping = false;
+        was_sleeping
+    }
+
+    // Counts a step of length dt whose largest particle displacement was max_displacement.
+    // Returns true on the step it falls asleep.
+    pub fn observe(&mut self, max_displacement : f32, dt : f32) -> bool
+    {
+        if !self.enabled || max_displacement >= self.threshold {
+            self.still_time = 0.0;
+            return false;
+        }
+        self.still_time += dt;
+        if !self.sleeping && self.still_time >= self.delay {
+            self.sleeping = true;
+            return true;
+        }
+        false
+    }
+}
+
+// How far the furthest moving particle went in the last step.
+pub fn max_displacement(positions : &[Vec3], previous_positions : &[Vec3], is_fixed : &[bool]) -> f32
+{
+    positions.iter().zip(previous_positions.iter()).zip(is_fixed.iter())
+        .filter(|&(_, &fixed)| !fixed)
+        .map(|((p, q), _)| (*p - *q).length())
+        .fold(0.0, f32::max)
+}
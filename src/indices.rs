@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+use web_sys::{WebGlBuffer, WebGlRenderingContext as GL};
+use crate::cloth::Constraint;
+use crate::gpu_buffers::GpuBuffers;
+
+// Vertices a 16 bit index can address.
+pub const MAX_SHORT_VERTICES : usize = 65536;
+
+// How element indices reach GL. Without OES_element_index_uint, WebGL1 only takes 16 bit indices,
+// so grids past MAX_SHORT_VERTICES particles are drawn in batches, each with its own vertices.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum IndexMode
+{
+    Uint,
+    Short,
+    Batched,
+}
+
+impl IndexMode {
+    pub fn choose(has_uint : bool, num_vertices : usize) -> IndexMode
+    {
+        if has_uint {
+            IndexMode::Uint
+        } else if num_vertices <= MAX_SHORT_VERTICES {
+            IndexMode::Short
+        } else {
+            IndexMode::Batched
+        }
+    }
+
+    pub fn name(&self) -> &'static str
+    {
+        match self {
+            IndexMode::Uint => "32 bit",
+            IndexMode::Short => "16 bit",
+            IndexMode::Batched => "16 bit, batched",
+        }
+    }
+}
+
+// A run of primitives touching no more vertices than a 16 bit index can address, indexed into
+// the batch's own list of vertices.
+pub struct IndexBatch
+{
+    // The vertex behind each local index.
+    pub vertices : Vec<u32>,
+    pub indices : Vec<u16>,
+    // Which primitive of the unsplit list each of the batch's came from.
+    pub primitives : Vec<usize>,
+}
+
+// Splits indices, taken primitive_size at a time, into batches of at most max_vertices distinct
+// vertices each. Primitives are kept whole and in their original order.
+pub fn split_batches(indices : &[u32], primitive_size : usize, max_vertices : usize) -> Vec<IndexBatch>
+{
+    let mut batches = vec![];
+    let mut batch = IndexBatch { vertices : vec![], indices : vec![], primitives : vec![] };
+    let mut local : HashMap<u32, u16> = HashMap::new();
+    for (k, primitive) in indices.chunks(primitive_size).enumerate() {
+        let mut new_vertices = primitive.iter().filter(|v| !local.contains_key(v)).collect::<Vec<_>>();
+        new_vertices.dedup();
+        if batch.vertices.len() + new_vertices.len() > max_vertices {
+            batches.push(std::mem::replace(&mut batch, IndexBatch { vertices : vec![], indices : vec![], primitives : vec![] }));
+            local.clear();
+        }
+        for &v in primitive {
+            let index = *local.entry(v).or_insert_with(|| {
+                batch.vertices.push(v);
+                (batch.vertices.len() - 1) as u16
+            });
+            batch.indices.push(index);
+        }
+        batch.primitives.push(k);
+    }
+    if !batch.primitives.is_empty() {
+        batches.push(batch);
+    }
+    batches
+}
+
+// Line indices for the given constraints. Seam constraints would cut straight across a wrapped
+// sheet in a view without depth, so they are left out unless show_seams, which is while depth is
+// sheared into the picture. Every draw of edges builds its indices here.
+pub fn edge_indices(constraints : &[Constraint], edges : impl Iterator<Item = usize>, show_seams : bool) -> Vec<u32>
+{
+    edges.filter(|&k| show_seams || !constraints[k].wraps).flat_map(|k| vec![constraints[k].p0 as u32, constraints[k].p1 as u32]).collect()
+}
+
+// The same for one batch split from every constraint's line, in the batch's own indices.
+pub fn batch_edge_indices(batch : &IndexBatch, constraints : &[Constraint], show_seams : bool) -> Vec<u16>
+{
+    batch.primitives.iter().enumerate().filter(|&(_, &k)| show_seams || !constraints[k].wraps).flat_map(|(j, _)| vec![batch.indices[2 * j], batch.indices[2 * j + 1]]).collect()
+}
+
+// Draws indexed primitives over positions, the 2D vertices already uploaded to vertex_buffer and
+// bound to the position attribute, in whatever way mode calls for. name keys the buffers used in
+// the GPU buffer registry.
+pub fn draw_indexed(gl : &GL, gpu_buffers : &mut GpuBuffers, mode : IndexMode, name : &str, primitive : u32, indices : &[u32], positions : &[f32], vertex_buffer : &WebGlBuffer, position : u32)
+{
+    match mode {
+        IndexMode::Uint => {
+            let array = js_sys::Uint32Array::from(indices);
+            let buffer = gpu_buffers.get_or_create(gl, name, indices.len() * 4);
+            gl.bind_buffer(GL::ELEMENT_ARRAY_BUFFER, Some(&buffer));
+            gl.buffer_data_with_array_buffer_view(GL::ELEMENT_ARRAY_BUFFER, &array, GL::STATIC_DRAW);
+            gl.draw_elements_with_i32(primitive, indices.len() as i32, GL::UNSIGNED_INT, 0);
+        }
+        IndexMode::Short => {
+            let short : Vec<u16> = indices.iter().map(|&i| i as u16).collect();
+            let array = js_sys::Uint16Array::from(short.as_slice());
+            let buffer = gpu_buffers.get_or_create(gl, name, short.len() * 2);
+            gl.bind_buffer(GL::ELEMENT_ARRAY_BUFFER, Some(&buffer));
+            gl.buffer_data_with_array_buffer_view(GL::ELEMENT_ARRAY_BUFFER, &array, GL::STATIC_DRAW);
+            gl.draw_elements_with_i32(primitive, short.len() as i32, GL::UNSIGNED_SHORT, 0);
+        }
+        IndexMode::Batched => {
+            let primitive_size = if primitive == GL::POINTS {1} else {2};
+            let batches = split_batches(indices, primitive_size, MAX_SHORT_VERTICES);
+            draw_batches(gl, gpu_buffers, name, primitive, batches.iter().map(|b| (&b.vertices[..], b.indices.clone())), positions, vertex_buffer, position);
+        }
+    }
+}
+
+// Draws each batch from a copy of its own vertices, then points the position attribute back at
+// vertex_buffer for the draws that follow.
+pub fn draw_batches<'a>(gl : &GL, gpu_buffers : &mut GpuBuffers, name : &str, primitive : u32, batches : impl Iterator<Item = (&'a [u32], Vec<u16>)>, positions : &[f32], vertex_buffer : &WebGlBuffer, position : u32)
+{
+    let vertex_name = format!("{}_batch_vertices", name);
+    let index_name = format!("{}_batch_indices", name);
+    for (vertices, indices) in batches {
+        if indices.is_empty() {
+            continue;
+        }
+        let local : Vec<f32> = vertices.iter().flat_map(|&v| vec![positions[2 * v as usize], positions[2 * v as usize + 1]]).collect();
+        let vertex_array = js_sys::Float32Array::from(local.as_slice());
+        let batch_buffer = gpu_buffers.get_or_create(gl, &vertex_name, local.len() * 4);
+        gl.bind_buffer(GL::ARRAY_BUFFER, Some(&batch_buffer));
+        gl.buffer_data_with_array_buffer_view(GL::ARRAY_BUFFER, &vertex_array, GL::STATIC_DRAW);
+        gl.vertex_attrib_pointer_with_i32(position, 2, GL::FLOAT, false, 0, 0);
+
+        let index_array = js_sys::Uint16Array::from(indices.as_slice());
+        let index_buffer = gpu_buffers.get_or_create(gl, &index_name, indices.len() * 2);
+        gl.bind_buffer(GL::ELEMENT_ARRAY_BUFFER, Some(&index_buffer));
+        gl.buffer_data_with_array_buffer_view(GL::ELEMENT_ARRAY_BUFFER, &index_array, GL::STATIC_DRAW);
+        gl.draw_elements_with_i32(primitive, indices.len() as i32, GL::UNSIGNED_SHORT, 0);
+    }
+    gl.bind_buffer(GL::ARRAY_BUFFER, Some(vertex_buffer));
+    gl.vertex_attrib_pointer_with_i32(position, 2, GL::FLOAT, false, 0, 0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cloth::{build_cloth, Connectivity, Scene};
+
+    // The primitives the batches draw, back in global vertex indices.
+    fn unbatched(batches : &[IndexBatch], primitive_size : usize) -> Vec<Vec<u32>>
+    {
+        batches.iter().flat_map(|b| b.indices.chunks(primitive_size).map(move |p| p.iter().map(|&i| b.vertices[i as usize]).collect::<Vec<u32>>())).collect()
+    }
+
+    fn check(indices : &[u32], primitive_size : usize, max_vertices : usize) -> Vec<IndexBatch>
+    {
+        let batches = split_batches(indices, primitive_size, max_vertices);
+        let expected : Vec<Vec<u32>> = indices.chunks(primitive_size).map(|p| p.to_vec()).collect();
+        assert_eq!(unbatched(&batches, primitive_size), expected);
+        let primitives : Vec<usize> = batches.iter().flat_map(|b| b.primitives.iter().cloned()).collect();
+        assert_eq!(primitives, (0..expected.len()).collect::<Vec<_>>());
+        for b in batches.iter() {
+            assert!(b.vertices.len() <= max_vertices, "a batch has {} vertices", b.vertices.len());
+            assert_eq!(b.indices.len(), b.primitives.len() * primitive_size);
+        }
+        batches
+    }
+
+    #[test]
+    fn grid_straddling_the_limit_draws_the_same_edges()
+    {
+        // 257 by 256 particles is just over what 16 bit indices reach.
+        let cloth = build_cloth(&Scene::Hanging, Connectivity::Eight, false, 257, 256);
+        assert!(cloth.positions.len() > MAX_SHORT_VERTICES);
+        assert_eq!(IndexMode::choose(false, cloth.positions.len()), IndexMode::Batched);
+        let edges : Vec<u32> = cloth.constraints.iter().flat_map(|c| vec![c.p0 as u32, c.p1 as u32]).collect();
+        let batches = check(&edges, 2, MAX_SHORT_VERTICES);
+        assert!(batches.len() >= 2);
+    }
+
+    #[test]
+    fn small_batches_keep_primitives_whole()
+    {
+        let lines = [0, 1, 1, 2, 2, 3, 3, 0, 0, 2, 4, 5, 5, 6, 6, 4];
+        let batches = check(&lines, 2, 3);
+        assert!(batches.len() > 1);
+        check(&[7, 3, 3, 9, 1, 7, 9], 1, 2);
+    }
+
+    #[test]
+    fn everything_fits_in_one_batch_under_the_limit()
+    {
+        let batches = check(&[0, 1, 1, 2, 2, 0], 2, 3);
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].vertices, vec![0, 1, 2]);
+        assert!(split_batches(&[], 2, 3).is_empty());
+    }
+
+    #[test]
+    fn a_flat_view_of_a_wrapped_sheet_draws_every_edge_but_the_seams()
+    {
+        for &connectivity in [Connectivity::Four, Connectivity::Six, Connectivity::Eight].iter() {
+            let cloth = build_cloth(&Scene::Hanging, connectivity, true, 6, 5);
+            let seams = cloth.constraints.iter().filter(|c| c.wraps).count();
+            assert!(seams > 0);
+
+            let all : Vec<u32> = cloth.constraints.iter().flat_map(|c| vec![c.p0 as u32, c.p1 as u32]).collect();
+            let unwrapped : Vec<u32> = cloth.constraints.iter().filter(|c| !c.wraps).flat_map(|c| vec![c.p0 as u32, c.p1 as u32]).collect();
+            assert_eq!(unwrapped.len(), all.len() - 2 * seams);
+            assert_eq!(edge_indices(&cloth.constraints, 0..cloth.constraints.len(), false), unwrapped);
+            assert_eq!(edge_indices(&cloth.constraints, 0..cloth.constraints.len(), true), all);
+
+            // Batched, the same lines come back out.
+            let batches = split_batches(&all, 2, 8);
+            assert!(batches.len() > 1);
+            for &show_seams in [false, true].iter() {
+                let drawn : Vec<u32> = batches.iter().flat_map(|b| batch_edge_indices(b, &cloth.constraints, show_seams).into_iter().map(move |i| b.vertices[i as usize])).collect();
+                assert_eq!(drawn, if show_seams {all.clone()} else {unwrapped.clone()});
+            }
+        }
+    }
+
+    #[test]
+    fn a_layer_draws_only_its_own_edges()
+    {
+        let cloth = build_cloth(&Scene::Hanging, Connectivity::Eight, true, 6, 5);
+        let seam = cloth.constraints.iter().position(|c| c.wraps).unwrap();
+        let c = &cloth.constraints[3];
+        assert_eq!(edge_indices(&cloth.constraints, vec![3, seam].into_iter(), false), vec![c.p0 as u32, c.p1 as u32]);
+        assert_eq!(edge_indices(&cloth.constraints, vec![seam].into_iter(), true).len(), 2);
+    }
+
+    #[test]
+    fn index_mode_follows_the_extension_and_size()
+    {
+        assert_eq!(IndexMode::choose(true, MAX_SHORT_VERTICES * 4), IndexMode::Uint);
+        assert_eq!(IndexMode::choose(false, MAX_SHORT_VERTICES), IndexMode::Short);
+        assert_eq!(IndexMode::choose(false, MAX_SHORT_VERTICES + 1), IndexMode::Batched);
+    }
+}
@@ -0,0 +1,58 @@
+use crate::observer::{SimulationObserver, StepStats};
+use crate::top_k::top_k;
+
+// How many constraints the inspector lists.
+pub const NUM_WORST : usize = 10;
+
+// A listed constraint stays until its residual falls below the weakest newcomer's by this factor,
+// so constraints near the cut don't swap in and out every frame.
+const HYSTERESIS : f32 = 1.5;
+
+// The constraints with the largest post-solve residuals. Rows keep their place while they stay
+// listed, and newcomers take the places that were freed.
+pub struct WorstConstraints
+{
+    pub constraints : Vec<usize>,
+}
+
+impl WorstConstraints {
+    pub fn new() -> WorstConstraints
+    {
+        WorstConstraints { constraints : vec![] }
+    }
+
+    pub fn clear(&mut self)
+    {
+        self.constraints.clear();
+    }
+
+    pub fn update(&mut self, residuals : &[f32])
+    {
+        let candidates = top_k(0..residuals.len(), NUM_WORST, |&i| residuals[i]);
+        let threshold = candidates.last().map_or(0.0, |&i| residuals[i]);
+
+        let mut rows : Vec<Option<usize>> = self.constraints.iter()
+            .map(|&i| if i < residuals.len() && residuals[i] * HYSTERESIS >= threshold {Some(i)} else {None})
+            .collect();
+        rows.resize(NUM_WORST, None);
+
+        let newcomers : Vec<usize> = candidates.into_iter().filter(|i| !rows.contains(&Some(*i))).collect();
+        let mut newcomers = newcomers.into_iter();
+        for row in rows.iter_mut().filter(|row| row.is_none()) {
+            *row = newcomers.next();
+        }
+        self.constraints = rows.into_iter().flatten().collect();
+    }
+}
+
+// Ranks the distance constraints by how far the step left them from satisfied.
+impl SimulationObserver for WorstConstraints {
+    fn on_step_end(&mut self, stats : &StepStats)
+    {
+        let residuals : Vec<f32> = stats.constraints.iter().map(|c| {
+            let error = (stats.positions[c.p0] - stats.positions[c.p1]).length() - c.length;
+            if stats.params.tension_only {error.max(0.0)} else {error.abs()}
+        }).collect();
+        self.update(&residuals);
+    }
+}
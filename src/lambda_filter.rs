@@ -0,0 +1,289 @@
+use glam::*;
+use crate::cloth::{AreaConstraint, Constraint, DihedralConstraint, EdgeKind};
+use crate::contacts::Contact;
+use crate::rail::Anchor;
+
+// Which stored impulses Forget Stored Impulse clears. Area constraints belong to no kind, so only
+// All and Selected region reach them.
+#[derive(Clone, Copy, PartialEq)]
+pub enum LambdaFilter
+{
+    All,
+    // Vertical and horizontal grid edges.
+    Structural,
+    // Diagonal grid edges.
+    Shear,
+    // Distance or dihedral bend constraints, whichever model is in use.
+    Bend,
+    // Self-contacts and collider contacts.
+    Contacts,
+    // Anything touching a particle of the rubber-band selection.
+    Selection,
+}
+
+pub const LAMBDA_FILTERS : [LambdaFilter; 6] = [
+    LambdaFilter::All,
+    LambdaFilter::Structural,
+    LambdaFilter::Shear,
+    LambdaFilter::Bend,
+    LambdaFilter::Contacts,
+    LambdaFilter::Selection,
+];
+
+impl LambdaFilter {
+    pub fn name(&self) -> &'static str
+    {
+        match self {
+            LambdaFilter::All => "all",
+            LambdaFilter::Structural => "structural",
+            LambdaFilter::Shear => "shear",
+            LambdaFilter::Bend => "bend",
+            LambdaFilter::Contacts => "contacts",
+            LambdaFilter::Selection => "selection",
+        }
+    }
+
+    pub fn label(&self) -> &'static str
+    {
+        match self {
+            LambdaFilter::All => "All",
+            LambdaFilter::Structural => "Structural",
+            LambdaFilter::Shear => "Shear",
+            LambdaFilter::Bend => "Bend",
+            LambdaFilter::Contacts => "Contacts",
+            LambdaFilter::Selection => "Selected region",
+        }
+    }
+
+    pub fn from_name(name : &str) -> Option<LambdaFilter>
+    {
+        LAMBDA_FILTERS.iter().copied().find(|f| f.name() == name)
+    }
+
+    // Whether a grid edge of the given family is cleared, going by kind alone. Edges that aren't
+    // grid neighbours, like those from a resample, only go with All.
+    pub fn takes_edge(&self, kind : Option<EdgeKind>) -> bool
+    {
+        match (self, kind) {
+            (LambdaFilter::All, _) => true,
+            (LambdaFilter::Structural, Some(EdgeKind::Vertical)) | (LambdaFilter::Structural, Some(EdgeKind::Horizontal)) => true,
+            (LambdaFilter::Shear, Some(EdgeKind::Diagonal)) | (LambdaFilter::Shear, Some(EdgeKind::AntiDiagonal)) => true,
+            _ => false,
+        }
+    }
+}
+
+// Every lambda carried from step to step, for a filter to clear. Contact impulses are carried like
+// any other, so they are here too.
+pub struct StoredImpulses<'a>
+{
+    pub constraints : &'a mut [Constraint],
+    pub area_constraints : &'a mut [AreaConstraint],
+    pub bend_constraints : &'a mut [Constraint],
+    pub dihedral_constraints : &'a mut [DihedralConstraint],
+    pub contacts : &'a mut [Contact],
+    pub collider_lambda : &'a mut [f32],
+    pub anchors : &'a mut [Anchor],
+}
+
+impl StoredImpulses<'_> {
+    // Zeroes the lambdas the filter takes, leaving the rest untouched. edge_kind gives the grid
+    // family of a distance constraint's ends, and selected whether a particle is in the selection.
+    pub fn clean(&mut self, filter : LambdaFilter, edge_kind : impl Fn(usize, usize) -> Option<EdgeKind>, selected : impl Fn(usize) -> bool)
+    {
+        let in_region = |particles : &[usize]| filter == LambdaFilter::All || (filter == LambdaFilter::Selection && particles.iter().any(|&p| selected(p)));
+        let takes_bend = filter == LambdaFilter::Bend;
+        let takes_contacts = filter == LambdaFilter::Contacts;
+
+        for c in self.constraints.iter_mut() {
+            if filter.takes_edge(edge_kind(c.p0, c.p1)) || in_region(&[c.p0, c.p1]) {
+                c.lambda = vec3(0.0, 0.0, 0.0);
+            }
+        }
+        for c in self.area_constraints.iter_mut() {
+            if in_region(&c.particles) {
+                c.lambda = 0.0;
+            }
+        }
+        for c in self.bend_constraints.iter_mut() {
+            if takes_bend || in_region(&[c.p0, c.p1]) {
+                c.lambda = vec3(0.0, 0.0, 0.0);
+            }
+        }
+        for c in self.dihedral_constraints.iter_mut() {
+            if takes_bend || in_region(&c.particles) {
+                c.lambda = 0.0;
+            }
+        }
+        for c in self.contacts.iter_mut() {
+            let (a, b) = c.key.particles;
+            if takes_contacts || in_region(&[a, b]) {
+                c.lambda = 0.0;
+            }
+        }
+        for (p, lambda) in self.collider_lambda.iter_mut().enumerate() {
+            if takes_contacts || in_region(&[p]) {
+                *lambda = 0.0;
+            }
+        }
+        for a in self.anchors.iter_mut() {
+            if in_region(&[a.particle]) {
+                a.lambda = vec3(0.0, 0.0, 0.0);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cloth::{build_cloth, ClothBuild, Connectivity, Scene};
+    use crate::contacts::ContactKey;
+
+    // A small cloth with every lambda set to a different nonzero value.
+    struct Fixture
+    {
+        cloth : ClothBuild,
+        contacts : Vec<Contact>,
+        collider_lambda : Vec<f32>,
+        anchors : Vec<Anchor>,
+    }
+
+    impl Fixture {
+        fn new() -> Fixture
+        {
+            let mut cloth = build_cloth(&Scene::Hanging, Connectivity::Eight, false, 4, 4);
+            let mut next = 0.0;
+            let mut value = || { next += 1.0; next };
+            for c in cloth.constraints.iter_mut().chain(cloth.bend_constraints.iter_mut()) {
+                c.lambda = vec3(value(), value(), value());
+            }
+            for c in cloth.area_constraints.iter_mut() {
+                c.lambda = value();
+            }
+            for c in cloth.dihedral_constraints.iter_mut() {
+                c.lambda = value();
+            }
+            let contacts = vec![
+                Contact { key : ContactKey { sheets : (0, 0), particles : (0, 15) }, lambda : value(), age : 3 },
+                Contact { key : ContactKey { sheets : (0, 0), particles : (5, 10) }, lambda : value(), age : 1 },
+            ];
+            let collider_lambda = (0..cloth.positions.len()).map(|_| value()).collect();
+            let mut anchors = vec![Anchor::new(5, vec3(0.0, 0.0, 0.0)), Anchor::new(12, vec3(0.0, 0.0, 0.0))];
+            for a in anchors.iter_mut() {
+                a.lambda = vec3(value(), value(), value());
+            }
+            Fixture { cloth : cloth, contacts : contacts, collider_lambda : collider_lambda, anchors : anchors }
+        }
+
+        fn clean(&mut self, filter : LambdaFilter, selected : &[usize])
+        {
+            let grid = self.cloth.sheet_grids[0];
+            let mut impulses = StoredImpulses {
+                constraints : &mut self.cloth.constraints,
+                area_constraints : &mut self.cloth.area_constraints,
+                bend_constraints : &mut self.cloth.bend_constraints,
+                dihedral_constraints : &mut self.cloth.dihedral_constraints,
+                contacts : &mut self.contacts,
+                collider_lambda : &mut self.collider_lambda,
+                anchors : &mut self.anchors,
+            };
+            impulses.clean(filter, |p0, p1| grid.edge_kind(p0, p1), |p| selected.contains(&p));
+        }
+
+        // Every lambda's bits, labelled with what it belongs to and the particles it touches.
+        fn lambdas(&self) -> Vec<(&'static str, Vec<usize>, Vec<u32>)>
+        {
+            let bits = |v : Vec3| vec![v.x.to_bits(), v.y.to_bits(), v.z.to_bits()];
+            let mut lambdas = vec![];
+            for c in self.cloth.constraints.iter() {
+                lambdas.push(("distance", vec![c.p0, c.p1], bits(c.lambda)));
+            }
+            for c in self.cloth.area_constraints.iter() {
+                lambdas.push(("area", c.particles.to_vec(), vec![c.lambda.to_bits()]));
+            }
+            for c in self.cloth.bend_constraints.iter() {
+                lambdas.push(("bend", vec![c.p0, c.p1], bits(c.lambda)));
+            }
+            for c in self.cloth.dihedral_constraints.iter() {
+                lambdas.push(("dihedral", c.particles.to_vec(), vec![c.lambda.to_bits()]));
+            }
+            for c in self.contacts.iter() {
+                lambdas.push(("contact", vec![c.key.particles.0, c.key.particles.1], vec![c.lambda.to_bits()]));
+            }
+            for (p, lambda) in self.collider_lambda.iter().enumerate() {
+                lambdas.push(("collider", vec![p], vec![lambda.to_bits()]));
+            }
+            for a in self.anchors.iter() {
+                lambdas.push(("anchor", vec![a.particle], bits(a.lambda)));
+            }
+            lambdas
+        }
+    }
+
+    // Cleans a fresh fixture and checks that exactly the lambdas expected says are zeroed, and
+    // that every other one keeps its exact bits.
+    fn check(filter : LambdaFilter, selected : &[usize], expected : impl Fn(&str, &[usize], Option<EdgeKind>) -> bool)
+    {
+        let mut fixture = Fixture::new();
+        let before = fixture.lambdas();
+        fixture.clean(filter, selected);
+        let grid = fixture.cloth.sheet_grids[0];
+        for ((kind, particles, old), (_, _, new)) in before.iter().zip(fixture.lambdas().iter()) {
+            let edge_kind = if *kind == "distance" {grid.edge_kind(particles[0], particles[1])} else {None};
+            if expected(kind, particles, edge_kind) {
+                assert!(new.iter().all(|&b| b == 0), "{}: {} {:?} was not cleared", filter.name(), kind, particles);
+            } else {
+                assert_eq!(old, new, "{}: {} {:?} changed", filter.name(), kind, particles);
+            }
+        }
+    }
+
+    #[test]
+    fn all_clears_everything()
+    {
+        check(LambdaFilter::All, &[], |_, _, _| true);
+    }
+
+    #[test]
+    fn structural_clears_only_grid_lines()
+    {
+        check(LambdaFilter::Structural, &[], |kind, _, edge| kind == "distance" && (edge == Some(EdgeKind::Vertical) || edge == Some(EdgeKind::Horizontal)));
+    }
+
+    #[test]
+    fn shear_clears_only_diagonals()
+    {
+        check(LambdaFilter::Shear, &[], |kind, _, edge| kind == "distance" && (edge == Some(EdgeKind::Diagonal) || edge == Some(EdgeKind::AntiDiagonal)));
+    }
+
+    #[test]
+    fn bend_clears_both_bend_models()
+    {
+        check(LambdaFilter::Bend, &[], |kind, _, _| kind == "bend" || kind == "dihedral");
+    }
+
+    #[test]
+    fn contacts_clears_self_and_collider_contacts()
+    {
+        check(LambdaFilter::Contacts, &[], |kind, _, _| kind == "contact" || kind == "collider");
+    }
+
+    #[test]
+    fn selection_clears_whatever_touches_it()
+    {
+        let selected = [5, 6];
+        check(LambdaFilter::Selection, &selected, |_, particles, _| particles.iter().any(|p| selected.contains(p)));
+        // An empty selection clears nothing.
+        check(LambdaFilter::Selection, &[], |_, _, _| false);
+    }
+
+    #[test]
+    fn names_round_trip()
+    {
+        for filter in LAMBDA_FILTERS.iter() {
+            assert!(LambdaFilter::from_name(filter.name()) == Some(*filter));
+        }
+        assert!(LambdaFilter::from_name("nothing").is_none());
+    }
+}
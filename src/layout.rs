@@ -0,0 +1,172 @@
+use serde::{Deserialize, Serialize};
+
+// The overlay's panels: which column each sits in, in what order, and which are collapsed. Kept
+// across page loads.
+const STORAGE_KEY : &str = "warmstart.layout";
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum PanelId
+{
+    Controls,
+    Stats,
+    Inspector,
+    Debug,
+    Log,
+    Notebook,
+    History,
+    Charts,
+}
+
+// Every panel, in the order of the default layout.
+pub const PANELS : [PanelId; 8] = [PanelId::Controls, PanelId::Stats, PanelId::Inspector, PanelId::Debug, PanelId::Log, PanelId::Notebook, PanelId::History, PanelId::Charts];
+
+impl PanelId {
+    pub fn title(&self) -> &'static str
+    {
+        match self {
+            PanelId::Controls => "Controls",
+            PanelId::Stats => "Stats",
+            PanelId::Inspector => "Inspector",
+            PanelId::Debug => "Debug",
+            PanelId::Log => "Log",
+            PanelId::Notebook => "Notebook",
+            PanelId::History => "History",
+            PanelId::Charts => "Charts",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Dock
+{
+    Left,
+    Right,
+}
+
+impl Dock {
+    pub fn other(&self) -> Dock
+    {
+        match self {
+            Dock::Left => Dock::Right,
+            Dock::Right => Dock::Left,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PanelState
+{
+    pub id : PanelId,
+    pub collapsed : bool,
+    pub dock : Dock,
+}
+
+// Panels are drawn top to bottom in the order they appear here, each in its own column.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PanelLayout
+{
+    pub panels : Vec<PanelState>,
+}
+
+fn storage() -> Option<web_sys::Storage>
+{
+    web_sys::window()?.local_storage().ok()?
+}
+
+impl PanelLayout {
+    pub fn default() -> PanelLayout
+    {
+        PanelLayout { panels : PANELS.iter().map(|&id| PanelState { id : id, collapsed : false, dock : Dock::Left }).collect() }
+    }
+
+    // The layout saved by an earlier session, or the default. A saved layout from before a panel
+    // existed gets the new panel at the bottom of the left column.
+    pub fn saved() -> PanelLayout
+    {
+        let mut layout = storage()
+            .and_then(|s| s.get_item(STORAGE_KEY).ok().flatten())
+            .and_then(|text| serde_json::from_str::<PanelLayout>(&text).ok())
+            .unwrap_or_else(PanelLayout::default);
+        let mut seen = vec![];
+        layout.panels.retain(|p| {
+            let first = !seen.contains(&p.id);
+            seen.push(p.id);
+            first
+        });
+        for &id in PANELS.iter() {
+            if !seen.contains(&id) {
+                layout.panels.push(PanelState { id : id, collapsed : false, dock : Dock::Left });
+            }
+        }
+        layout
+    }
+
+    pub fn save(&self)
+    {
+        if let (Some(s), Ok(text)) = (storage(), serde_json::to_string(self)) {
+            let _ = s.set_item(STORAGE_KEY, &text);
+        }
+    }
+
+    pub fn column(&self, dock : Dock) -> impl Iterator<Item = &PanelState>
+    {
+        self.panels.iter().filter(move |p| p.dock == dock)
+    }
+
+    fn find(&mut self, id : PanelId) -> Option<&mut PanelState>
+    {
+        self.panels.iter_mut().find(|p| p.id == id)
+    }
+
+    pub fn toggle_collapsed(&mut self, id : PanelId)
+    {
+        if let Some(p) = self.find(id) {
+            p.collapsed = !p.collapsed;
+        }
+    }
+
+    // Sends a panel to the bottom of the other column.
+    pub fn toggle_dock(&mut self, id : PanelId)
+    {
+        if let Some(k) = self.panels.iter().position(|p| p.id == id) {
+            let mut panel = self.panels.remove(k);
+            panel.dock = panel.dock.other();
+            self.panels.push(panel);
+        }
+    }
+
+    // Puts the dragged panel where the target is, in the target's column. Dragged down it lands
+    // below the target and dragged up above it, so passing over a panel swaps the two. Returns
+    // whether anything moved.
+    pub fn move_to(&mut self, dragged : PanelId, target : PanelId) -> bool
+    {
+        let from = self.panels.iter().position(|p| p.id == dragged);
+        let to = self.panels.iter().position(|p| p.id == target);
+        match (from, to) {
+            (Some(from), Some(to)) if from != to => {
+                let dock = self.panels[to].dock;
+                let mut panel = self.panels.remove(from);
+                panel.dock = dock;
+                self.panels.insert(to, panel);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Moves the dragged panel to the bottom of a column, for drops below the last panel or into
+    // an empty column.
+    pub fn move_to_end(&mut self, dragged : PanelId, dock : Dock) -> bool
+    {
+        let last = self.panels.iter().rposition(|p| p.dock == dock);
+        match self.panels.iter().position(|p| p.id == dragged) {
+            Some(from) if last != Some(from) => {
+                let mut panel = self.panels.remove(from);
+                panel.dock = dock;
+                self.panels.push(panel);
+                true
+            }
+            _ => false,
+        }
+    }
+}
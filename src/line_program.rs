@@ -0,0 +1,87 @@
+use log::error;
+use web_sys::{WebGlProgram, WebGlShader, WebGlUniformLocation, WebGlRenderingContext as GL};
+
+// The program every line and point on the canvas is drawn with, and the locations render_gl sets.
+// It is compiled once for the context it was built in, and again only if that context is replaced
+// or lost.
+pub struct LineProgram
+{
+    context : GL,
+    pub program : WebGlProgram,
+    pub position : u32,
+    pub time : Option<WebGlUniformLocation>,
+    pub aspect_ratio : Option<WebGlUniformLocation>,
+    pub view_center : Option<WebGlUniformLocation>,
+    pub view_scale : Option<WebGlUniformLocation>,
+    pub color : Option<WebGlUniformLocation>,
+}
+
+fn compile(gl : &GL, kind : u32, source : &str) -> Option<WebGlShader>
+{
+    let shader = gl.create_shader(kind)?;
+    gl.shader_source(&shader, source);
+    gl.compile_shader(&shader);
+    if !gl.get_shader_parameter(&shader, GL::COMPILE_STATUS).as_bool().unwrap_or(false) {
+        let kind = if kind == GL::VERTEX_SHADER {"Vertex"} else {"Fragment"};
+        error!("{} shader failed to compile: {}", kind, gl.get_shader_info_log(&shader).unwrap_or_default());
+        return None;
+    }
+    Some(shader)
+}
+
+impl LineProgram {
+    pub fn new(gl : &GL) -> Option<LineProgram>
+    {
+        let vert_shader = compile(gl, GL::VERTEX_SHADER, include_str!("./basic.vert"))?;
+        let frag_shader = compile(gl, GL::FRAGMENT_SHADER, include_str!("./basic.frag"))?;
+        let program = gl.create_program()?;
+        gl.attach_shader(&program, &vert_shader);
+        gl.attach_shader(&program, &frag_shader);
+        gl.link_program(&program);
+        if !gl.get_program_parameter(&program, GL::LINK_STATUS).as_bool().unwrap_or(false) {
+            error!("Shader program failed to link: {}", gl.get_program_info_log(&program).unwrap_or_default());
+            return None;
+        }
+        // Linked, the program no longer needs its shaders.
+        gl.delete_shader(Some(&vert_shader));
+        gl.delete_shader(Some(&frag_shader));
+
+        Some(LineProgram {
+            context : gl.clone(),
+            position : gl.get_attrib_location(&program, "a_position") as u32,
+            time : gl.get_uniform_location(&program, "u_time"),
+            aspect_ratio : gl.get_uniform_location(&program, "u_aspect_ratio"),
+            view_center : gl.get_uniform_location(&program, "u_view_center"),
+            view_scale : gl.get_uniform_location(&program, "u_view_scale"),
+            color : gl.get_uniform_location(&program, "u_color"),
+            program : program,
+        })
+    }
+
+    // Whether the program can still be used with gl.
+    pub fn is_for(&self, gl : &GL) -> bool
+    {
+        self.context == *gl && !gl.is_context_lost()
+    }
+}
+
+// The context a program failed to build in, and whether it was lost then. Building again in the
+// same context would fail the same way, so it is only tried once the context is replaced, lost
+// or restored.
+pub struct BuildFailure
+{
+    context : GL,
+    lost : bool,
+}
+
+impl BuildFailure {
+    pub fn new(gl : &GL) -> BuildFailure
+    {
+        BuildFailure { context : gl.clone(), lost : gl.is_context_lost() }
+    }
+
+    pub fn is_for(&self, gl : &GL) -> bool
+    {
+        self.context == *gl && self.lost == gl.is_context_lost()
+    }
+}
@@ -0,0 +1,84 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use yew::services::ConsoleService;
+
+// How many recent messages the in-page log overlay keeps.
+const RING_CAPACITY : usize = 50;
+
+thread_local! {
+    static RING : RefCell<VecDeque<String>> = RefCell::new(VecDeque::with_capacity(RING_CAPACITY));
+}
+
+// Forwards `log` records to the browser console and keeps the most recent ones around for the
+// overlay, which is handy on mobile where devtools are hard to reach.
+struct ConsoleLogger;
+
+static LOGGER : ConsoleLogger = ConsoleLogger;
+
+impl Log for ConsoleLogger {
+    fn enabled(&self, metadata : &Metadata) -> bool
+    {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record : &Record)
+    {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let message = format!("[{}] {}", record.level(), record.args());
+        match record.level() {
+            Level::Error => ConsoleService::error(&message),
+            Level::Warn => ConsoleService::warn(&message),
+            Level::Info => ConsoleService::info(&message),
+            Level::Debug | Level::Trace => ConsoleService::debug(&message),
+        }
+
+        RING.with(|ring| {
+            let mut ring = ring.borrow_mut();
+            if ring.len() == RING_CAPACITY {
+                ring.pop_front();
+            }
+            ring.push_back(message);
+        });
+    }
+
+    fn flush(&self) {}
+}
+
+// Reads the level from a `log=<level>` URL parameter, staying quiet (warnings and errors only)
+// when it is absent or unrecognised.
+fn level_from_url() -> LevelFilter
+{
+    let search = web_sys::window()
+        .and_then(|w| w.location().search().ok())
+        .unwrap_or_default();
+
+    search.trim_start_matches('?')
+        .split('&')
+        .filter_map(|pair| {
+            let mut parts = pair.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some("log"), Some(value)) => value.parse().ok(),
+                _ => None,
+            }
+        })
+        .next()
+        .unwrap_or(LevelFilter::Warn)
+}
+
+pub fn init()
+{
+    let level = level_from_url();
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(level);
+    }
+    log::info!("Logging at level {}", level);
+}
+
+pub fn recent_messages() -> Vec<String>
+{
+    RING.with(|ring| ring.borrow().iter().cloned().collect())
+}
@@ -1,83 +1,764 @@
 #![recursion_limit="1024"]
 #![allow(non_snake_case)] 
 
-use wasm_bindgen::JsCast;
-use web_sys::{HtmlCanvasElement, WebGlRenderingContext as GL};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen::closure::Closure;
+use web_sys::{HtmlCanvasElement, HtmlInputElement, IdbDatabase, WebGlBuffer, WebGlRenderingContext as GL, WebGlUniformLocation};
 use yew::services::render::RenderTask;
-use yew::services::{RenderService, ConsoleService};
+use yew::services::RenderService;
 use yew::services::resize::WindowDimensions;
+use yew::services::reader::{FileData, ReaderService, ReaderTask};
 use yew::{html, Component, ComponentLink, Html, NodeRef, ShouldRender};
-use yew::events::InputData;
+use yew::events::{ChangeData, InputData, KeyboardEvent, MouseEvent};
 use glam::*;
+use log::{debug, error, info, warn};
 
-pub enum SimType
-{
-    Jacobi,
-    GaussSeidel,
-}
+mod alarm;
+mod benchmark;
+mod brush;
+mod chart;
+mod cloth;
+mod collision;
+mod color_scale;
+mod contacts;
+mod context_menu;
+mod crash;
+mod cursor;
+mod determinism;
+mod diffusion;
+mod download;
+mod edge_colors;
+mod freeze;
+mod fuzz;
+mod ghost;
+mod golden;
+mod gravity_ramp;
+mod gpu_buffers;
+mod idle;
+mod indices;
+mod inspector;
+mod lambda_filter;
+mod layout;
+mod line_program;
+mod logging;
+mod measure;
+mod micro_step;
+mod notebook;
+mod obj;
+mod observer;
+mod pacing;
+mod picking;
+mod palette;
+mod profiling;
+mod rail;
+mod recording;
+mod resample;
+mod reversal;
+mod rollup;
+mod params;
+mod pluck;
+mod rng;
+mod sdf;
+mod seed;
+mod selection;
+mod session;
+mod small_multiples;
+mod solver;
+mod stability;
+mod stereo;
+mod sweep;
+mod time_source;
+mod timeline;
+mod title;
+mod top_k;
+mod topology;
+mod tutorial;
+mod validation;
+mod view;
+mod watchdog;
+mod weight;
+mod wrinkle;
+use alarm::StrainAlarm;
+use benchmark::{Benchmark, QualityTier, QUALITY_TIERS};
+use chart::{ChartRect, ChartUniforms, TimeSeriesChart};
+use cloth::{build_cloth, build_cloth_rows, build_mesh_cloth, AreaConstraint, BendModel, ClothBuild, Connectivity, Constraint, DihedralConstraint, EdgeKind, Scene, SheetGrid};
+use color_scale::{ColorScales, ScaleUse, SCALE_USES};
+use collision::{ColliderContact, Colliders, CollisionOrder, CollisionResponse, Sphere, COLLISION_ORDERS, FLOOR_HEIGHT};
+use contacts::{Contact, ContactKey, NewbornBoost, SpatialHash};
+use context_menu::{ContextMenu, MenuAction, MenuCommand, DRAG_THRESHOLD_PIXELS, PUSH_SPEED};
+use crash::CrashReport;
+use brush::{BrushMode, ForceBrush};
+use cursor::CursorHistory;
+use determinism::{HashAudit, StateHasher};
+use diffusion::LambdaAdjacency;
+use edge_colors::EdgeLayer;
+use freeze::Freeze;
+use fuzz::{FuzzAction, Fuzzer};
+use ghost::Ghost;
+use golden::{GoldenMode, GoldenRun};
+use gravity_ramp::{RampCase, RampExperiment};
+use gpu_buffers::GpuBuffers;
+use idle::IdleTracker;
+use indices::{batch_edge_indices, draw_batches, draw_indexed, edge_indices, split_batches, IndexBatch, IndexMode, MAX_SHORT_VERTICES};
+use inspector::WorstConstraints;
+use measure::{MeasurePoint, Measurement};
+use micro_step::{MicroStep, StepPhase};
+use obj::ObjMesh;
+use notebook::{NotebookEntry, NotebookMetrics, NotebookSort, NOTEBOOK_SORTS};
+use lambda_filter::{LambdaFilter, StoredImpulses, LAMBDA_FILTERS};
+use layout::{Dock, PanelId, PanelLayout, PanelState};
+use line_program::{BuildFailure, LineProgram};
+use observer::{SimulationObserver, StepStats};
+use sweep::{SweepLog, SweepReplay};
+use picking::{GpuPicker, GPU_PICK_MIN_PARTICLES, GRAB_PIXELS};
+use pacing::{FramePacing, HighRefreshPolicy, HIGH_REFRESH_POLICIES};
+use palette::PALETTES;
+use recording::{ParticleSelection, Recorder, RecordingSettings};
+use resample::{carry_over, resample, ClothSample};
+use reversal::{ReversalResult, ReversalRun};
+use rollup::{Rollup, Rollups, StepHealth};
+use params::{parse_count, parse_iterations, parse_param, parse_seed, parse_stiffness, Effect, Param, Params, ParamsDelta};
+use pluck::Pluck;
+use rail::{Anchor, Curve, PinMode, RailShape};
+use selection::{GroupDrag, Selection};
+use small_multiples::SmallMultiples;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use sdf::SdfGrid;
+use session::{SavedCloth, Session};
+use stability::StiffnessResolution;
+use solver::{ClothState, Scratch, Solver, SolverParams};
+use time_source::{FrameTrace, RealTime, ScriptedTime, TimeSource};
+use timeline::{Timeline, TimelineEvent, PARAMETERS};
+use title::Activity;
+use tutorial::{Tutorial, TutorialStep};
+use topology::Topology;
+use validation::{Fix, Severity, Settings, RULES};
+use view::ViewTransform;
+use wrinkle::{WrinkleLog, WrinkleSample};
+use watchdog::Watchdog;
+use weight::Weight;
+
+// The settling burst run before the first frame is shown: at most this many steps, spending no
+// more than the budget per frame and giving up after the frame limit.
+const WARM_UP_STEPS : u32 = 300;
+const WARM_UP_BUDGET_MS : f64 = 100.0;
+const WARM_UP_MAX_FRAMES : u32 = 4;
+// Time a reversal check may take out of each frame.
+const REVERSAL_BUDGET_MS : f64 = 8.0;
+
+// Aspect ratios are kept within these, so a canvas squashed to a sliver can't send the view
+// math to infinity.
+const MIN_ASPECT_RATIO : f32 = 0.1;
+const MAX_ASPECT_RATIO : f32 = 10.0;
+
+// Downward acceleration, scaled down so the cloth settles at a watchable pace.
+const GRAVITY : f32 = -9.8 * 0.1;
 
 pub enum Msg {
     Render(f64),
+    // Any number of parameters at once. The parameter controls each send one of these with the
+    // field they set.
+    ApplyParams(ParamsDelta),
     ResetClicked,
+    PauseToggled,
+    TutorialStarted,
+    TutorialNext,
+    TutorialClosed,
     CleanLambdaClicked,
-    SimTypeClicked(SimType),
+    CleanFilterChanged(ChangeData),
+    SolverSelected(usize),
     NumIterationsChanged(InputData),
     StiffnessChanged(InputData),
     WarmStartChanged,
+    AnalyticSeedChanged,
     EtaChanged(InputData),
+    AdaptiveEtaChanged(InputData),
+    LambdaDiffusionChanged(InputData),
+    AnaglyphChanged,
+    InterocularChanged(InputData),
+    LambdaClampChanged,
+    ClampSafetyChanged(InputData),
     NuChanged(InputData),
-    JacobiRelaxationChanged(InputData),
+    GravityAngleChanged(InputData),
+    GravityPeriodChanged(InputData),
+    SolverParamChanged(usize, InputData),
+    LogPanelToggled,
+    RollupsChanged,
+    RollupWindowChanged(InputData),
+    ExportRollupsClicked,
+    ChartsChanged,
+    ChartLogScaleChanged(usize),
+    PanelCollapseToggled(PanelId),
+    PanelDockToggled(PanelId),
+    PanelDragStarted(PanelId),
+    PanelDraggedOver(PanelId),
+    PanelDraggedToEnd(Dock),
+    PanelDragEnded,
+    NotebookOpened(Result<IdbDatabase, String>),
+    NotebookLoaded(Result<Vec<NotebookEntry>, String>),
+    NotebookWritten(Result<(), String>),
+    NotebookToggled,
+    NotebookNoteChanged(InputData),
+    NotebookFilterChanged(InputData),
+    NotebookSortChanged(ChangeData),
+    NotebookLogClicked,
+    NotebookRestoreClicked(u32),
+    NotebookDeleteClicked(u32),
+    NotebookExportClicked,
+    ScriptedTimeChanged,
+    SeedChanged(InputData),
+    RerollSeedClicked,
+    ExportTraceClicked,
+    TraceFileChosen(ChangeData),
+    TraceFileLoaded(FileData),
+    AnimatedResetChanged,
+    AutoFitChanged,
+    LodModeChanged(LodMode),
+    LodThresholdChanged(InputData),
+    TensionOnlyChanged,
+    AreaConstraintsChanged,
+    AreaStiffnessChanged(InputData),
+    StiffenPerimeterChanged,
+    PerimeterStiffnessChanged(InputData),
+    SoftPinsChanged,
+    PinStiffnessChanged(InputData),
+    PinWeightChanged(InputData),
+    BendModelChanged(ChangeData),
+    BendStiffnessChanged(InputData),
+    WeightChanged,
+    WeightMassChanged(InputData),
+    MouseDown(MouseEvent),
+    MouseMove(MouseEvent),
+    MouseUp,
+    // The cursor left the canvas, which lets go of a held particle as it can't follow any further.
+    MouseLeave,
+    CanvasContextMenu(MouseEvent),
+    ContextMenuChosen(MenuAction),
+    ContextMenuClosed,
+    SceneChanged(Scene),
+    ConnectivityChanged(ChangeData),
+    WrapXChanged,
+    RailChanged(ChangeData),
+    RailSizeChanged(InputData),
+    GridWidthChanged(InputData),
+    GridHeightChanged(InputData),
+    PreserveOnResizeChanged(bool),
+    StaggeredSpawnChanged,
+    SpawnIntervalChanged(InputData),
+    SheetOverrideChanged(usize),
+    SheetIterationsChanged(usize, InputData),
+    SheetWarmStartChanged(usize),
+    SdfFileChosen(ChangeData),
+    SdfFileLoaded(FileData),
+    ClearSdfClicked,
+    MeshFileChosen(ChangeData),
+    MeshFileLoaded(FileData),
+    ClearMeshClicked,
+    CollisionResponseChanged(ChangeData),
+    CollisionOrderChanged(ChangeData),
+    RestitutionChanged(InputData),
+    FloorChanged,
+    ReleasePinsClicked,
+    PinSelectionClicked,
+    FreezeSelectionClicked,
+    ClearSelectionClicked,
+    CanvasKeyDown(KeyboardEvent),
+    PaletteChanged(ChangeData),
+    TitleSummaryChanged,
+    SelfCollisionChanged,
+    MaxSubstepsChanged(InputData),
+    WatchdogCeilingChanged(InputData),
+    WatchdogRestoreClicked,
+    PanicNowClicked,
+    SmallMultiplesChanged,
+    SmallMultiplesRowsChanged(InputData),
+    SmallMultiplesColumnsChanged(InputData),
+    ScaleUseChanged(ChangeData),
+    CustomScaleChanged,
+    ScaleBarPressed(MouseEvent),
+    ScaleBarMoved(MouseEvent),
+    ScaleBarReleased,
+    StopColorChanged(InputData),
+    AddStopClicked,
+    RemoveStopClicked,
+    IdleSleepChanged,
+    IdleThresholdChanged(InputData),
+    IdleDelayChanged(InputData),
+    IdleLowFrameRateChanged,
+    NewbornBoostChanged,
+    NewbornFramesChanged(InputData),
+    NewbornPassesChanged(InputData),
+    NewbornSeedChanged,
+    PacingPolicyChanged(ChangeData),
+    QualityTierChanged(ChangeData),
+    FullDetailLockChanged,
+    PhaseSteppingChanged,
+    PhaseStepClicked,
+    TierNoteDismissed,
+    KeepSessionChanged,
+    ResumeNoteDismissed,
+    StartFreshClicked,
+    PageHidden,
+    RuleBannerDismissed(&'static str),
+    TimelineFileChosen(ChangeData),
+    TimelineFileLoaded(FileData),
+    GhostFileChosen(ChangeData),
+    GhostFileLoaded(FileData),
+    GhostFromRecordingClicked,
+    GhostVisibleChanged,
+    GhostOffsetChanged(InputData),
+    ClearGhostClicked,
+    LoadSqueezeClicked,
+    LoadGravityRampClicked,
+    StiffSheetClicked,
+    RampExperimentClicked,
+    TimelinePlayClicked,
+    TimelineStopClicked,
+    TimelineScrubbed(InputData),
+    ShowValenceChanged,
+    InspectorChanged,
+    ReverseTimeClicked,
+    ReversalCheckClicked,
+    ReversalStepsChanged(InputData),
+    ExportReversalClicked,
+    ProbeConstraintSelected(usize),
+    ProfilingChanged,
+    RecordingToggled,
+    RecordSelectionChanged(ParticleSelection),
+    RecordProbesChanged(InputData),
+    RecordParticleStrideChanged(InputData),
+    RecordStepStrideChanged(InputData),
+    RecordCapChanged(InputData),
+    RecordCompressedChanged,
+    RecordKeyframeIntervalChanged(InputData),
+    RecordQuantumChanged(InputData),
+    ExportRecordingClicked,
+    PluckToolChanged,
+    DragPredictionChanged,
+    DragLeadChanged(InputData),
+    BrushModeChanged(BrushMode),
+    BrushStrengthChanged(InputData),
+    BrushRadiusChanged(InputData),
+    FreezeToolChanged,
+    MeasureToolChanged,
+    RulersChanged,
+    MeasurementDismissed(usize),
+    ClearMeasurementsClicked,
+    StepClicked,
+    SweepRecordClicked,
+    SweepPlayToggled,
+    SweepScrubbed(InputData),
+    SweepSpeedChanged(InputData),
+    SweepExited,
+    PickingCheckClicked,
+    UnfreezeClicked,
+    PluckStepsChanged(InputData),
+    ExportPluckClicked,
+    StrainAlarmChanged,
+    MinStrainChanged(InputData),
+    MaxStrainChanged(InputData),
+    PauseOnAlarmChanged,
+    ExportAlarmsClicked,
+    WrinkleLogChanged,
+    ExportWrinkleClicked,
+    EditStarted(Param),
+    EditTextChanged(InputData),
+    EditKeyDown(KeyboardEvent),
+    EditCommitted,
+}
+
+impl Msg {
+    // Whether handling this changes how the cloth moves, which would spoil a measurement taken
+    // across it.
+    fn changes_simulation(&self) -> bool
+    {
+        match self {
+            Msg::ApplyParams(_) | Msg::ResetClicked | Msg::CleanLambdaClicked | Msg::TutorialStarted | Msg::TutorialNext | Msg::TutorialClosed | Msg::SolverSelected(_) | Msg::NumIterationsChanged(_) |
+            Msg::StiffnessChanged(_) | Msg::WarmStartChanged | Msg::AnalyticSeedChanged | Msg::EtaChanged(_) | Msg::GravityAngleChanged(_) | Msg::GravityPeriodChanged(_) | Msg::AdaptiveEtaChanged(_) | Msg::LambdaDiffusionChanged(_) | Msg::LambdaClampChanged | Msg::ClampSafetyChanged(_) | Msg::NuChanged(_) |
+            Msg::SolverParamChanged(_, _) | Msg::ScriptedTimeChanged | Msg::SeedChanged(_) | Msg::RerollSeedClicked | Msg::TraceFileLoaded(_) |
+            Msg::LodModeChanged(_) | Msg::LodThresholdChanged(_) | Msg::FullDetailLockChanged | Msg::TensionOnlyChanged |
+            Msg::AreaConstraintsChanged | Msg::AreaStiffnessChanged(_) | Msg::StiffenPerimeterChanged | Msg::PerimeterStiffnessChanged(_) | Msg::SoftPinsChanged | Msg::PinStiffnessChanged(_) | Msg::PinWeightChanged(_) | Msg::BendModelChanged(_) |
+            Msg::BendStiffnessChanged(_) | Msg::WeightChanged | Msg::WeightMassChanged(_) | Msg::SceneChanged(_) | Msg::StiffSheetClicked | Msg::ConnectivityChanged(_) |
+            Msg::WrapXChanged | Msg::RailChanged(_) | Msg::RailSizeChanged(_) | Msg::QualityTierChanged(_) |
+            Msg::GridWidthChanged(_) | Msg::GridHeightChanged(_) | Msg::StaggeredSpawnChanged | Msg::SpawnIntervalChanged(_) | Msg::SheetOverrideChanged(_) |
+            Msg::SheetIterationsChanged(_, _) | Msg::SheetWarmStartChanged(_) | Msg::SdfFileLoaded(_) | Msg::MeshFileLoaded(_) | Msg::ClearMeshClicked |
+            Msg::ClearSdfClicked | Msg::CollisionResponseChanged(_) | Msg::CollisionOrderChanged(_) | Msg::RestitutionChanged(_) | Msg::FloorChanged |
+            Msg::ReleasePinsClicked | Msg::PinSelectionClicked | Msg::ContextMenuChosen(_) | Msg::ReverseTimeClicked | Msg::ReversalCheckClicked | Msg::RampExperimentClicked | Msg::EditCommitted | Msg::NewbornBoostChanged |
+            Msg::NewbornFramesChanged(_) | Msg::NewbornPassesChanged(_) | Msg::NewbornSeedChanged | Msg::SelfCollisionChanged | Msg::MaxSubstepsChanged(_) | Msg::WatchdogRestoreClicked => true,
+            _ => false,
+        }
+    }
+}
+
+// Per-sheet overrides of the global solver settings. None means use the global value.
+#[derive(Default)]
+pub struct SheetParams
+{
+    iterations : Option<i32>,
+    warm_start : Option<bool>,
+}
+
+// Whether the decimated constraint set is solved. Auto picks it when the canvas is narrower
+// than the LOD threshold.
+#[derive(Clone, Copy, PartialEq)]
+pub enum LodMode
+{
+    Auto,
+    Full,
+    Low,
 }
 
-pub struct Constraint
+// An in-progress animated reset. Each existing particle is eased towards the nearest particle
+// of the new cloth, and once the blend has run its course the new cloth is swapped in whole.
+pub struct ResetBlend
 {
-    p0 : usize,
-    p1 : usize,
-    length: f32,
-    lambda : Vec3,
+    targets : Vec<Vec3>,
+    velocities : Vec<Vec3>,
+    elapsed : f32,
+    cloth : ClothBuild,
 }
 
-impl Constraint {
-    fn new(p0 : usize, p1 : usize, positions : &[Vec3]) -> Constraint
+impl ResetBlend {
+    const DURATION : f32 = 0.5;
+
+    fn new(current_positions : &[Vec3], cloth : ClothBuild) -> ResetBlend
+    {
+        // When the grid size changed there is no one-to-one mapping, so every old particle heads
+        // for its closest new one. New particles nobody heads for simply appear at the swap.
+        // Cells the size of the new cloth's mean spacing keep the search to a few neighbours.
+        let spacing = cloth.constraints.iter().map(|c| c.length).sum::<f32>() / cloth.constraints.len().max(1) as f32;
+        let mut hash = SpatialHash::new(1.0);
+        hash.rebuild(&cloth.positions, if spacing > 1e-6 {spacing} else {1.0});
+        let targets = current_positions.iter().map(|&p| {
+            let j = hash.nearest(&cloth.positions, p).unwrap_or(0);
+            cloth.positions.get(j).cloned().unwrap_or(p)
+        }).collect();
+
+        ResetBlend {
+            targets : targets,
+            velocities : vec![vec3(0.0, 0.0, 0.0); current_positions.len()],
+            elapsed : 0.0,
+            cloth : cloth,
+        }
+    }
+
+    // Critically damped spring towards the targets, using the usual closed-form approximation so
+    // the motion settles within DURATION regardless of dt. Returns true once the blend is done.
+    fn advance(&mut self, positions : &mut [Vec3], dt : f32) -> bool
     {
-        Constraint {
-            p0 : p0,
-            p1 : p1,
-            length : (positions[p0] - positions[p1]).length(),
-            lambda : vec3(0.0,0.0,0.0),
+        self.elapsed += dt;
+
+        let omega = 8.0 / Self::DURATION;
+        let x = omega * dt;
+        let decay = 1.0 / (1.0 + x + 0.48 * x * x + 0.235 * x * x * x);
+
+        for i in 0..positions.len() {
+            let change = positions[i] - self.targets[i];
+            let temp = (self.velocities[i] + omega * change) * dt;
+            self.velocities[i] = (self.velocities[i] - omega * temp) * decay;
+            positions[i] = self.targets[i] + (change + temp) * decay;
         }
+
+        self.elapsed >= Self::DURATION
     }
 }
 
 
 pub struct Model {
+    params : Params,
     canvas: Option<HtmlCanvasElement>,
     gl: Option<GL>,
     link: ComponentLink<Self>,
     node_ref: NodeRef,
     render_loop: Option<RenderTask>,
+    gpu_buffers : GpuBuffers,
+    frame_index : u64,
+    idle : IdleTracker,
+    last_draw_timestamp : f64,
+    skipped_frames : u64,
+    last_real_timestamp : f64,
+    refresh_interval : f64,
+    frame_pacing : FramePacing,
+    quality_tier : QualityTier,
+    // Runs over the first frames when no tier has been saved yet.
+    benchmark : Option<Benchmark>,
+    // Set for a golden-image run, which takes over the frame loop until it has drawn every
+    // scenario.
+    golden : Option<GoldenRun>,
+    // Set for a determinism audit, which likewise takes over the frame loop until both of its runs
+    // are done.
+    audit : Option<HashAudit>,
+    // Keeps every constraint whatever the canvas width, which the automatic detail level follows.
+    // It leaves the arithmetic as it is, so it only takes the window out of a run's inputs.
+    full_detail_lock : bool,
+    // Single steps go one phase at a time, with the step held between phases.
+    phase_stepping : bool,
+    micro_step : Option<MicroStep>,
+    // What the last phase was and how the cloth stood after it, for the overlay.
+    phase_note : Option<Vec<String>>,
+    tier_note : Option<String>,
+    // Keep the parameters and the cloth in localStorage so a reload resumes where it left off.
+    keep_session : bool,
+    last_session_save_ms : f64,
+    // The cloth from the previous page load, put back in place of the first reset's.
+    pending_resume : Option<SavedCloth>,
+    // Set after resuming until dismissed: how old the resumed session was, in seconds, and the
+    // parameters to go back to for a fresh start.
+    resumed : Option<(f64, Params)>,
+    // Rules whose warning was dismissed while it held, and blocking rules that moved a setting
+    // and haven't had their explanation dismissed.
+    dismissed_rules : Vec<&'static str>,
+    blocked_notes : Vec<&'static str>,
+    // Positions from before the last step, kept while drawing interpolates between steps.
+    interpolation_from : Vec<Vec3>,
+    // Something other than a step may have changed what is drawn since the last draw.
+    needs_draw : bool,
     width : i32,
     height : i32,
-    num_particles_x : i32,
-    num_particles_y : i32,
+    // The canvas takes up no space on the page, as inside a display:none container.
+    canvas_hidden : bool,
     num_particles : usize,
     num_constraints : usize,
     current_positions : Vec<Vec3>,
     previous_positions : Vec<Vec3>,
     is_fixed: Vec<bool>,
+    pin_modes : Vec<PinMode>,
+    // Each fixed pin's inverse mass as a fraction of a free particle's, set from the pin weight
+    // slider but kept per pin. Only read for particles pinned as Fixed.
+    pin_weights : Vec<f32>,
+    // What the solver and the integration take each particle's inverse mass to be, refreshed at
+    // the start of every step.
+    inverse_masses : Vec<f32>,
+    // Hang the first sheet's top row from a rail instead of its usual pins.
+    rail_shape : Option<RailShape>,
+    rail_size : f32,
+    curves : Vec<Curve>,
+    anchors : Vec<Anchor>,
+    sheet_of : Vec<usize>,
+    sheet_grids : Vec<SheetGrid>,
+    preserve_on_resize : bool,
+    // Build the cloth from its top row down, adding a row every spawn_interval steps.
+    staggered_spawn : bool,
+    spawn_interval : i32,
+    steps_since_spawn : i32,
+    sheet_params : Vec<SheetParams>,
+    sheet_kinetic_energy : Vec<f32>,
+    view_shear : Vec2,
+    // Draw the cloth twice, for red-cyan glasses, with the eyes' pictures this far apart per unit
+    // of depth.
+    anaglyph : bool,
+    interocular : f32,
+    auto_fit : bool,
+    view_transform : ViewTransform,
+    view_target : ViewTransform,
+    contact_distance : f32,
+    contacts : Vec<Contact>,
+    newborn_boost : NewbornBoost,
+    spatial_hash : SpatialHash,
     constraints : Vec<Constraint>,
+    lod_constraints : Vec<usize>,
+    topology : Topology,
+    // Whether OES_element_index_uint was granted, found on the first render.
+    has_uint_indices : bool,
+    index_mode : IndexMode,
+    // Every constraint's edge split into 16 bit batches, built with the topology when batching.
+    edge_batches : Vec<IndexBatch>,
+    show_valence : bool,
+    // List the worst-converged constraints after every step.
+    inspector : bool,
+    // Summaries of the run's health every so many simulated seconds, for the History panel.
+    rollups_enabled : bool,
+    rollups : Rc<RefCell<Rollups>>,
+    // Residual and energy plotted over the canvas's bottom corners while the Charts panel is open.
+    show_charts : bool,
+    charts : Vec<TimeSeriesChart>,
+    worst_constraints : Rc<RefCell<WorstConstraints>>,
+    probe_constraint : Option<usize>,
+    reversal_steps : i32,
+    reversal_run : Option<ReversalRun>,
+    ramp_experiment : Option<RampExperiment>,
+    reversal_results : Vec<ReversalResult>,
+    profiling : bool,
+    lod_mode : LodMode,
+    lod_threshold : i32,
+    area_constraints : Vec<AreaConstraint>,
+    bend_constraints : Vec<Constraint>,
+    dihedral_constraints : Vec<DihedralConstraint>,
     prev_timestamp : f64,
-    target_dt: f32,
     time_step : i32,
-    num_iterations : i32,
-    do_jacobi : bool,
+    solvers : Vec<Box<dyn Solver>>,
+    scratch : Scratch,
+    // Lambda clamp engagements in the last frame that stepped, and since the last reset.
+    frame_lambda_clamps : u32,
+    total_lambda_clamps : u64,
     do_reset: bool,
-    do_clean_lambda: bool,
-    stiffness : f32,
-    warm_start : bool,
-    eta : f32,
-    nu : f32,
-    jacobi_relaxation : f32,
+    animated_reset : bool,
+    reset_blend : Option<ResetBlend>,
+    // Consumed at the next step boundary.
+    do_clean_lambda: Option<LambdaFilter>,
+    clean_filter : LambdaFilter,
+    last_clean_filter : Option<LambdaFilter>,
+    weight : Option<Weight>,
+    reader : ReaderService,
+    reader_task : Option<ReaderTask>,
+    sdf : Option<SdfGrid>,
+    // An imported mesh replaces the scene's grid until it is cleared or another scene picked.
+    mesh : Option<ObjMesh>,
+    mesh_error : Option<String>,
+    sdf_contour : Vec<f32>,
+    collision_thickness : f32,
+    collision_response : CollisionResponse,
+    collision_order : CollisionOrder,
+    restitution : f32,
+    floor : bool,
+    // Sphere colliders brought in by the loaded timeline.
+    spheres : Vec<Sphere>,
+    collider_contacts : Vec<ColliderContact>,
+    // The deepest any particle was left inside the collision thickness by a step this frame.
+    max_penetration : f32,
+    // Each particle's collider impulse from the last step, for warm starting XPBD contacts.
+    collider_lambda : Vec<f32>,
+    kinetic_energy : f32,
+    // Means over the free particles on the boundary and inside it.
+    boundary_kinetic_energy : f32,
+    interior_kinetic_energy : f32,
+    wrinkle : Option<WrinkleSample>,
+    wrinkle_log : WrinkleLog,
+    potential_energy : f32,
+    show_log : bool,
+    layout : PanelLayout,
+    // The panel being dragged by its handle. It moves as the pointer passes over other panels
+    // and the layout is saved when it is let go.
+    dragging_panel : Option<PanelId>,
+    // The experiment notebook. Every database request is asynchronous, and notebook_pending counts
+    // those still outstanding so the panel can say it is busy.
+    notebook : Option<IdbDatabase>,
+    notebook_entries : Vec<NotebookEntry>,
+    notebook_pending : u32,
+    notebook_error : Option<String>,
+    show_notebook : bool,
+    notebook_note : String,
+    notebook_filter : String,
+    notebook_sort : NotebookSort,
+    palette_index : usize,
+    // Scales edited in place of the palette's ramp, the view whose scale the editor shows, and the
+    // stop picked in it and whether it is being dragged.
+    // Turns optional features down when a step runs far too long. Set while it applies its own
+    // reductions, so they aren't taken for the user's.
+    watchdog : Watchdog,
+    watchdog_reducing : bool,
+    color_scales : ColorScales,
+    scale_use : ScaleUse,
+    selected_stop : Option<usize>,
+    dragging_stop : bool,
+    // Keep the tab's title to a summary of the configuration. The title last set, and the one the
+    // page had, for when it is switched off.
+    title_summary : bool,
+    title : String,
+    original_title : String,
+    title_checked_ms : f64,
+    recorder : Option<Rc<RefCell<Recorder>>>,
+    // A recorded run drawn behind the cloth, and why the last one offered was turned away.
+    ghost : Option<Ghost>,
+    ghost_error : Option<String>,
+    // Mini cloths over a grid of iterations and etas, and the text of the two axes as typed.
+    small_multiples : Option<SmallMultiples>,
+    small_multiples_rows : String,
+    small_multiples_columns : String,
+    // The constraints sharing a particle with each, for lambda diffusion, and the copy of the
+    // lambdas it reads from.
+    lambda_adjacency : LambdaAdjacency,
+    lambda_scratch : Vec<Vec3>,
+    // The first iteration's mean residual summed over the steps of the timeline playing, and the
+    // mean over the last run that finished.
+    first_iteration_residual : (f64, u32),
+    last_run_residual : Option<f32>,
+    // Diagnostics called back from inside every step, rebuilt by register_observers.
+    observers : Vec<Box<dyn SimulationObserver>>,
+    recording : bool,
+    recording_settings : RecordingSettings,
+    pluck_tool : bool,
+    freeze : Freeze,
+    freeze_tool : bool,
+    // Clicks place the ends of measurements, the first waiting in measure_start for the second.
+    measure_tool : bool,
+    measure_start : Option<MeasurePoint>,
+    measurements : Vec<Measurement>,
+    // Where the right button went down, to tell a right-click from a right-drag.
+    right_press : Option<(i32, i32)>,
+    context_menu : Option<ContextMenu>,
+    show_rulers : bool,
+    // A single paused step's structural corrections, replayed one at a time over positions of the
+    // replay's own. The log is only registered as an observer for the recorded step.
+    sweep_log : Rc<RefCell<SweepLog>>,
+    recording_sweep : bool,
+    sweep_replay : Option<SweepReplay>,
+    // Random actions and invariant checks every step, when the page is opened with fuzz=1.
+    fuzzer : Option<Fuzzer>,
+    // Corners of the box being dragged with the freeze tool, in simulation space.
+    freeze_box : Option<(Vec2, Vec2)>,
+    // The rubber band being shift-dragged, the particles it last picked and the drag moving them.
+    select_box : Option<(Vec2, Vec2)>,
+    selection : Selection,
+    group_drag : Option<GroupDrag>,
+    // Built on the first GPU pick. Once it has failed to build, picking stays on the CPU.
+    gpu_picker : Option<GpuPicker>,
+    gpu_picking_unavailable : bool,
+    // Built on the first render, for the context it was built in, or why it couldn't be.
+    line_program : Option<LineProgram>,
+    line_program_failure : Option<BuildFailure>,
+    // Agreements and samples from the last picking check.
+    picking_check : Option<(usize, usize)>,
+    pluck_steps : i32,
+    // The particle held by the pluck tool and where it rested before it was grabbed.
+    pluck_drag : Option<(usize, Vec3)>,
+    pluck : Option<Pluck>,
+    // The particle held to the cursor by a plain drag, pinned until it is let go, and where it
+    // was grabbed. Pinned particles can't be grabbed, so letting go always unpins.
+    particle_drag : Option<(usize, Vec3)>,
+    cursor_history : CursorHistory,
+    // Leads dragged targets to where the cursor will be when the step is shown, drag_lead_ms on.
+    drag_prediction : bool,
+    drag_lead_ms : f32,
+    brush : ForceBrush,
+    // The brush follows the cursor as it was at each step's share of the frame, on the cursor's
+    // clock, so the steps of one frame don't all push from the same spot.
+    brush_time_ms : f64,
+    last_stepped_frame_ms : f64,
+    // Summed on-screen distance in pixels between the cursor and what it drags, and the frames
+    // summed, since the drag began.
+    drag_lag : (f64, usize),
+    strain_alarm : StrainAlarm,
+    // Steps are held while paused, but frames keep being drawn.
+    paused : bool,
+    timeline : Option<Timeline>,
+    timeline_name : Option<String>,
+    tutorial : Option<Tutorial>,
+    timeline_playing : bool,
+    timeline_time : f32,
+    // Each pinned particle and where it was when the timeline first moved the pins.
+    pin_origins : Vec<(usize, Vec3)>,
+    timeline_peak_kinetic_energy : f32,
+    time_source : Box<dyn TimeSource>,
+    scripted_time : bool,
+    // Whether the scripted frame times come from a loaded trace rather than the seed.
+    replaying_trace : bool,
+    seed : u64,
+    warm_up : bool,
+    warm_up_remaining : u32,
+    warm_up_frames : u32,
+    // Messages that arrived during the warm-up, handled once it is over.
+    queued_msgs : Vec<Msg>,
+    pop_threshold : f32,
+    pop_count : u32,
+    offscreen_canvas_supported : bool,
+    shared_memory_supported : bool,
+    editing : Option<Param>,
+    edit_text : String,
+    edit_ref : NodeRef,
+    focus_edit : bool,
 }
 
 impl Component for Model {
@@ -86,33 +767,225 @@ impl Component for Model {
 
     fn create(_props: Self::Properties, link: ComponentLink<Self>) -> Self {
         Self {
+            params : Params::default(),
             canvas: None,
             gl: None,
             link,
             node_ref: NodeRef::default(),
             render_loop: None,
+            gpu_buffers : GpuBuffers::new(60),
+            frame_index : 0,
+            idle : IdleTracker::new(),
+            last_draw_timestamp : 0.0,
+            skipped_frames : 0,
+            last_real_timestamp : 0.0,
+            refresh_interval : 1000.0 / 60.0,
+            frame_pacing : frame_pacing_from_url(),
+            quality_tier : QualityTier::Medium,
+            benchmark : None,
+            golden : golden::requested().map(GoldenRun::new),
+            audit : determinism::requested().map(HashAudit::new),
+            full_detail_lock : false,
+            phase_stepping : false,
+            micro_step : None,
+            phase_note : None,
+            tier_note : None,
+            keep_session : false,
+            last_session_save_ms : 0.0,
+            pending_resume : None,
+            resumed : None,
+            dismissed_rules : vec![],
+            blocked_notes : vec![],
+            interpolation_from : vec![],
+            needs_draw : true,
             width : 100,
+            canvas_hidden : false,
             height : 100,
-            num_particles_x : 10,
-            num_particles_y : 10,
             current_positions: vec![],
             previous_positions: vec![],
             is_fixed : vec![],
+            pin_modes : vec![],
+            pin_weights : vec![],
+            inverse_masses : vec![],
+            rail_shape : None,
+            rail_size : 0.8,
+            curves : vec![],
+            anchors : vec![],
+            sheet_of : vec![],
+            sheet_grids : vec![],
+            preserve_on_resize : true,
+            staggered_spawn : false,
+            spawn_interval : 30,
+            steps_since_spawn : 0,
+            sheet_params : vec![],
+            sheet_kinetic_energy : vec![],
+            view_shear : vec2(0.0, 0.0),
+            anaglyph : false,
+            interocular : stereo::saved_interocular(),
+            auto_fit : false,
+            view_transform : ViewTransform::identity(),
+            view_target : ViewTransform::identity(),
+            contact_distance : 0.0,
+            contacts : vec![],
+            newborn_boost : NewbornBoost::new(),
+            spatial_hash : SpatialHash::new(1.0),
             constraints : vec![],
+            lod_constraints : vec![],
+            topology : Topology::empty(),
+            has_uint_indices : true,
+            index_mode : IndexMode::Uint,
+            edge_batches : vec![],
+            show_valence : false,
+            inspector : false,
+            rollups_enabled : false,
+            rollups : Rc::new(RefCell::new(Rollups::new())),
+            show_charts : false,
+            charts : vec![
+                TimeSeriesChart::new("residual", "Distance residual", &["mean", "max"], chart::DEFAULT_CAPACITY, true),
+                TimeSeriesChart::new("energy", "Energy", &["kinetic", "potential", "total"], chart::DEFAULT_CAPACITY, false),
+            ],
+            worst_constraints : Rc::new(RefCell::new(WorstConstraints::new())),
+            probe_constraint : None,
+            reversal_steps : 100,
+            reversal_run : None,
+            ramp_experiment : None,
+            reversal_results : vec![],
+            profiling : false,
+            lod_mode : LodMode::Auto,
+            lod_threshold : 400,
+            area_constraints : vec![],
+            bend_constraints : vec![],
+            dihedral_constraints : vec![],
             num_particles : 0,
             num_constraints : 0, 
             prev_timestamp : 0.0f64,
             time_step : 0,
-            target_dt : 1.0 / 60.0,
-            num_iterations : 2,
-            do_jacobi : false,
+            solvers : solver::registry(),
+            // Gauss-Seidel
+            scratch : Scratch::default(),
+            frame_lambda_clamps : 0,
+            total_lambda_clamps : 0,
             do_reset: true,
-            do_clean_lambda: true,
-            stiffness : 5000.0f32,
-            warm_start : true,
-            nu : 0.6f32,
-            eta : 1.0f32,
-            jacobi_relaxation : 0.6f32,
+            animated_reset : false,
+            reset_blend : None,
+            do_clean_lambda: Some(LambdaFilter::All),
+            clean_filter : LambdaFilter::All,
+            last_clean_filter : None,
+            weight : None,
+            reader : ReaderService::new(),
+            reader_task : None,
+            sdf : None,
+            mesh : None,
+            mesh_error : None,
+            sdf_contour : vec![],
+            collision_thickness : 0.01f32,
+            collision_response : CollisionResponse::Projection,
+            collision_order : CollisionOrder::AfterConstraints,
+            restitution : 0.5,
+            floor : false,
+            spheres : vec![],
+            collider_contacts : vec![],
+            max_penetration : 0.0,
+            collider_lambda : vec![],
+            kinetic_energy : 0.0,
+            boundary_kinetic_energy : 0.0,
+            interior_kinetic_energy : 0.0,
+            wrinkle : None,
+            wrinkle_log : WrinkleLog::new(),
+            potential_energy : 0.0,
+            show_log : false,
+            layout : PanelLayout::saved(),
+            dragging_panel : None,
+            notebook : None,
+            notebook_entries : vec![],
+            notebook_pending : 0,
+            notebook_error : None,
+            show_notebook : false,
+            notebook_note : String::new(),
+            notebook_filter : String::new(),
+            notebook_sort : NotebookSort::Newest,
+            palette_index : palette::saved_index(),
+            watchdog : Watchdog::new(),
+            watchdog_reducing : false,
+            color_scales : ColorScales::saved(),
+            scale_use : ScaleUse::Valence,
+            selected_stop : None,
+            dragging_stop : false,
+            title_summary : title::saved_enabled(),
+            title : String::new(),
+            original_title : title::current(),
+            title_checked_ms : 0.0,
+            recorder : None,
+            ghost : None,
+            ghost_error : None,
+            small_multiples : None,
+            small_multiples_rows : small_multiples::axis_text(&small_multiples::DEFAULT_ROWS),
+            small_multiples_columns : small_multiples::axis_text(&small_multiples::DEFAULT_COLUMNS),
+            lambda_adjacency : LambdaAdjacency::new(),
+            lambda_scratch : vec![],
+            first_iteration_residual : (0.0, 0),
+            last_run_residual : None,
+            observers : vec![],
+            recording : false,
+            recording_settings : RecordingSettings::new(),
+            pluck_tool : false,
+            freeze : Freeze::new(),
+            freeze_tool : false,
+            measure_tool : false,
+            measure_start : None,
+            measurements : vec![],
+            right_press : None,
+            context_menu : None,
+            show_rulers : false,
+            sweep_log : Rc::new(RefCell::new(SweepLog::new())),
+            recording_sweep : false,
+            sweep_replay : None,
+            fuzzer : None,
+            freeze_box : None,
+            select_box : None,
+            selection : Selection::new(),
+            group_drag : None,
+            gpu_picker : None,
+            line_program : None,
+            line_program_failure : None,
+            gpu_picking_unavailable : false,
+            picking_check : None,
+            pluck_steps : 600,
+            pluck_drag : None,
+            particle_drag : None,
+            pluck : None,
+            cursor_history : CursorHistory::new(),
+            drag_prediction : false,
+            drag_lead_ms : 16.0,
+            brush : ForceBrush::new(),
+            brush_time_ms : 0.0,
+            last_stepped_frame_ms : 0.0,
+            drag_lag : (0.0, 0),
+            strain_alarm : StrainAlarm::new(),
+            paused : false,
+            timeline : None,
+            timeline_name : None,
+            tutorial : None,
+            timeline_playing : false,
+            timeline_time : 0.0,
+            pin_origins : vec![],
+            timeline_peak_kinetic_energy : 0.0,
+            time_source : Box::new(RealTime),
+            scripted_time : false,
+            replaying_trace : false,
+            seed : seed::saved_or_random(),
+            warm_up : warm_up_by_default(),
+            warm_up_remaining : 0,
+            warm_up_frames : 0,
+            queued_msgs : vec![],
+            pop_threshold : 0.05f32,
+            pop_count : 0,
+            offscreen_canvas_supported : false,
+            shared_memory_supported : false,
+            editing : None,
+            edit_text : String::new(),
+            edit_ref : NodeRef::default(),
+            focus_edit : false,
         }
     }
 
@@ -123,8 +996,16 @@ impl Component for Model {
 
         let canvas = self.node_ref.cast::<HtmlCanvasElement>().unwrap();
 
-        let gl: GL = canvas
-            .get_context("webgl")
+        // Golden runs read the picture back, which the default attributes only allow until the
+        // browser composites it.
+        let context = if self.golden.is_some() {
+            let options = js_sys::Object::new();
+            let _ = js_sys::Reflect::set(&options, &JsValue::from_str("preserveDrawingBuffer"), &JsValue::TRUE);
+            canvas.get_context_with_context_options("webgl", &options)
+        } else {
+            canvas.get_context("webgl")
+        };
+        let gl: GL = context
             .unwrap()
             .unwrap()
             .dyn_into()
@@ -133,439 +1014,6405 @@ impl Component for Model {
         self.canvas = Some(canvas);
         self.gl = Some(gl);
 
+        if self.focus_edit {
+            if let Some(input) = self.edit_ref.cast::<HtmlInputElement>() {
+                let _ = input.focus();
+                input.select();
+            }
+            self.focus_edit = false;
+        }
+
         // In a more complex use-case, there will be additional WebGL initialization that should be
         // done here, such as enabling or disabling depth testing, depth functions, face
         // culling etc.
 
         if first_render {
-            // The callback to request animation frame is passed a time value which can be used for
-            // rendering motion independent of the framerate which may vary.
-            let render_frame = self.link.callback(Msg::Render);
-            let handle = RenderService::request_animation_frame(render_frame);
-            
-            // A reference to the handle must be stored, otherwise it is dropped and the render won't
-            // occur.
-            self.render_loop = Some(handle);
+            // Draw the first frame at the window's size rather than the defaults.
+            self.read_dimensions();
+
+            // Asking for the extension is what enables it, and it stays enabled for the context.
+            self.has_uint_indices = self.gl.as_ref().unwrap().get_extension("OES_element_index_uint").ok().flatten().is_some();
+            if !self.has_uint_indices {
+                warn!("OES_element_index_uint is unavailable, drawing with 16 bit indices");
+            }
+            self.update_index_mode();
+
+            if fuzz_requested() {
+                let initial = PARAMETERS.iter().map(|&(name, _)| (name, self.named_param(name))).collect();
+                info!("Fuzzing with seed {}", self.seed);
+                self.fuzzer = Some(Fuzzer::new(self.seed, initial, self.params.dt));
+            }
 
+            self.notebook_pending += 1;
+            notebook::open(self.link.callback(Msg::NotebookOpened));
+
+            self.offscreen_canvas_supported = offscreen_canvas_supported();
+            info!("OffscreenCanvas transfer {}", if self.offscreen_canvas_supported {"is supported"} else {"is not supported"});
+            self.shared_memory_supported = shared_memory_supported();
+            info!("SharedArrayBuffer {}", if self.shared_memory_supported {"is available"} else {"is unavailable without cross-origin isolation"});
+
+            // Golden runs keep the default settings so their pictures don't depend on the device.
+            crash::keep_params(&self.params);
+            // The overlay's reload after a crash takes up its cloth in place of any session.
+            let crash_report = if crash::restore_requested() {crash::take_saved()} else {None};
+            let session = if self.golden.is_some() || self.audit.is_some() || crash_report.is_some() {None} else {session::saved()};
+            match benchmark::saved_tier() {
+                _ if self.golden.is_some() => info!("Running the golden image scenarios"),
+                _ if self.audit.is_some() => {
+                    info!("Running the determinism audit");
+                    self.full_detail_lock = true;
+                }
+                Some(tier) => self.apply_quality_tier(tier),
+                // The resumed parameters would override whatever the benchmark picked.
+                None if session.is_some() || crash_report.is_some() => {}
+                None => self.benchmark = Some(Benchmark::new(&self.params)),
+            }
+            if let Some(session) = session {
+                self.resume_session(session);
+            }
+            if let Some(report) = crash_report {
+                self.restore_crash(report);
+            }
+
+            let page_hidden = self.link.callback(|_ : ()| Msg::PageHidden);
+            let handler = Closure::wrap(Box::new(move |_ : JsValue| page_hidden.emit(())) as Box<dyn FnMut(JsValue)>);
+            if let Some(window) = web_sys::window() {
+                window.set_onpagehide(Some(handler.as_ref().unchecked_ref()));
+            }
+            handler.forget();
+
+            self.schedule_next_frame();
         }
     }
 
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        if self.warm_up_remaining > 0 {
+            match msg {
+                Msg::Render(_) => {}
+                msg => {
+                    self.queued_msgs.push(msg);
+                    return false;
+                }
+            }
+        }
+
+        if !matches!(msg, Msg::Render(_)) {
+            self.needs_draw = true;
+            if self.idle.wake() {
+                info!("Woke the cloth");
+            }
+        }
+
+        if msg.changes_simulation() && self.pluck.as_ref().map_or(false, |p| !p.is_complete()) {
+            warn!("Aborted the pluck measurement because the simulation settings changed during the capture");
+            self.pluck = None;
+        }
+        // Nothing is rebuilt under a step held between phases.
+        if msg.changes_simulation() && self.micro_step.is_some() {
+            self.complete_micro_step();
+        }
+        if msg.changes_simulation() && self.reversal_run.is_some() {
+            warn!("Abandoned the reversal check because the simulation changed during it");
+            self.abandon_reversal_check();
+        }
+        if msg.changes_simulation() && self.ramp_experiment.is_some() && !matches!(msg, Msg::RampExperimentClicked) {
+            warn!("Abandoned the gravity ramp experiment because the simulation changed during it");
+            self.end_ramp_experiment();
+        }
+
         match msg {
-            Msg::StiffnessChanged(e) => {
-                match e.value.parse::<f32>()
-                {
-                    Ok(f) =>
-                    {
-                        self.stiffness = 10.0f32.powf(f);
+            Msg::ApplyParams(delta) => {
+                self.apply_params(delta);
+                true
+            }
+            Msg::EditStarted(param) => {
+                self.edit_text = match param {
+                    Param::Iterations => self.params.num_iterations.to_string(),
+                    Param::Eta => self.params.eta.to_string(),
+                    Param::Nu => self.params.nu.to_string(),
+                    Param::Stiffness => format!("{:e}", self.params.stiffness),
+                    Param::SolverParam(index) => self.solvers[self.params.solver_index].param(index).to_string(),
+                    Param::AreaStiffness => format!("{:e}", self.params.area_stiffness),
+                    Param::BendStiffness => format!("{:e}", self.params.bend_stiffness),
+                    Param::WeightMass => self.params.weight_mass.to_string(),
+                    Param::PinStiffness => format!("{:e}", self.params.pin_stiffness),
+                };
+                self.editing = Some(param);
+                self.focus_edit = true;
+                true
+            }
+            Msg::EditTextChanged(e) => {
+                self.edit_text = e.value;
+                false
+            }
+            Msg::EditKeyDown(e) => {
+                match e.key().as_str() {
+                    "Enter" => {
+                        // Keep the surrounding form from submitting.
+                        e.prevent_default();
+                        self.update(Msg::EditCommitted)
+                    }
+                    "Escape" => {
+                        self.editing = None;
+                        true
                     }
-                    Err(_) => {}
+                    _ => false,
                 }
-                true
             }
-            Msg::JacobiRelaxationChanged(e) => {
-                match e.value.parse::<f32>()
-                {
-                    Ok(f) =>
-                    {
-                        self.jacobi_relaxation = f;
+            Msg::EditCommitted => {
+                // Blur also fires after Enter or Escape removed the input, by which point there is
+                // nothing left to commit.
+                match self.editing.take() {
+                    Some(param) => {
+                        let value = InputData { value : self.edit_text.clone() };
+                        // Routed through the slider messages so typed values get the same handling.
+                        let msg = match param {
+                            Param::Iterations => Msg::NumIterationsChanged(value),
+                            Param::Eta => Msg::EtaChanged(value),
+                            Param::Nu => Msg::NuChanged(value),
+                            Param::Stiffness => Msg::StiffnessChanged(value),
+                            Param::SolverParam(index) => Msg::SolverParamChanged(index, value),
+                            Param::AreaStiffness => Msg::AreaStiffnessChanged(value),
+                            Param::BendStiffness => Msg::BendStiffnessChanged(value),
+                            Param::WeightMass => Msg::WeightMassChanged(value),
+                            Param::PinStiffness => Msg::PinStiffnessChanged(value),
+                        };
+                        self.update(msg);
+                        true
                     }
-                    Err(_) => {}
+                    None => false,
+                }
+            }
+            Msg::StiffnessChanged(e) => {
+                if let Some(f) = parse_stiffness("stiffness", &e.value) {
+                    self.update(Msg::ApplyParams(ParamsDelta { stiffness : Some(f), ..ParamsDelta::default() }));
+                }
+                true
+            }
+            Msg::SolverParamChanged(index, e) => {
+                let solver = &mut self.solvers[self.params.solver_index];
+                if let Some(f) = parse_param(solver.param_specs()[index].name, &e.value) {
+                    solver.set_param(index, f);
+                    // Solver parameters aren't in Params, but the rules still cover them.
+                    self.update(Msg::ApplyParams(ParamsDelta::default()));
                 }
                 true
             }
             Msg::NuChanged(e) => {
-                match e.value.parse::<f32>()
-                {
-                    Ok(f) =>
-                    {
-                        self.nu = f;
-                    }
-                    Err(_) => {}
+                if let Some(f) = parse_param("nu", &e.value) {
+                    self.update(Msg::ApplyParams(ParamsDelta { nu : Some(f), ..ParamsDelta::default() }));
+                }
+                true
+            }
+            Msg::GravityAngleChanged(e) => {
+                if let Some(f) = parse_param("gravity_angle", &e.value) {
+                    self.update(Msg::ApplyParams(ParamsDelta { gravity_angle : Some(f), ..ParamsDelta::default() }));
+                }
+                true
+            }
+            Msg::GravityPeriodChanged(e) => {
+                if let Some(f) = parse_param("gravity_period", &e.value) {
+                    self.update(Msg::ApplyParams(ParamsDelta { gravity_period : Some(f), ..ParamsDelta::default() }));
                 }
                 true
             }
             Msg::EtaChanged(e) => {
-                match e.value.parse::<f32>()
-                {
-                    Ok(f) =>
-                    {
-                        self.eta = f;
-                    }
-                    Err(_) => {}
+                if let Some(f) = parse_param("eta", &e.value) {
+                    self.update(Msg::ApplyParams(ParamsDelta { eta : Some(f), ..ParamsDelta::default() }));
+                }
+                true
+            }
+            Msg::LambdaClampChanged => {
+                self.update(Msg::ApplyParams(ParamsDelta { lambda_clamp : Some(!self.params.lambda_clamp), ..ParamsDelta::default() }));
+                true
+            }
+            Msg::ClampSafetyChanged(e) => {
+                if let Some(f) = parse_param("clamp_safety", &e.value) {
+                    self.update(Msg::ApplyParams(ParamsDelta { clamp_safety : Some(f), ..ParamsDelta::default() }));
+                }
+                true
+            }
+            Msg::LambdaDiffusionChanged(e) => {
+                if let Some(f) = parse_param("lambda_diffusion", &e.value) {
+                    self.update(Msg::ApplyParams(ParamsDelta { lambda_diffusion : Some(f), ..ParamsDelta::default() }));
+                }
+                true
+            }
+            Msg::AdaptiveEtaChanged(e) => {
+                if let Some(f) = parse_param("adaptive_eta", &e.value) {
+                    self.update(Msg::ApplyParams(ParamsDelta { adaptive_eta : Some(f), ..ParamsDelta::default() }));
                 }
                 true
             }
             Msg::WarmStartChanged =>
             {
-                self.warm_start = !self.warm_start;
-                self.do_clean_lambda = true;
+                self.update(Msg::ApplyParams(ParamsDelta { warm_start : Some(!self.params.warm_start), ..ParamsDelta::default() }));
+                true
+            }
+            Msg::AnalyticSeedChanged => {
+                self.update(Msg::ApplyParams(ParamsDelta { analytic_seed : Some(!self.params.analytic_seed), ..ParamsDelta::default() }));
                 true
             }
             Msg::NumIterationsChanged(e) =>
             {
-                self.num_iterations = e.value.parse().unwrap();
+                if let Some(n) = parse_iterations("num_iterations", &e.value) {
+                    self.update(Msg::ApplyParams(ParamsDelta { num_iterations : Some(n), ..ParamsDelta::default() }));
+                }
                 true
             }
-            Msg::SimTypeClicked(t)=> {
-                match t {
-                    SimType::Jacobi => {
-                        self.do_jacobi = true;
-                    }
-                    SimType::GaussSeidel => {
-                        self.do_jacobi = false;
-                    }
-                }
-                self.do_clean_lambda = true;
+            Msg::SolverSelected(index)=> {
+                self.update(Msg::ApplyParams(ParamsDelta { solver_index : Some(index), ..ParamsDelta::default() }));
                 true
             }
             Msg::ResetClicked => {
+                if self.animated_reset {
+                    // The solver is paused for the blend and the lambdas go away with the old
+                    // constraints when the new cloth is swapped in.
+                    let cloth = self.fresh_cloth();
+                    self.reset_blend = Some(ResetBlend::new(&self.current_positions, cloth));
+                    debug!("Started animated reset");
+                } else {
+                    self.do_reset = true;
+                    self.do_clean_lambda = Some(LambdaFilter::All);
+                }
+                false
+            }
+            Msg::TensionOnlyChanged => {
+                self.update(Msg::ApplyParams(ParamsDelta { tension_only : Some(!self.params.tension_only), ..ParamsDelta::default() }));
+                true
+            }
+            Msg::ScriptedTimeChanged => {
+                self.scripted_time = !self.scripted_time;
+                self.replaying_trace = false;
+                if self.scripted_time {
+                    info!("Using scripted frame intervals with seed {}", self.seed);
+                    self.time_source = Box::new(ScriptedTime::random(self.seed));
+                } else {
+                    self.time_source = Box::new(RealTime);
+                }
+                // A scripted run only reproduces from a reset, so start one now.
+                self.pop_count = 0;
                 self.do_reset = true;
-                self.do_clean_lambda = true;
+                self.do_clean_lambda = Some(LambdaFilter::All);
+                true
+            }
+            Msg::SeedChanged(e) => {
+                match parse_seed(&e.value) {
+                    Some(seed) => {
+                        self.set_seed(seed);
+                        true
+                    }
+                    None => false,
+                }
+            }
+            Msg::RerollSeedClicked => {
+                self.set_seed(seed::random_seed());
+                true
+            }
+            Msg::ExportTraceClicked => {
+                if let Some(intervals) = self.time_source.intervals() {
+                    let trace = FrameTrace { intervals_ms : intervals.to_vec(), seed : Some(self.seed) };
+                    let json = serde_json::to_string(&trace).unwrap();
+                    if let Err(e) = download::download_text("frame_trace.json", "application/json", &json) {
+                        error!("Failed to export frame trace: {:?}", e);
+                    }
+                }
                 false
             }
-            Msg::CleanLambdaClicked => {
-                self.do_clean_lambda = true;
+            Msg::TraceFileChosen(ChangeData::Files(files)) => {
+                if let Some(file) = files.get(0) {
+                    let callback = self.link.callback(Msg::TraceFileLoaded);
+                    match self.reader.read_file(file, callback) {
+                        Ok(task) => self.reader_task = Some(task),
+                        Err(e) => error!("Failed to read frame trace: {}", e),
+                    }
+                }
                 false
             }
-            Msg::Render(timestamp) => {
-
-                let do_reset = self.do_reset;
-
-                if do_reset
-                {
-                    self.time_step = 0;
-                    self.do_reset = false;
-                    self.prev_timestamp = timestamp;
-
-                    self.current_positions.clear();
-                    self.previous_positions.clear();
-                    self.is_fixed.clear();
-                    self.constraints.clear();
-
-                    for i in 0..self.num_particles_x
-                    {
-                        for j in 0..self.num_particles_y
-                        {
-                            let xpos = i as f32 / self.num_particles_x as f32 - 0.5f32;
-                            let ypos = j as f32 / self.num_particles_y as f32 - 0.5f32;
-                            self.current_positions.push(vec3(xpos, -ypos, xpos * 0.01f32));
-
-                            self.is_fixed.push(j == 0 && (i == 0 || i == self.num_particles_x-1));
+            Msg::TraceFileChosen(_) => false,
+            Msg::TraceFileLoaded(file) => {
+                self.reader_task = None;
+                match FrameTrace::from_json(&file.content) {
+                    Ok(trace) => {
+                        info!("Replaying frame trace {} ({} intervals)", file.name, trace.intervals_ms.len());
+                        self.time_source = Box::new(ScriptedTime::from_trace(trace.intervals_ms));
+                        self.scripted_time = true;
+                        self.replaying_trace = true;
+                        // Everything else random in the run has to match the trace too.
+                        if let Some(seed) = trace.seed {
+                            self.seed = seed;
+                            seed::save(seed);
                         }
+                        self.pop_count = 0;
+                        self.do_reset = true;
+                        self.do_clean_lambda = Some(LambdaFilter::All);
                     }
-
-                    self.previous_positions = self.current_positions.clone();
-
-                    for i in 0..self.num_particles_x
-                    {
-                        for j in 0..self.num_particles_y-1
-                        {
-                            let p0 = (i*self.num_particles_y + j) as usize;
-                            let p1 = (i*self.num_particles_y + j + 1) as usize;
-                            self.constraints.push(Constraint::new(p0, p1, &self.current_positions));
-                        }
+                    Err(e) => error!("Rejected frame trace {}: {}", file.name, e),
+                }
+                true
+            }
+            Msg::LogPanelToggled => {
+                self.show_log = !self.show_log;
+                true
+            }
+            Msg::RollupsChanged => {
+                self.rollups_enabled = !self.rollups_enabled;
+                self.rollups.borrow_mut().clear();
+                self.register_observers();
+                true
+            }
+            Msg::RollupWindowChanged(e) => {
+                if let Some(f) = parse_param("rollup_window", &e.value) {
+                    if f > 0.0 {
+                        self.rollups.borrow_mut().window_seconds = f;
+                    } else {
+                        warn!("Ignoring rollup_window {}, it must be positive", f);
                     }
-
-                    for i in 0..self.num_particles_x -1
-                    {
-                        for j in 0..self.num_particles_y
-                        {
-                            let p0 = (i*self.num_particles_y + j) as usize;
-                            let p1 = ((i+1)*self.num_particles_y + j) as usize;
-                            self.constraints.push(Constraint::new(p0, p1, &self.current_positions));
-
-                        }
+                }
+                true
+            }
+            Msg::ExportRollupsClicked => {
+                self.export_rollups();
+                false
+            }
+            Msg::ChartsChanged => {
+                self.show_charts = !self.show_charts;
+                for chart in self.charts.iter_mut() {
+                    chart.clear();
+                }
+                true
+            }
+            Msg::ChartLogScaleChanged(k) => {
+                if let Some(chart) = self.charts.get_mut(k) {
+                    chart.log_scale = !chart.log_scale;
+                }
+                true
+            }
+            Msg::PanelCollapseToggled(id) => {
+                self.layout.toggle_collapsed(id);
+                self.layout.save();
+                true
+            }
+            Msg::PanelDockToggled(id) => {
+                self.layout.toggle_dock(id);
+                self.layout.save();
+                true
+            }
+            Msg::PanelDragStarted(id) => {
+                self.dragging_panel = Some(id);
+                true
+            }
+            Msg::PanelDraggedOver(target) => {
+                match self.dragging_panel {
+                    Some(dragged) => self.layout.move_to(dragged, target),
+                    None => false,
+                }
+            }
+            Msg::PanelDraggedToEnd(dock) => {
+                match self.dragging_panel {
+                    Some(dragged) => self.layout.move_to_end(dragged, dock),
+                    None => false,
+                }
+            }
+            Msg::PanelDragEnded => {
+                // A press on the canvas may be let go over a panel, which the canvas never hears.
+                self.let_go_of_particle();
+                match self.dragging_panel.take() {
+                    Some(_) => {
+                        self.layout.save();
+                        true
                     }
-
-                    for i in 0..self.num_particles_x -1
-                    {
-                        for j in 0..self.num_particles_y - 1
-                        {
-                            let p0 = (i*self.num_particles_y + j) as usize;
-                            let p1 = ((i+1)*self.num_particles_y + j + 1) as usize;
-                            self.constraints.push(Constraint::new(p0, p1, &self.current_positions));
-
-                            let p0 = ((i+1)*self.num_particles_y + j) as usize;
-                            let p1 = (i*self.num_particles_y + j + 1) as usize;
-                            self.constraints.push(Constraint::new(p0, p1, &self.current_positions));
-                        }
+                    None => false,
+                }
+            }
+            Msg::NotebookOpened(result) => {
+                self.notebook_pending -= 1;
+                match result {
+                    Ok(db) => {
+                        self.notebook = Some(db);
+                        self.reload_notebook();
+                    }
+                    Err(e) => {
+                        warn!("The experiment notebook is unavailable: {}", e);
+                        self.notebook_error = Some(e);
                     }
-
-                    self.num_particles = self.current_positions.len();
-                    self.num_constraints = self.constraints.len();
                 }
-
-                if self.do_clean_lambda {
-                    for i in 0..self.num_constraints {
-                        self.constraints[i].lambda = vec3(0.0, 0.0, 0.0);
+                true
+            }
+            Msg::NotebookLoaded(result) => {
+                self.notebook_pending -= 1;
+                match result {
+                    Ok(entries) => {
+                        self.notebook_entries = entries;
+                        self.evict_notebook_entries();
+                    }
+                    Err(e) => {
+                        error!("Failed to read the experiment notebook: {}", e);
+                        self.notebook_error = Some(e);
                     }
-                    self.do_clean_lambda = false;
                 }
-
-                let delta_time = (timestamp - self.prev_timestamp) as f32 / 1000.0;
-
-                if delta_time >= self.target_dt
-                {
-                    self.time_step += 1;
-                    self.prev_timestamp = timestamp;
-
-                    let gravity = vec3(0.0f32, -9.8f32, 0.0f32) * 0.1;
-
-                    for i in 0..self.num_particles
-                    {
-                        let mut p = self.current_positions[i];
-                        let p0 = p;
-                        let pm1 = self.previous_positions[i];
-
-                        let is_fixed = self.is_fixed[i];
-
-                        if !is_fixed {
-                            let mut d = p-pm1;
-                            d = d * self.nu;
-                            d = d + gravity*self.target_dt;
-                            p = p + d; 
-                        }
-
-                        self.current_positions[i] = p;
-                        self.previous_positions[i] = p0;
-                    }
-
-                    let stiffness = self.stiffness;
-                    let aTilde = 1.0f32 / (stiffness * self.target_dt * self.target_dt);
-                    let mut workspace = vec![vec3(0.0,0.0,0.0); self.num_particles];
-                    let mut workspace2 = vec![vec3(0.0,0.0,0.0); self.num_particles];
-                    
-                    for iteration in 0..self.num_iterations
-                    {
-                        for constraint_index in 0..self.num_constraints
-                        {
-                            let mut i = constraint_index;
-                            let mut c = &mut self.constraints[i];
-    
-                            let p0InvMass = if self.is_fixed[c.p0] {0.0f32} else {1.0f32};
-                            let p1InvMass = if self.is_fixed[c.p1] {0.0f32} else {1.0f32};
-                            let totalInvMass = p0InvMass + p1InvMass;
-                            let p0RelMass = p0InvMass/totalInvMass;
-                            let p1RelMass = p1InvMass/totalInvMass;
-
-                            let mut p0 = self.current_positions[c.p0];
-                            let mut p1 = self.current_positions[c.p1];
-                            
-                            let len = (p0-p1).length();
-                            let normal = (p0-p1)/len;
-    
-                            let mut residual = len - c.length;
-
-                            let mut velocityCorrection = vec3(0.0, 0.0, 0.0);
-
-                            let effectiveEta = if self.do_jacobi {self.eta} else {0.7*self.eta};
-
-                            let mut deltaLambda = -(residual * normal + aTilde*if iteration == 0 {vec3(0.0, 0.0, 0.0)} else {c.lambda}) / (totalInvMass + aTilde);
-                            if iteration == 0 && self.warm_start{
-                                deltaLambda += effectiveEta*c.lambda;
-                                velocityCorrection +=  effectiveEta*c.lambda;
-                            }
-
-                            if iteration == 0
-                            {
-                                c.lambda = vec3(0.0, 0.0, 0.0);
-                            }
-                                
-                            c.lambda += deltaLambda;
-
-                            let p0Correction = deltaLambda * p0RelMass;
-                            let p1Correction = -deltaLambda * p1RelMass;
-    
-                            let p0VeloCorrection = velocityCorrection*p0RelMass;
-                            let p1VeloCorrection = -velocityCorrection*p1RelMass;
-
-                            if self.do_jacobi
-                            {
-                                workspace[c.p0] += p0Correction;
-                                workspace[c.p1] += p1Correction;
-
-                                //workspace2[c.p0] += p0VeloCorrection;
-                                //workspace2[c.p1] += p1VeloCorrection;
-                            }
-                            else
-                            {
-                                p0 += p0Correction;
-                                p1 += p1Correction;
-    
-                                self.current_positions[c.p0] = p0;
-                                self.current_positions[c.p1] = p1;
-
-                                //self.previous_positions[c.p0] += p0VeloCorrection;
-                                //self.previous_positions[c.p1] += p1VeloCorrection;
-                            }
-                        }
-
-                        if self.do_jacobi {
-                            for i in 0..self.num_particles {
-                                let impulse = workspace[i];
-                                self.current_positions[i] += impulse * self.jacobi_relaxation;
-                                workspace[i] = vec3(0.0, 0.0, 0.0);
-                                let veloImpulse = workspace2[i];
-                                self.previous_positions[i] += veloImpulse * self.jacobi_relaxation;
-                                workspace2[i] = vec3(0.0, 0.0, 0.0);
-                            }
+                true
+            }
+            Msg::NotebookWritten(result) => {
+                self.notebook_pending -= 1;
+                if let Err(e) = result {
+                    error!("Failed to update the experiment notebook: {}", e);
+                    self.notebook_error = Some(e);
+                }
+                self.reload_notebook();
+                true
+            }
+            Msg::NotebookToggled => {
+                self.show_notebook = !self.show_notebook;
+                true
+            }
+            Msg::NotebookNoteChanged(e) => {
+                self.notebook_note = e.value;
+                false
+            }
+            Msg::NotebookFilterChanged(e) => {
+                self.notebook_filter = e.value;
+                true
+            }
+            Msg::NotebookSortChanged(ChangeData::Select(select)) => {
+                if let Some(sort) = NotebookSort::from_name(&select.value()) {
+                    self.notebook_sort = sort;
+                }
+                true
+            }
+            Msg::NotebookSortChanged(_) => false,
+            Msg::NotebookLogClicked => {
+                if let Some(db) = &self.notebook {
+                    let entry = NotebookEntry {
+                        id : None,
+                        timestamp : js_sys::Date::now(),
+                        params : self.params.clone(),
+                        scenario : self.timeline_name.clone(),
+                        seed : self.seed,
+                        metrics : NotebookMetrics {
+                            steps : self.time_step,
+                            kinetic_energy : self.kinetic_energy,
+                            potential_energy : self.potential_energy,
+                            pops : self.pop_count,
+                            wrinkle_energy : self.wrinkle.map(|w| w.energy),
+                            first_iteration_residual : self.last_run_residual,
+                        },
+                        note : std::mem::take(&mut self.notebook_note),
+                    };
+                    self.notebook_pending += 1;
+                    notebook::add(db, &entry, self.link.callback(Msg::NotebookWritten));
+                }
+                true
+            }
+            Msg::NotebookRestoreClicked(id) => {
+                if let Some(entry) = self.notebook_entries.iter().find(|e| e.id == Some(id)).cloned() {
+                    info!("Restored the settings of notebook entry {}", id);
+                    self.update(Msg::ApplyParams(entry.params.delta()));
+                    self.set_seed(entry.seed);
+                }
+                true
+            }
+            Msg::NotebookDeleteClicked(id) => {
+                if let Some(db) = &self.notebook {
+                    self.notebook_pending += 1;
+                    notebook::delete(db, id, self.link.callback(Msg::NotebookWritten));
+                }
+                true
+            }
+            Msg::NotebookExportClicked => {
+                if let Err(e) = download::download_text("notebook.json", "application/json", &notebook::to_json(&self.notebook_entries)) {
+                    error!("Failed to export the notebook: {:?}", e);
+                }
+                false
+            }
+            Msg::AreaConstraintsChanged => {
+                self.update(Msg::ApplyParams(ParamsDelta { use_area_constraints : Some(!self.params.use_area_constraints), ..ParamsDelta::default() }));
+                true
+            }
+            Msg::AreaStiffnessChanged(e) => {
+                if let Some(f) = parse_stiffness("area_stiffness", &e.value) {
+                    self.update(Msg::ApplyParams(ParamsDelta { area_stiffness : Some(f), ..ParamsDelta::default() }));
+                }
+                true
+            }
+            Msg::StiffenPerimeterChanged => {
+                self.update(Msg::ApplyParams(ParamsDelta { stiffen_perimeter : Some(!self.params.stiffen_perimeter), ..ParamsDelta::default() }));
+                true
+            }
+            Msg::PerimeterStiffnessChanged(e) => {
+                if let Some(f) = parse_param("perimeter_stiffness", &e.value) {
+                    self.update(Msg::ApplyParams(ParamsDelta { perimeter_stiffness : Some(f), ..ParamsDelta::default() }));
+                }
+                true
+            }
+            Msg::SoftPinsChanged => {
+                self.update(Msg::ApplyParams(ParamsDelta { soft_pins : Some(!self.params.soft_pins), ..ParamsDelta::default() }));
+                true
+            }
+            Msg::PinStiffnessChanged(e) => {
+                if let Some(f) = parse_stiffness("pin_stiffness", &e.value) {
+                    self.update(Msg::ApplyParams(ParamsDelta { pin_stiffness : Some(f), ..ParamsDelta::default() }));
+                }
+                true
+            }
+            Msg::PinWeightChanged(e) => {
+                if let Some(f) = parse_param("pin_inverse_mass", &e.value) {
+                    self.update(Msg::ApplyParams(ParamsDelta { pin_inverse_mass : Some(f), ..ParamsDelta::default() }));
+                }
+                true
+            }
+            Msg::BendModelChanged(ChangeData::Select(select)) => {
+                let bend_model = match select.value().as_str() {
+                    "distance" => BendModel::Distance,
+                    "dihedral" => BendModel::Dihedral,
+                    _ => BendModel::None,
+                };
+                self.update(Msg::ApplyParams(ParamsDelta { bend_model : Some(bend_model), ..ParamsDelta::default() }));
+                true
+            }
+            Msg::BendModelChanged(_) => false,
+            Msg::BendStiffnessChanged(e) => {
+                if let Some(f) = parse_stiffness("bend_stiffness", &e.value) {
+                    self.update(Msg::ApplyParams(ParamsDelta { bend_stiffness : Some(f), ..ParamsDelta::default() }));
+                }
+                true
+            }
+            Msg::WeightChanged => {
+                self.weight = match self.weight {
+                    Some(_) => None,
+                    None => Some(self.new_weight()),
+                };
+                self.rebuild_topology();
+                true
+            }
+            Msg::WeightMassChanged(e) => {
+                if let Some(f) = parse_param("weight_mass", &e.value) {
+                    self.update(Msg::ApplyParams(ParamsDelta { weight_mass : Some(f), ..ParamsDelta::default() }));
+                }
+                true
+            }
+            Msg::MouseDown(e) => {
+                // A click anywhere on the canvas closes the quick actions menu and does nothing else.
+                let closed_menu = self.context_menu.take().is_some();
+                if e.button() == 2 {
+                    self.right_press = Some((e.offset_x(), e.offset_y()));
+                    return closed_menu;
+                }
+                if closed_menu {
+                    return true;
+                }
+                // With the small multiples up, a click hands the cell's settings to the main cloth.
+                if let Some(grid) = &self.small_multiples {
+                    if let Some((iterations, eta)) = grid.cell_at(e.offset_x(), e.offset_y(), self.width, self.height).map(|c| (c.iterations, c.eta)) {
+                        self.small_multiples = None;
+                        self.update(Msg::ApplyParams(ParamsDelta { num_iterations : Some(iterations), eta : Some(eta), ..ParamsDelta::default() }));
+                    }
+                    return true;
+                }
+                let cursor = self.client_to_sim(e.offset_x(), e.offset_y());
+                self.cursor_history.clear();
+                self.cursor_history.push(e.time_stamp(), cursor);
+                self.drag_lag = (0.0, 0);
+                if e.ctrl_key() {
+                    self.brush.active = true;
+                } else if e.alt_key() && self.weight.is_some() {
+                    // Alt-click moves the weight's attachment to the particle under the cursor.
+                    let p = self.pick_particle(e.offset_x(), e.offset_y());
+                    let particle_position = self.current_positions[p];
+                    let w = self.weight.as_mut().unwrap();
+                    w.attached_particle = p;
+                    w.length = (particle_position - w.position).length();
+                    w.lambda = vec3(0.0, 0.0, 0.0);
+                    debug!("Attached weight to particle {}", p);
+                } else if self.pluck_tool {
+                    // The grabbed particle is pinned to the cursor until it is let go.
+                    let p = self.pick_particle(e.offset_x(), e.offset_y());
+                    if self.is_fixed[p] {
+                        warn!("Particle {} is pinned and can't be plucked", p);
+                    } else {
+                        self.pluck_drag = Some((p, self.current_positions[p]));
+                        self.pluck = None;
+                        self.is_fixed[p] = true;
+                    }
+                } else if self.freeze_tool {
+                    // A box started from the quick actions menu is finished by this click.
+                    let start = self.freeze_box.map_or(cursor, |(start, _)| start);
+                    self.freeze_box = Some((start, cursor));
+                } else if self.measure_tool {
+                    let point = self.measure_point(e.offset_x(), e.offset_y());
+                    match self.measure_start.take() {
+                        Some(start) => {
+                            let measurement = Measurement { start : start, end : point };
+                            info!("Measured {:.4} units", measurement.length(&self.current_positions));
+                            self.measurements.push(measurement);
                         }
+                        None => self.measure_start = Some(point),
+                    }
+                    return true;
+                } else if e.shift_key() {
+                    self.select_box = Some((cursor, cursor));
+                } else if let Some(w) = self.weight.as_mut().filter(|w| (vec2(w.position.x, w.position.y) - cursor).length() < 2.0 * Weight::HALF_SIZE) {
+                    w.drag_target = Some(vec3(cursor.x, cursor.y, w.position.z));
+                } else if !self.selection.is_empty() {
+                    // Grabbing a selected particle drags the whole selection, and a click anywhere
+                    // else drops it.
+                    let p = self.pick_particle(e.offset_x(), e.offset_y());
+                    if self.selection.contains(p) {
+                        self.group_drag = Some(GroupDrag::new(&self.selection.particles, &self.current_positions, &self.is_fixed, cursor));
+                    } else {
+                        self.selection.clear();
+                        return true;
                     }
+                } else {
+                    self.start_particle_drag(e.offset_x(), e.offset_y());
                 }
-                
-                // Render functions are likely to get quite large, so it is good practice to split
-                // it into it's own function rather than keeping it inline in the update match
-                // case. This also allows for updating other UI elements that may be rendered in
-                // the DOM like a framerate counter, or other overlaid textual elements.
-                self.render_gl(timestamp);
-
-                let window = web_sys::window().unwrap();
-                let dimensions = WindowDimensions::get_dimensions(&window);
-                let width = dimensions.width;
-                let height = dimensions.height;
-
-                let should_render = !(width == self.width && height == self.height);
-
-                self.width = width;
-                self.height = height;
-                should_render
+                false
             }
-        }
-    }
-
-    fn view(&self) -> Html {
-
-        let jacobi_slider = if self.do_jacobi {
+            Msg::MouseMove(e) => {
+                let cursor = self.client_to_sim(e.offset_x(), e.offset_y());
+                self.cursor_history.push(e.time_stamp(), cursor);
+                self.move_drag_target(cursor);
+                if let Some((start, _)) = self.freeze_box {
+                    self.freeze_box = Some((start, cursor));
+                }
+                if let Some((start, _)) = self.select_box {
+                    self.select_box = Some((start, cursor));
+                }
+                false
+            }
+            Msg::MouseUp => {
+                self.brush.active = false;
+                self.let_go_of_particle();
+                if let Some((start, end)) = self.freeze_box.take() {
+                    let inside = selection::particles_in_box(&self.current_positions, start, end);
+                    let count = self.freeze.freeze(inside.into_iter(), &self.current_positions, &mut self.previous_positions, &mut self.is_fixed);
+                    info!("Froze {} particles, {} frozen in all", count, self.freeze.num_frozen());
+                }
+                if let Some((start, end)) = self.select_box.take() {
+                    self.selection.set(selection::particles_in_box(&self.current_positions, start, end));
+                    info!("Selected {} particles", self.selection.particles.len());
+                }
+                if let Some(drag) = self.group_drag.take() {
+                    debug!("Dragged {} selected particles", drag.num_particles());
+                }
+                if let Some(w) = &mut self.weight {
+                    w.drag_target = None;
+                }
+                true
+            }
+            Msg::MouseLeave => {
+                self.brush.active = false;
+                let held = self.held_particle().is_some();
+                self.let_go_of_particle();
+                held
+            }
+            Msg::CanvasContextMenu(e) => {
+                let (x, y) = (e.offset_x(), e.offset_y());
+                let moved = self.right_press.take().map_or(0, |(px, py)| (x - px).abs().max((y - py).abs()));
+                if moved >= DRAG_THRESHOLD_PIXELS || self.num_particles == 0 {
+                    return false;
+                }
+                let point = self.client_to_sim(x, y);
+                let particle = self.pick_particle(x, y);
+                self.context_menu = Some(ContextMenu {
+                    x : x,
+                    y : y,
+                    point : point,
+                    particle : particle,
+                    pinned : self.is_fixed[particle] || self.pin_modes[particle] != PinMode::Free,
+                    constraint : context_menu::nearest_constraint(&self.current_positions, &self.constraints, point),
+                });
+                true
+            }
+            Msg::ContextMenuChosen(action) => {
+                let command = match self.context_menu.take() {
+                    Some(menu) => menu.command(action),
+                    None => return false,
+                };
+                match command {
+                    Some(MenuCommand::Unpin(p)) => {
+                        self.release_pin(p);
+                        info!("Released particle {}", p);
+                    }
+                    Some(MenuCommand::Pin(p)) => {
+                        if self.pin(p) {
+                            info!("Pinned particle {}", p);
+                        }
+                    }
+                    Some(MenuCommand::Probe(k)) => return self.update(Msg::ProbeConstraintSelected(k)),
+                    Some(MenuCommand::PushAway(point)) => self.push_away(point),
+                    Some(MenuCommand::MeasureFrom(x, y)) => {
+                        // The next click ends the measurement, as with the measure tool's second click.
+                        self.measure_tool = true;
+                        self.measure_start = Some(self.measure_point(x, y));
+                    }
+                    Some(MenuCommand::FreezeFrom(point)) => {
+                        // The box follows the cursor until the next click closes it.
+                        self.freeze_tool = true;
+                        self.freeze_box = Some((point, point));
+                    }
+                    None => {}
+                }
+                true
+            }
+            Msg::ContextMenuClosed => self.context_menu.take().is_some(),
+            Msg::SceneChanged(scene) => {
+                if self.mesh.take().is_some() {
+                    self.do_reset = true;
+                }
+                self.update(Msg::ApplyParams(ParamsDelta { scene : Some(scene), ..ParamsDelta::default() }));
+                true
+            }
+            Msg::ConnectivityChanged(ChangeData::Select(select)) => {
+                let connectivity = match select.value().as_str() {
+                    "4" => Connectivity::Four,
+                    "6" => Connectivity::Six,
+                    _ => Connectivity::Eight,
+                };
+                self.update(Msg::ApplyParams(ParamsDelta { connectivity : Some(connectivity), ..ParamsDelta::default() }));
+                true
+            }
+            Msg::ConnectivityChanged(_) => false,
+            Msg::WrapXChanged => {
+                self.update(Msg::ApplyParams(ParamsDelta { wrap_x : Some(!self.params.wrap_x), ..ParamsDelta::default() }));
+                true
+            }
+            Msg::RailChanged(ChangeData::Select(select)) => {
+                self.rail_shape = match select.value().as_str() {
+                    "line" => Some(RailShape::Line),
+                    "circle" => Some(RailShape::Circle),
+                    _ => None,
+                };
+                self.do_reset = true;
+                true
+            }
+            Msg::RailChanged(_) => false,
+            Msg::RailSizeChanged(e) => {
+                if let Some(f) = parse_param("rail_size", &e.value) {
+                    self.rail_size = f.max(0.05);
+                    self.do_reset = true;
+                }
+                true
+            }
+            Msg::SheetOverrideChanged(sheet) => {
+                let params = &mut self.sheet_params[sheet];
+                if params.iterations.is_some() {
+                    *params = SheetParams::default();
+                } else {
+                    params.iterations = Some(self.params.num_iterations);
+                    params.warm_start = Some(self.params.warm_start);
+                }
+                true
+            }
+            Msg::SheetIterationsChanged(sheet, e) => {
+                if let Some(n) = parse_iterations("sheet_iterations", &e.value) {
+                    self.sheet_params[sheet].iterations = Some(n);
+                }
+                true
+            }
+            Msg::SheetWarmStartChanged(sheet) => {
+                let params = &mut self.sheet_params[sheet];
+                params.warm_start = params.warm_start.map(|w| !w);
+                true
+            }
+            Msg::LodModeChanged(mode) => {
+                self.lod_mode = mode;
+                true
+            }
+            Msg::LodThresholdChanged(e) => {
+                if let Some(n) = parse_count("lod_threshold", &e.value) {
+                    self.lod_threshold = n;
+                }
+                true
+            }
+            Msg::GridWidthChanged(e) => {
+                if let Some(n) = parse_count("grid_width", &e.value) {
+                    self.update(Msg::ApplyParams(ParamsDelta { num_particles_x : Some(n.max(2)), ..ParamsDelta::default() }));
+                }
+                true
+            }
+            Msg::GridHeightChanged(e) => {
+                if let Some(n) = parse_count("grid_height", &e.value) {
+                    self.update(Msg::ApplyParams(ParamsDelta { num_particles_y : Some(n.max(2)), ..ParamsDelta::default() }));
+                }
+                true
+            }
+            Msg::PreserveOnResizeChanged(preserve) => {
+                self.preserve_on_resize = preserve;
+                true
+            }
+            Msg::StaggeredSpawnChanged => {
+                self.staggered_spawn = !self.staggered_spawn;
+                self.do_reset = true;
+                self.do_clean_lambda = Some(LambdaFilter::All);
+                true
+            }
+            Msg::SpawnIntervalChanged(e) => {
+                if let Some(n) = parse_count("spawn_interval", &e.value) {
+                    self.spawn_interval = n;
+                }
+                true
+            }
+            Msg::AutoFitChanged => {
+                self.auto_fit = !self.auto_fit;
+                if !self.auto_fit {
+                    self.view_target = ViewTransform::identity();
+                }
+                true
+            }
+            Msg::AnimatedResetChanged => {
+                self.animated_reset = !self.animated_reset;
+                true
+            }
+            Msg::CleanLambdaClicked => {
+                self.do_clean_lambda = Some(self.clean_filter);
+                self.last_clean_filter = Some(self.clean_filter);
+                true
+            }
+            Msg::CleanFilterChanged(ChangeData::Select(select)) => {
+                if let Some(filter) = LambdaFilter::from_name(&select.value()) {
+                    self.clean_filter = filter;
+                }
+                true
+            }
+            Msg::CleanFilterChanged(_) => false,
+            Msg::SdfFileChosen(ChangeData::Files(files)) => {
+                if let Some(file) = files.get(0) {
+                    let callback = self.link.callback(Msg::SdfFileLoaded);
+                    match self.reader.read_file(file, callback) {
+                        Ok(task) => self.reader_task = Some(task),
+                        Err(e) => error!("Failed to read SDF file: {}", e),
+                    }
+                }
+                false
+            }
+            Msg::SdfFileChosen(_) => false,
+            Msg::MeshFileChosen(ChangeData::Files(files)) => {
+                if let Some(file) = files.get(0) {
+                    let callback = self.link.callback(Msg::MeshFileLoaded);
+                    match self.reader.read_file(file, callback) {
+                        Ok(task) => self.reader_task = Some(task),
+                        Err(e) => error!("Failed to read mesh file: {}", e),
+                    }
+                }
+                false
+            }
+            Msg::MeshFileChosen(_) => false,
+            Msg::MeshFileLoaded(file) => {
+                self.reader_task = None;
+                let text = String::from_utf8_lossy(&file.content);
+                match ObjMesh::parse(&text) {
+                    Ok(mesh) => {
+                        info!("Imported {} with {} vertices and {} faces. Nothing is pinned, so select particles and pin them before resuming.",
+                            file.name, mesh.positions.len(), mesh.faces.len());
+                        self.mesh = Some(mesh);
+                        self.mesh_error = None;
+                        self.do_reset = true;
+                        self.do_clean_lambda = Some(LambdaFilter::All);
+                        self.paused = true;
+                    }
+                    Err(e) => {
+                        error!("Failed to import mesh {}: {}", file.name, e);
+                        self.mesh_error = Some(format!("{}: {}", file.name, e));
+                    }
+                }
+                true
+            }
+            Msg::ClearMeshClicked => {
+                info!("Back to the {}x{} grid", self.params.num_particles_x, self.params.num_particles_y);
+                self.mesh = None;
+                self.mesh_error = None;
+                self.do_reset = true;
+                self.do_clean_lambda = Some(LambdaFilter::All);
+                true
+            }
+            Msg::PaletteChanged(ChangeData::Select(select)) => {
+                let index = select.selected_index();
+                if index >= 0 && (index as usize) < PALETTES.len() {
+                    self.palette_index = index as usize;
+                    palette::save(self.palette_index);
+                    info!("Switched palette to {}", PALETTES[self.palette_index].name);
+                }
+                true
+            }
+            Msg::PaletteChanged(_) => false,
+            Msg::ScaleUseChanged(ChangeData::Select(select)) => {
+                if let Some(&scale_use) = SCALE_USES.get(select.selected_index().max(0) as usize) {
+                    self.scale_use = scale_use;
+                    self.selected_stop = None;
+                }
+                true
+            }
+            Msg::ScaleUseChanged(_) => false,
+            Msg::CustomScaleChanged => {
+                let palette = &PALETTES[self.palette_index];
+                let scale = self.color_scales.get_mut(self.scale_use);
+                *scale = match scale {
+                    Some(_) => None,
+                    None => Some(color_scale::palette_stops(palette)),
+                };
+                self.selected_stop = None;
+                self.color_scales.save();
+                self.needs_draw = true;
+                true
+            }
+            Msg::ScaleBarPressed(e) => {
+                let x = e.offset_x();
+                if let Some(stops) = self.color_scales.get(self.scale_use) {
+                    let nearest = (0..stops.len()).min_by_key(|&k| ((stops[k].t * color_scale::BAR_WIDTH as f32) as i32 - x).abs());
+                    self.selected_stop = nearest.filter(|&k| ((stops[k].t * color_scale::BAR_WIDTH as f32) as i32 - x).abs() <= color_scale::GRAB_PIXELS);
+                    self.dragging_stop = self.selected_stop.is_some();
+                }
+                true
+            }
+            Msg::ScaleBarMoved(e) => {
+                if !self.dragging_stop {
+                    return false;
+                }
+                let t = e.offset_x() as f32 / color_scale::BAR_WIDTH as f32;
+                if let (Some(stops), Some(k)) = (self.color_scales.get_mut(self.scale_use), self.selected_stop) {
+                    self.selected_stop = Some(color_scale::move_stop(stops, k, t));
+                    self.needs_draw = true;
+                }
+                true
+            }
+            Msg::ScaleBarReleased => {
+                if self.dragging_stop {
+                    self.dragging_stop = false;
+                    self.color_scales.save();
+                }
+                false
+            }
+            Msg::StopColorChanged(e) => {
+                if let (Some(stops), Some(k), Some(color)) = (self.color_scales.get_mut(self.scale_use), self.selected_stop, color_scale::from_hex(&e.value)) {
+                    if let Some(stop) = stops.get_mut(k) {
+                        stop.color = color;
+                        self.color_scales.save();
+                        self.needs_draw = true;
+                    }
+                }
+                true
+            }
+            Msg::AddStopClicked => {
+                if let Some(stops) = self.color_scales.get_mut(self.scale_use) {
+                    self.selected_stop = Some(color_scale::add_stop(stops));
+                    self.color_scales.save();
+                    self.needs_draw = true;
+                }
+                true
+            }
+            Msg::RemoveStopClicked => {
+                if let (Some(stops), Some(k)) = (self.color_scales.get_mut(self.scale_use), self.selected_stop) {
+                    if stops.len() > color_scale::MIN_STOPS && k < stops.len() {
+                        stops.remove(k);
+                        self.selected_stop = None;
+                        self.color_scales.save();
+                        self.needs_draw = true;
+                    }
+                }
+                true
+            }
+            Msg::TitleSummaryChanged => {
+                self.title_summary = !self.title_summary;
+                title::save_enabled(self.title_summary);
+                if self.title_summary {
+                    self.update_title();
+                } else {
+                    title::set(&self.original_title);
+                    self.title.clear();
+                }
+                true
+            }
+            Msg::AnaglyphChanged => {
+                self.anaglyph = !self.anaglyph;
+                self.needs_draw = true;
+                true
+            }
+            Msg::InterocularChanged(e) => {
+                if let Some(f) = parse_param("interocular", &e.value) {
+                    self.interocular = f.max(0.0).min(stereo::MAX_INTEROCULAR);
+                    stereo::save(self.interocular);
+                    self.needs_draw = true;
+                }
+                true
+            }
+            Msg::PacingPolicyChanged(ChangeData::Select(select)) => {
+                if let Some(policy) = HighRefreshPolicy::from_name(&select.value()) {
+                    self.frame_pacing.policy = policy;
+                    info!("High refresh policy set to {}", policy.name());
+                }
+                true
+            }
+            Msg::PacingPolicyChanged(_) => false,
+            Msg::IdleSleepChanged => {
+                self.idle.enabled = !self.idle.enabled;
+                true
+            }
+            Msg::IdleThresholdChanged(e) => {
+                if let Some(f) = parse_param("idle_threshold", &e.value) {
+                    self.idle.threshold = f.max(0.0);
+                }
+                true
+            }
+            Msg::IdleDelayChanged(e) => {
+                if let Some(f) = parse_param("idle_delay", &e.value) {
+                    self.idle.delay = f.max(0.0);
+                }
+                true
+            }
+            Msg::IdleLowFrameRateChanged => {
+                self.idle.low_frame_rate = !self.idle.low_frame_rate;
+                true
+            }
+            Msg::NewbornBoostChanged => {
+                self.newborn_boost.enabled = !self.newborn_boost.enabled;
+                true
+            }
+            Msg::NewbornFramesChanged(e) => {
+                if let Some(n) = parse_count("newborn_frames", &e.value) {
+                    self.newborn_boost.frames = n as u32;
+                }
+                true
+            }
+            Msg::NewbornPassesChanged(e) => {
+                if let Some(n) = parse_count("newborn_passes", &e.value) {
+                    self.newborn_boost.passes = n;
+                }
+                true
+            }
+            Msg::NewbornSeedChanged => {
+                self.newborn_boost.seed_lambda = !self.newborn_boost.seed_lambda;
+                true
+            }
+            Msg::QualityTierChanged(ChangeData::Select(select)) => {
+                if let Some(&tier) = QUALITY_TIERS.iter().find(|t| t.name() == select.value()) {
+                    self.benchmark = None;
+                    self.tier_note = None;
+                    self.apply_quality_tier(tier);
+                    info!("Switched to the {} quality tier", tier.name());
+                }
+                true
+            }
+            Msg::QualityTierChanged(_) => false,
+            Msg::PhaseSteppingChanged => {
+                self.phase_stepping = !self.phase_stepping;
+                if !self.phase_stepping {
+                    self.complete_micro_step();
+                    self.phase_note = None;
+                }
+                true
+            }
+            Msg::PhaseStepClicked => {
+                if self.paused && self.reset_blend.is_none() {
+                    self.phase_stepping = true;
+                    self.advance_phase();
+                }
+                true
+            }
+            Msg::FullDetailLockChanged => {
+                self.full_detail_lock = !self.full_detail_lock;
+                true
+            }
+            Msg::TierNoteDismissed => {
+                self.tier_note = None;
+                true
+            }
+            Msg::KeepSessionChanged => {
+                self.keep_session = !self.keep_session;
+                if self.keep_session {
+                    self.save_session();
+                } else {
+                    session::clear();
+                }
+                true
+            }
+            Msg::ResumeNoteDismissed => {
+                self.resumed = None;
+                true
+            }
+            Msg::StartFreshClicked => {
+                if let Some((_, params)) = self.resumed.take() {
+                    info!("Started fresh instead of resuming");
+                    self.update(Msg::ApplyParams(params.delta()));
+                    self.do_reset = true;
+                    self.do_clean_lambda = Some(LambdaFilter::All);
+                    if self.warm_up {
+                        self.warm_up_remaining = WARM_UP_STEPS;
+                    }
+                    self.save_session();
+                }
+                true
+            }
+            Msg::PageHidden => {
+                self.save_session();
+                false
+            }
+            Msg::SelfCollisionChanged => {
+                self.update(Msg::ApplyParams(ParamsDelta { self_collision : Some(!self.params.self_collision), ..ParamsDelta::default() }));
+                true
+            }
+            Msg::MaxSubstepsChanged(e) => {
+                if let Some(n) = parse_param("max_substeps", &e.value) {
+                    self.update(Msg::ApplyParams(ParamsDelta { max_substeps : Some(n.max(1.0) as u32), ..ParamsDelta::default() }));
+                }
+                true
+            }
+            Msg::WatchdogCeilingChanged(e) => {
+                if let Some(ms) = parse_param("watchdog_ceiling", &e.value) {
+                    self.watchdog.ceiling_ms = ms.max(0.0) as f64;
+                }
+                true
+            }
+            Msg::SmallMultiplesChanged => {
+                self.small_multiples = match (&self.small_multiples, self.small_multiples_axes()) {
+                    (None, Some((rows, columns))) => Some(SmallMultiples::new(&self.params, rows, columns)),
+                    _ => None,
+                };
+                true
+            }
+            Msg::SmallMultiplesRowsChanged(e) => {
+                self.small_multiples_rows = e.value;
+                self.rebuild_small_multiples();
+                true
+            }
+            Msg::SmallMultiplesColumnsChanged(e) => {
+                self.small_multiples_columns = e.value;
+                self.rebuild_small_multiples();
+                true
+            }
+            Msg::PanicNowClicked => {
+                crash::record_action(format!("Step {}: Panic Now", self.time_step));
+                panic!("Panic Now was pressed at step {}", self.time_step);
+            }
+            Msg::WatchdogRestoreClicked => {
+                self.restore_watchdog();
+                true
+            }
+            Msg::RuleBannerDismissed(name) => {
+                self.blocked_notes.retain(|&n| n != name);
+                if !self.dismissed_rules.contains(&name) {
+                    self.dismissed_rules.push(name);
+                }
+                true
+            }
+            Msg::TimelineFileChosen(ChangeData::Files(files)) => {
+                if let Some(file) = files.get(0) {
+                    let callback = self.link.callback(Msg::TimelineFileLoaded);
+                    match self.reader.read_file(file, callback) {
+                        Ok(task) => self.reader_task = Some(task),
+                        Err(e) => error!("Failed to read timeline: {}", e),
+                    }
+                }
+                false
+            }
+            Msg::TimelineFileChosen(_) => false,
+            Msg::TimelineFileLoaded(file) => {
+                self.reader_task = None;
+                let text = String::from_utf8_lossy(&file.content);
+                self.load_timeline(&file.name, &text);
+                true
+            }
+            Msg::GhostFileChosen(ChangeData::Files(files)) => {
+                if let Some(file) = files.get(0) {
+                    let callback = self.link.callback(Msg::GhostFileLoaded);
+                    match self.reader.read_file(file, callback) {
+                        Ok(task) => self.reader_task = Some(task),
+                        Err(e) => error!("Failed to read ghost recording: {}", e),
+                    }
+                }
+                false
+            }
+            Msg::GhostFileChosen(_) => false,
+            Msg::GhostFileLoaded(file) => {
+                self.reader_task = None;
+                self.set_ghost(file.name, Recorder::from_npy(&file.content));
+                true
+            }
+            Msg::GhostFromRecordingClicked => {
+                if let Some(recorder) = &self.recorder {
+                    let recording = recorder.borrow().clone();
+                    self.set_ghost("the recording".to_string(), Ok(recording));
+                }
+                true
+            }
+            Msg::GhostVisibleChanged => {
+                if let Some(ghost) = &mut self.ghost {
+                    ghost.visible = !ghost.visible;
+                }
+                true
+            }
+            Msg::GhostOffsetChanged(e) => {
+                match (e.value.trim().parse::<i32>(), &mut self.ghost) {
+                    (Ok(offset), Some(ghost)) => ghost.offset = offset,
+                    (Err(_), _) => warn!("Ignoring unparsable ghost_offset value {:?}", e.value),
+                    _ => {}
+                }
+                true
+            }
+            Msg::ClearGhostClicked => {
+                self.ghost = None;
+                self.ghost_error = None;
+                true
+            }
+            Msg::LoadSqueezeClicked => {
+                if self.load_timeline("squeeze benchmark", include_str!("./scenarios/squeeze.json")) {
+                    self.timeline_playing = true;
+                }
+                true
+            }
+            Msg::LoadGravityRampClicked => {
+                if self.load_timeline("gravity ramp", include_str!("./scenarios/gravity_ramp.json")) {
+                    self.timeline_playing = true;
+                }
+                true
+            }
+            Msg::StiffSheetClicked => {
+                self.load_stiff_sheet();
+                true
+            }
+            Msg::RampExperimentClicked => {
+                if self.ramp_experiment.is_some() {
+                    info!("Stopped the gravity ramp experiment");
+                    self.end_ramp_experiment();
+                } else {
+                    self.ramp_experiment = Some(RampExperiment::new(&self.params, self.export_settings()));
+                }
+                true
+            }
+            Msg::TimelinePlayClicked => {
+                self.timeline_playing = !self.timeline_playing;
+                true
+            }
+            Msg::TimelineStopClicked => {
+                self.timeline_playing = false;
+                self.timeline_time = 0.0;
+                self.first_iteration_residual = (0.0, 0);
+                self.restore_pins();
+                true
+            }
+            Msg::RecordingToggled => {
+                self.recording = !self.recording;
+                if self.recording {
+                    // Starting again discards the previous recording.
+                    let particles = self.recording_settings.particles(&self.is_fixed);
+                    info!("Recording {} particles", particles.len());
+                    self.recorder = Some(Rc::new(RefCell::new(Recorder::new(particles, &self.recording_settings))));
+                }
+                self.register_observers();
+                true
+            }
+            Msg::RecordSelectionChanged(selection) => {
+                self.recording_settings.selection = selection;
+                true
+            }
+            Msg::RecordProbesChanged(e) => {
+                self.recording_settings.probes = e.value;
+                true
+            }
+            Msg::RecordParticleStrideChanged(e) => {
+                if let Some(n) = parse_count("record_particle_stride", &e.value) {
+                    self.recording_settings.particle_stride = n as usize;
+                }
+                true
+            }
+            Msg::RecordStepStrideChanged(e) => {
+                if let Some(n) = parse_count("record_step_stride", &e.value) {
+                    self.recording_settings.step_stride = n as u32;
+                }
+                true
+            }
+            Msg::RecordCapChanged(e) => {
+                if let Some(f) = parse_param("record_cap", &e.value) {
+                    self.recording_settings.cap_megabytes = f.max(1.0);
+                }
+                true
+            }
+            Msg::RecordCompressedChanged => {
+                self.recording_settings.compressed = !self.recording_settings.compressed;
+                true
+            }
+            Msg::RecordKeyframeIntervalChanged(e) => {
+                if let Some(n) = parse_count("record_keyframe_interval", &e.value) {
+                    self.recording_settings.keyframe_interval = n as u32;
+                }
+                true
+            }
+            Msg::RecordQuantumChanged(e) => {
+                if let Some(f) = parse_param("record_quantum", &e.value) {
+                    if f > 0.0 {
+                        self.recording_settings.quantum = f;
+                    } else {
+                        warn!("Ignoring record_quantum {}, it must be positive", f);
+                    }
+                }
+                true
+            }
+            Msg::ExportRecordingClicked => {
+                if let Some(recorder) = &self.recorder {
+                    let npy = recorder.borrow().to_npy();
+                    if let Err(e) = download::download_bytes(&format!("positions_seed{}.npy", self.seed), "application/octet-stream", &npy) {
+                        error!("Failed to export position history: {:?}", e);
+                    }
+                }
+                // The run's health over the same time, so the two can be read side by side.
+                if self.rollups_enabled && !self.rollups.borrow().history.is_empty() {
+                    self.export_rollups();
+                }
+                false
+            }
+            Msg::DragPredictionChanged => {
+                self.drag_prediction = !self.drag_prediction;
+                self.drag_lag = (0.0, 0);
+                true
+            }
+            Msg::BrushModeChanged(mode) => {
+                self.brush.mode = mode;
+                true
+            }
+            Msg::BrushStrengthChanged(e) => {
+                if let Some(f) = parse_param("brush_strength", &e.value) {
+                    self.brush.strength = f.max(0.0);
+                }
+                true
+            }
+            Msg::BrushRadiusChanged(e) => {
+                if let Some(f) = parse_param("brush_radius", &e.value) {
+                    self.brush.radius = f.max(0.01);
+                }
+                true
+            }
+            Msg::DragLeadChanged(e) => {
+                if let Some(f) = parse_param("drag_lead_ms", &e.value) {
+                    self.drag_lead_ms = f.max(0.0).min(32.0);
+                }
+                true
+            }
+            Msg::PluckToolChanged => {
+                self.pluck_tool = !self.pluck_tool;
+                true
+            }
+            Msg::FreezeToolChanged => {
+                self.freeze_tool = !self.freeze_tool;
+                self.freeze_box = None;
+                true
+            }
+            Msg::MeasureToolChanged => {
+                self.measure_tool = !self.measure_tool;
+                self.measure_start = None;
+                self.needs_draw = true;
+                true
+            }
+            Msg::RulersChanged => {
+                self.show_rulers = !self.show_rulers;
+                self.needs_draw = true;
+                true
+            }
+            Msg::MeasurementDismissed(index) => {
+                if index < self.measurements.len() {
+                    self.measurements.remove(index);
+                }
+                self.needs_draw = true;
+                true
+            }
+            Msg::ClearMeasurementsClicked => {
+                self.measurements.clear();
+                self.measure_start = None;
+                self.needs_draw = true;
+                true
+            }
+            Msg::StepClicked if self.micro_step.is_some() => {
+                self.complete_micro_step();
+                true
+            }
+            Msg::StepClicked => {
+                if self.paused && self.reset_blend.is_none() {
+                    self.sweep_replay = None;
+                    self.interpolation_from.clear();
+                    self.time_step += 1;
+                    self.step_once();
+                    self.needs_draw = true;
+                }
+                true
+            }
+            Msg::SweepRecordClicked => {
+                self.complete_micro_step();
+                if self.paused && self.reset_blend.is_none() {
+                    self.record_sweep();
+                }
+                true
+            }
+            Msg::SweepPlayToggled => {
+                if let Some(replay) = &mut self.sweep_replay {
+                    if replay.cursor == replay.len() {
+                        replay.seek(0);
+                    }
+                    replay.playing = !replay.playing;
+                }
+                true
+            }
+            Msg::SweepScrubbed(e) => {
+                if let (Some(replay), Ok(cursor)) = (&mut self.sweep_replay, e.value.trim().parse::<usize>()) {
+                    replay.playing = false;
+                    replay.seek(cursor);
+                    self.needs_draw = true;
+                }
+                true
+            }
+            Msg::SweepSpeedChanged(e) => {
+                if let (Some(replay), Some(speed)) = (&mut self.sweep_replay, parse_param("sweep_speed", &e.value)) {
+                    replay.speed = speed.max(1.0);
+                }
+                true
+            }
+            Msg::SweepExited => {
+                self.sweep_replay = None;
+                self.needs_draw = true;
+                true
+            }
+            Msg::PickingCheckClicked => {
+                self.run_picking_check();
+                true
+            }
+            Msg::UnfreezeClicked => {
+                if !self.selection.is_empty() {
+                    let before = self.freeze.num_frozen();
+                    for &p in self.selection.particles.iter() {
+                        self.freeze.release(p, &self.current_positions, &mut self.previous_positions, &mut self.is_fixed);
+                    }
+                    info!("Unfroze {} selected particles", before - self.freeze.num_frozen());
+                    return true;
+                }
+                info!("Unfroze {} particles", self.freeze.num_frozen());
+                self.freeze.unfreeze(&self.current_positions, &mut self.previous_positions, &mut self.is_fixed);
+                true
+            }
+            Msg::PluckStepsChanged(e) => {
+                if let Some(n) = parse_count("pluck_steps", &e.value) {
+                    self.pluck_steps = n;
+                }
+                true
+            }
+            Msg::PauseToggled => {
+                self.complete_micro_step();
+                self.paused = !self.paused;
+                if !self.paused {
+                    self.sweep_replay = None;
+                }
+                true
+            }
+            Msg::TutorialStarted => {
+                if self.tutorial.is_none() {
+                    self.tutorial = Some(Tutorial::new(self.params.clone()));
+                    self.paused = false;
+                    info!("Started the tour");
+                    self.run_tutorial();
+                }
+                true
+            }
+            Msg::TutorialNext => {
+                if let Some(tutorial) = &mut self.tutorial {
+                    tutorial.advance();
+                }
+                self.run_tutorial();
+                true
+            }
+            Msg::TutorialClosed => {
+                self.end_tutorial();
+                true
+            }
+            Msg::StrainAlarmChanged => {
+                self.strain_alarm.enabled = !self.strain_alarm.enabled;
+                self.strain_alarm.reset(self.num_constraints);
+                true
+            }
+            Msg::MinStrainChanged(e) => {
+                if let Some(f) = parse_param("min_strain", &e.value) {
+                    self.strain_alarm.min_strain = f.min(0.0);
+                }
+                true
+            }
+            Msg::MaxStrainChanged(e) => {
+                if let Some(f) = parse_param("max_strain", &e.value) {
+                    self.strain_alarm.max_strain = f.max(0.0);
+                }
+                true
+            }
+            Msg::PauseOnAlarmChanged => {
+                self.strain_alarm.pause_on_alarm = !self.strain_alarm.pause_on_alarm;
+                true
+            }
+            Msg::ExportAlarmsClicked => {
+                let settings = self.export_settings();
+                if let Err(e) = download::download_text(&format!("strain_alarms_seed{}.csv", self.seed), "text/csv", &self.strain_alarm.to_csv(&settings)) {
+                    error!("Failed to export strain alarms: {:?}", e);
+                }
+                false
+            }
+            Msg::WrinkleLogChanged => {
+                self.wrinkle_log.enabled = !self.wrinkle_log.enabled;
+                if self.wrinkle_log.enabled {
+                    self.wrinkle_log.clear();
+                }
+                true
+            }
+            Msg::ExportWrinkleClicked => {
+                let settings = self.export_settings();
+                if let Err(e) = download::download_text(&format!("wrinkle_seed{}.csv", self.seed), "text/csv", &self.wrinkle_log.to_csv(&settings)) {
+                    error!("Failed to export wrinkle energy: {:?}", e);
+                }
+                false
+            }
+            Msg::ExportPluckClicked => {
+                if let Some(pluck) = &self.pluck {
+                    let settings = self.export_settings();
+                    if let Err(e) = download::download_text("pluck.csv", "text/csv", &pluck.to_csv(&settings)) {
+                        error!("Failed to export pluck measurement: {:?}", e);
+                    }
+                }
+                false
+            }
+            Msg::ProfilingChanged => {
+                self.profiling = !self.profiling;
+                profiling::set_enabled(self.profiling);
+                true
+            }
+            Msg::ShowValenceChanged => {
+                self.show_valence = !self.show_valence;
+                true
+            }
+            Msg::InspectorChanged => {
+                self.inspector = !self.inspector;
+                self.worst_constraints.borrow_mut().clear();
+                self.register_observers();
+                true
+            }
+            Msg::ReverseTimeClicked => {
+                self.warn_irreversible();
+                self.reverse_time();
+                true
+            }
+            Msg::ReversalCheckClicked => {
+                self.start_reversal_check();
+                true
+            }
+            Msg::ReversalStepsChanged(e) => {
+                if let Some(n) = parse_count("reversal_steps", &e.value) {
+                    self.reversal_steps = n;
+                }
+                true
+            }
+            Msg::ExportReversalClicked => {
+                if let Err(e) = download::download_text("reversal.csv", "text/csv", &reversal::to_csv(&self.reversal_results)) {
+                    error!("Failed to export time reversal checks: {:?}", e);
+                }
+                false
+            }
+            Msg::ProbeConstraintSelected(index) => {
+                self.probe_constraint = Some(index);
+                self.focus_constraint(index);
+                true
+            }
+            Msg::TimelineScrubbed(e) => {
+                // Scrubbing applies the continuous state at the new time but skips the events in
+                // between, which only fire during playback.
+                if let Some(t) = parse_param("timeline_time", &e.value) {
+                    self.timeline_time = t.max(0.0);
+                    self.apply_timeline_state();
+                }
+                true
+            }
+            Msg::SdfFileLoaded(file) => {
+                self.reader_task = None;
+                let text = String::from_utf8_lossy(&file.content);
+                match SdfGrid::from_json(&text) {
+                    Ok(grid) => {
+                        // The contour only depends on the grid, so it is built once here rather than per frame.
+                        self.sdf_contour = grid.isocontour();
+                        info!("Loaded SDF collider {} ({} contour segments)", file.name, self.sdf_contour.len() / 4);
+                        self.sdf = Some(grid);
+                    }
+                    Err(e) => error!("Failed to load SDF collider {}: {}", file.name, e),
+                }
+                true
+            }
+            Msg::CollisionResponseChanged(ChangeData::Select(select)) => {
+                self.collision_response = match select.value().as_str() {
+                    "reflection" => CollisionResponse::Reflection,
+                    "xpbd" => CollisionResponse::Xpbd,
+                    _ => CollisionResponse::Projection,
+                };
+                self.collider_lambda.iter_mut().for_each(|l| *l = 0.0);
+                true
+            }
+            Msg::CollisionResponseChanged(_) => false,
+            Msg::CollisionOrderChanged(ChangeData::Select(select)) => {
+                if let Some(order) = COLLISION_ORDERS.iter().find(|o| o.name() == select.value()) {
+                    self.collision_order = *order;
+                }
+                true
+            }
+            Msg::CollisionOrderChanged(_) => false,
+            Msg::RestitutionChanged(e) => {
+                if let Some(f) = parse_param("restitution", &e.value) {
+                    self.restitution = f.max(0.0).min(1.0);
+                }
+                true
+            }
+            Msg::FloorChanged => {
+                self.floor = !self.floor;
+                true
+            }
+            Msg::ReleasePinsClicked => {
+                if !self.selection.is_empty() {
+                    for p in self.selection.particles.clone() {
+                        self.release_pin(p);
+                    }
+                    info!("Released the pins among {} selected particles", self.selection.particles.len());
+                    return false;
+                }
+                self.freeze.unfreeze(&self.current_positions, &mut self.previous_positions, &mut self.is_fixed);
+                self.is_fixed.iter_mut().for_each(|fixed| *fixed = false);
+                self.pin_modes.iter_mut().for_each(|mode| *mode = PinMode::Free);
+                self.anchors.clear();
+                info!("Released every pinned particle");
+                false
+            }
+            Msg::PinSelectionClicked => {
+                let count = self.selection.particles.clone().into_iter().filter(|&p| self.pin(p)).count();
+                info!("Pinned {} of {} selected particles", count, self.selection.particles.len());
+                false
+            }
+            Msg::FreezeSelectionClicked => {
+                let count = self.freeze.freeze(self.selection.particles.iter().cloned(), &self.current_positions, &mut self.previous_positions, &mut self.is_fixed);
+                info!("Froze {} selected particles, {} frozen in all", count, self.freeze.num_frozen());
+                true
+            }
+            Msg::ClearSelectionClicked => {
+                self.selection.clear();
+                true
+            }
+            Msg::CanvasKeyDown(e) => {
+                if e.key() == "Escape" && self.context_menu.is_some() {
+                    self.context_menu = None;
+                    return true;
+                }
+                if e.key() == "." && self.paused && self.reset_blend.is_none() {
+                    self.phase_stepping = true;
+                    self.advance_phase();
+                    return true;
+                }
+                if e.key() == "Escape" && self.measure_start.is_some() {
+                    self.measure_start = None;
+                    return true;
+                }
+                if e.key() == "Escape" && !self.selection.is_empty() {
+                    self.selection.clear();
+                    self.group_drag = None;
+                    return true;
+                }
+                false
+            }
+            Msg::ClearSdfClicked => {
+                info!("Removed SDF collider");
+                self.sdf = None;
+                self.sdf_contour.clear();
+                if let Some(gl) = &self.gl {
+                    self.gpu_buffers.release_prefix(gl, "sdf_");
+                }
+                true
+            }
+            Msg::Render(timestamp) => {
+                if self.golden.is_some() {
+                    return self.advance_golden(timestamp);
+                }
+                if self.audit.is_some() {
+                    return self.advance_audit(timestamp);
+                }
+
+                let frame_scope = profiling::scope("frame", || format!("Frame {}", self.frame_index));
+
+                // The task that delivered this frame has fired and can go.
+                self.render_loop = None;
+                self.track_frame_timing(timestamp);
+                if self.benchmark.is_some() {
+                    self.advance_benchmark();
+                }
+
+                // Cursor events share the frame's clock, not the simulation's.
+                if self.drag_prediction {
+                    self.predict_drag_target(timestamp);
+                }
+                let frame_ms = timestamp;
+                if frame_ms - self.title_checked_ms >= title::CHECK_INTERVAL_MS {
+                    self.title_checked_ms = frame_ms;
+                    self.update_title();
+                }
+
+                let timestamp = self.time_source.now(timestamp);
+
+                let do_reset = self.do_reset;
+
+                if do_reset
+                {
+                    crash::record_action(format!("Step {}: reset", self.time_step));
+                    self.time_step = 0;
+                    self.do_reset = false;
+                    // A step held between phases goes with the cloth it was stepping.
+                    self.micro_step = None;
+                    self.phase_note = None;
+                    self.total_lambda_clamps = 0;
+                    self.prev_timestamp = timestamp;
+
+                    self.reset_blend = None;
+                    self.interpolation_from.clear();
+
+                    let cloth = self.fresh_cloth();
+                    self.apply_cloth(cloth);
+                    self.rollups.borrow_mut().restart_window();
+                    debug!("Reset to a {}x{} cloth with {} constraints", self.params.num_particles_x, self.params.num_particles_y, self.num_constraints);
+
+                    if self.frame_index == 0 && self.warm_up && self.pending_resume.is_none() {
+                        self.warm_up_remaining = WARM_UP_STEPS;
+                    }
+                }
+
+                if let Some(filter) = self.do_clean_lambda.take() {
+                    self.clean_lambdas(filter);
+                }
+                // After the clean, which a reset always asks for.
+                if do_reset && self.params.analytic_seed {
+                    self.seed_analytic_lambdas();
+                }
+                // Last, so the resumed lambdas are the ones the first step sees.
+                if do_reset {
+                    if let Some(saved) = self.pending_resume.take() {
+                        self.restore_cloth(saved);
+                    }
+                }
+
+                let warming_up = self.warm_up_remaining > 0;
+                if warming_up {
+                    self.advance_warm_up();
+                    // Pick up in real time from here rather than catching up on the burst.
+                    self.prev_timestamp = timestamp;
+                }
+
+                let delta_time = (timestamp - self.prev_timestamp) as f32 / 1000.0;
+
+                let mut stepped = false;
+                let checking_reversal = self.reversal_run.is_some() || self.ramp_experiment.is_some();
+                if self.reversal_run.is_some() {
+                    self.prev_timestamp = timestamp;
+                    stepped = true;
+                    self.interpolation_from.clear();
+                    self.advance_reversal_check();
+                } else if self.ramp_experiment.is_some() {
+                    self.prev_timestamp = timestamp;
+                    stepped = true;
+                    self.interpolation_from.clear();
+                    self.advance_ramp_experiment();
+                } else if delta_time >= self.params.dt && !self.paused && !self.idle.is_sleeping()
+                {
+                    self.prev_timestamp = timestamp;
+                    stepped = true;
+                    self.frame_lambda_clamps = 0;
+                    self.max_penetration = 0.0;
+
+                    // Slow displays take several steps a frame to keep physics up to speed.
+                    let steps = self.frame_pacing.steps_per_frame();
+                    let steps = steps.min(self.params.max_substeps.max(1));
+                    let performance = web_sys::window().and_then(|w| w.performance());
+                    let start = performance.as_ref().map_or(0.0, |p| p.now());
+                    let mut steps_taken = 0;
+                    for k in 0..steps {
+                        if self.paused {
+                            break;
+                        }
+                        steps_taken += 1;
+                        self.time_step += 1;
+                        self.brush_time_ms = self.last_stepped_frame_ms + (frame_ms - self.last_stepped_frame_ms) * (k + 1) as f64 / steps as f64;
+
+                        if self.frame_pacing.interpolates(self.params.dt) {
+                            self.interpolation_from.clone_from(&self.current_positions);
+                        } else {
+                            self.interpolation_from.clear();
+                        }
+
+                        let step_start = performance.as_ref().map_or(0.0, |p| p.now());
+                        if self.reset_blend.is_some() {
+                            self.advance_reset_blend();
+                        } else {
+                            self.step_once();
+                        }
+                        let step_ms = performance.as_ref().map_or(0.0, |p| p.now() - step_start);
+                        if self.watchdog.is_tripped_by(step_ms) {
+                            self.watchdog_tripped(step_ms);
+                            break;
+                        }
+                    }
+                    if let (true, Some(performance)) = (self.rollups_enabled, &performance) {
+                        self.rollups.borrow_mut().add_steps_time(performance.now() - start, steps_taken);
+                    }
+                    self.last_stepped_frame_ms = frame_ms;
+                    let gravity = self.gravity();
+                    if let (Some(grid), Some(performance)) = (&mut self.small_multiples, &performance) {
+                        grid.advance(performance, &self.params, gravity, steps_taken);
+                    }
+                    if self.keep_session && frame_ms - self.last_session_save_ms >= session::SAVE_INTERVAL_MS {
+                        self.last_session_save_ms = frame_ms;
+                        self.save_session();
+                    }
+                    if self.frame_lambda_clamps > 0 {
+                        info!("Lambda clamp engaged {} times in frame {}", self.frame_lambda_clamps, self.frame_index);
+                    }
+                }
+
+                let replaying = self.sweep_replay.as_ref().map_or(false, |r| r.playing);
+                if let Some(replay) = &mut self.sweep_replay {
+                    replay.advance(timestamp);
+                    self.needs_draw |= replaying;
+                }
+                
+                // Drawing lives in render_gl, kept out of this match arm. The draw is skipped on a
+                // fast display's frames between steps, which would show the same positions unless
+                // something else changed, and while the canvas has no area, where the simulation
+                // carries on undrawn.
+                let skip_draw = (!stepped && !self.needs_draw && self.frame_pacing.skips_draws(self.params.dt)) || !self.has_drawable_size();
+                // Asleep, nothing changes unless something is input, so a frame a second will do.
+                let dozing = self.idle.is_sleeping() && self.idle.low_frame_rate && !self.needs_draw && timestamp - self.last_draw_timestamp < 1000.0;
+                if self.warm_up_remaining == 0 && !skip_draw && !dozing {
+                    self.needs_draw = false;
+                    self.last_draw_timestamp = timestamp;
+                    self.update_view_transform();
+                    let performance = if self.rollups_enabled {web_sys::window().and_then(|w| w.performance())} else {None};
+                    let start = performance.as_ref().map_or(0.0, |p| p.now());
+                    self.render_gl(timestamp);
+                    if let Some(performance) = &performance {
+                        self.rollups.borrow_mut().add_render_time(performance.now() - start);
+                    }
+                    self.measure_drag_lag();
+                    // Flashes hold while paused so the edges that tripped stay marked.
+                    if !self.paused {
+                        self.strain_alarm.fade();
+                    }
+                }
+                self.schedule_next_frame();
+
+                drop(frame_scope);
+                profiling::end_frame();
+
+                // Besides resizes, refresh the overlay every few frames so the debug readouts stay live.
+                self.frame_index += 1;
+                let resized = self.read_dimensions();
+                // Labels on the canvas have to keep up with the camera and the cloth.
+                let labelled = !self.measurements.is_empty() || self.show_rulers;
+                resized || self.frame_index % 10 == 0 || warming_up || checking_reversal || labelled || replaying
+            }
+        }
+    }
+
+    fn view(&self) -> Html {
+        let _view = profiling::scope("view", || "View".to_string());
+
+        html! {
+            <div id="container" style="display:flex" onmouseup={self.link.callback(|_| Msg::PanelDragEnded)}
+                onmousedown={self.link.callback(|_| Msg::ContextMenuClosed)}>
+                <canvas ref=self.node_ref.clone() width={self.width} height={self.height} style="position: absolute" tabindex="0"
+                    onkeydown={self.link.callback(|e| Msg::CanvasKeyDown(e))}
+                    onmousedown={self.link.callback(|e| Msg::MouseDown(e))}
+                    onmousemove={self.link.callback(|e| Msg::MouseMove(e))}
+                    onmouseup={self.link.callback(|_| Msg::MouseUp)}
+                    onmouseleave={self.link.callback(|_| Msg::MouseLeave)}
+                    oncontextmenu={self.link.callback(|e : MouseEvent| { e.prevent_default(); Msg::CanvasContextMenu(e) })}/>
+                {self.view_canvas_labels()}
+                {self.view_chart_labels()}
+                {self.view_small_multiples_labels()}
+                {self.view_context_menu()}
+                <div id="overlay" style="position: absolute; display:flex; width:20vw; flex-direction:column"> 
+                    {self.view_tutorial()}
+                    {self.view_warm_up_progress()}
+                    {self.view_reversal_progress()}
+                    {self.view_ramp_progress()}
+                    {self.view_idle_indicator()}
+                    {self.view_valence_warning()}
+                    {self.view_rule_banners()}
+                    {self.view_watchdog_banner()}
+                    {self.view_tier_note()}
+                    {self.view_resume_note()}
+                    {self.view_golden_status()}
+                    {self.view_audit_status()}
+                    {self.view_column(Dock::Left)}
+                </div>
+                <div id="overlay_right" style="position: absolute; right:0; display:flex; width:20vw; flex-direction:column; margin-right:10px;">
+                    {self.view_column(Dock::Right)}
+                </div>
+            </div>
+        }
+    }
+
+    fn change(&mut self, _props: Self::Properties) -> ShouldRender {
+        false
+    }
+}
+
+impl Model {
+    // The slider for param, or a text box for typing an exact value while it is being edited.
+    fn view_param_input(&self, param : Param, slider : Html) -> Html
+    {
+        if self.editing == Some(param) {
+            html! {
+                <input type="text" size="8" ref=self.edit_ref.clone() value={&self.edit_text}
+                    oninput={self.link.callback(|e| Msg::EditTextChanged(e))}
+                    onkeydown={self.link.callback(|e| Msg::EditKeyDown(e))}
+                    onblur={self.link.callback(|_| Msg::EditCommitted)}/>
+            }
+        } else {
+            html! {
+                <>
+                {slider}
+                <button type="button" title="Type an exact value" onclick={self.link.callback(move |_| Msg::EditStarted(param))}>{"✎"}</button>
+                </>
+            }
+        }
+    }
+
+    // The constraint frequency the stiffness implies, against what the time step can resolve.
+    fn view_stiffness_resolution(&self) -> Html
+    {
+        let resolution = StiffnessResolution::of(&self.params);
+        let suggestion = match stability::suggestion(&self.params) {
+            Some(text) => html! {<><br/>{text}</>},
+            None => html!{<></>},
+        };
+        html! {
+            <div id="stiffness_resolution" style={format!("font-size:12px; color:{};", resolution.margin().color())}>
+                {&format!("{:.1} Hz, {:.0}% of the {:.0} Hz the step resolves", resolution.frequency, 100.0 * resolution.ratio(), resolution.nyquist)}
+                {suggestion}
+            </div>
+        }
+    }
+
+    fn view_adaptive_eta(&self) -> Html
+    {
+        let label = if self.params.adaptive_eta > 0.0 {format!("Adaptive Warm Start: {}", self.params.adaptive_eta)} else {"Adaptive Warm Start: off".to_string()};
+        html! {
+            <>
+            <input type="range" id="adaptive_eta" min="0" max="200" step="1" value={self.params.adaptive_eta} oninput={self.link.callback(|e| Msg::AdaptiveEtaChanged(e))}/>
+            <label for="adaptive_eta">{label}</label><br/>
+            </>
+        }
+    }
+
+    fn view_lambda_diffusion(&self) -> Html
+    {
+        let label = if self.params.lambda_diffusion > 0.0 {format!("Lambda Diffusion: {}", self.params.lambda_diffusion)} else {"Lambda Diffusion: off".to_string()};
+        html! {
+            <>
+            <input type="range" id="lambda_diffusion" min="0" max="0.5" step="0.01" value={self.params.lambda_diffusion} oninput={self.link.callback(|e| Msg::LambdaDiffusionChanged(e))}/>
+            <label for="lambda_diffusion" title="Smooths stored lambdas towards their neighbours' before the warm start. Play a timeline to compare first-iteration residuals.">{label}</label><br/>
+            </>
+        }
+    }
+
+    fn view_clamp_controls(&self) -> Html
+    {
+        html! {
+            <>
+            <label for="lambda_clamp">{"Clamp Runaway Lambdas"}</label>
+            <input type="checkbox" id="lambda_clamp" checked =self.params.lambda_clamp onclick={self.link.callback(|_| Msg::LambdaClampChanged)}/>
+            <input type="range" id="clamp_safety" min="1" max="100" step="1" disabled=!self.params.lambda_clamp value={self.params.clamp_safety} oninput={self.link.callback(|e| Msg::ClampSafetyChanged(e))}/>
+            <label for="clamp_safety">{&format!("Safety: {}x", self.params.clamp_safety)}</label><br/>
+            </>
+        }
+    }
+
+    fn view_seed_toggle(&self) -> Html
+    {
+        html! {
+            <>
+            <label for="analytic_seed">{"Seed Hanging Tension on Reset"}</label>
+            <input type="checkbox" id="analytic_seed" checked =self.params.analytic_seed onclick={self.link.callback(|_| Msg::AnalyticSeedChanged)}/><br/>
+            </>
+        }
+    }
+
+    fn view_feature_toggles(&self) -> Html
+    {
+        html! {
+            <>
+            <label for="tension_only">{"Tension Only"}</label>
+            <input type="checkbox" id="tension_only" checked =self.params.tension_only onclick={self.link.callback(|_| Msg::TensionOnlyChanged)}/><br/>
+            <label for="area_constraints">{"Area Constraints"}</label>
+            <input type="checkbox" id="area_constraints" checked =self.params.use_area_constraints onclick={self.link.callback(|_| Msg::AreaConstraintsChanged)}/><br/>
+            {
+                if self.params.use_area_constraints {
+                    html! {
+                    <>
+                    {self.view_param_input(Param::AreaStiffness, html! {<input type="range" id="area_stiffness" min="3" max ="8" step ="0.01" value={self.params.area_stiffness.log10()} oninput={self.link.callback(|e| Msg::AreaStiffnessChanged(e))}/>})}
+                    <label for="area_stiffness">{&format!("Area Stiffness: {}", self.params.area_stiffness)}</label><br/>
+                    </>
+                    }
+                } else { html!{<></>} }
+            }
+            <label for="stiffen_perimeter">{"Stiffen Perimeter"}</label>
+            <input type="checkbox" id="stiffen_perimeter" checked =self.params.stiffen_perimeter onclick={self.link.callback(|_| Msg::StiffenPerimeterChanged)}/><br/>
+            {
+                if self.params.stiffen_perimeter {
+                    html! {
+                    <>
+                    <input type="range" id="perimeter_stiffness" min="1" max="5" step="0.1" value={self.params.perimeter_stiffness} oninput={self.link.callback(|e| Msg::PerimeterStiffnessChanged(e))}/>
+                    <label for="perimeter_stiffness">{&format!("Perimeter Stiffness: {}x", self.params.perimeter_stiffness)}</label><br/>
+                    </>
+                    }
+                } else { html!{<></>} }
+            }
+            {self.view_pin_controls()}
+            <label for="bend_model">{"Bending "}</label>
+            <select id="bend_model" onchange={self.link.callback(|e| Msg::BendModelChanged(e))}>
+                <option value="none" selected=self.params.bend_model == BendModel::None>{"None"}</option>
+                <option value="distance" selected=self.params.bend_model == BendModel::Distance>{"Distance"}</option>
+                <option value="dihedral" selected=self.params.bend_model == BendModel::Dihedral>{"Dihedral"}</option>
+            </select><br/>
+            {
+                if self.params.bend_model != BendModel::None {
+                    html! {
+                    <>
+                    {self.view_param_input(Param::BendStiffness, html! {<input type="range" id="bend_stiffness" min="0" max ="6" step ="0.01" value={self.params.bend_stiffness.log10()} oninput={self.link.callback(|e| Msg::BendStiffnessChanged(e))}/>})}
+                    <label for="bend_stiffness">{&format!("Bend Stiffness: {}", self.params.bend_stiffness)}</label><br/>
+                    </>
+                    }
+                } else { html!{<></>} }
+            }
+            <label for="weight">{"Hanging Weight"}</label>
+            <input type="checkbox" id="weight" checked =self.weight.is_some() onclick={self.link.callback(|_| Msg::WeightChanged)}/><br/>
+            {
+                if self.weight.is_some() {
+                    html! {
+                    <>
+                    {self.view_param_input(Param::WeightMass, html! {<input type="range" id="weight_mass" min="0.1" max ="20" step ="0.1" value={self.params.weight_mass} oninput={self.link.callback(|e| Msg::WeightMassChanged(e))}/>})}
+                    <label for="weight_mass">{&format!("Weight Mass: {} (drag it, alt-click to reattach)", self.params.weight_mass)}</label><br/>
+                    </>
+                    }
+                } else { html!{<></>} }
+            }
+            <label for="animated_reset">{"Animated Reset"}</label>
+            <input type="checkbox" id="animated_reset" checked =self.animated_reset onclick={self.link.callback(|_| Msg::AnimatedResetChanged)}/><br/>
+            <label for="auto_fit">{"Auto Fit View"}</label>
+            <input type="checkbox" id="auto_fit" checked =self.auto_fit onclick={self.link.callback(|_| Msg::AutoFitChanged)}/><br/>
+            </>
+        }
+    }
+
+    fn view_pin_controls(&self) -> Html
+    {
+        let stiffness = if self.params.soft_pins {
+            html! {
+                <>
+                {self.view_param_input(Param::PinStiffness, html! {<input type="range" id="pin_stiffness" min="3" max ="9" step ="0.01" value={self.params.pin_stiffness.log10()} oninput={self.link.callback(|e| Msg::PinStiffnessChanged(e))}/>})}
+                <label for="pin_stiffness">{&format!("Pin Stiffness: {}", self.params.pin_stiffness)}</label><br/>
+                </>
+            }
+        } else {
+            let label = if self.params.pin_inverse_mass > 0.0 {format!("Pin Weight: {}", self.params.pin_inverse_mass)} else {"Pin Weight: fixed".to_string()};
+            html! {
+                <>
+                <input type="range" id="pin_inverse_mass" min="0" max="0.1" step="0.001" value={self.params.pin_inverse_mass} oninput={self.link.callback(|e| Msg::PinWeightChanged(e))}/>
+                <label for="pin_inverse_mass" title="Each pin's inverse mass as a fraction of a free particle's. 0 holds pins still; a little more lets them give under heavy loads.">{label}</label><br/>
+                </>
+            }
+        };
+
+        html! {
+            <>
+            <label for="soft_pins">{"Soft Pins"}</label>
+            <input type="checkbox" id="soft_pins" checked =self.params.soft_pins onclick={self.link.callback(|_| Msg::SoftPinsChanged)}/><br/>
+            {stiffness}
+            </>
+        }
+    }
+
+    fn view_scene_choice(&self) -> Html
+    {
+        let scene = self.params.scene;
+        html! {
+            <>
+            <label for="scene_hanging">{"Hanging"}</label>
+            <input type="radio" id="scene_hanging" name="scene" checked=scene == Scene::Hanging onclick={self.link.callback(|_| Msg::SceneChanged(Scene::Hanging))}/>
+            <label for="scene_stacked">{"Stacked Sheets"}</label>
+            <input type="radio" id="scene_stacked" name="scene" checked=scene == Scene::Stacked onclick={self.link.callback(|_| Msg::SceneChanged(Scene::Stacked))}/>
+            <label for="scene_cantilever">{"Cantilever"}</label>
+            <input type="radio" id="scene_cantilever" name="scene" checked=scene == Scene::Cantilever onclick={self.link.callback(|_| Msg::SceneChanged(Scene::Cantilever))}/><br/>
+            <button type="button" class="button" style="background-color:#5756EB" title="A stiff, nearly inextensible sheet clamped at one corner, held up by its bending"
+                onclick={self.link.callback(|_| Msg::StiffSheetClicked)}>{"Stiff Sheet"}</button><br/>
+            </>
+        }
+    }
+
+    fn view_scene_controls(&self) -> Html
+    {
+        let sheet_controls = if self.sheet_params.len() > 1 {
+            html! {
+                { for self.sheet_params.iter().enumerate().map(|(sheet, params)| {
+                    let energy = self.sheet_kinetic_energy.get(sheet).cloned().unwrap_or(0.0);
+                    let overrides = match (params.iterations, params.warm_start) {
+                        (Some(iterations), Some(warm_start)) => html! {
+                            <>
+                            <input type="range" min="0" max="10" value={iterations} oninput={self.link.callback(move |e| Msg::SheetIterationsChanged(sheet, e))}/>
+                            <label>{&format!("Iterations: {}", iterations)}</label>
+                            <label>{" Warm Start"}</label>
+                            <input type="checkbox" checked=warm_start onclick={self.link.callback(move |_| Msg::SheetWarmStartChanged(sheet))}/><br/>
+                            </>
+                        },
+                        _ => html!{<></>},
+                    };
+                    html! {
+                        <div>
+                            <label>{&format!("Sheet {} (KE {:.2e}) override", sheet, energy)}</label>
+                            <input type="checkbox" checked=params.iterations.is_some() onclick={self.link.callback(move |_| Msg::SheetOverrideChanged(sheet))}/><br/>
+                            {overrides}
+                        </div>
+                    }
+                })}
+            }
+        } else { html!{<></>} };
+
+        html! {
+            <>
+            <label for="quality_tier">{"Quality: "}</label>
+            <select id="quality_tier" onchange={self.link.callback(|e| Msg::QualityTierChanged(e))}>
+                { for QUALITY_TIERS.iter().map(|t| html! {
+                    <option value={t.name()} selected=*t == self.quality_tier>{t.name()}</option>
+                })}
+            </select><br/>
+            {self.view_scene_choice()}
+            <label for="rail">{"Top Row: "}</label>
+            <select id="rail" onchange={self.link.callback(|e| Msg::RailChanged(e))}>
+                <option value="pinned" selected=self.rail_shape.is_none()>{"Pinned"}</option>
+                <option value="line" selected=self.rail_shape == Some(RailShape::Line)>{"On a line rail"}</option>
+                <option value="circle" selected=self.rail_shape == Some(RailShape::Circle)>{"On a circle rail"}</option>
+            </select>
+            {
+                if self.rail_shape.is_some() {
+                    html! {
+                        <>
+                        <input type="range" id="rail_size" min="0.2" max="1.5" step="0.05" value={self.rail_size} oninput={self.link.callback(|e| Msg::RailSizeChanged(e))}/>
+                        <label for="rail_size">{&format!("Rail size: {}", self.rail_size)}</label>
+                        </>
+                    }
+                } else { html!{<></>} }
+            }<br/>
+            <label for="connectivity">{"Connectivity: "}</label>
+            <select id="connectivity" onchange={self.link.callback(|e| Msg::ConnectivityChanged(e))}>
+                { for [("4", Connectivity::Four), ("6", Connectivity::Six), ("8", Connectivity::Eight)].iter().map(|&(value, connectivity)| html! {
+                    <option value={value} selected=self.params.connectivity == connectivity>{connectivity.name()}</option>
+                })}
+            </select>
+            <label for="wrap_x">{" Wrap Into Tube"}</label>
+            <input type="checkbox" id="wrap_x" checked=self.params.wrap_x disabled=self.params.scene != Scene::Hanging onclick={self.link.callback(|_| Msg::WrapXChanged)}/><br/>
+            <input type="range" id="grid_width" min="2" max="100" value={self.params.num_particles_x} oninput={self.link.callback(|e| Msg::GridWidthChanged(e))}/>
+            <label for="grid_width">{&format!("Grid Width: {}", self.params.num_particles_x)}</label><br/>
+            <input type="range" id="grid_height" min="2" max="100" value={self.params.num_particles_y} oninput={self.link.callback(|e| Msg::GridHeightChanged(e))}/>
+            <label for="grid_height">{&format!("Grid Height: {}", self.params.num_particles_y)}</label><br/>
+            <label for="staggered_spawn">{"Staggered Spawn"}</label>
+            <input type="checkbox" id="staggered_spawn" checked =self.staggered_spawn onclick={self.link.callback(|_| Msg::StaggeredSpawnChanged)}/>
+            <label for="spawn_interval">{" a row every "}</label>
+            <input type="number" id="spawn_interval" min="1" value={self.spawn_interval} oninput={self.link.callback(|e| Msg::SpawnIntervalChanged(e))}/>{" steps"}<br/>
+            <label for="resize_preserve">{"Preserve State"}</label>
+            <input type="radio" id="resize_preserve" name="resize_mode" checked=self.preserve_on_resize onclick={self.link.callback(|_| Msg::PreserveOnResizeChanged(true))}/>
+            <label for="resize_cold">{"Cold Reset"}</label>
+            <input type="radio" id="resize_cold" name="resize_mode" checked=!self.preserve_on_resize onclick={self.link.callback(|_| Msg::PreserveOnResizeChanged(false))}/><br/>
+            {sheet_controls}
+            </>
+        }
+    }
+
+    fn view_controls(&self) -> Html
+    {
+        let solver = &self.solvers[self.params.solver_index];
+        let solver_sliders = html! {
+            { for solver.param_specs().iter().enumerate().map(|(index, spec)| {
+                let value = solver.param(index);
+                html! {
+                    <>
+                    {self.view_param_input(Param::SolverParam(index), html! {<input type="range" id={spec.name} min={spec.min} max={spec.max} step={spec.step} value={value} oninput={self.link.callback(move |e| Msg::SolverParamChanged(index, e))}/>})}
+                    <label for={spec.name}>{&format!("{}: {}", spec.label, value)}</label><br/>
+                    </>
+                }
+            })}
+        };
+
+        html! {
+            <>
+            <form style="padding-left:10px;" action="/action_page.php">
+                { for self.solvers.iter().enumerate().map(|(index, solver)| html! {
+                    <>
+                    <label for={solver.name()}>{solver.name()}</label>
+                    <input type="radio" id={solver.name()} name="sim_type" value={solver.name()} checked=index == self.params.solver_index onclick={self.link.callback(move |_| Msg::SolverSelected(index))}/>
+                    </>
+                })}<br/>
+                {self.view_param_input(Param::Iterations, html! {<input type="range" id="iterations" style={self.tutorial_highlight("iterations")} min="0" max="10" value={self.params.num_iterations} oninput={self.link.callback(|e| Msg::NumIterationsChanged(e))}/>})}
+                <label for="iterations">{&format!("Iterations: {}", self.params.num_iterations)}</label><br/>
+                {self.view_param_input(Param::Eta, html! {<input type="range" id="eta" style={self.tutorial_highlight("eta")} min="0" max = "1" step = "0.01" value={self.params.eta} oninput={self.link.callback(|e|Msg::EtaChanged(e))}/>})}
+                <label for="eta">{&format!("η (Warmness Factor): {}", self.params.eta)}</label><br/>
+                {self.view_adaptive_eta()}
+                {self.view_lambda_diffusion()}
+                {self.view_param_input(Param::Nu, html! {<input type="range" id="nu" min="0" max="1" step="0.01" value={self.params.nu} oninput={self.link.callback(|e|Msg::NuChanged(e))}/>})}
+                <label for="nu">{&format!("𝜈 (Damping Factor): {}", self.params.nu)}</label><br/>
+                {self.view_param_input(Param::Stiffness, html! {<input type="range" id="stiffness" min="3" max ="8" step ="0.01" value={self.params.stiffness.log10()} oninput={self.link.callback(|e| Msg::StiffnessChanged(e))}/>})}
+                <label for="stiffness">{&format!("ξ (XPBD Stiffness): {}", self.params.stiffness)}</label><br/>
+                {self.view_stiffness_resolution()}
+                {solver_sliders}
+                <label for="warm_start">{"Warm Start"}</label>
+                <input type="checkbox" id="warm_start" style={self.tutorial_highlight("warm_start")} checked =self.params.warm_start onclick={self.link.callback(|_| Msg::WarmStartChanged)}/><br/>
+                <label for="keep_session" title="Save the parameters, positions and lambdas so a reload carries on warm">{"Keep Across Reloads"}</label>
+                <input type="checkbox" id="keep_session" checked =self.keep_session onclick={self.link.callback(|_| Msg::KeepSessionChanged)}/><br/>
+                {self.view_seed_toggle()}
+                {self.view_clamp_controls()}
+                {self.view_feature_toggles()}
+                {self.view_scene_controls()}
+                {self.view_gravity_controls()}
+                {self.view_lod_controls()}
+            </form>
+            {self.view_run_buttons()}
+            <div id="import" style="padding-left:10px;">
+                <label for="sdf_file">{"SDF Collider: "}</label>
+                <input type="file" id="sdf_file" accept=".json" onchange={self.link.callback(|e| Msg::SdfFileChosen(e))}/>
+                {
+                    if self.sdf.is_some() {
+                        html! {<button class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::ClearSdfClicked)}>{"Remove Collider"}</button>}
+                    } else { html!{<></>} }
+                }
+                {self.view_collision_controls()}
+                <label for="mesh_file">{"Cloth Mesh (OBJ): "}</label>
+                <input type="file" id="mesh_file" accept=".obj" onchange={self.link.callback(|e| Msg::MeshFileChosen(e))}/>
+                {
+                    if let Some(mesh) = &self.mesh {
+                        html! {
+                            <>
+                            <span>{&format!("{} vertices, {} faces ", mesh.positions.len(), mesh.faces.len())}</span>
+                            <button class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::ClearMeshClicked)}>{"Back to Grid"}</button>
+                            </>
+                        }
+                    } else { html!{<></>} }
+                }
+                {
+                    match &self.mesh_error {
+                        Some(e) => html! {<div style="color:#C62828;">{&format!("Import failed, {}", e)}</div>},
+                        None => html!{<></>},
+                    }
+                }
+            </div>
+            </>
+        }
+    }
+
+    // One column of the overlay. While a panel is being dragged there is a space below the last
+    // panel to drop it at the bottom, which is the only way into an empty column.
+    fn view_column(&self, dock : Dock) -> Html
+    {
+        let drop_zone = if self.dragging_panel.is_some() {
+            html! {<div style="min-height:40px; margin-top:10px; margin-left:10px; border:2px dashed #5756EB; border-radius:5px;" onmouseenter={self.link.callback(move |_| Msg::PanelDraggedToEnd(dock))}/>}
+        } else { html!{<></>} };
+
+        html! {
+            <>
+            { for self.layout.column(dock).filter(|p| p.id != PanelId::Inspector || self.inspector).map(|p| self.view_panel(p)) }
+            {drop_zone}
+            </>
+        }
+    }
+
+    // A panel's header, and its body unless it is collapsed. Collapsed bodies are never built,
+    // so they cost nothing to render.
+    fn view_panel(&self, panel : &PanelState) -> Html
+    {
+        let id = panel.id;
+        let body = if panel.collapsed {
+            html!{<></>}
+        } else {
+            match id {
+                PanelId::Controls => self.view_controls(),
+                PanelId::Stats => self.view_stats(),
+                PanelId::Inspector => self.view_inspector(),
+                PanelId::Debug => self.view_debug_controls(),
+                PanelId::Log => self.view_log_panel(),
+                PanelId::Notebook => self.view_notebook(),
+                PanelId::History => self.view_history_panel(),
+                PanelId::Charts => self.view_charts_panel(),
+            }
+        };
+        let opacity = if self.dragging_panel == Some(id) {"0.6"} else {"1"};
+        let style = format!("background-color:#96DEEB; border-radius:5px; margin-top:10px; margin-left:10px; padding: 2px; padding-right: 4px; opacity:{};", opacity);
+        let dock_arrow = if panel.dock == Dock::Left {"→"} else {"←"};
+
+        html! {
+            <div id={format!("panel_{}", id.title().to_lowercase())} style={style} onmouseenter={self.link.callback(move |_| Msg::PanelDraggedOver(id))}>
+                <div style="display:flex; align-items:center; padding-left:10px; font-weight:bold; user-select:none;">
+                    <span style="cursor:grab;" title="Drag to move" onmousedown={self.link.callback(move |_| Msg::PanelDragStarted(id))}>{"⠿ "}{id.title()}</span>
+                    <span style="flex-grow:1;"/>
+                    <button type="button" title="Collapse" onclick={self.link.callback(move |_| Msg::PanelCollapseToggled(id))}>{if panel.collapsed {"▸"} else {"▾"}}</button>
+                    <button type="button" title="Dock on the other side" onclick={self.link.callback(move |_| Msg::PanelDockToggled(id))}>{dock_arrow}</button>
+                </div>
+                {body}
+            </div>
+        }
+    }
+
+    fn view_debug_controls(&self) -> Html
+    {
+        let trace_controls = if self.scripted_time {
+            html! {
+                <>
+                <span>{&format!("Pops: {}", self.pop_count)}</span>
+                <button class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::ExportTraceClicked)}>{"Export Trace"}</button>
+                </>
+            }
+        } else { html!{<></>} };
+
+        html! {
+            <div id="debug_controls" style="padding-left:10px;">
+                <label for="seed">{"Seed: "}</label>
+                <input type="number" id="seed" min="0" value={self.seed} onchange={self.link.callback(|e| match e {
+                    ChangeData::Value(value) => Msg::SeedChanged(InputData { value : value }),
+                    _ => Msg::SeedChanged(InputData { value : String::new() }),
+                })}/>
+                <button class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::RerollSeedClicked)}>{"Reroll"}</button><br/>
+                <label for="scripted_time">{"Scripted Frame Times"}</label>
+                <input type="checkbox" id="scripted_time" checked =self.scripted_time onclick={self.link.callback(|_| Msg::ScriptedTimeChanged)}/><br/>
+                {trace_controls}<br/>
+                <label for="trace_file">{"Replay Trace: "}</label>
+                <input type="file" id="trace_file" accept=".json" onchange={self.link.callback(|e| Msg::TraceFileChosen(e))}/><br/>
+                {self.view_timeline_controls()}
+                {self.view_recording_controls()}
+                {self.view_ghost_controls()}
+                {self.view_pluck_controls()}
+                {self.view_drag_controls()}
+                {self.view_brush_controls()}
+                {self.view_freeze_controls()}
+                {self.view_measure_controls()}
+                {self.view_selection_controls()}
+                <button type="button" class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::PickingCheckClicked)}>{"Check GPU Picking"}</button><br/>
+                {self.view_alarm_controls()}
+                {self.view_wrinkle_controls()}
+                <label for="profiling">{"Profiling Marks"}</label>
+                <input type="checkbox" id="profiling" checked =self.profiling onclick={self.link.callback(|_| Msg::ProfilingChanged)}/><br/>
+                <label for="show_valence">{"Show Valence"}</label>
+                <input type="checkbox" id="show_valence" checked =self.show_valence onclick={self.link.callback(|_| Msg::ShowValenceChanged)}/><br/>
+                <label for="inspector">{"Worst Constraints"}</label>
+                <input type="checkbox" id="inspector" checked =self.inspector onclick={self.link.callback(|_| Msg::InspectorChanged)}/><br/>
+                {self.view_reversal_controls()}
+                {self.view_idle_controls()}
+                {self.view_newborn_controls()}
+                <label for="pacing">{"High Refresh: "}</label>
+                <select id="pacing" onchange={self.link.callback(|e| Msg::PacingPolicyChanged(e))}>
+                    { for HIGH_REFRESH_POLICIES.iter().map(|p| html! {
+                        <option value={p.name()} selected=*p == self.frame_pacing.policy>{p.name()}</option>
+                    })}
+                </select><br/>
+                {self.view_watchdog_controls()}
+                {self.view_small_multiples_controls()}
+                {self.view_panic_button()}
+                <label for="palette">{"Palette: "}</label>
+                <select id="palette" onchange={self.link.callback(|e| Msg::PaletteChanged(e))}>
+                    { for PALETTES.iter().enumerate().map(|(index, p)| html! {
+                        <option value={p.name} selected=index == self.palette_index>{p.name}</option>
+                    })}
+                </select><br/>
+                <label for="title_summary" title="Keeps the tab's title to a summary of the solver, iterations, eta, warm start and grid size">{"Settings in Tab Title"}</label>
+                <input type="checkbox" id="title_summary" checked =self.title_summary onclick={self.link.callback(|_| Msg::TitleSummaryChanged)}/><br/>
+                {self.view_color_scale_editor()}
+                {self.view_stereo_controls()}
+            </div>
+        }
+    }
+
+    fn view_stereo_controls(&self) -> Html
+    {
+        html! {
+            <>
+            <label for="anaglyph">{"Anaglyph 3D (red-cyan)"}</label>
+            <input type="checkbox" id="anaglyph" checked =self.anaglyph onclick={self.link.callback(|_| Msg::AnaglyphChanged)}/><br/>
+            <input type="range" id="interocular" min="0" max={stereo::MAX_INTEROCULAR} step="0.005" value={self.interocular} oninput={self.link.callback(|e| Msg::InterocularChanged(e))}/>
+            <label for="interocular">{&format!("Eye Separation: {}", self.interocular)}</label>
+            </>
+        }
+    }
+
+    fn view_timeline_controls(&self) -> Html
+    {
+        let transport = match &self.timeline {
+            Some(timeline) => html! {
+                <>
+                <button type="button" class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::TimelinePlayClicked)}>
+                    {if self.timeline_playing {"Pause"} else {"Play"}}
+                </button>
+                <button type="button" class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::TimelineStopClicked)}>{"Stop"}</button><br/>
+                <input type="range" id="timeline_time" min="0" max={timeline.duration()} step="0.01" value={self.timeline_time} oninput={self.link.callback(|e| Msg::TimelineScrubbed(e))}/>
+                <label for="timeline_time">{&format!("{:.2}s / {:.2}s", self.timeline_time, timeline.duration())}</label><br/>
+                </>
+            },
+            None => html!{<></>},
+        };
+
+        html! {
+            <>
+            <label for="timeline_file">{"Timeline: "}</label>
+            <input type="file" id="timeline_file" accept=".json" onchange={self.link.callback(|e| Msg::TimelineFileChosen(e))}/><br/>
+            <button type="button" class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::LoadSqueezeClicked)}>{"Squeeze Benchmark"}</button>
+            <button type="button" class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::LoadGravityRampClicked)}>{"Gravity Ramp"}</button>
+            <button type="button" class="button" style="background-color:#5756EB" title="Warm start off and on at 1 to 6 iterations, over two turns of gravity each, saved as CSV"
+                onclick={self.link.callback(|_| Msg::RampExperimentClicked)}>{if self.ramp_experiment.is_some() {"Stop Experiment"} else {"Gravity Ramp Experiment"}}</button><br/>
+            {transport}
+            </>
+        }
+    }
+
+    fn view_recording_controls(&self) -> Html
+    {
+        let settings = &self.recording_settings;
+        let radio = |selection : ParticleSelection, id : &'static str, label : &'static str| html! {
+            <>
+            <label for={id}>{label}</label>
+            <input type="radio" id={id} name="record_selection" checked=settings.selection == selection onclick={self.link.callback(move |_| Msg::RecordSelectionChanged(selection))}/>
+            </>
+        };
+
+        let selection_input = match settings.selection {
+            ParticleSelection::Probes => html! {
+                <input type="text" size="12" placeholder="e.g. 0, 45, 99" value={&settings.probes} oninput={self.link.callback(|e| Msg::RecordProbesChanged(e))}/>
+            },
+            ParticleSelection::Strided => html! {
+                <>
+                <label for="record_particle_stride">{"k: "}</label>
+                <input type="number" id="record_particle_stride" min="1" value={settings.particle_stride} oninput={self.link.callback(|e| Msg::RecordParticleStrideChanged(e))}/>
+                </>
+            },
+            _ => html!{<></>},
+        };
+
+        let status = match self.recorder.as_ref().map(|recorder| recorder.borrow()) {
+            Some(recorder) => html! {
+                <>
+                <span>{&format!("{} frames x {} particles, {:.1} KB ({:.1} KB/s), {} evicted",
+                    recorder.num_frames(), recorder.num_particles(), recorder.bytes() as f32 / 1024.0,
+                    recorder.bytes_per_second(1.0 / self.params.dt) / 1024.0, recorder.evicted)}</span>
+                {if recorder.is_compressed() {format!(", compressed {:.1}x ", recorder.compression_ratio())} else {" ".to_string()}}
+                <button type="button" class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::ExportRecordingClicked)}>{"Export .npy"}</button><br/>
+                </>
+            },
+            None => html!{<></>},
+        };
+
+        html! {
+            <>
+            <label for="recording">{"Record Positions"}</label>
+            <input type="checkbox" id="recording" checked =self.recording onclick={self.link.callback(|_| Msg::RecordingToggled)}/><br/>
+            {radio(ParticleSelection::All, "record_all", "All")}
+            {radio(ParticleSelection::Pinned, "record_pinned", "Pinned")}
+            {radio(ParticleSelection::Probes, "record_probes", "Probes")}
+            {radio(ParticleSelection::Strided, "record_strided", "Every k-th")}
+            {selection_input}<br/>
+            <label for="record_step_stride">{"Every n-th step: "}</label>
+            <input type="number" id="record_step_stride" min="1" value={settings.step_stride} oninput={self.link.callback(|e| Msg::RecordStepStrideChanged(e))}/><br/>
+            <label for="record_cap">{"Cap (MB): "}</label>
+            <input type="number" id="record_cap" min="1" value={settings.cap_megabytes} oninput={self.link.callback(|e| Msg::RecordCapChanged(e))}/><br/>
+            <label for="record_compressed">{"Compress"}</label>
+            <input type="checkbox" id="record_compressed" checked =settings.compressed onclick={self.link.callback(|_| Msg::RecordCompressedChanged)}/>
+            <label for="record_keyframe_interval">{" keyframe every "}</label>
+            <input type="number" id="record_keyframe_interval" min="1" value={settings.keyframe_interval} oninput={self.link.callback(|e| Msg::RecordKeyframeIntervalChanged(e))}/>
+            <label for="record_quantum">{" frames, quantum "}</label>
+            <input type="number" id="record_quantum" min="0" step="any" value={settings.quantum} oninput={self.link.callback(|e| Msg::RecordQuantumChanged(e))}/><br/>
+            {status}
+            </>
+        }
+    }
+
+    fn view_ghost_controls(&self) -> Html
+    {
+        let from_recording = if self.recorder.is_some() {
+            html! {
+                <button type="button" class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::GhostFromRecordingClicked)}>{"From Recording"}</button>
+            }
+        } else { html!{<></>} };
+
+        let status = match &self.ghost {
+            Some(ghost) => {
+                let at = match ghost.frame_at_step(self.time_step) {
+                    Some(k) => format!("frame {} of {}", k + 1, ghost.num_frames()),
+                    None => format!("no frame for step {}", self.time_step),
+                };
+                let mismatch = if ghost.num_particles() != self.num_particles {
+                    html! {<div style="color:#C62828;">{&format!("Hidden, it has {} particles but the cloth now has {}", ghost.num_particles(), self.num_particles)}</div>}
+                } else { html!{<></>} };
+                html! {
+                    <>
+                    <label for="ghost_visible">{"Show Ghost"}</label>
+                    <input type="checkbox" id="ghost_visible" checked =ghost.visible onclick={self.link.callback(|_| Msg::GhostVisibleChanged)}/>
+                    <label for="ghost_offset">{" offset "}</label>
+                    <input type="number" id="ghost_offset" value={ghost.offset} oninput={self.link.callback(|e| Msg::GhostOffsetChanged(e))}/>
+                    {" steps "}
+                    <button type="button" class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::ClearGhostClicked)}>{"Clear"}</button><br/>
+                    <span>{&format!("{}, {}", ghost.name, at)}</span><br/>
+                    {mismatch}
+                    </>
+                }
+            }
+            None => html!{<></>},
+        };
+
+        html! {
+            <>
+            <label for="ghost_file" title="A recording exported as .npy, drawn behind the cloth at the frame for the live step">{"Ghost: "}</label>
+            <input type="file" id="ghost_file" accept=".npy" onchange={self.link.callback(|e| Msg::GhostFileChosen(e))}/>
+            {from_recording}<br/>
+            {status}
+            {
+                match &self.ghost_error {
+                    Some(e) => html! {<div style="color:#C62828;">{&format!("Can't show that ghost, {}", e)}</div>},
+                    None => html!{<></>},
+                }
+            }
+            </>
+        }
+    }
+
+    fn view_collision_controls(&self) -> Html
+    {
+        let restitution = if self.collision_response == CollisionResponse::Reflection {
+            html! {
+                <>
+                <input type="range" id="restitution" min="0" max ="1" step ="0.01" value={self.restitution} oninput={self.link.callback(|e| Msg::RestitutionChanged(e))}/>
+                <label for="restitution">{&format!("Restitution: {}", self.restitution)}</label><br/>
+                </>
+            }
+        } else { html!{<></>} };
+
+        // XPBD contacts are solved in every iteration with the other constraints, so the order
+        // only applies to the push-out responses.
+        let order = if self.collision_response != CollisionResponse::Xpbd {
+            html! {
+                <>
+                <label for="collision_order">{"Collision Order: "}</label>
+                <select id="collision_order" onchange={self.link.callback(|e| Msg::CollisionOrderChanged(e))}>
+                    { for COLLISION_ORDERS.iter().map(|o| html! {
+                        <option value={o.name()} selected=*o == self.collision_order>{o.label()}</option>
+                    })}
+                </select><br/>
+                </>
+            }
+        } else { html!{<></>} };
+
+        html! {
+            <>
+            <br/>
+            <label for="floor">{"Floor"}</label>
+            <input type="checkbox" id="floor" checked =self.floor onclick={self.link.callback(|_| Msg::FloorChanged)}/>
+            <button class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::ReleasePinsClicked)}>{if self.selection.is_empty() {"Release Pins"} else {"Release Selected Pins"}}</button><br/>
+            <label for="collision_response">{"Collision Response: "}</label>
+            <select id="collision_response" onchange={self.link.callback(|e| Msg::CollisionResponseChanged(e))}>
+                <option value="projection" selected=self.collision_response == CollisionResponse::Projection>{"Projection"}</option>
+                <option value="reflection" selected=self.collision_response == CollisionResponse::Reflection>{"Reflection"}</option>
+                <option value="xpbd" selected=self.collision_response == CollisionResponse::Xpbd>{"XPBD Contact"}</option>
+            </select><br/>
+            {restitution}
+            {order}
+            </>
+        }
+    }
+
+    fn view_pluck_controls(&self) -> Html
+    {
+        let export = match &self.pluck {
+            Some(pluck) if pluck.is_complete() => html! {
+                <><button type="button" class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::ExportPluckClicked)}>{"Export Pluck CSV"}</button><br/></>
+            },
+            _ => html!{<></>},
+        };
+
+        html! {
+            <>
+            <label for="pluck_tool">{"Pluck Tool (drag a particle and let go)"}</label>
+            <input type="checkbox" id="pluck_tool" checked =self.pluck_tool onclick={self.link.callback(|_| Msg::PluckToolChanged)}/><br/>
+            <label for="pluck_steps">{"Capture steps: "}</label>
+            <input type="number" id="pluck_steps" min="1" value={self.pluck_steps} oninput={self.link.callback(|e| Msg::PluckStepsChanged(e))}/><br/>
+            {export}
+            </>
+        }
+    }
+
+    fn view_drag_controls(&self) -> Html
+    {
+        html! {
+            <>
+            <label for="drag_prediction">{"Predict Drag"}</label>
+            <input type="checkbox" id="drag_prediction" checked =self.drag_prediction onclick={self.link.callback(|_| Msg::DragPredictionChanged)}/>
+            <input type="range" id="drag_lead" min="0" max="32" step="1" value={self.drag_lead_ms} disabled=!self.drag_prediction oninput={self.link.callback(|e| Msg::DragLeadChanged(e))}/>
+            <label for="drag_lead">{&format!("Lead: {} ms", self.drag_lead_ms)}</label><br/>
+            </>
+        }
+    }
+
+    fn view_brush_controls(&self) -> Html
+    {
+        let radio = |mode : BrushMode, id : &'static str, label : &'static str| html! {
+            <>
+            <label for={id}>{label}</label>
+            <input type="radio" id={id} name="brush_mode" checked=self.brush.mode == mode onclick={self.link.callback(move |_| Msg::BrushModeChanged(mode))}/>
+            </>
+        };
+        html! {
+            <>
+            {"Force Brush (ctrl-drag): "}
+            {radio(BrushMode::Attract, "brush_attract", "Attract")}
+            {radio(BrushMode::Repel, "brush_repel", "Repel")}<br/>
+            <input type="range" id="brush_strength" min="0" max="20" step="0.1" value={self.brush.strength} oninput={self.link.callback(|e| Msg::BrushStrengthChanged(e))}/>
+            <label for="brush_strength">{&format!("Strength: {}g", self.brush.strength)}</label><br/>
+            <input type="range" id="brush_radius" min="0.02" max="0.5" step="0.01" value={self.brush.radius} oninput={self.link.callback(|e| Msg::BrushRadiusChanged(e))}/>
+            <label for="brush_radius">{&format!("Radius: {}", self.brush.radius)}</label><br/>
+            </>
+        }
+    }
+
+    fn view_freeze_controls(&self) -> Html
+    {
+        html! {
+            <>
+            <label for="freeze_tool">{"Freeze Tool (drag a box over the cloth)"}</label>
+            <input type="checkbox" id="freeze_tool" checked =self.freeze_tool onclick={self.link.callback(|_| Msg::FreezeToolChanged)}/>
+            {
+                if self.freeze.num_frozen() > 0 {
+                    html! {
+                        <button type="button" class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::UnfreezeClicked)}>{if self.selection.is_empty() {format!("Unfreeze {}", self.freeze.num_frozen())} else {"Unfreeze Selected".to_string()}}</button>
+                    }
+                } else { html!{<></>} }
+            }<br/>
+            </>
+        }
+    }
+
+    fn view_measure_controls(&self) -> Html
+    {
+        let measurements = self.measurements.iter().enumerate().map(|(index, m)| {
+            let name = |end : MeasurePoint| match end {
+                MeasurePoint::Particle(p) => format!("particle {}", p),
+                MeasurePoint::Fixed(p) => format!("({:.3}, {:.3})", p.x, p.y),
+            };
+            html! {
+                <>
+                {&format!("{} to {}: {:.4} ", name(m.start), name(m.end), m.length(&self.current_positions))}
+                <button type="button" class="button" style="background-color:#5756EB" onclick={self.link.callback(move |_| Msg::MeasurementDismissed(index))}>{"Dismiss"}</button><br/>
+                </>
+            }
+        });
+        html! {
+            <>
+            <label for="measure_tool">{"Measure Tool (click two points)"}</label>
+            <input type="checkbox" id="measure_tool" checked =self.measure_tool onclick={self.link.callback(|_| Msg::MeasureToolChanged)}/>
+            <label for="rulers">{"Rulers"}</label>
+            <input type="checkbox" id="rulers" checked =self.show_rulers onclick={self.link.callback(|_| Msg::RulersChanged)}/>
+            {
+                if self.measurements.is_empty() { html!{<></>} } else {
+                    html! {<button type="button" class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::ClearMeasurementsClicked)}>{"Clear Measurements"}</button>}
+                }
+            }<br/>
+            { for measurements }
+            </>
+        }
+    }
+
+    // Distance labels and ruler numbers, laid over the canvas at the points they belong to.
+    fn view_canvas_labels(&self) -> Html
+    {
+        let label = |at : Vec2, text : String| html! {
+            <span style={format!("position:absolute; left:{:.0}px; top:{:.0}px; font-size:11px; background-color:rgba(255,255,255,0.7);", at.x, at.y)}>{text}</span>
+        };
+        let mut labels : Vec<Html> = self.measurements.iter().map(|m| {
+            let a = self.on_screen(m.start.position(&self.current_positions));
+            let b = self.on_screen(m.end.position(&self.current_positions));
+            label(self.sim_to_client((a + b) * 0.5), format!("{:.4}", m.length(&self.current_positions)))
+        }).collect();
+        if self.show_rulers {
+            let (min, max) = self.visible_bounds();
+            let bottom = self.height as f32 - 28.0;
+            for (x, _) in measure::ruler_ticks(min.x, max.x).into_iter().filter(|&(_, labelled)| labelled) {
+                labels.push(label(vec2(self.sim_to_client(vec2(x, 0.0)).x, bottom), format!("{:.1}", x)));
+            }
+            for (y, _) in measure::ruler_ticks(min.y, max.y).into_iter().filter(|&(_, labelled)| labelled) {
+                labels.push(label(vec2(16.0, self.sim_to_client(vec2(0.0, y)).y), format!("{:.1}", y)));
+            }
+        }
+        if labels.is_empty() {
+            return html!{<></>};
+        }
+        html! {
+            <div style={format!("position:absolute; width:{}px; height:{}px; overflow:hidden; pointer-events:none;", self.width, self.height)}>
+                { for labels.into_iter() }
+            </div>
+        }
+    }
+
+    // Each chart's title and its series' latest values, over its top left corner.
+    fn view_chart_labels(&self) -> Html
+    {
+        if !self.charts_active() {
+            return html!{<></>};
+        }
+        let palette = &PALETTES[self.palette_index];
+        let to_css = |c : [f32; 3]| format!("rgb({:.0},{:.0},{:.0})", 255.0 * c[0], 255.0 * c[1], 255.0 * c[2]);
+        html! {
+            <div style={format!("position:absolute; width:{}px; height:{}px; overflow:hidden; pointer-events:none;", self.width, self.height)}>
+                { for self.charts.iter().enumerate().map(|(k, chart)| {
+                    let rect = self.chart_rect(k);
+                    html! {
+                        <span style={format!("position:absolute; left:{}px; top:{}px; font-size:10px; background-color:rgba(255,255,255,0.7);", rect.x + 4, rect.y + 2)}>
+                            {chart.title}
+                            { for chart.labels(palette, &self.color_scales).into_iter().map(|(text, color)| html! {
+                                <span style={format!("color:{}; margin-left:6px;", to_css(color))}>{text}</span>
+                            }) }
+                        </span>
+                    }
+                }) }
+            </div>
+        }
+    }
+
+    // The quick actions opened by a right-click, at the point clicked. Items act on mouse down, before
+    // the click reaches the container and closes the menu.
+    fn view_context_menu(&self) -> Html
+    {
+        let menu = match &self.context_menu {
+            Some(menu) => menu,
+            None => return html!{<></>},
+        };
+        let item = |(label, action) : (String, MenuAction)| html! {
+            <button type="button" class="button" style="background-color:#5756EB; display:block; margin:2px;"
+                onmousedown={self.link.callback(move |e : MouseEvent| { e.stop_propagation(); Msg::ContextMenuChosen(action) })}>{label}</button>
+        };
+        html! {
+            <div style={format!("position:absolute; left:{}px; top:{}px; z-index:10; background-color:#96DEEB; border-radius:5px; padding:2px;", menu.x, menu.y)}>
+                { for menu.items().into_iter().map(item) }
+            </div>
+        }
+    }
+
+    fn view_selection_controls(&self) -> Html
+    {
+        if self.selection.is_empty() {
+            return html! {<>{"Shift-drag to select particles"}<br/></>};
+        }
+        html! {
+            <>
+            {&format!("{} selected (drag one to move them all, Escape to clear) ", self.selection.particles.len())}
+            <button type="button" class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::PinSelectionClicked)}>{"Pin"}</button>
+            <button type="button" class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::ReleasePinsClicked)}>{"Unpin"}</button>
+            <button type="button" class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::FreezeSelectionClicked)}>{"Freeze"}</button>
+            <button type="button" class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::ClearSelectionClicked)}>{"Clear"}</button><br/>
+            </>
+        }
+    }
+
+    fn view_alarm_controls(&self) -> Html
+    {
+        let alarm = &self.strain_alarm;
+        let settings = if alarm.enabled {
+            html! {
+                <>
+                <label for="min_strain">{"Min strain: "}</label>
+                <input type="number" id="min_strain" max="0" step="0.01" value={alarm.min_strain} oninput={self.link.callback(|e| Msg::MinStrainChanged(e))}/><br/>
+                <label for="max_strain">{"Max strain: "}</label>
+                <input type="number" id="max_strain" min="0" step="0.01" value={alarm.max_strain} oninput={self.link.callback(|e| Msg::MaxStrainChanged(e))}/><br/>
+                <label for="pause_on_alarm">{"Pause on Alarm"}</label>
+                <input type="checkbox" id="pause_on_alarm" checked =alarm.pause_on_alarm onclick={self.link.callback(|_| Msg::PauseOnAlarmChanged)}/>
+                <button type="button" class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::ExportAlarmsClicked)}>{"Export Alarms CSV"}</button><br/>
+                </>
+            }
+        } else { html!{<></>} };
+
+        html! {
+            <>
+            <label for="strain_alarm">{"Strain Alarm"}</label>
+            <input type="checkbox" id="strain_alarm" checked =alarm.enabled onclick={self.link.callback(|_| Msg::StrainAlarmChanged)}/><br/>
+            {settings}
+            </>
+        }
+    }
+
+    fn view_wrinkle_controls(&self) -> Html
+    {
+        html! {
+            <>
+            <label for="wrinkle_log">{&format!("Log Wrinkle Energy ({} steps)", self.wrinkle_log.num_samples())}</label>
+            <input type="checkbox" id="wrinkle_log" checked =self.wrinkle_log.enabled onclick={self.link.callback(|_| Msg::WrinkleLogChanged)}/>
+            {
+                if self.wrinkle_log.num_samples() > 0 {
+                    html! {<button type="button" class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::ExportWrinkleClicked)}>{"Export Wrinkle CSV"}</button>}
+                } else { html!{<></>} }
+            }<br/>
+            </>
+        }
+    }
+
+    fn pluck_summary(&self) -> Option<String>
+    {
+        let pluck = self.pluck.as_ref()?;
+        if !pluck.is_complete() {
+            let (done, total) = pluck.progress();
+            return Some(format!("Pluck: capturing {}/{} steps", done, total));
+        }
+        Some(match pluck.analyze() {
+            Some(fit) => format!("Pluck: decay {:.3} /s, period {:.3} s ({} peaks)", fit.decay_rate, fit.period, fit.num_peaks),
+            None => "Pluck: too few peaks to fit".to_string(),
+        })
+    }
+
+    fn view_warm_up_progress(&self) -> Html
+    {
+        if self.warm_up_remaining == 0 {
+            return html!{<></>};
+        }
+        html! {
+            <div id="warm_up_progress" style="background-color:#96DEEB; border-radius:5px; margin-top:10px; margin-left:10px; padding: 2px; padding-left:10px;">
+                {&format!("Settling the cloth: {} of {} steps", WARM_UP_STEPS - self.warm_up_remaining, WARM_UP_STEPS)}
+            </div>
+        }
+    }
+
+    fn view_idle_indicator(&self) -> Html
+    {
+        if !self.idle.is_sleeping() {
+            return html!{<></>};
+        }
+        html! {
+            <div id="idle_indicator" style="font-size:12px; margin-top:10px; margin-left:10px; padding-left:10px; opacity:0.6;">
+                {"Sleeping — move the mouse to resume"}
+            </div>
+        }
+    }
+
+    fn view_idle_controls(&self) -> Html
+    {
+        html! {
+            <>
+            <label for="idle_sleep">{"Sleep When Idle"}</label>
+            <input type="checkbox" id="idle_sleep" checked =self.idle.enabled onclick={self.link.callback(|_| Msg::IdleSleepChanged)}/>
+            <label for="idle_low_frame_rate">{" 1 fps asleep"}</label>
+            <input type="checkbox" id="idle_low_frame_rate" checked =self.idle.low_frame_rate onclick={self.link.callback(|_| Msg::IdleLowFrameRateChanged)}/><br/>
+            <label for="idle_threshold">{"At rest below: "}</label>
+            <input type="number" id="idle_threshold" min="0" step="any" value={self.idle.threshold} oninput={self.link.callback(|e| Msg::IdleThresholdChanged(e))}/>
+            <label for="idle_delay">{" for "}</label>
+            <input type="number" id="idle_delay" min="0" step="1" value={self.idle.delay} oninput={self.link.callback(|e| Msg::IdleDelayChanged(e))}/>{" s"}<br/>
+            </>
+        }
+    }
+
+    fn view_newborn_controls(&self) -> Html
+    {
+        if self.contact_distance <= 0.0 {
+            return html!{<></>};
+        }
+        html! {
+            <>
+            <label for="self_collision">{"Sheet Contacts"}</label>
+            <input type="checkbox" id="self_collision" checked =self.params.self_collision onclick={self.link.callback(|_| Msg::SelfCollisionChanged)}/><br/>
+            <label for="newborn_boost">{"Boost New Contacts"}</label>
+            <input type="checkbox" id="newborn_boost" checked =self.newborn_boost.enabled onclick={self.link.callback(|_| Msg::NewbornBoostChanged)}/>
+            <label for="newborn_seed">{" Seed λ"}</label>
+            <input type="checkbox" id="newborn_seed" checked =self.newborn_boost.seed_lambda disabled=!self.newborn_boost.enabled onclick={self.link.callback(|_| Msg::NewbornSeedChanged)}/><br/>
+            <label for="newborn_passes">{"Extra passes: "}</label>
+            <input type="number" id="newborn_passes" min="1" value={self.newborn_boost.passes} oninput={self.link.callback(|e| Msg::NewbornPassesChanged(e))}/>
+            <label for="newborn_frames">{" for "}</label>
+            <input type="number" id="newborn_frames" min="1" value={self.newborn_boost.frames} oninput={self.link.callback(|e| Msg::NewbornFramesChanged(e))}/>{" steps"}<br/>
+            </>
+        }
+    }
+
+    fn view_reversal_progress(&self) -> Html
+    {
+        let run = match &self.reversal_run {
+            Some(run) => run,
+            None => return html!{<></>},
+        };
+        html! {
+            <div id="reversal_progress" style="background-color:#96DEEB; border-radius:5px; margin-top:10px; margin-left:10px; padding: 2px; padding-left:10px;">
+                {&format!("Time reversal check: step {} of {}, running {}", run.steps_done, 2 * run.steps, if run.is_reversed() {"backwards"} else {"forwards"})}<br/>
+                {&run.settings}
+            </div>
+        }
+    }
+
+    fn view_ramp_progress(&self) -> Html
+    {
+        let experiment = match &self.ramp_experiment {
+            Some(experiment) => experiment,
+            None => return html!{<></>},
+        };
+        html! {
+            <div id="ramp_progress" style="background-color:#96DEEB; border-radius:5px; margin-top:10px; margin-left:10px; padding: 2px; padding-left:10px;">
+                {experiment.progress()}
+            </div>
+        }
+    }
+
+    fn view_reversal_controls(&self) -> Html
+    {
+        let export = if self.reversal_results.is_empty() {
+            html!{<></>}
+        } else {
+            html! {<button type="button" class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::ExportReversalClicked)}>{"Export Reversal CSV"}</button>}
+        };
+
+        html! {
+            <>
+            <button type="button" class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::ReverseTimeClicked)}>{"Reverse Time"}</button>
+            <button type="button" class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::ReversalCheckClicked)}>{"Reversal Check"}</button>
+            <label for="reversal_steps">{" over "}</label>
+            <input type="number" id="reversal_steps" min="1" value={self.reversal_steps} oninput={self.link.callback(|e| Msg::ReversalStepsChanged(e))}/>{" steps"}<br/>
+            {export}
+            </>
+        }
+    }
+
+    // The worst-converged constraints, one row each. Clicking a row picks it as the probe.
+    fn view_inspector(&self) -> Html
+    {
+        if !self.inspector {
+            return html!{<></>};
+        }
+
+        let row = |index : usize| {
+            let c = &self.constraints[index];
+            let kind = self.sheet_grids.get(self.sheet_of[c.p0]).and_then(|grid| grid.edge_kind(c.p0, c.p1)).map_or("mesh", |k| k.name());
+            let strain = (self.current_positions[c.p0] - self.current_positions[c.p1]).length() / c.length - 1.0;
+            let style = if self.probe_constraint == Some(index) {"cursor:pointer; font-weight:bold;"} else {"cursor:pointer;"};
+            html! {
+                <tr style={style} onclick={self.link.callback(move |_| Msg::ProbeConstraintSelected(index))}>
+                    <td>{index}</td>
+                    <td>{&format!("{}-{}", c.p0, c.p1)}</td>
+                    <td>{kind}</td>
+                    <td>{&format!("{:+.2}%", strain * 100.0)}</td>
+                    <td>{&format!("{:.2e}", c.lambda.length())}</td>
+                </tr>
+            }
+        };
+
+        html! {
+            <div id="inspector" style="padding-left:10px; font-size:12px;">
+                <table>
+                    <tr><th>{"#"}</th><th>{"Particles"}</th><th>{"Kind"}</th><th>{"Strain"}</th><th>{"|λ|"}</th></tr>
+                    { for self.worst_constraints.borrow().constraints.iter().filter(|&&i| i < self.constraints.len()).map(|&i| row(i)) }
+                </table>
+            </div>
+        }
+    }
+
+    fn view_tier_note(&self) -> Html
+    {
+        match &self.tier_note {
+            Some(note) => html! {
+                <div id="tier_note" style="background-color:#96DEEB; border-radius:5px; margin-top:10px; margin-left:10px; padding: 2px; padding-left:10px;">
+                    {note}
+                    <button class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::TierNoteDismissed)}>{"Dismiss"}</button>
+                </div>
+            },
+            None => html!{<></>},
+        }
+    }
+
+    fn view_resume_note(&self) -> Html
+    {
+        match &self.resumed {
+            Some((age, _)) => html! {
+                <div id="resume_note" style="background-color:#96DEEB; border-radius:5px; margin-top:10px; margin-left:10px; padding: 2px; padding-left:10px;">
+                    {&format!("Resumed the session from {:.0} s ago.", age)}
+                    <button class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::StartFreshClicked)}>{"Start Fresh"}</button>
+                    <button class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::ResumeNoteDismissed)}>{"Keep"}</button>
+                </div>
+            },
+            None => html!{<></>},
+        }
+    }
+
+    fn view_run_buttons(&self) -> Html
+    {
+        html! {
+            <>
+            <button class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::ResetClicked)}>{"Reset"}</button>
+            <button class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::PauseToggled)}>{if self.paused {"Resume"} else {"Pause"}}</button>
+            <button class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::CleanLambdaClicked)}>{"Forget Stored Impulse"}</button>
+            <select id="clean_filter" onchange={self.link.callback(|e| Msg::CleanFilterChanged(e))}>
+                { for LAMBDA_FILTERS.iter().map(|f| html! {
+                    <option value={f.name()} selected=*f == self.clean_filter disabled={*f == LambdaFilter::Selection && self.selection.is_empty()}>{f.label()}</option>
+                })}
+            </select>
+            {match self.last_clean_filter {
+                Some(f) => html! {<span>{format!(" Last forgot: {}", f.label())}</span>},
+                None => html!{<></>},
+            }}
+            <button class="button" style="background-color:#5756EB" disabled=self.tutorial.is_some() onclick={self.link.callback(|_| Msg::TutorialStarted)}>{"Take the Tour"}</button>
+            {self.view_sweep_controls()}
+            {self.view_fuzz_status()}
+            </>
+        }
+    }
+
+    // The golden run's progress, with its outcome in data-result for a test driver to read.
+    fn view_golden_status(&self) -> Html
+    {
+        let run = match &self.golden {
+            Some(run) => run,
+            None => return html!{<></>},
+        };
+        let result = match (run.is_done(), run.failed, run.missing) {
+            (false, _, _) => "running",
+            (true, 0, 0) => "pass",
+            (true, 0, _) => "incomplete",
+            (true, _, _) => "fail",
+        };
+        html! {
+            <div id="golden" data-result={result} style="background-color:#96DEEB; border-radius:5px; margin-top:10px; margin-left:10px; padding: 2px; padding-left:10px;">
+                {run.summary()}
+            </div>
+        }
+    }
+
+    // The determinism audit's progress, with its outcome in data-result for a test driver to read.
+    fn view_audit_status(&self) -> Html
+    {
+        let audit = match &self.audit {
+            Some(audit) => audit,
+            None => return html!{<></>},
+        };
+        let result = match audit.passed {
+            None => "running",
+            Some(true) => "pass",
+            Some(false) => "fail",
+        };
+        html! {
+            <div id="determinism" data-result={result} style="background-color:#96DEEB; border-radius:5px; margin-top:10px; margin-left:10px; padding: 2px; padding-left:10px;">
+                {audit.summary()}
+            </div>
+        }
+    }
+
+    fn view_fuzz_status(&self) -> Html
+    {
+        let fuzzer = match &self.fuzzer {
+            Some(fuzzer) => fuzzer,
+            None => return html!{<></>},
+        };
+        let status = match &fuzzer.failure {
+            Some(reason) => format!("Fuzzing seed {} failed at step {}: {}", fuzzer.seed, fuzzer.steps, reason),
+            None => format!("Fuzzing seed {}: step {}, {} actions, no failures", fuzzer.seed, fuzzer.steps, fuzzer.actions.len()),
+        };
+        html! {<div id="fuzz" style="margin-left:10px;">{status}</div>}
+    }
+
+    // Single steps while paused, and the slow-motion replay of one.
+    fn view_sweep_controls(&self) -> Html
+    {
+        if !self.paused {
+            return html!{<></>};
+        }
+        let replay = match &self.sweep_replay {
+            Some(replay) => replay,
+            None => return html! {
+                <div id="sweep" style="background-color:#96DEEB; border-radius:5px; margin-top:10px; margin-left:10px; padding: 2px; padding-left:10px;">
+                    <button class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::StepClicked)}>{"Step"}</button>
+                    <button class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::SweepRecordClicked)}>{"Step in Slow Motion"}</button>
+                    <button class="button" style="background-color:#5756EB" title="Or press . on the canvas" onclick={self.link.callback(|_| Msg::PhaseStepClicked)}>{"Step Phase"}</button><br/>
+                    {self.view_phase_stepping()}
+                </div>
+            },
+        };
+        let current = match replay.current() {
+            Some((constraint, iteration)) => format!("Constraint {} in iteration {}", constraint, iteration),
+            None => "Before the solve".to_string(),
+        };
+        html! {
+            <div id="sweep" style="background-color:#96DEEB; border-radius:5px; margin-top:10px; margin-left:10px; padding: 2px; padding-left:10px;">
+                <button class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::SweepPlayToggled)}>{if replay.playing {"Pause Replay"} else {"Play Replay"}}</button>
+                <button class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::SweepExited)}>{"Back to Live"}</button><br/>
+                <label for="sweep_cursor">{format!("Move {} of {} ", replay.cursor, replay.len())}</label>
+                <input type="range" id="sweep_cursor" min="0" max={replay.len()} value={replay.cursor} oninput={self.link.callback(|e| Msg::SweepScrubbed(e))}/><br/>
+                <label for="sweep_speed">{format!("Speed: {} constraints/s ", replay.speed)}</label>
+                <input type="range" id="sweep_speed" min="10" max="2000" step="10" value={replay.speed} oninput={self.link.callback(|e| Msg::SweepSpeedChanged(e))}/><br/>
+                {current}
+                {
+                    if replay.len() == 0 {
+                        html! {<><br/>{"Nothing to replay: only solvers that apply corrections as they go, like Gauss-Seidel, record moves."}</>}
+                    } else if replay.is_truncated() {
+                        html! {<><br/>{"The log filled up before the step finished."}</>}
+                    } else { html!{<></>} }
+                }
+            </div>
+        }
+    }
+
+    // The phase stepper's switch, and once it has run a phase, what ran and how the cloth stands.
+    fn view_phase_stepping(&self) -> Html
+    {
+        html! {
+            <>
+            <label for="phase_stepping" title="Turning it off finishes a step held between phases">{"Phase Stepping"}</label>
+            <input type="checkbox" id="phase_stepping" checked =self.phase_stepping onclick={self.link.callback(|_| Msg::PhaseSteppingChanged)}/><br/>
+            {
+                match &self.phase_note {
+                    Some(lines) if self.phase_stepping => html! {
+                        <div id="phase_note">
+                            { for lines.iter().map(|line| html! {<>{line}<br/></>}) }
+                        </div>
+                    },
+                    _ => html!{<></>},
+                }
+            }
+            </>
+        }
+    }
+
+    fn view_tutorial(&self) -> Html
+    {
+        let text = match self.tutorial.as_ref().and_then(|t| t.current()) {
+            Some(TutorialStep::Say { text, .. }) => *text,
+            _ => return html!{<></>},
+        };
+        html! {
+            <div id="tutorial" style="background-color:#96DEEB; border-radius:5px; margin-top:10px; margin-left:10px; padding: 2px; padding-left:10px;">
+                {text}<br/>
+                <button class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::TutorialNext)}>{"Next"}</button>
+                <button class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::TutorialClosed)}>{"End Tour"}</button>
+            </div>
+        }
+    }
+
+    // An outline for the control the tour is talking about.
+    fn tutorial_highlight(&self, id : &str) -> &'static str
+    {
+        match self.tutorial.as_ref().and_then(|t| t.current()) {
+            Some(TutorialStep::Say { highlight : Some(highlight), .. }) if *highlight == id => "outline: 3px solid #EB9696;",
+            _ => "",
+        }
+    }
+
+    // Warnings for the rules the settings break, until dismissed, and the explanation for any
+    // setting a blocking rule moved.
+    fn view_rule_banners(&self) -> Html
+    {
+        let settings = self.settings();
+        let warnings = validation::violations(&settings)
+            .filter(|rule| rule.severity == Severity::Warn && !self.dismissed_rules.contains(&rule.name));
+        let blocked = RULES.iter().filter(|rule| self.blocked_notes.contains(&rule.name));
+        html! {
+            { for warnings.chain(blocked).map(|rule| {
+                let name = rule.name;
+                html! {
+                    <div style="background-color:#EB9696; border-radius:5px; margin-top:10px; margin-left:10px; padding: 2px; padding-left:10px;">
+                        {rule.message}
+                        <button class="button" style="background-color:#5756EB" onclick={self.link.callback(move |_| Msg::RuleBannerDismissed(name))}>{"Dismiss"}</button>
+                    </div>
+                }
+            })}
+        }
+    }
+
+    fn view_watchdog_banner(&self) -> Html
+    {
+        if self.watchdog.reductions.is_empty() {
+            return html!{<></>};
+        }
+        let reductions : Vec<String> = self.watchdog.reductions.iter().map(|r| r.describe()).collect();
+        html! {
+            <div id="watchdog_banner" style="background-color:#EB9696; border-radius:5px; margin-top:10px; margin-left:10px; padding: 2px; padding-left:10px;">
+                {&format!("A step took {:.0} ms, over the {:.0} ms ceiling, so the watchdog turned down: {}. ", self.watchdog.worst_step_ms, self.watchdog.ceiling_ms, reductions.join(", "))}
+                <button class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::WatchdogRestoreClicked)}>{"Restore"}</button>
+            </div>
+        }
+    }
+
+    fn view_small_multiples_controls(&self) -> Html
+    {
+        let invalid = self.small_multiples_axes().is_none();
+        html! {
+            <>
+            <input type="checkbox" id="small_multiples" checked=self.small_multiples.is_some() onclick={self.link.callback(|_| Msg::SmallMultiplesChanged)}/>
+            <label for="small_multiples" title="Mini cloths over a grid of iterations and etas, pushed by the same gust. Click a cell to use its settings.">{" Small Multiples"}</label><br/>
+            <label for="small_multiples_rows">{"Iterations: "}</label>
+            <input type="text" id="small_multiples_rows" size="8" value={&self.small_multiples_rows} oninput={self.link.callback(|e| Msg::SmallMultiplesRowsChanged(e))}/>
+            <label for="small_multiples_columns">{" Eta: "}</label>
+            <input type="text" id="small_multiples_columns" size="10" value={&self.small_multiples_columns} oninput={self.link.callback(|e| Msg::SmallMultiplesColumnsChanged(e))}/><br/>
+            { if invalid {
+                html! { <span style="color:#C62828;">{&format!("Each axis takes {} comma separated values", small_multiples::GRID_CELLS)}<br/></span> }
+            } else {
+                html!{<></>}
+            } }
+            </>
+        }
+    }
+
+    // Each cell's settings and residual in its top left corner.
+    fn view_small_multiples_labels(&self) -> Html
+    {
+        let grid = match &self.small_multiples {
+            Some(grid) => grid,
+            None => return html!{<></>},
+        };
+        html! {
+            <div style={format!("position:absolute; width:{}px; height:{}px; overflow:hidden; pointer-events:none;", self.width, self.height)}>
+                { for grid.cells.iter().enumerate().map(|(k, cell)| {
+                    let rect = SmallMultiples::cell_rect(k, self.width, self.height);
+                    html! {
+                        <span style={format!("position:absolute; left:{}px; top:{}px; font-size:10px; background-color:rgba(255,255,255,0.7);", rect.x + 4, rect.y + 2)}>
+                            {&format!("i{} η{} residual {:.2e}", cell.iterations, cell.eta, cell.mean_residual())}
+                        </span>
+                    }
+                }) }
+            </div>
+        }
+    }
+
+    // Debug builds only, to try the crash overlay and restore end to end.
+    fn view_panic_button(&self) -> Html
+    {
+        if !cfg!(debug_assertions) {
+            return html!{<></>};
+        }
+        html! {
+            <>
+            <button class="button" style="background-color:#5756EB" title="Panics on purpose, to check the crash report and restore" onclick={self.link.callback(|_| Msg::PanicNowClicked)}>{"Panic Now"}</button><br/>
+            </>
+        }
+    }
+
+    fn view_watchdog_controls(&self) -> Html
+    {
+        html! {
+            <>
+            <input type="number" id="max_substeps" min="1" max={pacing::MAX_STEPS_PER_FRAME} value={self.params.max_substeps} oninput={self.link.callback(|e| Msg::MaxSubstepsChanged(e))}/>
+            <label for="max_substeps" title="The most steps a slow display may take in a frame to keep physics up to speed">{" max steps per frame"}</label><br/>
+            <input type="number" id="watchdog_ceiling" min="0" step="10" value={self.watchdog.ceiling_ms} oninput={self.link.callback(|e| Msg::WatchdogCeilingChanged(e))}/>
+            <label for="watchdog_ceiling" title="A step over this many ms turns down sheet contacts, then steps per frame, then iterations. 0 turns the watchdog off.">{" ms watchdog ceiling"}</label><br/>
+            </>
+        }
+    }
+
+    fn view_valence_warning(&self) -> Html
+    {
+        match self.valence_warning() {
+            Some(warning) => html! {
+                <div id="valence_warning" style="background-color:#EB9696; border-radius:5px; margin-top:10px; margin-left:10px; padding: 2px; padding-left:10px;">{warning}</div>
+            },
+            None => html!{<></>},
+        }
+    }
+
+    fn view_stats(&self) -> Html
+    {
+        html! {
+            <div id="debug_overlay" style="font-size:12px; padding-left:10px;">
+                {&format!("Frames: {} ({} skipped)", self.frame_index, self.skipped_frames)}<br/>
+                {self.frame_pacing.summary(self.params.dt)}<br/>
+                {&format!("Energy: kinetic {:.3} + potential {:.3} = {:.3}", self.kinetic_energy, self.potential_energy, self.kinetic_energy + self.potential_energy)}<br/>
+                {&format!("Kinetic energy per particle: boundary {:.4}, interior {:.4}", self.boundary_kinetic_energy, self.interior_kinetic_energy)}<br/>
+                {
+                    match self.wrinkle {
+                        Some(w) => format!("Wrinkle energy: {:.3e} (stretch {:.3} to {:.3})", w.energy, w.min_stretch, w.max_stretch),
+                        None => "Wrinkle energy: no quads".to_string(),
+                    }
+                }<br/>
+                {&format!("Valence: min {} / mean {:.2} / max {}", self.topology.min_valence(), self.topology.mean_valence(), self.topology.max_valence())}<br/>
+                {
+                    if self.params.lambda_clamp {
+                        format!("Lambda clamps: {} last frame, {} since reset", self.frame_lambda_clamps, self.total_lambda_clamps)
+                    } else {
+                        "Lambda clamp: off".to_string()
+                    }
+                }<br/>
+                {
+                    if !self.colliders().is_empty() {
+                        html! {<>{&format!("Max penetration: {:.2e} last frame ({})", self.max_penetration,
+                            if self.collision_response == CollisionResponse::Xpbd {"XPBD contacts"} else {self.collision_order.label()})}<br/></>}
+                    } else { html!{<></>} }
+                }
+                {&format!("GL buffers: {} ({} bytes)", self.gpu_buffers.live_count(), self.gpu_buffers.total_bytes())}<br/>
+                {
+                    if self.index_mode == IndexMode::Batched {
+                        format!("Indices: {} in {} draws", self.index_mode.name(), self.edge_batches.len())
+                    } else {
+                        format!("Indices: {}", self.index_mode.name())
+                    }
+                }<br/>
+                {
+                    if self.low_detail() {
+                        format!("Detail: low ({} of {} constraints)", self.lod_constraints.len(), self.num_constraints)
+                    } else {
+                        format!("Detail: full ({} constraints)", self.num_constraints)
+                    }
+                }<br/>
+                {&format!("Renderer: main thread{}", if self.offscreen_canvas_supported {" (OffscreenCanvas available)"} else {""})}<br/>
+                {&format!("Position transport: none needed on the main thread{}", if self.shared_memory_supported {" (SharedArrayBuffer available)"} else {""})}<br/>
+                {
+                    match self.picking_check {
+                        Some((agree, total)) => format!("Picking: GPU from {} particles, last check {}/{} agree with the CPU", GPU_PICK_MIN_PARTICLES, agree, total),
+                        None => format!("Picking: GPU from {} particles", GPU_PICK_MIN_PARTICLES),
+                    }
+                }
+                {
+                    match self.drag_lag {
+                        (sum, frames) if frames > 0 && self.dragged_position().is_some() => {
+                            html! {<><br/>{&format!("Drag lag: {:.1} px mean over {} frames{}", sum / frames as f64, frames, if self.drag_prediction {" (predicted)"} else {""})}</>}
+                        }
+                        _ => html!{<></>},
+                    }
+                }
+                { for self.pluck_summary().into_iter().map(|summary| html! {<><br/>{summary}</>}) }
+                { for self.tip_droop().into_iter().map(|droop| html! {<><br/>{&format!("Tip droop: {:.4}", droop)}</>}) }
+                { for self.reversal_results.last().into_iter().map(|r| html! {
+                    <><br/>{&format!("Time reversal over {} steps: rms error {:.2e}, max {:.2e}", r.steps, r.rms_error, r.max_error)}</>
+                })}
+                {
+                    if self.strain_alarm.enabled {
+                        html! {<><br/>{&format!("Strain alarms: {}", self.strain_alarm.count)}</>}
+                    } else { html!{<></>} }
+                }
+                { for profiling::summary().into_iter().map(|(category, ms)| html! {
+                    <><br/>{&format!("{}: {:.3} ms", category, ms)}</>
+                })}
+            </div>
+        }
+    }
+
+    fn view_log_panel(&self) -> Html
+    {
+        let messages = logging::recent_messages();
+
+        let body = if self.show_log {
+            html! {
+                <div style="max-height:30vh; overflow-y:auto; font-family:monospace; font-size:11px;">
+                    { for messages.iter().rev().map(|m| html! {<div>{m}</div>}) }
+                </div>
+            }
+        } else { html!{<></>} };
+
+        html! {
+            <div id="log_panel">
+                <button class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::LogPanelToggled)}>
+                    {&format!("{} Log ({})", if self.show_log {"Hide"} else {"Show"}, messages.len())}
+                </button>
+                {body}
+            </div>
+        }
+    }
+
+    // The roll-up summaries, latest first, as the console prints them.
+    fn view_history_panel(&self) -> Html
+    {
+        let rollups = self.rollups.borrow();
+        let table = if self.rollups_enabled {
+            html! {
+                <>
+                <div style="max-height:30vh; overflow:auto; font-family:monospace; font-size:11px; white-space:pre;">
+                    <div style="font-weight:bold;">{Rollup::TABLE_HEADER}</div>
+                    { for rollups.history.iter().rev().map(|r| html! {<div>{r.table_row()}</div>}) }
+                </div>
+                <button type="button" class="button" style="background-color:#5756EB" disabled=rollups.history.is_empty() onclick={self.link.callback(|_| Msg::ExportRollupsClicked)}>{"Export Summaries JSON"}</button><br/>
+                </>
+            }
+        } else { html!{<></>} };
+
+        html! {
+            <div id="history_panel" style="padding-left:10px;">
+                <label for="rollups">{"Roll-up Summaries"}</label>
+                <input type="checkbox" id="rollups" checked =self.rollups_enabled onclick={self.link.callback(|_| Msg::RollupsChanged)}/><br/>
+                <input type="number" id="rollup_window" min="1" step="1" value={rollups.window_seconds} oninput={self.link.callback(|e| Msg::RollupWindowChanged(e))}/>
+                <label for="rollup_window">{" simulated s per summary"}</label><br/>
+                {table}
+            </div>
+        }
+    }
+
+    // The active view's scale as a bar with its stops marked under it. Stops are dragged along the
+    // bar, and the picked one can be recolored or removed.
+    fn view_color_scale_editor(&self) -> Html
+    {
+        let palette = &PALETTES[self.palette_index];
+        let custom = self.color_scales.get(self.scale_use);
+        let stops = custom.cloned().unwrap_or_else(|| color_scale::palette_stops(palette));
+        let gradient : Vec<String> = (0..=16).map(|k| {
+            let t = k as f32 / 16.0;
+            format!("{} {:.0}%", color_scale::to_hex(color_scale::evaluate(&stops, t)), 100.0 * t)
+        }).collect();
+        let width = color_scale::BAR_WIDTH;
+
+        let editing = match (custom, self.selected_stop.and_then(|k| stops.get(k))) {
+            (Some(_), selected) => html! {
+                <>
+                <button type="button" onclick={self.link.callback(|_| Msg::AddStopClicked)}>{"Add Stop"}</button>
+                <button type="button" disabled={selected.is_none() || stops.len() <= color_scale::MIN_STOPS} onclick={self.link.callback(|_| Msg::RemoveStopClicked)}>{"Remove Stop"}</button>
+                {
+                    match selected {
+                        Some(stop) => html! {
+                            <>
+                            <input type="color" id="stop_color" value={color_scale::to_hex(stop.color)} oninput={self.link.callback(|e| Msg::StopColorChanged(e))}/>
+                            <label for="stop_color">{&format!(" at {:.2}", stop.t)}</label>
+                            </>
+                        },
+                        None => html!{<></>},
+                    }
+                }
+                <br/>
+                </>
+            },
+            (None, _) => html!{<></>},
+        };
+
+        html! {
+            <>
+            <label for="scale_use">{"Color Scale: "}</label>
+            <select id="scale_use" onchange={self.link.callback(|e| Msg::ScaleUseChanged(e))}>
+                { for SCALE_USES.iter().map(|u| html! {
+                    <option value={u.name()} selected=*u == self.scale_use>{u.name()}</option>
+                })}
+            </select>
+            <label for="custom_scale">{" Custom"}</label>
+            <input type="checkbox" id="custom_scale" checked =custom.is_some() onclick={self.link.callback(|_| Msg::CustomScaleChanged)}/><br/>
+            <div style={format!("position:relative; width:{}px; height:26px; cursor:{};", width, if custom.is_some() {"pointer"} else {"default"})}
+                onmousedown={self.link.callback(|e| Msg::ScaleBarPressed(e))}
+                onmousemove={self.link.callback(|e| Msg::ScaleBarMoved(e))}
+                onmouseup={self.link.callback(|_| Msg::ScaleBarReleased)}
+                onmouseleave={self.link.callback(|_| Msg::ScaleBarReleased)}>
+                <div style={format!("position:absolute; left:0px; top:0px; width:{}px; height:14px; pointer-events:none; background:linear-gradient(to right, {});", width, gradient.join(", "))}/>
+                { for stops.iter().enumerate().map(|(k, stop)| {
+                    let border = if Some(k) == self.selected_stop && custom.is_some() {"2px solid black"} else {"1px solid gray"};
+                    html! {
+                        <div style={format!("position:absolute; left:{:.0}px; top:16px; width:8px; height:8px; margin-left:-5px; pointer-events:none; border:{}; background-color:{};", stop.t * width as f32, border, color_scale::to_hex(stop.color))}/>
+                    }
+                })}
+            </div>
+            {editing}
+            </>
+        }
+    }
+
+    // Ends the frame's steps after one that ran past the ceiling, and turns down the next feature in
+    // the watchdog's order for the frames after.
+    fn watchdog_tripped(&mut self, step_ms : f64)
+    {
+        warn!("Step {} took {:.0} ms, over the watchdog's {:.0} ms ceiling, with {}", self.time_step, step_ms, self.watchdog.ceiling_ms, self.export_settings());
+        match watchdog::next_reduction(&self.params, self.contact_distance > 0.0) {
+            Some(reduction) => {
+                self.watchdog_reducing = true;
+                self.apply_params(reduction.delta());
+                self.watchdog_reducing = false;
+                self.watchdog.record(reduction, step_ms);
+                warn!("The watchdog turned down {}", reduction.describe());
+            }
+            None => warn!("The watchdog has nothing left to turn down"),
+        }
+    }
+
+    fn restore_watchdog(&mut self)
+    {
+        let delta = self.watchdog.restore();
+        self.watchdog_reducing = true;
+        self.apply_params(delta);
+        self.watchdog_reducing = false;
+    }
+
+    // Sets the tab's title to the configuration's summary, if it is on and the summary has changed.
+    fn update_title(&mut self)
+    {
+        if !self.title_summary {
+            return;
+        }
+        let activity = if self.recording {
+            Some(Activity::Recording)
+        } else if self.benchmark.is_some() || self.reversal_run.is_some() || self.ramp_experiment.is_some() || self.sweep_replay.is_some() {
+            Some(Activity::Experiment)
+        } else {
+            None
+        };
+        let summary = title::summary(&self.params, self.solvers[self.params.solver_index].name(), activity);
+        if summary != self.title {
+            title::set(&summary);
+            self.title = summary;
+        }
+    }
+
+    fn view_charts_panel(&self) -> Html
+    {
+        html! {
+            <div id="charts_panel" style="padding-left:10px;">
+                <label for="show_charts">{"Show Charts"}</label>
+                <input type="checkbox" id="show_charts" checked =self.show_charts onclick={self.link.callback(|_| Msg::ChartsChanged)}/><br/>
+                { for self.charts.iter().enumerate().map(|(k, chart)| {
+                    let id = format!("chart_log_{}", chart.id);
+                    html! {
+                        <>
+                        <label for={id.clone()}>{format!("{} Log Scale", chart.title)}</label>
+                        <input type="checkbox" id={id} checked =chart.log_scale onclick={self.link.callback(move |_| Msg::ChartLogScaleChanged(k))}/><br/>
+                        </>
+                    }
+                }) }
+            </div>
+        }
+    }
+
+    fn reload_notebook(&mut self)
+    {
+        if let Some(db) = &self.notebook {
+            self.notebook_pending += 1;
+            notebook::load_all(db, self.link.callback(Msg::NotebookLoaded));
+        }
+    }
+
+    // Deletes the oldest entries past the cap. The notebook is reloaded once they are all gone.
+    fn evict_notebook_entries(&mut self)
+    {
+        let excess = self.notebook_entries.len().saturating_sub(notebook::MAX_ENTRIES);
+        if let (true, Some(db)) = (excess > 0, &self.notebook) {
+            info!("Evicting the {} oldest notebook entries", excess);
+            for id in self.notebook_entries[..excess].iter().filter_map(|e| e.id) {
+                self.notebook_pending += 1;
+                notebook::delete(db, id, self.link.callback(Msg::NotebookWritten));
+            }
+        }
+    }
+
+    fn view_notebook(&self) -> Html
+    {
+        let status = match (&self.notebook, &self.notebook_error) {
+            (_, Some(e)) => format!("Notebook error: {}", e),
+            (None, None) => "Opening the notebook...".to_string(),
+            (Some(_), None) if self.notebook_pending > 0 => "Working...".to_string(),
+            (Some(_), None) => format!("{} of {} entries stored", self.notebook_entries.len(), notebook::MAX_ENTRIES),
+        };
+
+        let row = |e : &NotebookEntry| {
+            let id = e.id.unwrap_or(0);
+            let when = js_sys::Date::new(&JsValue::from_f64(e.timestamp)).to_iso_string().as_string().unwrap_or_default();
+            let wrinkle = e.metrics.wrinkle_energy.map_or(String::new(), |w| format!(", wrinkle {:.3e}", w));
+            let residual = e.metrics.first_iteration_residual.map_or(String::new(), |r| format!(", first-iteration residual {:.3e} at diffusion {}", r, e.params.lambda_diffusion));
+            html! {
+                <div style="margin-top:4px;">
+                    {&format!("{} {} seed {}: {} steps, energy {:.3}, {} pops{}{}",
+                        when.get(..16).unwrap_or(&when).replace("T", " "), e.scenario.as_deref().unwrap_or("free run"), e.seed,
+                        e.metrics.steps, e.metrics.kinetic_energy + e.metrics.potential_energy, e.metrics.pops, wrinkle, residual)}
+                    {if e.note.is_empty() {String::new()} else {format!(" — {}", e.note)}}
+                    <button type="button" class="button" style="background-color:#5756EB" onclick={self.link.callback(move |_| Msg::NotebookRestoreClicked(id))}>{"Restore"}</button>
+                    <button type="button" class="button" style="background-color:#5756EB" onclick={self.link.callback(move |_| Msg::NotebookDeleteClicked(id))}>{"Delete"}</button>
+                </div>
+            }
+        };
+
+        let body = if self.show_notebook {
+            let ready = self.notebook.is_some();
             html! {
+                <>
+                <br/>
+                <input type="text" size="30" placeholder="Note for this run" value={&self.notebook_note} oninput={self.link.callback(|e| Msg::NotebookNoteChanged(e))}/>
+                <button type="button" class="button" style="background-color:#5756EB" disabled=!ready onclick={self.link.callback(|_| Msg::NotebookLogClicked)}>{"Log This Run"}</button>
+                <button type="button" class="button" style="background-color:#5756EB" disabled=self.notebook_entries.is_empty() onclick={self.link.callback(|_| Msg::NotebookExportClicked)}>{"Export All"}</button><br/>
+                <label for="notebook_sort">{"Sort: "}</label>
+                <select id="notebook_sort" onchange={self.link.callback(|e| Msg::NotebookSortChanged(e))}>
+                    { for NOTEBOOK_SORTS.iter().map(|s| html! {
+                        <option value={s.name()} selected=*s == self.notebook_sort>{s.name()}</option>
+                    })}
+                </select>
+                <input type="text" size="16" placeholder="Filter notes" value={&self.notebook_filter} oninput={self.link.callback(|e| Msg::NotebookFilterChanged(e))}/>
+                <div style="max-height:30vh; overflow-y:auto; font-size:12px;">
+                    { for self.notebook_sort.sorted(&self.notebook_entries, &self.notebook_filter).into_iter().map(row) }
+                </div>
+                </>
+            }
+        } else { html!{<></>} };
+
+        html! {
+            <div id="notebook">
+                <button class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::NotebookToggled)}>
+                    {if self.show_notebook {"Hide Notebook"} else {"Show Notebook"}}
+                </button>
+                <span>{&format!(" {}", status)}</span>
+                {body}
+            </div>
+        }
+    }
+
+    fn apply_cloth(&mut self, cloth : ClothBuild)
+    {
+        let previous_num_particles = self.num_particles;
+        self.previous_positions = cloth.positions.clone();
+        self.current_positions = cloth.positions;
+        self.is_fixed = cloth.is_fixed;
+        self.constraints = cloth.constraints;
+        self.lod_constraints = cloth.lod_constraints;
+        self.area_constraints = cloth.area_constraints;
+        self.bend_constraints = cloth.bend_constraints;
+        self.dihedral_constraints = cloth.dihedral_constraints;
+        self.sheet_of = cloth.sheet_of;
+        self.sheet_grids = cloth.sheet_grids;
+        self.apply_rail();
+        self.apply_soft_pins();
+        self.contact_distance = cloth.contact_distance;
+        self.contacts.clear();
+        self.collider_contacts.clear();
+        self.pin_origins.clear();
+        self.worst_constraints.borrow_mut().clear();
+        self.probe_constraint = None;
+
+        // A rebuilt cloth has nothing left to ring.
+        self.pluck_drag = None;
+        self.particle_drag = None;
+        self.pluck = None;
+        self.freeze.reset(self.current_positions.len());
+        self.selection.reset(self.current_positions.len());
+        self.select_box = None;
+        self.group_drag = None;
+        self.reversal_run = None;
+        self.freeze_box = None;
+
+        self.num_particles = self.current_positions.len();
+        self.num_constraints = self.constraints.len();
+        self.strain_alarm.reset(self.num_constraints);
+        self.collider_lambda = vec![0.0; self.num_particles];
+
+        // Recorded particle indices refer to the old cloth, so a recording can't carry on into a
+        // differently sized one.
+        if self.recording && self.num_particles != previous_num_particles {
+            self.recording = false;
+            self.register_observers();
+            warn!("Stopped recording because the cloth now has {} particles instead of {}", self.num_particles, previous_num_particles);
+        }
+
+        // Keep the per-sheet overrides across resets of the same scene.
+        if self.sheet_params.len() != cloth.num_sheets {
+            self.sheet_params = (0..cloth.num_sheets).map(|_| SheetParams::default()).collect();
+        }
+        self.sheet_kinetic_energy = vec![0.0; cloth.num_sheets];
+
+        // Flat sheets are edge-on to the camera, so skew depth into the picture to see them.
+        // A mesh gets the same treatment when it is deeper than it is tall.
+        let flat_mesh = self.mesh.is_some() && {
+            let (low, high) = self.current_positions.iter().fold((Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)), |(l, h), &p| (l.min(p), h.max(p)));
+            high.z - low.z > high.y - low.y
+        };
+        self.view_shear = match self.params.scene {
+            _ if flat_mesh => vec2(0.4, 0.3),
+            _ if self.mesh.is_some() => vec2(0.0, 0.0),
+            Scene::Hanging => vec2(0.0, 0.0),
+            Scene::Stacked | Scene::Cantilever => vec2(0.4, 0.3),
+        };
+
+        // Particle indices don't survive a rebuild, so the weight is rehung from the new cloth.
+        if self.weight.is_some() {
+            self.weight = Some(self.new_weight());
+        }
+
+        self.rebuild_topology();
+    }
+
+    // Takes the pin modes from the pins, then moves the first sheet's top row from its pins onto
+    // the rail, if there is one. The rail runs through the row's centre.
+    fn apply_rail(&mut self)
+    {
+        self.pin_modes = self.is_fixed.iter().map(|&fixed| if fixed {PinMode::Fixed} else {PinMode::Free}).collect();
+        self.pin_weights = vec![self.params.pin_inverse_mass; self.is_fixed.len()];
+        self.curves.clear();
+        let (shape, grid) = match (self.rail_shape, self.sheet_grids.first()) {
+            (Some(shape), Some(&grid)) => (shape, grid),
+            _ => return,
+        };
+
+        let row : Vec<usize> = (0..grid.num_particles_x).map(|i| grid.particle(i, 0)).collect();
+        let anchor = row.iter().fold(vec3(0.0, 0.0, 0.0), |sum, &p| sum + self.current_positions[p]) / row.len() as f32;
+        self.curves.push(shape.curve(anchor, self.rail_size));
+        for p in row {
+            self.pin_modes[p] = PinMode::OnCurve(0);
+            self.is_fixed[p] = false;
+        }
+    }
+
+    // Swaps the fixed pins left after the rail for anchors where they are, when soft pins are on.
+    fn apply_soft_pins(&mut self)
+    {
+        self.anchors.clear();
+        if !self.params.soft_pins {
+            return;
+        }
+        for p in 0..self.pin_modes.len() {
+            if self.pin_modes[p] == PinMode::Fixed {
+                self.anchors.push(Anchor::new(p, self.current_positions[p]));
+                self.pin_modes[p] = PinMode::Anchored;
+                self.is_fixed[p] = false;
+            }
+        }
+    }
+
+    // Holds a particle where it is, by an anchor if soft pins are on. Returns false if it was
+    // already held.
+    fn pin(&mut self, p : usize) -> bool
+    {
+        if self.is_fixed[p] || self.pin_modes[p] == PinMode::Anchored {
+            return false;
+        }
+        if self.params.soft_pins {
+            self.anchors.push(Anchor::new(p, self.current_positions[p]));
+            self.pin_modes[p] = PinMode::Anchored;
+        } else {
+            self.previous_positions[p] = self.current_positions[p];
+            self.is_fixed[p] = true;
+            self.pin_modes[p] = PinMode::Fixed;
+            self.pin_weights[p] = self.params.pin_inverse_mass;
+        }
+        true
+    }
+
+    // Kicks the free particles around centre outwards, with the brush's falloff over its radius. The
+    // kick goes into their previous positions so it carries on as velocity.
+    fn push_away(&mut self, centre : Vec2)
+    {
+        let kick = ForceBrush { mode : BrushMode::Repel, strength : PUSH_SPEED * self.params.dt, radius : self.brush.radius, active : false };
+        let mut count = 0;
+        for i in 0..self.num_particles {
+            let offset = kick.acceleration(centre, self.current_positions[i]);
+            if !self.is_fixed[i] && offset.length_squared() > 0.0 {
+                self.previous_positions[i] -= offset;
+                count += 1;
+            }
+        }
+        info!("Pushed {} particles away", count);
+    }
+
+    // Lets a particle go from whatever holds it, a pin, an anchor, a rail or the freeze tool.
+    fn release_pin(&mut self, p : usize)
+    {
+        self.freeze.release(p, &self.current_positions, &mut self.previous_positions, &mut self.is_fixed);
+        self.is_fixed[p] = false;
+        self.pin_modes[p] = PinMode::Free;
+        self.anchors.retain(|a| a.particle != p);
+    }
+
+    // Carries out tour steps until one waits on the reader, or ends the tour after the last.
+    fn run_tutorial(&mut self)
+    {
+        loop {
+            let step = match self.tutorial.as_ref().map(|t| t.current()) {
+                Some(Some(step)) => step,
+                Some(None) => {
+                    self.end_tutorial();
+                    return;
+                }
+                None => return,
+            };
+            match step {
+                TutorialStep::Say { .. } => return,
+                TutorialStep::Change(delta) => self.apply_params(delta()),
+                TutorialStep::Reset => self.do_reset = true,
+            }
+            if let Some(tutorial) = &mut self.tutorial {
+                tutorial.advance();
+            }
+        }
+    }
+
+    // Moves the tour on once the current text has been up for its time. Timed in simulated
+    // seconds, so a slow frame rate doesn't cut the text short of what it describes.
+    fn advance_tutorial(&mut self)
+    {
+        let tutorial = match &mut self.tutorial {
+            Some(tutorial) => tutorial,
+            None => return,
+        };
+        if let Some(&TutorialStep::Say { seconds, .. }) = tutorial.current() {
+            tutorial.elapsed += self.params.dt;
+            if tutorial.elapsed >= seconds {
+                tutorial.advance();
+                self.run_tutorial();
+            }
+        }
+    }
+
+    // Puts back the settings the tour found, and the cloth it reset.
+    fn end_tutorial(&mut self)
+    {
+        if let Some(tutorial) = self.tutorial.take() {
+            self.apply_params(tutorial.saved.delta());
+            self.do_reset = true;
+            info!("Ended the tour");
+        }
+    }
+
+    // Samples the plucked particle after a step, and logs the fit once the capture is in.
+    fn advance_pluck(&mut self)
+    {
+        if let Some(pluck) = &mut self.pluck {
+            if pluck.is_complete() {
+                return;
+            }
+            pluck.record(&self.current_positions);
+            if pluck.is_complete() {
+                match pluck.analyze() {
+                    Some(fit) => info!("Pluck of particle {}: decay rate {} /s, period {} s over {} peaks", pluck.particle, fit.decay_rate, fit.period, fit.num_peaks),
+                    None => info!("Pluck of particle {} rang too little to fit", pluck.particle),
+                }
+            }
+        }
+    }
+
+    fn rebuild_topology(&mut self)
+    {
+        let mut topology = Topology::new(self.num_particles);
+        for c in self.constraints.iter() {
+            topology.add_constraint(&[c.p0, c.p1]);
+            match self.sheet_grids.get(self.sheet_of[c.p0]).and_then(|grid| grid.edge_kind(c.p0, c.p1)) {
+                Some(EdgeKind::Horizontal) | Some(EdgeKind::Vertical) => topology.add_structural_edge(c.p0, c.p1),
+                _ => {}
+            }
+        }
+        if self.params.use_area_constraints {
+            for c in self.area_constraints.iter() {
+                topology.add_constraint(&c.particles);
+            }
+        }
+        match self.params.bend_model {
+            BendModel::None => {}
+            BendModel::Distance => for c in self.bend_constraints.iter() {
+                topology.add_constraint(&[c.p0, c.p1]);
+            },
+            BendModel::Dihedral => for c in self.dihedral_constraints.iter() {
+                topology.add_constraint(&c.particles);
+            },
+        }
+        if let Some(weight) = &self.weight {
+            topology.add_constraint(&[weight.attached_particle]);
+        }
+        if let Some(mesh) = &self.mesh {
+            topology.set_boundary(mesh.boundary());
+        }
+        self.topology = topology;
+        self.lambda_adjacency = LambdaAdjacency::build(&self.constraints[..self.num_constraints], self.num_particles);
+        self.apply_perimeter_stiffness();
+        self.update_index_mode();
+    }
+
+    // Picks how indices are drawn for the current particle count, and splits the edges into
+    // batches up front when 16 bit indices can't reach every particle.
+    fn update_index_mode(&mut self)
+    {
+        self.index_mode = IndexMode::choose(self.has_uint_indices, self.num_particles);
+        self.edge_batches = if self.index_mode == IndexMode::Batched {
+            let edges : Vec<u32> = self.constraints.iter().flat_map(|c| vec![c.p0 as u32, c.p1 as u32]).collect();
+            split_batches(&edges, 2, MAX_SHORT_VERTICES)
+        } else {
+            vec![]
+        };
+    }
+
+    // Boundary particles have about half the support of interior ones, so free edges curl.
+    // Stiffening the constraints along them counters that. The boundary comes from the topology,
+    // so it is found afresh whenever constraints are added or removed.
+    fn apply_perimeter_stiffness(&mut self)
+    {
+        let scale = if self.params.stiffen_perimeter {self.params.perimeter_stiffness} else {1.0};
+        for c in self.constraints.iter_mut() {
+            let on_perimeter = self.topology.is_boundary(c.p0) && self.topology.is_boundary(c.p1);
+            c.stiffness_scale = if on_perimeter {scale} else {1.0};
+        }
+    }
+
+    // The index and value of the selected solver's Jacobi relaxation, if it has one.
+    fn jacobi_relaxation(&self) -> Option<(usize, f32)>
+    {
+        let solver = &self.solvers[self.params.solver_index];
+        let index = solver.param_specs().iter().position(|spec| spec.name == "jacobi_relaxation")?;
+        Some((index, solver.param(index)))
+    }
+
+    fn settings(&self) -> Settings<'_>
+    {
+        Settings { params : &self.params, jacobi_relaxation : self.jacobi_relaxation().map(|(_, r)| r) }
+    }
+
+    // Moves the settings off any blocked combination to the nearest valid one and notes why,
+    // returning the parameter changes that took. Warnings are left to the view.
+    fn enforce_rules(&mut self) -> Vec<(&'static str, Effect)>
+    {
+        let mut changes = vec![];
+        for rule in RULES.iter().filter(|rule| rule.severity == Severity::Block) {
+            let fix = {
+                let settings = self.settings();
+                if !rule.is_violated(&settings) {
+                    continue;
+                }
+                rule.fix(&settings)
+            };
+            match fix {
+                Some(Fix::Params(delta)) => changes.extend(self.params.apply(&delta)),
+                Some(Fix::JacobiRelaxation(relaxation)) => {
+                    if let Some((index, _)) = self.jacobi_relaxation() {
+                        self.solvers[self.params.solver_index].set_param(index, relaxation);
+                    }
+                }
+                None => {}
+            }
+            warn!("{}", rule.message);
+            if !self.blocked_notes.contains(&rule.name) {
+                self.blocked_notes.push(rule.name);
+            }
+        }
+
+        // A dismissed warning comes back the next time its combination is set.
+        let violated : Vec<&'static str> = validation::violations(&self.settings()).map(|rule| rule.name).collect();
+        self.dismissed_rules.retain(|name| violated.contains(name));
+        changes
+    }
+
+    // A Jacobi sweep adds up one correction per constraint on a particle, so relaxation times the
+    // largest valence above about 2 tends to overshoot.
+    fn valence_warning(&self) -> Option<String>
+    {
+        let (_, relaxation) = self.jacobi_relaxation()?;
+        let max_valence = self.topology.max_valence();
+        if relaxation * max_valence as f32 > 2.0 {
+            Some(format!("Jacobi relaxation {} x max valence {} = {:.1} exceeds the stability bound of 2",
+                relaxation, max_valence, relaxation * max_valence as f32))
+        } else {
+            None
+        }
+    }
+
+    // The middle of the first sheet's last row, which is only the bottom of the full grid once a
+    // staggered spawn has finished.
+    fn bottom_centre_particle(&self) -> usize
+    {
+        match self.sheet_grids.first() {
+            Some(grid) => grid.particle(grid.num_particles_x / 2, grid.num_particles_y - 1),
+            None => 0,
+        }
+    }
+
+    fn new_weight(&self) -> Weight
+    {
+        let bottom_centre = self.bottom_centre_particle();
+        Weight::new(bottom_centre, self.current_positions[bottom_centre], self.params.weight_mass)
+    }
+
+    // Runs settling steps until the frame's budget is spent. Once the burst is done or has used
+    // up its frames, the render loop carries on as usual and the queued messages are handled.
+    fn advance_warm_up(&mut self)
+    {
+        let performance = web_sys::window().and_then(|w| w.performance());
+        let start = performance.as_ref().map_or(0.0, |p| p.now());
+        while self.warm_up_remaining > 0 {
+            self.step();
+            self.time_step += 1;
+            self.warm_up_remaining -= 1;
+            if performance.as_ref().map_or(false, |p| p.now() - start > WARM_UP_BUDGET_MS) {
+                break;
+            }
+        }
+        self.warm_up_frames += 1;
+
+        if self.warm_up_remaining > 0 && self.warm_up_frames < WARM_UP_MAX_FRAMES {
+            return;
+        }
+        info!("Settled the cloth with {} steps over {} frames", WARM_UP_STEPS - self.warm_up_remaining, self.warm_up_frames);
+        self.warm_up_remaining = 0;
+        for msg in std::mem::take(&mut self.queued_msgs) {
+            self.update(msg);
+        }
+    }
+
+    // Applies any number of parameter changes at once, then carries out what they call for,
+    // each side effect at most once. Every parameter message comes through here.
+    fn apply_params(&mut self, delta : ParamsDelta)
+    {
+        let old = self.params.clone();
+        let mut changes = self.params.apply(&delta);
+        changes.extend(self.enforce_rules());
+        if changes.is_empty() {
+            return;
+        }
+        let values = serde_json::to_value(&self.params).ok();
+        for (name, effect) in changes.iter() {
+            debug!("Parameter {} changed ({:?})", name, effect);
+            let value = values.as_ref().and_then(|v| v.get(*name)).map_or(String::new(), |v| v.to_string());
+            crash::record_action(format!("Step {}: {} = {}", self.time_step, name, value));
+        }
+        crash::keep_params(&self.params);
+        self.update_title();
+        if !self.watchdog_reducing {
+            let shrunk = self.params.num_particles_x * self.params.num_particles_y < old.num_particles_x * old.num_particles_y;
+            if shrunk && !self.watchdog.reductions.is_empty() {
+                info!("The grid is smaller, so everything the watchdog turned down is put back");
+                self.restore_watchdog();
+            } else {
+                self.watchdog.forget_changed(&changes);
+            }
+        }
+        let any = |effect : Effect| changes.iter().any(|change| change.1 == effect);
+
+        if self.params.solver_index != old.solver_index {
+            // The parameter being typed in may not exist on the new solver.
+            if let Some(Param::SolverParam(_)) = self.editing {
+                self.editing = None;
+            }
+            info!("Switched solver to {}", self.solvers[self.params.solver_index].name());
+        }
+        if let Some(w) = &mut self.weight {
+            w.mass = self.params.weight_mass;
+        }
+        if self.params.pin_inverse_mass != old.pin_inverse_mass {
+            let weight = self.params.pin_inverse_mass;
+            self.pin_weights.iter_mut().for_each(|w| *w = weight);
+        }
+
+        if any(Effect::Reset) {
+            self.do_reset = true;
+            self.do_clean_lambda = Some(LambdaFilter::All);
+            return;
+        }
+        if any(Effect::Resize) {
+            self.resize_grid();
+        }
+        if any(Effect::CleanLambda) {
+            self.do_clean_lambda = Some(LambdaFilter::All);
+        }
+        if any(Effect::Topology) {
+            // An idle family shouldn't warm start from whenever it was last used, and the bend
+            // families don't share units.
+            if self.params.use_area_constraints != old.use_area_constraints {
+                for c in self.area_constraints.iter_mut() {
+                    c.lambda = 0.0;
+                }
+            }
+            if self.params.bend_model != old.bend_model {
+                for c in self.bend_constraints.iter_mut() {
+                    c.lambda = vec3(0.0, 0.0, 0.0);
+                }
+                for c in self.dihedral_constraints.iter_mut() {
+                    c.lambda = 0.0;
+                }
+            }
+            self.rebuild_topology();
+        }
+        if any(Effect::RescaleLambda) {
+            let ratio = self.params.dt / old.dt;
+            self.scale_lambdas(ratio * ratio);
+        }
+    }
+
+    // Seeds the vertical constraints of every sheet that hangs from its whole top row with the
+    // tension of the particles below, and the weight if one hangs from them.
+    fn seed_analytic_lambdas(&mut self)
+    {
+        // The integrator adds gravity times dt to a particle's displacement each step.
+        let particle_load = -GRAVITY * self.params.dt;
+        let weight = self.weight.as_ref().map(|w| (w.attached_particle, w.mass));
+        let pin_modes = &self.pin_modes;
+        let mut seeded = 0;
+        for grid in self.sheet_grids.iter() {
+            let load = |p : usize| particle_load * (1.0 + weight.filter(|&(attached, _)| attached == p).map_or(0.0, |(_, mass)| mass));
+            if cloth::seed_hanging_lambdas(grid, &mut self.constraints, &self.current_positions, |p| pin_modes[p] != PinMode::Free, load) {
+                seeded += 1;
+            }
+        }
+        debug!("Seeded hanging tension on {} of {} sheets", seeded, self.sheet_grids.len());
+    }
+
+    // Zeroes the stored lambdas the filter takes, leaving the rest untouched.
+    fn clean_lambdas(&mut self, filter : LambdaFilter)
+    {
+        let sheet_grids = &self.sheet_grids;
+        let sheet_of = &self.sheet_of;
+        let selection = &self.selection;
+        let mut impulses = StoredImpulses {
+            constraints : &mut self.constraints[..self.num_constraints],
+            area_constraints : &mut self.area_constraints,
+            bend_constraints : &mut self.bend_constraints,
+            dihedral_constraints : &mut self.dihedral_constraints,
+            contacts : &mut self.contacts,
+            collider_lambda : &mut self.collider_lambda,
+            anchors : &mut self.anchors,
+        };
+        impulses.clean(filter, |p0, p1| sheet_grids.get(sheet_of[p0]).and_then(|grid| grid.edge_kind(p0, p1)), |p| selection.contains(p));
+    }
+
+    // Multiplies every stored lambda by factor.
+    fn scale_lambdas(&mut self, factor : f32)
+    {
+        for c in self.constraints.iter_mut().chain(self.bend_constraints.iter_mut()) {
+            c.lambda *= factor;
+        }
+        for c in self.area_constraints.iter_mut() {
+            c.lambda *= factor;
+        }
+        for c in self.dihedral_constraints.iter_mut() {
+            c.lambda *= factor;
+        }
+        for c in self.contacts.iter_mut() {
+            c.lambda *= factor;
+        }
+        for lambda in self.collider_lambda.iter_mut() {
+            *lambda *= factor;
+        }
+        for a in self.anchors.iter_mut() {
+            a.lambda *= factor;
+        }
+        if let Some(w) = &mut self.weight {
+            w.lambda *= factor;
+        }
+    }
+
+    // Switches the session seed. Anything already drawn from the old one is stale, so a seeded
+    // frame time run starts over from a reset.
+    fn set_seed(&mut self, seed : u64)
+    {
+        if seed == self.seed {
+            return;
+        }
+        self.seed = seed;
+        seed::save(seed);
+        info!("Session seed is now {}", seed);
+
+        if self.scripted_time && !self.replaying_trace {
+            self.time_source = Box::new(ScriptedTime::random(seed));
+            self.pop_count = 0;
+            self.do_reset = true;
+            self.do_clean_lambda = Some(LambdaFilter::All);
+        }
+    }
+
+    // What a reset builds: the whole grid, or just its top row for a staggered spawn.
+    fn fresh_cloth(&self) -> ClothBuild
+    {
+        if let Some(mesh) = &self.mesh {
+            return build_mesh_cloth(mesh);
+        }
+        let rows = if self.staggered_spawn {1} else {self.params.num_particles_y};
+        build_cloth_rows(&self.params.scene, self.params.connectivity, self.params.wrap_x, self.params.num_particles_x, self.params.num_particles_y, rows)
+    }
+
+    fn cloth_sample(&self) -> ClothSample<'_>
+    {
+        ClothSample {
+            positions : &self.current_positions,
+            previous_positions : &self.previous_positions,
+            is_fixed : &self.is_fixed,
+            constraints : &self.constraints,
+            area_constraints : &self.area_constraints,
+            sheet_grids : &self.sheet_grids,
+        }
+    }
+
+    // Swaps in a cloth rebuilt from the running one. The weight keeps its own state and is only
+    // moved to the new cloth's particle.
+    fn apply_rebuilt_cloth(&mut self, cloth : ClothBuild, previous_positions : Vec<Vec3>)
+    {
+        let weight = self.weight.take();
+        self.apply_cloth(cloth);
+        self.previous_positions = previous_positions;
+        if let Some(mut w) = weight {
+            w.attached_particle = self.bottom_centre_particle();
+            self.weight = Some(w);
+            self.rebuild_topology();
+        }
+    }
+
+    // Rebuilds the cloth at the current grid size, either from scratch on the next frame or by
+    // resampling the running cloth so it keeps its pose and stored impulses. A staggered spawn
+    // that hasn't finished starts over.
+    // Sets the grid size, iterations and detail level of a tier and remembers it for later loads.
+    // Takes up the parameters of the previous page load, and its cloth once the first reset has
+    // built one to put it in.
+    fn resume_session(&mut self, session : Session)
+    {
+        let age = (js_sys::Date::now() - session.saved_ms) / 1000.0;
+        info!("Resuming the session saved {:.0} s ago{}", age, if session.state.is_some() {""} else {", parameters only"});
+        let fresh = self.params.clone();
+        self.apply_params(session.params.delta());
+        self.keep_session = true;
+        self.pending_resume = session.state;
+        self.resumed = Some((age, fresh));
+    }
+
+    // Whether the cloth is the one the parameters build, unlike a mesh, a weight or a rail, which
+    // aren't kept.
+    fn can_save_cloth(&self) -> bool
+    {
+        self.mesh.is_none() && self.weight.is_none() && self.rail_shape.is_none() && self.reset_blend.is_none()
+    }
+
+    // The cloth as it stands, or None when it can't be saved.
+    fn saved_cloth(&self) -> Option<SavedCloth>
+    {
+        if !self.can_save_cloth() {
+            return None;
+        }
+        Some(SavedCloth {
+            time_step : self.time_step,
+            positions : session::encode_vec3(&self.current_positions),
+            previous_positions : session::encode_vec3(&self.previous_positions),
+            is_fixed : self.is_fixed.iter().map(|&f| if f {'1'} else {'0'}).collect(),
+            pin_weights : session::encode(self.pin_weights.iter().cloned()),
+            lambdas : session::encode_vec3(&self.constraints[..self.num_constraints].iter().map(|c| c.lambda).collect::<Vec<Vec3>>()),
+            bend_lambdas : session::encode_vec3(&self.bend_constraints.iter().map(|c| c.lambda).collect::<Vec<Vec3>>()),
+            area_lambdas : session::encode(self.area_constraints.iter().map(|c| c.lambda)),
+            dihedral_lambdas : session::encode(self.dihedral_constraints.iter().map(|c| c.lambda)),
+            anchor_lambdas : session::encode_vec3(&self.anchors.iter().map(|a| a.lambda).collect::<Vec<Vec3>>()),
+        })
+    }
+
+    // Copies the cloth as the step just finished left it, for a crash report to save. The model
+    // can't be reached from the panic hook, so this is the last good state it has.
+    fn keep_last_good(&self)
+    {
+        crash::keep(|s| {
+            s.time_step = self.time_step;
+            s.seed = self.seed;
+            s.restorable = self.can_save_cloth();
+            s.positions.clone_from(&self.current_positions);
+            s.previous_positions.clone_from(&self.previous_positions);
+            s.is_fixed.clone_from(&self.is_fixed);
+            s.pin_weights.clone_from(&self.pin_weights);
+            s.lambdas.clear();
+            s.lambdas.extend(self.constraints[..self.num_constraints].iter().map(|c| c.lambda));
+            s.bend_lambdas.clear();
+            s.bend_lambdas.extend(self.bend_constraints.iter().map(|c| c.lambda));
+            s.area_lambdas.clear();
+            s.area_lambdas.extend(self.area_constraints.iter().map(|c| c.lambda));
+            s.dihedral_lambdas.clear();
+            s.dihedral_lambdas.extend(self.dihedral_constraints.iter().map(|c| c.lambda));
+            s.anchor_lambdas.clear();
+            s.anchor_lambdas.extend(self.anchors.iter().map(|a| a.lambda));
+        });
+    }
+
+    // Takes up the parameters and cloth a crash report kept, once the first reset has built a
+    // cloth to put them in.
+    fn restore_crash(&mut self, report : CrashReport)
+    {
+        info!("Restoring from before the crash with {}", report.message);
+        self.set_seed(report.seed);
+        if let Some(params) = report.params {
+            self.apply_params(params.delta());
+        }
+        self.pending_resume = report.state;
+    }
+
+    fn save_session(&self)
+    {
+        if self.keep_session {
+            session::save(&self.params, self.saved_cloth());
+        }
+    }
+
+    // Puts a saved cloth in place of the one just built, if every list is the length the built one
+    // has. Otherwise the built one stays and starts cold.
+    fn restore_cloth(&mut self, saved : SavedCloth)
+    {
+        let is_fixed : Vec<bool> = saved.is_fixed.chars().map(|c| c == '1').collect();
+        let decoded = (
+            session::decode_vec3(&saved.positions).filter(|v| v.len() == self.current_positions.len()),
+            session::decode_vec3(&saved.previous_positions).filter(|v| v.len() == self.previous_positions.len()),
+            session::decode_vec3(&saved.lambdas).filter(|v| v.len() == self.num_constraints),
+            session::decode_vec3(&saved.bend_lambdas).filter(|v| v.len() == self.bend_constraints.len()),
+            session::decode(&saved.area_lambdas).filter(|v| v.len() == self.area_constraints.len()),
+            session::decode(&saved.dihedral_lambdas).filter(|v| v.len() == self.dihedral_constraints.len()),
+            session::decode_vec3(&saved.anchor_lambdas).filter(|v| v.len() == self.anchors.len()),
+        );
+        let (positions, previous_positions, lambdas, bend_lambdas, area_lambdas, dihedral_lambdas, anchor_lambdas) = match decoded {
+            (Some(a), Some(b), Some(c), Some(d), Some(e), Some(f), Some(g)) if is_fixed.len() == self.is_fixed.len() => (a, b, c, d, e, f, g),
+            _ => {
+                info!("The saved cloth doesn't match the one its parameters build, so it starts cold");
+                return;
+            }
+        };
+
+        self.time_step = saved.time_step;
+        self.current_positions = positions;
+        self.previous_positions = previous_positions;
+        self.is_fixed = is_fixed;
+        // Sessions saved before pins had weights keep the slider's.
+        if let Some(pin_weights) = session::decode(&saved.pin_weights).filter(|v| v.len() == self.pin_weights.len()) {
+            self.pin_weights = pin_weights;
+        }
+        for (c, lambda) in self.constraints.iter_mut().zip(lambdas) {
+            c.lambda = lambda;
+        }
+        for (c, lambda) in self.bend_constraints.iter_mut().zip(bend_lambdas) {
+            c.lambda = lambda;
+        }
+        for (c, lambda) in self.area_constraints.iter_mut().zip(area_lambdas) {
+            c.lambda = lambda;
+        }
+        for (c, lambda) in self.dihedral_constraints.iter_mut().zip(dihedral_lambdas) {
+            c.lambda = lambda;
+        }
+        for (a, lambda) in self.anchors.iter_mut().zip(anchor_lambdas) {
+            a.lambda = lambda;
+        }
+        info!("Resumed the cloth at step {}", self.time_step);
+    }
+
+    fn apply_quality_tier(&mut self, tier : QualityTier)
+    {
+        self.quality_tier = tier;
+        self.lod_mode = if tier == QualityTier::Low {LodMode::Low} else {LodMode::Auto};
+        self.apply_params(tier.delta());
+        benchmark::save_tier(tier);
+    }
+
+    // Runs the next chunk of the first-load benchmark. Once it is done the tier it points to is
+    // applied and saved, so it never runs again.
+    fn advance_benchmark(&mut self)
+    {
+        let performance = match web_sys::window().and_then(|w| w.performance()) {
+            Some(performance) => performance,
+            None => {
+                warn!("No performance timer, so the benchmark can't pick a quality tier");
+                self.benchmark = None;
+                return;
+            }
+        };
+        let benchmark = match &mut self.benchmark {
+            Some(benchmark) => benchmark,
+            None => return,
+        };
+        benchmark.run_chunk(&performance);
+        if !benchmark.is_done() {
+            return;
+        }
+
+        let ns = benchmark.ns_per_constraint();
+        self.benchmark = None;
+        let tier = QualityTier::for_cost(ns);
+        info!("Benchmark took {:.1}ns per constraint iteration, picking the {} tier", ns, tier.name());
+        self.tier_note = Some(format!("Picked {} quality for this device. Change it under Quality if it runs poorly or you want more detail. ", tier.name()));
+        self.apply_quality_tier(tier);
+    }
+
+    // Draws the next golden scenario from a fresh reset and hands the picture to the run,
+    // scheduling another frame until every scenario is done.
+    fn advance_golden(&mut self, timestamp : f64) -> ShouldRender
+    {
+        self.render_loop = None;
+        let scenario = match &self.golden {
+            Some(run) if !run.is_done() => &golden::SCENARIOS[run.next],
+            _ => return false,
+        };
+
+        self.palette_index = 0;
+        self.show_valence = scenario.mode == GoldenMode::Valence;
+        self.anaglyph = scenario.mode == GoldenMode::Anaglyph;
+        self.interocular = stereo::DEFAULT_INTEROCULAR;
+        self.interpolation_from.clear();
+        self.time_step = 0;
+        let cloth = self.fresh_cloth();
+        self.apply_cloth(cloth);
+        self.clean_lambdas(LambdaFilter::All);
+        for _ in 0..scenario.steps {
+            self.time_step += 1;
+            self.step();
+        }
+
+        self.read_dimensions();
+        self.update_view_transform();
+        self.render_gl(timestamp);
+        let hash = self.gl.as_ref().and_then(|gl| golden::read_hash(gl, self.width as i32, self.height as i32));
+        let run = self.golden.as_mut().unwrap();
+        run.record(hash);
+        if !run.is_done() {
+            self.schedule_next_frame();
+        }
+        true
+    }
+
+    // Takes the next steps of the determinism audit, starting each of its runs from a fresh reset.
+    fn advance_audit(&mut self, timestamp : f64) -> ShouldRender
+    {
+        self.render_loop = None;
+        let run = match self.audit.as_ref().and_then(|a| a.current_run()) {
+            Some(run) => run,
+            None => return false,
+        };
+
+        if self.audit.as_ref().unwrap().runs[run].is_empty() {
+            self.interpolation_from.clear();
+            self.time_step = 0;
+            let cloth = self.fresh_cloth();
+            self.apply_cloth(cloth);
+            self.clean_lambdas(LambdaFilter::All);
+        }
+        for _ in 0..determinism::STEPS_PER_FRAME {
+            if self.audit.as_ref().unwrap().current_run() != Some(run) {
+                break;
+            }
+            self.time_step += 1;
+            self.step();
+            let hash = self.state_hash();
+            self.audit.as_mut().unwrap().record(hash);
+        }
+
+        self.update_view_transform();
+        self.render_gl(timestamp);
+        if !self.audit.as_ref().unwrap().is_done() {
+            self.schedule_next_frame();
+        }
+        true
+    }
+
+    // Everything the next step starts from, hashed bit for bit.
+    fn state_hash(&self) -> u64
+    {
+        let mut hasher = StateHasher::new();
+        hasher.add_vec3s(&self.current_positions);
+        hasher.add_vec3s(&self.previous_positions);
+        hasher.add_vec3s(&self.constraints[..self.num_constraints].iter().map(|c| c.lambda).collect::<Vec<Vec3>>());
+        hasher.add_vec3s(&self.bend_constraints.iter().map(|c| c.lambda).collect::<Vec<Vec3>>());
+        hasher.add_floats(self.area_constraints.iter().map(|c| c.lambda));
+        hasher.add_floats(self.dihedral_constraints.iter().map(|c| c.lambda));
+        hasher.add_floats(self.contacts.iter().map(|c| c.lambda));
+        hasher.finish()
+    }
+
+    fn resize_grid(&mut self)
+    {
+        // An imported mesh has no grid to resize. The new size applies once it is cleared.
+        if self.mesh.is_some() {
+            return;
+        }
+        if !self.preserve_on_resize || self.reset_blend.is_some() || self.do_reset || self.spawning() {
+            self.do_reset = true;
+            return;
+        }
+
+        // Frozen particles would otherwise carry over as pins.
+        self.freeze.unfreeze(&self.current_positions, &mut self.previous_positions, &mut self.is_fixed);
+        let mut cloth = build_cloth(&self.params.scene, self.params.connectivity, self.params.wrap_x, self.params.num_particles_x, self.params.num_particles_y);
+        match resample(&self.cloth_sample(), &mut cloth) {
+            Some(previous_positions) => {
+                self.apply_rebuilt_cloth(cloth, previous_positions);
+                debug!("Resampled cloth to {}x{} with {} constraints", self.params.num_particles_x, self.params.num_particles_y, self.num_constraints);
+            }
+            None => self.do_reset = true,
+        }
+    }
+
+    // Whether a staggered spawn still has rows to add.
+    fn spawning(&self) -> bool
+    {
+        self.staggered_spawn && self.sheet_grids.first().map_or(false, |grid| grid.num_particles_y < self.params.num_particles_y)
+    }
+
+    // Adds the next row of a staggered spawn every spawn_interval steps. The running rows carry
+    // over untouched, lambdas included, and the new row starts at rest in its grid position.
+    fn advance_spawn(&mut self)
+    {
+        if !self.spawning() {
+            return;
+        }
+        self.steps_since_spawn += 1;
+        if self.steps_since_spawn < self.spawn_interval {
+            return;
+        }
+        self.steps_since_spawn = 0;
+
+        let rows = self.sheet_grids[0].num_particles_y + 1;
+        self.freeze.unfreeze(&self.current_positions, &mut self.previous_positions, &mut self.is_fixed);
+        let mut cloth = build_cloth_rows(&self.params.scene, self.params.connectivity, self.params.wrap_x, self.params.num_particles_x, self.params.num_particles_y, rows);
+        match carry_over(&self.cloth_sample(), &mut cloth) {
+            Some(previous_positions) => {
+                self.apply_rebuilt_cloth(cloth, previous_positions);
+                debug!("Spawned row {} of {}", rows, self.params.num_particles_y);
+            }
+            None => self.do_reset = true,
+        }
+    }
+
+    // Takes the canvas size from the window, and notes whether the canvas is laid out at all.
+    // Returns whether either changed.
+    fn read_dimensions(&mut self) -> bool
+    {
+        let window = web_sys::window().unwrap();
+        let mut dimensions = WindowDimensions::get_dimensions(&window);
+        if self.golden.is_some() {
+            dimensions.width = golden::CANVAS_SIZE as i32;
+            dimensions.height = golden::CANVAS_SIZE as i32;
+        }
+        let hidden = self.canvas.as_ref().map_or(false, |canvas| canvas.client_width() <= 0 || canvas.client_height() <= 0);
+        let changed = dimensions.width != self.width || dimensions.height != self.height || hidden != self.canvas_hidden;
+
+        let was_drawable = self.has_drawable_size();
+        self.width = dimensions.width;
+        self.height = dimensions.height;
+        self.canvas_hidden = hidden;
+        if self.has_drawable_size() != was_drawable {
+            if was_drawable {
+                info!("The canvas has no area, so drawing stops until it does");
+            } else {
+                info!("Drawing again at {}x{}", self.width, self.height);
+                self.needs_draw = true;
+            }
+        }
+        changed
+    }
+
+    // Minimized windows and hidden containers can leave the canvas with no pixels to draw.
+    fn has_drawable_size(&self) -> bool
+    {
+        self.width > 0 && self.height > 0 && !self.canvas_hidden
+    }
+
+    // Width over height, or square while the canvas has no area.
+    fn aspect_ratio(&self) -> f32
+    {
+        if self.width <= 0 || self.height <= 0 {
+            return 1.0;
+        }
+        (self.width as f32 / self.height as f32).max(MIN_ASPECT_RATIO).min(MAX_ASPECT_RATIO)
+    }
+
+    // Converts canvas pixel coordinates into simulation space by inverting the transform in
+    // basic.vert, including the view transform.
+    // Where a simulation position is drawn, in canvas pixels.
+    fn sim_to_client(&self, p : Vec2) -> Vec2
+    {
+        let view = self.view_transform.to_view(p);
+        let ndc = vec2(view.x / self.aspect_ratio(), view.y);
+        vec2((ndc.x + 1.0) * 0.5 * self.width as f32, (1.0 - ndc.y) * 0.5 * self.height as f32)
+    }
+
+    // The simulation space corners of the canvas, bottom left and top right.
+    fn visible_bounds(&self) -> (Vec2, Vec2)
+    {
+        (self.client_to_sim(0, self.height), self.client_to_sim(self.width, 0))
+    }
+
+    // A particle's position as drawn, with the view shear applied.
+    fn on_screen(&self, p : Vec3) -> Vec2
+    {
+        vec2(p.x + p.z * self.view_shear.x, p.y + p.z * self.view_shear.y)
+    }
+
+    // The measurement end for a click: the particle under it if one is close enough, or else the
+    // point itself.
+    fn measure_point(&mut self, x : i32, y : i32) -> MeasurePoint
+    {
+        let click = vec2(x as f32, y as f32);
+        if self.num_particles > 0 {
+            let p = self.pick_particle(x, y);
+            if (self.sim_to_client(self.on_screen(self.current_positions[p])) - click).length() <= measure::SNAP_PIXELS {
+                return MeasurePoint::Particle(p);
+            }
+        }
+        MeasurePoint::Fixed(self.client_to_sim(x, y))
+    }
+
+    fn client_to_sim(&self, x : i32, y : i32) -> Vec2
+    {
+        let aspect_ratio = self.aspect_ratio();
+        let ndc_x = x as f32 / self.width.max(1) as f32 * 2.0 - 1.0;
+        let ndc_y = 1.0 - y as f32 / self.height.max(1) as f32 * 2.0;
+        self.view_transform.to_sim(vec2(ndc_x * aspect_ratio, ndc_y))
+    }
+
+    // In auto fit mode the target framing follows the cloth's bounds, refreshed every few frames.
+    // The transform in use always eases towards the target so the view doesn't pump as the cloth
+    // swings.
+    fn update_view_transform(&mut self)
+    {
+        if self.auto_fit && self.frame_index % 10 == 0 && self.num_particles > 0 {
+            let shear = self.view_shear;
+            let mut min = vec2(f32::MAX, f32::MAX);
+            let mut max = vec2(f32::MIN, f32::MIN);
+            for p in self.current_positions.iter() {
+                let q = vec2(p.x + p.z * shear.x, p.y + p.z * shear.y);
+                min = min.min(q);
+                max = max.max(q);
+            }
+            if let Some(w) = &self.weight {
+                let h = vec2(Weight::HALF_SIZE, Weight::HALF_SIZE);
+                min = min.min(vec2(w.position.x, w.position.y) - h);
+                max = max.max(vec2(w.position.x, w.position.y) + h);
+            }
+            self.view_target = ViewTransform::fit(min, max, self.aspect_ratio());
+        }
+        let target = self.view_target;
+        self.view_transform.lerp_towards(&target, 0.05);
+    }
+
+    // Replaces the timeline and its colliders, rewound and stopped. Returns false if text isn't
+    // a valid timeline, leaving the current one in place.
+    fn load_timeline(&mut self, name : &str, text : &str) -> bool
+    {
+        match Timeline::from_json(text) {
+            Ok(timeline) => {
+                info!("Loaded timeline {} ({}s, {} colliders)", name, timeline.duration(), timeline.colliders.len());
+                self.restore_pins();
+                self.spheres = timeline.colliders.clone();
+                self.timeline = Some(timeline);
+                self.timeline_name = Some(name.to_string());
+                self.timeline_playing = false;
+                self.timeline_time = 0.0;
+                self.timeline_peak_kinetic_energy = 0.0;
+                self.first_iteration_residual = (0.0, 0);
+                true
+            }
+            Err(e) => {
+                error!("Rejected timeline {}: {}", name, e);
+                false
+            }
+        }
+    }
+
+    // Puts pins a timeline moved back where it found them.
+    fn restore_pins(&mut self)
+    {
+        for (i, origin) in self.pin_origins.drain(..) {
+            if let Some(a) = self.anchors.iter_mut().find(|a| a.particle == i) {
+                a.position = origin;
+            }
+            self.current_positions[i] = origin;
+            self.previous_positions[i] = origin;
+        }
+    }
+
+    // Sets every parameter, the camera and the pins to their timeline values at the cursor. Values go
+    // through the usual messages so they get the same handling as the controls.
+    fn apply_timeline_state(&mut self)
+    {
+        let t = self.timeline_time;
+        let timeline = match &self.timeline {
+            Some(timeline) => timeline,
+            None => return,
+        };
+
+        if let Some(offset) = timeline.pins_at(t) {
+            if self.pin_origins.is_empty() {
+                self.pin_origins = (0..self.num_particles).filter(|&i| self.is_fixed[i]).map(|i| (i, self.current_positions[i])).collect();
+                self.pin_origins.extend(self.anchors.iter().map(|a| (a.particle, a.position)));
+            }
+            for &(i, origin) in self.pin_origins.iter() {
+                // Pins never integrate, so this is the only thing that moves them. Soft pins move
+                // their anchor and the spring brings the particle along.
+                if self.pin_modes[i] == PinMode::Anchored {
+                    if let Some(a) = self.anchors.iter_mut().find(|a| a.particle == i) {
+                        a.position = origin + offset;
+                    }
+                    continue;
+                }
+                self.previous_positions[i] = self.current_positions[i];
+                self.current_positions[i] = origin + offset;
+            }
+        }
+
+        let values : Vec<(&str, f32)> = PARAMETERS.iter()
+            .filter_map(|&(name, interpolation)| timeline.value_at(name, interpolation, t).map(|v| (name, v)))
+            .collect();
+        let camera = timeline.camera_at(t);
+
+        for (name, value) in values {
+            self.set_named_param(name, value);
+        }
+
+        if let Some(camera) = camera {
+            self.view_transform = camera;
+            self.view_target = camera;
+        }
+    }
+
+    // A timeline parameter's current value, with switches as 0 or 1.
+    fn named_param(&self, name : &str) -> f32
+    {
+        let switch = |on : bool| if on {1.0} else {0.0};
+        match name {
+            "eta" => self.params.eta,
+            "lambda_diffusion" => self.params.lambda_diffusion,
+            "nu" => self.params.nu,
+            "gravity_angle" => self.params.gravity_angle,
+            "gravity_period" => self.params.gravity_period,
+            "stiffness" => self.params.stiffness,
+            "area_stiffness" => self.params.area_stiffness,
+            "weight_mass" => self.params.weight_mass,
+            "num_iterations" => self.params.num_iterations as f32,
+            "warm_start" => switch(self.params.warm_start),
+            "tension_only" => switch(self.params.tension_only),
+            "area_constraints" => switch(self.params.use_area_constraints),
+            _ => 0.0,
+        }
+    }
+
+    // Sets a timeline parameter through the usual message, so it gets the same handling as the control.
+    fn set_named_param(&mut self, name : &str, value : f32)
+    {
+        let input = |text : String| InputData { value : text };
+        let switch = |current : bool, msg : Msg| if current != (value != 0.0) {Some(msg)} else {None};
+        let msg = match name {
+            "eta" => Some(Msg::EtaChanged(input(value.to_string()))),
+            "lambda_diffusion" => Some(Msg::LambdaDiffusionChanged(input(value.to_string()))),
+            "nu" => Some(Msg::NuChanged(input(value.to_string()))),
+            "gravity_angle" => Some(Msg::GravityAngleChanged(input(value.to_string()))),
+            "gravity_period" => Some(Msg::GravityPeriodChanged(input(value.to_string()))),
+            "stiffness" => Some(Msg::StiffnessChanged(input(format!("{:e}", value)))),
+            "area_stiffness" => Some(Msg::AreaStiffnessChanged(input(format!("{:e}", value)))),
+            "weight_mass" => Some(Msg::WeightMassChanged(input(value.to_string()))),
+            "num_iterations" => Some(Msg::NumIterationsChanged(input((value.round() as i32).to_string()))),
+            "warm_start" => switch(self.params.warm_start, Msg::WarmStartChanged),
+            "tension_only" => switch(self.params.tension_only, Msg::TensionOnlyChanged),
+            "area_constraints" => switch(self.params.use_area_constraints, Msg::AreaConstraintsChanged),
+            _ => None,
+        };
+        if let Some(msg) = msg {
+            self.update(msg);
+        }
+    }
+
+    fn apply_timeline_event(&mut self, event : TimelineEvent)
+    {
+        match event {
+            TimelineEvent::Reset => self.do_reset = true,
+            TimelineEvent::ReleasePin { particle : Some(p) } => {
+                if p < self.num_particles {
+                    self.release_pin(p);
+                } else {
+                    warn!("Timeline releases particle {} but the cloth has {}", p, self.num_particles);
+                }
+            }
+            TimelineEvent::ReleasePin { particle : None } => {
+                self.freeze.unfreeze(&self.current_positions, &mut self.previous_positions, &mut self.is_fixed);
+                self.is_fixed.iter_mut().for_each(|fixed| *fixed = false);
+                self.pin_modes.iter_mut().for_each(|mode| *mode = PinMode::Free);
+                self.anchors.clear();
+            }
+            TimelineEvent::Pin { particle } => {
+                if particle < self.num_particles {
+                    self.pin(particle);
+                } else {
+                    warn!("Timeline pins particle {} but the cloth has {}", particle, self.num_particles);
+                }
+            }
+            TimelineEvent::Nudge { offset } => {
+                let offset = vec3(offset[0], offset[1], offset[2]);
+                for i in 0..self.num_particles {
+                    if !self.is_fixed[i] {
+                        self.current_positions[i] += offset;
+                    }
+                }
+            }
+            TimelineEvent::Pull { particle, offset } => {
+                if particle < self.num_particles && !self.is_fixed[particle] {
+                    self.current_positions[particle] += vec3(offset[0], offset[1], offset[2]);
+                }
+            }
+        }
+    }
+
+    // Moves the playback cursor on by one fixed step, firing the events it passes.
+    fn advance_timeline(&mut self)
+    {
+        let start = self.timeline_time;
+        let end = start + self.params.dt;
+        let (events, duration) = match &self.timeline {
+            Some(timeline) => (timeline.events_in(start, end), timeline.duration()),
+            None => return,
+        };
+
+        for event in events {
+            self.apply_timeline_event(event);
+        }
+
+        self.timeline_time = end;
+        self.apply_timeline_state();
+        self.timeline_peak_kinetic_energy = self.timeline_peak_kinetic_energy.max(self.kinetic_energy);
+
+        if start > duration {
+            self.timeline_playing = false;
+            info!("Timeline finished at {}s", start);
+            if let Some(limit) = self.timeline.as_ref().and_then(|t| t.max_kinetic_energy) {
+                if self.timeline_peak_kinetic_energy <= limit {
+                    info!("Kinetic energy peaked at {:.3}, within the timeline's limit of {}", self.timeline_peak_kinetic_energy, limit);
+                } else {
+                    warn!("Kinetic energy peaked at {:.3}, over the timeline's limit of {}", self.timeline_peak_kinetic_energy, limit);
+                }
+            }
+            self.timeline_peak_kinetic_energy = 0.0;
+            let (total, steps) = self.first_iteration_residual;
+            if steps > 0 {
+                let mean = (total / steps as f64) as f32;
+                info!("First-iteration residual averaged {:.3e} over {} steps, with lambda diffusion {}", mean, steps, self.params.lambda_diffusion);
+                self.last_run_residual = Some(mean);
+            }
+            self.first_iteration_residual = (0.0, 0);
+        }
+    }
+
+    fn low_detail(&self) -> bool
+    {
+        // The automatic level depends on the window, so a locked run keeps every constraint.
+        if self.full_detail_lock {
+            return false;
+        }
+        match self.lod_mode {
+            LodMode::Auto => self.width < self.lod_threshold,
+            LodMode::Full => false,
+            LodMode::Low => true,
+        }
+    }
+
+    fn load_stiff_sheet(&mut self)
+    {
+        if self.mesh.take().is_some() {
+            self.do_reset = true;
+        }
+        self.apply_params(params::stiff_sheet());
+        info!("Loaded the stiff sheet. Watch the tip droop in Stats as the iterations change: it converges slowly, and more so with the warm start off");
+    }
+
+    fn tip_droop(&self) -> Option<f32>
+    {
+        if self.params.scene != Scene::Cantilever || self.mesh.is_some() {
+            return None;
+        }
+        cloth::tip_droop(self.sheet_grids.first()?, &self.current_positions)
+    }
+
+    fn view_gravity_controls(&self) -> Html
+    {
+        html! {
+            <>
+            <input type="range" id="gravity_angle" min="0" max="360" step="1" value={self.params.gravity_angle} oninput={self.link.callback(|e| Msg::GravityAngleChanged(e))}/>
+            <label for="gravity_angle">{&format!("Gravity Direction: {}°", self.params.gravity_angle)}</label><br/>
+            <input type="number" id="gravity_period" min="0" step="1" value={self.params.gravity_period} oninput={self.link.callback(|e| Msg::GravityPeriodChanged(e))}/>
+            <label for="gravity_period" title="Seconds for gravity to turn a full circle, 0 to hold it still">{" s per Gravity Turn"}</label><br/>
+            </>
+        }
+    }
+
+    fn view_lod_controls(&self) -> Html
+    {
+        let radio = |mode : LodMode, id : &'static str, label : &'static str| html! {
             <>
-            <input type="range" id="jacobi_relax" min="0" max="1" step="0.01" value={self.jacobi_relaxation} oninput={self.link.callback(|e|Msg::JacobiRelaxationChanged(e))}/>
-            <label for="jacobi_relax">{&format!("Jacobi Relaxation: {}", self.jacobi_relaxation)}</label><br/>
+            <label for={id}>{label}</label>
+            <input type="radio" id={id} name="lod" checked=self.lod_mode == mode onclick={self.link.callback(move |_| Msg::LodModeChanged(mode))}/>
             </>
+        };
+
+        html! {
+            <>
+            <span>{"Detail: "}</span>
+            {radio(LodMode::Auto, "lod_auto", "Auto")}
+            {radio(LodMode::Full, "lod_full", "Full")}
+            {radio(LodMode::Low, "lod_low", "Low")}<br/>
+            <input type="range" id="lod_threshold" min="100" max="1000" step="50" value={self.lod_threshold} oninput={self.link.callback(|e| Msg::LodThresholdChanged(e))}/>
+            <label for="lod_threshold">{&format!("Auto Low Detail Below: {}px", self.lod_threshold)}</label><br/>
+            <label for="full_detail_lock" title="Keep every constraint whatever the window width">{"Lock Full Detail"}</label>
+            <input type="checkbox" id="full_detail_lock" checked =self.full_detail_lock onclick={self.link.callback(|_| Msg::FullDetailLockChanged)}/><br/>
+            </>
+        }
+    }
+
+    // The particle under the canvas pixel (x, y), falling back on the nearest one when none is
+    // drawn there. Large grids are picked on the GPU, small ones on the CPU.
+    fn pick_particle(&mut self, x : i32, y : i32) -> usize
+    {
+        if self.num_particles >= GPU_PICK_MIN_PARTICLES {
+            if let Some(p) = self.gpu_pick(x, y) {
+                return p;
+            }
+        }
+        self.nearest_particle(self.client_to_sim(x, y))
+    }
+
+    fn gpu_pick(&mut self, x : i32, y : i32) -> Option<usize>
+    {
+        let gl = self.gl.clone()?;
+        if !self.has_drawable_size() {
+            return None;
+        }
+        if self.gpu_picker.is_none() && !self.gpu_picking_unavailable {
+            self.gpu_picker = GpuPicker::new(&gl);
+            if self.gpu_picker.is_none() {
+                warn!("GPU picking is unavailable, picking on the CPU instead");
+                self.gpu_picking_unavailable = true;
+            }
+        }
+        let picker = self.gpu_picker.as_mut()?;
+
+        // Unsheared like the CPU path, so both pick from the same positions.
+        let positions : Vec<f32> = self.current_positions.iter().flat_map(|p| vec![p.x, p.y]).collect();
+        let canvas_size = vec2(self.width as f32, self.height as f32);
+        let cursor = vec2(x as f32 / canvas_size.x * 2.0 - 1.0, 1.0 - y as f32 / canvas_size.y * 2.0);
+        picker.pick(&gl, &mut self.gpu_buffers, &positions, &self.view_transform, canvas_size, cursor)
+    }
+
+    // Picks at the on-screen position of a spread of particles both ways, whatever the grid size,
+    // and counts how often the GPU agrees with the CPU. Particles closer together than a pixel may
+    // legitimately disagree.
+    fn run_picking_check(&mut self)
+    {
+        const NUM_SAMPLES : usize = 64;
+        if !self.has_drawable_size() {
+            warn!("The canvas has no area to pick from");
+            return;
+        }
+        let aspect_ratio = self.aspect_ratio();
+        let stride = (self.num_particles / NUM_SAMPLES).max(1);
+        let (mut agree, mut total) = (0, 0);
+        for i in (0..self.num_particles).step_by(stride) {
+            let p = self.current_positions[i];
+            let view = self.view_transform.to_view(vec2(p.x, p.y));
+            let x = ((view.x / aspect_ratio + 1.0) * 0.5 * self.width as f32).floor() as i32;
+            let y = ((1.0 - view.y) * 0.5 * self.height as f32).floor() as i32;
+
+            let cpu = self.nearest_particle(self.client_to_sim(x, y));
+            match self.gpu_pick(x, y) {
+                Some(gpu) if gpu == cpu => agree += 1,
+                Some(gpu) => debug!("Picking at ({}, {}) found particle {} on the GPU and {} on the CPU", x, y, gpu, cpu),
+                None => debug!("Picking at ({}, {}) found nothing on the GPU, {} on the CPU", x, y, cpu),
+            }
+            total += 1;
+        }
+        if agree == total {
+            info!("GPU picking agreed with the CPU at all {} samples", total);
+        } else {
+            warn!("GPU picking agreed with the CPU at {} of {} samples", agree, total);
+        }
+        self.picking_check = Some((agree, total));
+    }
+
+    fn nearest_particle(&self, p : Vec2) -> usize
+    {
+        let mut best = 0;
+        let mut best_distance = f32::MAX;
+        for (i, q) in self.current_positions.iter().enumerate() {
+            let distance = (vec2(q.x, q.y) - p).length_squared();
+            if distance < best_distance {
+                best = i;
+                best_distance = distance;
+            }
+        }
+        best
+    }
+
+    // Flags steps where some particle jumped further than pop_threshold, logging the frame
+    // intervals that led up to it.
+    fn check_for_pop(&mut self)
+    {
+        let mut max_displacement = 0.0f32;
+        for i in 0..self.num_particles {
+            max_displacement = max_displacement.max((self.current_positions[i] - self.previous_positions[i]).length());
+        }
+
+        if max_displacement > self.pop_threshold {
+            self.pop_count += 1;
+            let intervals = self.time_source.intervals().unwrap_or(&[]);
+            let recent = &intervals[intervals.len().saturating_sub(10)..];
+            warn!("Pop at step {}: max displacement {} after frame intervals {:?} ms", self.time_step, max_displacement, recent);
+        }
+    }
+
+    // Rebuilds the list of particle pairs from different sheets that are within the contact
+    // distance, carrying over the lambdas of contacts that existed last step.
+    fn find_contacts(&mut self)
+    {
+        let previous : HashMap<ContactKey, (f32, u32)> = self.contacts.drain(..).map(|c| (c.key, (c.lambda, c.age))).collect();
+
+        self.spatial_hash.rebuild(&self.current_positions, self.contact_distance);
+        for (a, b) in self.spatial_hash.close_pairs(&self.current_positions) {
+            let (sa, sb) = (self.sheet_of[a], self.sheet_of[b]);
+            if sa == sb {
+                continue;
+            }
+            let key = ContactKey { sheets : (sa, sb), particles : (a, b) };
+            let (lambda, age) = previous.get(&key).map_or((0.0, 0), |&(lambda, age)| (lambda, age.saturating_add(1)));
+            self.contacts.push(Contact {
+                key : key,
+                lambda : lambda,
+                age : age,
+            });
+        }
+        if self.newborn_boost.enabled && self.newborn_boost.seed_lambda {
+            contacts::seed_newborn_lambdas(&mut self.contacts);
+        }
+    }
+
+    // Smoothed mean kinetic energy of each sheet's particles, assuming unit masses.
+    fn update_sheet_kinetic_energy(&mut self)
+    {
+        let mut totals = vec![0.0f32; self.sheet_kinetic_energy.len()];
+        let mut counts = vec![0usize; self.sheet_kinetic_energy.len()];
+        for i in 0..self.num_particles {
+            let v = (self.current_positions[i] - self.previous_positions[i]) / self.params.dt;
+            totals[self.sheet_of[i]] += 0.5 * v.length_squared();
+            counts[self.sheet_of[i]] += 1;
+        }
+        for s in 0..totals.len() {
+            let energy = totals[s] / counts[s].max(1) as f32;
+            self.sheet_kinetic_energy[s] += 0.05 * (energy - self.sheet_kinetic_energy[s]);
+        }
+    }
+
+    // Total energy of the free particles, assuming unit masses, with potential measured from the
+    // floor height so drops onto the floor show how much each collision response keeps.
+    // Swaps the current and previous positions, which sends every particle back the way it came.
+    fn reverse_time(&mut self)
+    {
+        std::mem::swap(&mut self.current_positions, &mut self.previous_positions);
+        if let Some(w) = &mut self.weight {
+            std::mem::swap(&mut w.position, &mut w.previous_position);
+        }
+    }
+
+    // What is switched on that loses energy or history whatever the solver does, so a reversed
+    // run can't retrace its steps.
+    fn irreversible_features(&self) -> Vec<&'static str>
+    {
+        let mut features = vec![];
+        if self.params.nu < 1.0 {
+            features.push("damping (nu below 1)");
+        }
+        if self.params.warm_start {
+            features.push("warm start");
+        }
+        if self.sdf.is_some() || self.floor || !self.spheres.is_empty() {
+            features.push("colliders");
+        }
+        if self.contact_distance > 0.0 {
+            features.push("sheet contacts");
+        }
+        if self.params.tension_only {
+            features.push("tension-only constraints");
+        }
+        if self.timeline_playing {
+            features.push("timeline playback");
+        }
+        features
+    }
+
+    fn warn_irreversible(&self)
+    {
+        let features = self.irreversible_features();
+        if !features.is_empty() {
+            warn!("Time reversal won't retrace the trajectory with {} on", features.join(", "));
+        }
+    }
+
+    // The settings line at the top of every exported CSV. The lambda clamp is included since it
+    // changes what the solver computes.
+    fn export_settings(&self) -> String
+    {
+        let clamp = if self.params.lambda_clamp {format!("{}x", self.params.clamp_safety)} else {"off".to_string()};
+        format!("seed {}, solver {}, iterations {}, eta {}, nu {}, stiffness {}, warm start {}, lambda clamp {}",
+            self.seed, self.solvers[self.params.solver_index].name(), self.params.num_iterations, self.params.eta, self.params.nu, self.params.stiffness, self.params.warm_start, clamp)
+    }
+
+    // Runs reversal_steps steps forward from here, reverses time, runs as many back and reverses
+    // again, then measures how far the cloth ended up from where it started.
+    fn start_reversal_check(&mut self)
+    {
+        self.warn_irreversible();
+        let settings = format!("solver {}, iterations {}, eta {}, nu {}, stiffness {}, warm start {}",
+            self.solvers[self.params.solver_index].name(), self.params.num_iterations, self.params.eta, self.params.nu, self.params.stiffness, self.params.warm_start);
+        self.reversal_run = Some(ReversalRun::new(self.reversal_steps, self.current_positions.clone(), settings));
+    }
+
+    // Runs the check in progress for up to REVERSAL_BUDGET_MS, reversing time halfway, and
+    // records the result once it is back.
+    fn advance_reversal_check(&mut self)
+    {
+        let performance = web_sys::window().and_then(|w| w.performance());
+        let start = performance.as_ref().map_or(0.0, |p| p.now());
+        loop {
+            match &self.reversal_run {
+                Some(run) if !run.is_complete() => {}
+                Some(_) => break,
+                None => return,
+            }
+            self.step();
+            self.time_step += 1;
+            let halfway = match &mut self.reversal_run {
+                Some(run) => {
+                    run.steps_done += 1;
+                    run.steps_done == run.steps
+                }
+                None => false,
+            };
+            if halfway {
+                self.reverse_time();
+            }
+            if performance.as_ref().map_or(false, |p| p.now() - start > REVERSAL_BUDGET_MS) {
+                return;
+            }
+        }
+
+        self.reverse_time();
+        if let Some(run) = self.reversal_run.take() {
+            let result = ReversalResult::new(run.steps, &run.start, &self.current_positions, &self.is_fixed, run.settings, self.irreversible_features());
+            info!("Time reversal over {} steps: rms error {}, max error {}", result.steps, result.rms_error, result.max_error);
+            self.reversal_results.push(result);
+        }
+    }
+
+    // Stops the check where it is, turning time forwards again if it was on the way back.
+    fn abandon_reversal_check(&mut self)
+    {
+        if let Some(run) = self.reversal_run.take() {
+            if run.is_reversed() {
+                self.reverse_time();
+            }
+        }
+    }
+
+    // Steps the case under way for a frame's budget, starting each case from a fresh cloth, and
+    // saves the results once the last case is done.
+    fn advance_ramp_experiment(&mut self)
+    {
+        let performance = web_sys::window().and_then(|w| w.performance());
+        let start = performance.as_ref().map_or(0.0, |p| p.now());
+        loop {
+            let (case, steps_done) = match &self.ramp_experiment {
+                Some(experiment) => match experiment.current() {
+                    Some(case) => (case, experiment.steps_done),
+                    None => break,
+                },
+                None => return,
+            };
+            if steps_done == 0 {
+                self.start_ramp_case(case);
+            }
+            self.time_step += 1;
+            self.step();
+            let residual = diffusion::mean_residual(&self.current_positions, &self.constraints[..self.num_constraints]);
+            if let Some(experiment) = &mut self.ramp_experiment {
+                experiment.record(residual);
+            }
+            if performance.as_ref().map_or(false, |p| p.now() - start > gravity_ramp::BUDGET_MS) {
+                return;
+            }
+        }
+
+        if let Some(experiment) = &self.ramp_experiment {
+            for &k in gravity_ramp::ITERATIONS.iter() {
+                match experiment.warm_iterations_to_match(k) {
+                    Some(warm) => info!("Gravity ramp: {} iterations cold matched by {} warm started", k, warm),
+                    None => info!("Gravity ramp: {} iterations cold not matched warm started", k),
+                }
+            }
+            if let Err(e) = download::download_text(&format!("gravity_ramp_seed{}.csv", self.seed), "text/csv", &experiment.to_csv()) {
+                error!("Failed to export the gravity ramp experiment: {:?}", e);
+            }
+        }
+        self.end_ramp_experiment();
+    }
+
+    // A fresh cloth with gravity turning from straight down, so every case sees the same load.
+    fn start_ramp_case(&mut self, case : RampCase)
+    {
+        self.apply_params(ParamsDelta {
+            warm_start : Some(case.warm_start),
+            num_iterations : Some(case.iterations),
+            gravity_angle : Some(0.0),
+            gravity_period : Some(gravity_ramp::TURN_SECONDS),
+            ..ParamsDelta::default()
+        });
+        self.time_step = 0;
+        let cloth = self.fresh_cloth();
+        self.apply_cloth(cloth);
+        self.clean_lambdas(LambdaFilter::All);
+        self.do_clean_lambda = None;
+        self.do_reset = false;
+    }
+
+    // Puts the parameters back as they were and starts the cloth again from them.
+    fn end_ramp_experiment(&mut self)
+    {
+        if let Some(experiment) = self.ramp_experiment.take() {
+            self.apply_params(experiment.saved_params.delta());
+            self.do_reset = true;
+        }
+    }
+
+    // Which way gravity pulls this step. A turning gravity is a function of the step count alone,
+    // so playback is the same every time and each turn comes back exactly to where it started.
+    fn gravity(&self) -> Vec3
+    {
+        let mut angle = self.params.gravity_angle;
+        if self.params.gravity_period > 0.0 {
+            let turns = (self.time_step as f64 * self.params.dt as f64 / self.params.gravity_period as f64).fract();
+            angle += 360.0 * turns as f32;
+        }
+        let (sin, cos) = angle.to_radians().sin_cos();
+        vec3(-GRAVITY * sin, GRAVITY * cos, 0.0)
+    }
+
+    // Iterations are at least one, and etas are clamped as the eta slider's are.
+    fn small_multiples_axes(&self) -> Option<([i32; small_multiples::GRID_CELLS], [f32; small_multiples::GRID_CELLS])>
+    {
+        let mut rows = small_multiples::parse_axis::<i32>(&self.small_multiples_rows)?;
+        let mut columns = small_multiples::parse_axis::<f32>(&self.small_multiples_columns)?;
+        for iterations in rows.iter_mut() {
+            *iterations = (*iterations).max(1);
+        }
+        for eta in columns.iter_mut() {
+            *eta = eta.max(0.0).min(1.0);
+        }
+        Some((rows, columns))
+    }
+
+    // Starts the cells again on the axes as typed, once they parse.
+    fn rebuild_small_multiples(&mut self)
+    {
+        let axes = self.small_multiples_axes();
+        if let (Some(grid), Some((rows, columns))) = (&mut self.small_multiples, axes) {
+            grid.rows = rows;
+            grid.columns = columns;
+            grid.rebuild(&self.params);
+        }
+    }
+
+    // One step and everything that follows a step, as the frame loop takes them.
+    fn step_once(&mut self)
+    {
+        self.before_step();
+        self.step();
+        self.after_step();
+    }
+
+    // Runs the next phase of the held step, starting one if there is none, and notes how the
+    // cloth stands after it.
+    fn advance_phase(&mut self)
+    {
+        if self.micro_step.is_none() {
+            self.sweep_replay = None;
+            self.time_step += 1;
+            self.before_step();
+            self.micro_step = Some(MicroStep { next : StepPhase::Integration, params : self.solver_params() });
+        }
+        let mut micro_step = self.micro_step.take().unwrap();
+        let phase = micro_step.next;
+        self.run_phase(phase, &micro_step.params);
+        let next = phase.next(micro_step.params.max_iterations());
+        match next {
+            Some(next) => {
+                micro_step.next = next;
+                self.micro_step = Some(micro_step);
+            }
+            None => self.after_step(),
+        }
+
+        let apply_label = self.solvers[self.params.solver_index].apply_label();
+        let residuals : Vec<f32> = self.constraints[..self.num_constraints].iter()
+            .map(|c| ((self.current_positions[c.p0] - self.current_positions[c.p1]).length() - c.length).abs())
+            .collect();
+        let max_residual = residuals.iter().cloned().fold(0.0f32, f32::max);
+        let mean_residual = residuals.iter().sum::<f32>() / residuals.len().max(1) as f32;
+        self.update_energy();
+        self.phase_note = Some(vec![
+            format!("Step {}, after {}", self.time_step, phase.label(apply_label)),
+            match next {
+                Some(next) => format!("Next: {}", next.label(apply_label)),
+                None => "Step complete".to_string(),
+            },
+            format!("Distance residual: max {:.2e}, mean {:.2e}", max_residual, mean_residual),
+            format!("Energy: kinetic {:.4}, potential {:.4}", self.kinetic_energy, self.potential_energy),
+        ]);
+        self.interpolation_from.clear();
+        self.needs_draw = true;
+    }
+
+    // Runs the held step's remaining phases, so it ends as a whole step would.
+    fn complete_micro_step(&mut self)
+    {
+        while self.micro_step.is_some() {
+            self.advance_phase();
+        }
+    }
+
+    fn before_step(&mut self)
+    {
+        if self.timeline_playing {
+            self.advance_timeline();
+        }
+        let num_particles = self.num_particles;
+        match self.fuzzer.as_mut().and_then(|f| f.next(num_particles)) {
+            Some(FuzzAction::Param { name, value }) => self.set_named_param(name, value),
+            Some(FuzzAction::Event(event)) => self.apply_timeline_event(event),
+            None => {}
+        }
+    }
+
+    fn after_step(&mut self)
+    {
+        self.keep_last_good();
+        self.check_fuzz_invariants();
+        if self.strain_alarm.check(self.time_step, &self.current_positions, &self.constraints) && self.strain_alarm.pause_on_alarm {
+            self.paused = true;
+            info!("Paused on a strain alarm at step {}", self.time_step);
+        }
+        self.advance_pluck();
+        self.update_sheet_kinetic_energy();
+        self.update_energy();
+        if self.rollups_enabled {
+            self.end_rollup_step();
+        }
+        if self.charts_active() {
+            self.push_chart_values();
+        }
+        self.update_wrinkle();
+        self.update_idle();
+        self.advance_spawn();
+        self.advance_tutorial();
+        if self.scripted_time {
+            self.check_for_pop();
+        }
+    }
+
+    // Only charts that can be seen are fed, so a collapsed panel costs a flag test a step.
+    fn charts_active(&self) -> bool
+    {
+        self.show_charts && self.layout.panels.iter().any(|p| p.id == PanelId::Charts && !p.collapsed)
+    }
+
+    fn push_chart_values(&mut self)
+    {
+        let constraints = &self.constraints[..self.num_constraints];
+        let (sum, max) = constraints.iter().fold((0.0f32, 0.0f32), |(sum, max), c| {
+            let residual = ((self.current_positions[c.p0] - self.current_positions[c.p1]).length() - c.length).abs();
+            (sum + residual, max.max(residual))
+        });
+        let mean = sum / constraints.len().max(1) as f32;
+        self.charts[0].push(&[mean, max]);
+        self.charts[1].push(&[self.kinetic_energy, self.potential_energy, self.kinetic_energy + self.potential_energy]);
+    }
+
+    // Alternate charts take the bottom left and bottom right corners.
+    fn chart_rect(&self, k : usize) -> ChartRect
+    {
+        let x = if k % 2 == 0 {chart::CHART_MARGIN} else {self.width - chart::CHART_WIDTH - chart::CHART_MARGIN};
+        let y = self.height - (k / 2 + 1) as i32 * (chart::CHART_HEIGHT + chart::CHART_MARGIN);
+        ChartRect { x : x, y : y, width : chart::CHART_WIDTH, height : chart::CHART_HEIGHT }
+    }
+
+    // Adds the step to the roll-up window, printing the summary to the console when it closes one.
+    fn end_rollup_step(&mut self)
+    {
+        let health = StepHealth {
+            dt : self.params.dt,
+            energy : self.kinetic_energy + self.potential_energy,
+            contacts : self.contacts.len() + self.collider_contacts.len(),
+            total_lambda_clamps : self.total_lambda_clamps,
+            total_strain_alarms : self.strain_alarm.count,
+        };
+        if let Some(rollup) = self.rollups.borrow_mut().end_step(health) {
+            info!("Roll-up from {:.1} to {:.1} s simulated\n{}\n{}", rollup.start_time, rollup.end_time, Rollup::TABLE_HEADER, rollup.table_row());
+        }
+    }
+
+    fn export_rollups(&self)
+    {
+        let json = self.rollups.borrow().to_json(&self.export_settings());
+        if let Err(e) = download::download_text(&format!("rollups_seed{}.json", self.seed), "application/json", &json) {
+            error!("Failed to export roll-up summaries: {:?}", e);
+        }
+    }
+
+    // Ends a fuzz run at the first broken invariant, pausing on it and saving the actions that led
+    // there as a timeline.
+    fn check_fuzz_invariants(&mut self)
+    {
+        let fuzzer = match &mut self.fuzzer {
+            Some(fuzzer) if fuzzer.failure.is_none() => fuzzer,
+            _ => return,
+        };
+        if let Err(reason) = fuzz::check_invariants(&self.current_positions, &self.previous_positions, &self.is_fixed, &self.constraints, &self.bend_constraints) {
+            fuzzer.fail(reason);
+            self.paused = true;
+            if let Err(e) = download::download_text(&format!("fuzz_seed{}.json", fuzzer.seed), "application/json", &fuzzer.to_timeline()) {
+                error!("Failed to download the fuzz timeline: {:?}", e);
+            }
+        }
+    }
+
+    // Takes one step with the sweep log listening, then opens its replay at the start of the solve.
+    fn record_sweep(&mut self)
+    {
+        let capacity = self.constraints.len() * self.params.num_iterations.max(0) as usize;
+        self.sweep_log.borrow_mut().restart(capacity);
+        self.recording_sweep = true;
+        self.register_observers();
+        self.interpolation_from.clear();
+        self.time_step += 1;
+        self.step_once();
+        self.recording_sweep = false;
+        self.register_observers();
+
+        let replay = SweepReplay::new(self.sweep_log.clone());
+        info!("Recorded {} constraint moves at step {}", replay.len(), self.time_step);
+        self.sweep_replay = Some(replay);
+        self.needs_draw = true;
+    }
+
+    // Builds the observer list from whichever diagnostics are switched on.
+    fn register_observers(&mut self)
+    {
+        self.observers.clear();
+        if self.recording_sweep {
+            self.observers.push(Box::new(self.sweep_log.clone()));
+        }
+        if self.inspector {
+            self.observers.push(Box::new(self.worst_constraints.clone()));
+        }
+        if self.rollups_enabled {
+            self.observers.push(Box::new(self.rollups.clone()));
+        }
+        if self.recording {
+            if let Some(recorder) = &self.recorder {
+                self.observers.push(Box::new(recorder.clone()));
             }
-        } else { html!{<></>}};
+        }
+    }
 
-        html! {
-            <div id="container" style="display:flex">
-                <canvas ref=self.node_ref.clone() width={self.width} height={self.height} style="position: absolute"/>
-                <div id="overlay" style="position: absolute; display:flex; width:20vw; flex-direction:column"> 
-                    <div id="sim_type_selector" style="background-color:#96DEEB; border-radius:5px; margin-top:10px; margin-left:10px;
-                    padding: 2px;
-                    padding-right: 4px;">
-                        <form style="padding-left:10px;" action="/action_page.php">
-                            <label for="jacobi">{"Jacobi"}</label>
-                            <input type="radio" id="jacobi" name="sim_type" value="Jacobi" checked =self.do_jacobi onclick={self.link.callback(|_| Msg::SimTypeClicked(SimType::Jacobi))}/>
-                            <label for="gs">{"Gauss-Seidel"}</label>
-                            <input type="radio" id="gs" name="sim_type" value="Gauss-Seidel" checked=!self.do_jacobi onclick={self.link.callback(|_| Msg::SimTypeClicked(SimType::GaussSeidel))}/><br/>
-                            <input type="range" id="iterations" min="1" max="10" value={self.num_iterations} oninput={self.link.callback(|e| Msg::NumIterationsChanged(e))}/>
-                            <label for="iterations">{&format!("Iterations: {}", self.num_iterations)}</label><br/>
-                            <input type="range" id="eta" min="0" max = "1" step = "0.01" value={self.eta} oninput={self.link.callback(|e|Msg::EtaChanged(e))}/>
-                            <label for="eta">{&format!("η (Warmness Factor): {}", self.eta)}</label><br/>
-                            <input type="range" id="nu" min="0" max="1" step="0.01" value={self.nu} oninput={self.link.callback(|e|Msg::NuChanged(e))}/>
-                            <label for="nu">{&format!("𝜈 (Damping Factor): {}", self.nu)}</label><br/>
-                            <input type="range" id="stiffness" min="3" max ="8" step ="0.01" value={self.stiffness.log10()} oninput={self.link.callback(|e| Msg::StiffnessChanged(e))}/>
-                            <label for="stiffness">{&format!("ξ (XPBD Stiffness): {}", self.stiffness)}</label><br/>
-                            {jacobi_slider}
-                            <label for="warm_start">{"Warm Start"}</label>
-                            <input type="checkbox" id="warm_start" checked =self.warm_start onclick={self.link.callback(|_| Msg::WarmStartChanged)}/><br/>
-                        </form>
-                        <button class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::ResetClicked)}>{"Reset"}</button>
-                        <button class="button" style="background-color:#5756EB" onclick={self.link.callback(|_| Msg::CleanLambdaClicked)}>{"Forget Stored Impulse"}</button>
+    // Grabs the particle under the cursor to drag it around. Pinned particles stay put.
+    fn start_particle_drag(&mut self, x : i32, y : i32)
+    {
+        if self.num_particles == 0 {
+            return;
+        }
+        let p = self.pick_particle(x, y);
+        if (self.sim_to_client(self.on_screen(self.current_positions[p])) - vec2(x as f32, y as f32)).length() > GRAB_PIXELS {
+            return;
+        }
+        if self.is_fixed[p] {
+            debug!("Particle {} is pinned and can't be dragged", p);
+            return;
+        }
+        self.particle_drag = Some((p, self.current_positions[p]));
+        self.is_fixed[p] = true;
+    }
 
-                    </div>
-                </div>
-            </div>
+    // Lets go of the particle held by a drag or the pluck tool, unpinning it. A plucked particle
+    // starts ringing from where it was let go.
+    fn let_go_of_particle(&mut self)
+    {
+        if let Some((p, rest)) = self.pluck_drag.take() {
+            self.is_fixed[p] = false;
+            self.pluck = Pluck::new(p, rest, self.current_positions[p], self.params.dt, self.pluck_steps as usize);
+            match &self.pluck {
+                Some(_) => info!("Plucked particle {}, capturing {} steps", p, self.pluck_steps),
+                None => info!("Particle {} was let go where it started, nothing to measure", p),
+            }
+        }
+        if let Some((p, _)) = self.particle_drag.take() {
+            self.is_fixed[p] = false;
         }
     }
 
-    fn change(&mut self, _props: Self::Properties) -> ShouldRender {
-        false
+    // The particle held to the cursor by a drag or the pluck tool, and where it was grabbed.
+    fn held_particle(&self) -> Option<(usize, Vec3)>
+    {
+        self.pluck_drag.or(self.particle_drag)
     }
-}
 
-impl Model {
-    fn render_gl(&mut self, timestamp: f64) {
-        let gl = self.gl.as_ref().expect("GL Context not initialized!");
-        let _ext = gl.get_extension("OES_element_index_uint");
+    // Moves whatever is being dragged, the grabbed or plucked particle or the weight, to follow
+    // the cursor.
+    fn move_drag_target(&mut self, cursor : Vec2)
+    {
+        if let Some((p, rest)) = self.held_particle() {
+            let target = vec3(cursor.x, cursor.y, rest.z);
+            self.current_positions[p] = target;
+            self.previous_positions[p] = target;
+        }
+        if let Some(w) = &mut self.weight {
+            if w.drag_target.is_some() {
+                w.drag_target = Some(vec3(cursor.x, cursor.y, w.position.z));
+            }
+        }
+        if let Some(drag) = &mut self.group_drag {
+            drag.move_to(cursor);
+        }
+    }
+
+    // Where the dragged particle or weight is, if anything is being dragged.
+    fn dragged_position(&self) -> Option<Vec3>
+    {
+        if let Some(p) = self.group_drag.as_ref().and_then(|drag| drag.lead_particle()) {
+            return Some(self.current_positions[p]);
+        }
+        match (self.held_particle(), &self.weight) {
+            (Some((p, _)), _) => Some(self.current_positions[p]),
+            (None, Some(w)) if w.drag_target.is_some() => Some(w.position),
+            _ => None,
+        }
+    }
+
+    // A step taken now is first shown on the next frame, so the target leads the cursor by about
+    // a frame. The lead is capped at a tenth of the view's half-height.
+    fn predict_drag_target(&mut self, now : f64)
+    {
+        if self.dragged_position().is_none() {
+            return;
+        }
+        let max_distance = 0.1 / self.view_transform.scale;
+        if let Some(target) = self.cursor_history.predict(now, self.drag_lead_ms as f64, max_distance) {
+            self.move_drag_target(target);
+        }
+    }
+
+    // Adds this frame's distance between the cursor and what it drags, in canvas pixels.
+    fn measure_drag_lag(&mut self)
+    {
+        if let (Some(position), Some(cursor)) = (self.dragged_position(), self.cursor_history.latest()) {
+            let offset = self.view_transform.to_view(vec2(position.x, position.y)) - self.view_transform.to_view(cursor);
+            // The view's half-height spans half the canvas.
+            self.drag_lag.0 += (offset.length() * self.height as f32 * 0.5) as f64;
+            self.drag_lag.1 += 1;
+        }
+    }
+
+    // Seam constraints are drawn while depth is sheared into the picture, see edge_indices.
+    fn show_seams(&self) -> bool
+    {
+        self.view_shear != vec2(0.0, 0.0)
+    }
+
+    // Turns off auto fit and zooms the camera in on a constraint.
+    fn focus_constraint(&mut self, index : usize)
+    {
+        let c = &self.constraints[index];
+        let shear = self.view_shear;
+        let on_screen = |p : Vec3| vec2(p.x + p.z * shear.x, p.y + p.z * shear.y);
+        let middle = (on_screen(self.current_positions[c.p0]) + on_screen(self.current_positions[c.p1])) * 0.5;
+        let half_size = vec2(0.15, 0.15);
+        self.auto_fit = false;
+        self.view_target = ViewTransform::fit(middle - half_size, middle + half_size, self.aspect_ratio());
+    }
+
+    // Anything that moves the cloth or needs every step keeps it awake.
+    fn idle_inhibited(&self) -> bool
+    {
+        self.timeline_playing || self.recording || self.spawning() || self.reset_blend.is_some() || self.reversal_run.is_some()
+            || self.pluck_drag.is_some() || self.particle_drag.is_some() || self.pluck.as_ref().map_or(false, |p| !p.is_complete())
+            || self.weight.as_ref().map_or(false, |w| w.drag_target.is_some()) || self.group_drag.is_some() || self.tutorial.is_some()
+            || self.brush.active
+    }
+
+    fn update_idle(&mut self)
+    {
+        if self.idle_inhibited() {
+            self.idle.wake();
+            return;
+        }
+        let displacement = idle::max_displacement(&self.current_positions, &self.previous_positions, &self.is_fixed);
+        if self.idle.observe(displacement, self.params.dt) {
+            info!("The cloth has been at rest for {} s, sleeping until the next input", self.idle.delay);
+        }
+    }
+
+    fn update_wrinkle(&mut self)
+    {
+        self.wrinkle = wrinkle::measure(&self.current_positions, &self.constraints, &self.sheet_grids);
+        if let Some(sample) = self.wrinkle {
+            self.wrinkle_log.record(self.time_step, sample);
+        }
+    }
+
+    fn update_energy(&mut self)
+    {
+        self.kinetic_energy = 0.0;
+        self.potential_energy = 0.0;
+        let mut boundary = (0.0, 0);
+        let mut interior = (0.0, 0);
+        let gravity = self.gravity();
+        for i in 0..self.num_particles {
+            if self.is_fixed[i] {
+                continue;
+            }
+            let v = (self.current_positions[i] - self.previous_positions[i]) / self.params.dt;
+            let kinetic_energy = 0.5 * v.length_squared();
+            self.kinetic_energy += kinetic_energy;
+            self.potential_energy += -gravity.dot(self.current_positions[i] - vec3(0.0, FLOOR_HEIGHT, 0.0));
+
+            let group = if self.topology.is_boundary(i) {&mut boundary} else {&mut interior};
+            group.0 += kinetic_energy;
+            group.1 += 1;
+        }
+        self.boundary_kinetic_energy = boundary.0 / boundary.1.max(1) as f32;
+        self.interior_kinetic_energy = interior.0 / interior.1.max(1) as f32;
+    }
+
+    fn nearest_collider(&self, p : Vec3) -> Option<(f32, Vec3)>
+    {
+        self.colliders().nearest(p)
+    }
+
+    fn colliders(&self) -> Colliders<'_>
+    {
+        Colliders {
+            sdf : self.sdf.as_ref(),
+            spheres : &self.spheres,
+            floor : self.floor,
+            thickness : self.collision_thickness,
+            response : self.collision_response,
+            restitution : self.restitution,
+        }
+    }
+
+    // Linearises the colliders around every particle that could reach one this step, for the
+    // XPBD contact model.
+    fn find_collider_contacts(&mut self)
+    {
+        self.collider_contacts.clear();
+        for i in 0..self.num_particles {
+            if self.is_fixed[i] {
+                continue;
+            }
+            let p = self.current_positions[i];
+            let reach = self.collision_thickness + (p - self.previous_positions[i]).length();
+            if let Some((distance, normal)) = self.nearest_collider(p) {
+                if distance < reach {
+                    self.collider_contacts.push(ColliderContact {
+                        particle : i,
+                        normal : normal,
+                        offset : p.dot(normal) - distance + self.collision_thickness,
+                        lambda : self.collider_lambda[i],
+                    });
+                }
+            }
+        }
+    }
+
+    fn advance_reset_blend(&mut self)
+    {
+        let mut blend = self.reset_blend.take().unwrap();
+        if blend.advance(&mut self.current_positions, self.params.dt) {
+            self.time_step = 0;
+            self.apply_cloth(blend.cloth);
+            if self.params.analytic_seed {
+                self.seed_analytic_lambdas();
+            }
+            debug!("Animated reset finished with {} particles and {} constraints", self.num_particles, self.num_constraints);
+        } else {
+            self.previous_positions = self.current_positions.clone();
+            self.reset_blend = Some(blend);
+        }
+    }
+
+    // The solver settings for a step, with the per-sheet overrides applied.
+    fn solver_params(&self) -> SolverParams
+    {
+        SolverParams {
+            dt : self.params.dt,
+            num_iterations : self.params.num_iterations,
+            sheet_iterations : self.sheet_params.iter().map(|s| s.iterations.unwrap_or(self.params.num_iterations)).collect(),
+            sheet_warm_start : self.sheet_params.iter().map(|s| s.warm_start.unwrap_or(self.params.warm_start)).collect(),
+            warm_start : self.params.warm_start,
+            eta : self.params.eta,
+            adaptive_eta : self.params.adaptive_eta,
+            stiffness : self.params.stiffness,
+            tension_only : self.params.tension_only,
+            use_area_constraints : self.params.use_area_constraints,
+            area_stiffness : self.params.area_stiffness,
+            bend_model : self.params.bend_model,
+            bend_stiffness : self.params.bend_stiffness,
+            pin_stiffness : self.params.pin_stiffness,
+            lambda_limit : if self.params.lambda_clamp {Some(solver::lambda_limit(self.num_particles, self.params.dt, self.params.clamp_safety))} else {None},
+        }
+    }
+
+    // Every phase of a step in turn. The phase stepper runs the same phases one at a time, so the
+    // two end in the same state.
+    fn step(&mut self)
+    {
+        let params = self.solver_params();
+        let mut phase = Some(StepPhase::Integration);
+        while let Some(current) = phase {
+            self.run_phase(current, &params);
+            phase = current.next(params.max_iterations());
+        }
+    }
+
+    fn run_phase(&mut self, phase : StepPhase, params : &SolverParams)
+    {
+        match phase {
+            StepPhase::Integration => self.integrate(),
+            StepPhase::Detection => self.detect_contacts(),
+            _ => self.solve_phase(phase, params),
+        }
+    }
+
+    fn integrate(&mut self)
+    {
+        for observer in self.observers.iter_mut() {
+            observer.on_step_begin(self.time_step);
+        }
+
+        let gravity = self.gravity();
+        let brush_centre = if self.brush.active {self.cursor_history.at(self.brush_time_ms)} else {None};
+        solver::fill_inverse_masses(&mut self.inverse_masses, &self.is_fixed, &self.pin_modes, &self.pin_weights);
+
+        let _integration = profiling::scope("integration", || "Integration".to_string());
+        for i in 0..self.num_particles
+        {
+            let inverse_mass = self.inverse_masses[i];
+            let mut acceleration = gravity;
+            if let (true, Some(centre)) = (inverse_mass > 0.0, brush_centre) {
+                acceleration += self.brush.acceleration(centre, self.current_positions[i]) * -GRAVITY;
+            }
+            solver::integrate_particle(&mut self.current_positions[i], &mut self.previous_positions[i], inverse_mass, acceleration, self.params.nu, self.params.dt);
+        }
+
+        if let Some(w) = &mut self.weight {
+            w.integrate(gravity, self.params.nu, self.params.dt);
+        }
+    }
+
+    fn detect_contacts(&mut self)
+    {
+        if self.contact_distance > 0.0 && self.params.self_collision {
+            let _contacts = profiling::scope("collision", || "Contact detection".to_string());
+            self.find_contacts();
+        } else {
+            self.contacts.clear();
+        }
+
+        let has_colliders = !self.colliders().is_empty();
+        if has_colliders && self.collision_response == CollisionResponse::Xpbd {
+            let _contacts = profiling::scope("collision", || "Collider contact detection".to_string());
+            self.find_collider_contacts();
+        } else {
+            self.collider_contacts.clear();
+        }
+
+        let positional = has_colliders && self.collision_response != CollisionResponse::Xpbd;
+        if positional && self.collision_order == CollisionOrder::BeforeConstraints {
+            let _collision = profiling::scope("collision", || "Collider response".to_string());
+            let colliders = Colliders {
+                sdf : self.sdf.as_ref(),
+                spheres : &self.spheres,
+                floor : self.floor,
+                thickness : self.collision_thickness,
+                response : self.collision_response,
+                restitution : self.restitution,
+            };
+            colliders.push_out(&mut self.current_positions, &mut self.previous_positions, &self.is_fixed);
+        }
+    }
+
+    // One of the solve's phases, on a ClothState built for it. The solve opens on the first, and
+    // the last goes on to the collider response and the step's observers.
+    fn solve_phase(&mut self, phase : StepPhase, params : &SolverParams)
+    {
+        // Built from the fields rather than by colliders() so it can be held across the solve.
+        let colliders = Colliders {
+            sdf : self.sdf.as_ref(),
+            spheres : &self.spheres,
+            floor : self.floor,
+            thickness : self.collision_thickness,
+            response : self.collision_response,
+            restitution : self.restitution,
+        };
+        let has_colliders = !colliders.is_empty();
+        let positional = has_colliders && self.collision_response != CollisionResponse::Xpbd;
+
+        if phase.begins_solve(params.max_iterations()) && params.warm_start && self.params.lambda_diffusion > 0.0 {
+            let _diffusion = profiling::scope("solve", || "Lambda diffusion".to_string());
+            if self.lambda_adjacency.len() != self.num_constraints {
+                self.lambda_adjacency = LambdaAdjacency::build(&self.constraints[..self.num_constraints], self.num_particles);
+            }
+            diffusion::diffuse(&mut self.constraints[..self.num_constraints], &self.lambda_adjacency, self.params.lambda_diffusion, &mut self.lambda_scratch);
+        }
+
+        // A group drag's anchors ride along with the soft pins for the solve.
+        let num_pin_anchors = self.anchors.len();
+        if let Some(drag) = &mut self.group_drag {
+            self.anchors.append(&mut drag.anchors);
+        }
+
+        let low_detail = self.low_detail();
+        let mut state = ClothState {
+            positions : &mut self.current_positions,
+            previous_positions : &mut self.previous_positions,
+            is_fixed : &self.is_fixed,
+            inverse_masses : &self.inverse_masses,
+            pin_modes : &self.pin_modes,
+            curves : &self.curves,
+            sheet_of : &self.sheet_of,
+            constraints : &mut self.constraints,
+            active_constraints : if low_detail {Some(&self.lod_constraints)} else {None},
+            area_constraints : &mut self.area_constraints,
+            bend_constraints : &mut self.bend_constraints,
+            dihedral_constraints : &mut self.dihedral_constraints,
+            contacts : &mut self.contacts,
+            contact_distance : self.contact_distance,
+            collider_contacts : &mut self.collider_contacts,
+            colliders : if positional && self.collision_order == CollisionOrder::Interleaved {Some(colliders)} else {None},
+            weight : self.weight.as_mut(),
+            anchors : &mut self.anchors,
+            observers : &mut self.observers,
+        };
+
+        let solver = &mut self.solvers[self.params.solver_index];
+        let _solve = profiling::scope("solve", || format!("{} solve: {}", solver.name(), phase.label(solver.apply_label())));
+        if phase.begins_solve(params.max_iterations()) {
+            self.scratch.resize(self.num_particles);
+            state.notify_solve_begin();
+        }
+        match phase {
+            StepPhase::Project(iteration) => solver.project(&mut state, params, &mut self.scratch, iteration),
+            StepPhase::Apply(iteration) => solver.apply(&mut state, params, &mut self.scratch, iteration),
+            StepPhase::Finish => {
+                self.frame_lambda_clamps += self.scratch.lambda_clamps;
+                self.total_lambda_clamps += self.scratch.lambda_clamps as u64;
+                self.scratch.lambda_clamps = 0;
+                solver::decay_idle_lambdas(&mut state, params);
+                if self.newborn_boost.enabled {
+                    solver::boost_newborn_contacts(&mut state, self.newborn_boost.frames, self.newborn_boost.passes);
+                }
+            }
+            StepPhase::Integration | StepPhase::Detection => {}
+        }
+        drop(_solve);
+
+        if let Some(drag) = &mut self.group_drag {
+            drag.anchors = self.anchors.split_off(num_pin_anchors);
+        }
+        if phase == StepPhase::Apply(0) && self.timeline_playing {
+            let residual = diffusion::mean_residual(&self.current_positions, &self.constraints[..self.num_constraints]);
+            self.first_iteration_residual.0 += residual as f64;
+            self.first_iteration_residual.1 += 1;
+        }
+        if phase != StepPhase::Finish {
+            return;
+        }
+
+        if self.collision_response == CollisionResponse::Xpbd {
+            self.collider_lambda.iter_mut().for_each(|l| *l = 0.0);
+            for c in self.collider_contacts.iter() {
+                self.collider_lambda[c.particle] = c.lambda;
+            }
+        } else if positional && self.collision_order == CollisionOrder::AfterConstraints {
+            let _collision = profiling::scope("collision", || "Collider response".to_string());
+            colliders.push_out(&mut self.current_positions, &mut self.previous_positions, &self.is_fixed);
+        }
+        if has_colliders {
+            self.max_penetration = self.max_penetration.max(colliders.max_penetration(&self.current_positions, &self.is_fixed));
+        }
+
+        if !self.observers.is_empty() {
+            let stats = StepStats { positions : &self.current_positions, constraints : &self.constraints, params : params };
+            for observer in self.observers.iter_mut() {
+                observer.on_step_end(&stats);
+            }
+        }
+    }
+
+    // The cloth's edges in its base color, then the colored layers over them, from positions
+    // already uploaded to vertex_buffer. Every color goes through tint first.
+    fn draw_cloth(&mut self, gl : &GL, color_uniform : &Option<WebGlUniformLocation>, vertex_positions : &[f32], vertex_buffer : &WebGlBuffer, position : u32, tint : fn([f32; 3]) -> [f32; 3])
+    {
+        let palette = &PALETTES[self.palette_index];
+        let show_seams = self.show_seams();
+
+        let cloth = tint(palette.cloth);
+        gl.uniform3f(color_uniform.as_ref(), cloth[0], cloth[1], cloth[2]);
+
+        if self.index_mode == IndexMode::Batched {
+            let constraints = &self.constraints;
+            let batches = self.edge_batches.iter().map(|b| (&b.vertices[..], batch_edge_indices(b, constraints, show_seams)));
+            draw_batches(gl, &mut self.gpu_buffers, "cloth_edges", GL::LINES, batches, vertex_positions, vertex_buffer, position);
+        } else {
+            let edges = edge_indices(&self.constraints, 0..self.constraints.len(), show_seams);
+            draw_indexed(gl, &mut self.gpu_buffers, self.index_mode, "cloth_edges", GL::LINES, &edges, vertex_positions, vertex_buffer, position);
+        }
 
-        let vert_code = include_str!("./basic.vert");
-        let frag_code = include_str!("./basic.frag");
+        // Colored edges are drawn again over the base pass, one draw per layer.
+        let layers = vec![
+            EdgeLayer { priority : edge_colors::PROBE_PRIORITY, color : palette.probe, edges : self.probe_constraint.into_iter().collect() },
+            EdgeLayer { priority : edge_colors::ALARM_PRIORITY, color : palette.alarm, edges : self.strain_alarm.flashing() },
+            EdgeLayer { priority : edge_colors::SWEEP_PRIORITY, color : palette.selected, edges : self.sweep_replay.as_ref().and_then(|r| r.current()).map(|(c, _)| c).into_iter().collect() },
+        ];
+        for (color, layer_edges) in edge_colors::resolve(self.constraints.len(), &layers) {
+            let layer_indices = edge_indices(&self.constraints, layer_edges.iter().cloned(), show_seams);
 
-        let line_count = self.num_constraints as i32 * 2;
+            let color = tint(color);
+            gl.uniform3f(color_uniform.as_ref(), color[0], color[1], color[2]);
+            draw_indexed(gl, &mut self.gpu_buffers, self.index_mode, "edge_layer", GL::LINES, &layer_indices, vertex_positions, vertex_buffer, position);
+        }
+    }
+
+    // Lays a recording over the cloth, or says why it can't be and keeps any ghost already shown.
+    fn set_ghost(&mut self, name : String, recording : Result<Recorder, String>)
+    {
+        match recording.and_then(|r| Ghost::new(name.clone(), r, self.num_particles)) {
+            Ok(ghost) => {
+                info!("Showing {} as a ghost, {} frames", name, ghost.num_frames());
+                self.ghost = Some(ghost);
+                self.ghost_error = None;
+            }
+            Err(e) => {
+                error!("Failed to show {} as a ghost: {}", name, e);
+                self.ghost_error = Some(e);
+            }
+        }
+    }
+
+    // The ghost's positions for the step on show, on screen and blended between steps as the
+    // cloth's are. None while it is hidden, doesn't fit the cloth, or has no frame for the step.
+    fn ghost_vertices(&mut self, interpolate : bool, alpha : f32) -> Option<Vec<f32>>
+    {
+        let (step, shear, num_particles) = (self.time_step, self.view_shear, self.num_particles);
+        let ghost = self.ghost.as_mut().filter(|g| g.visible && g.num_particles() == num_particles)?;
+        let before = if interpolate {ghost.positions(step - 1).map(|p| p.to_vec())} else {None};
+        let positions = ghost.positions(step)?;
+        Some(positions.iter().enumerate().flat_map(|(i, &v)| {
+            let v = before.as_ref().map_or(v, |b| b[i].lerp(v, alpha));
+            vec![v.x + v.z * shear.x, v.y + v.z * shear.y]
+        }).collect())
+    }
+
+    fn render_gl(&mut self, timestamp: f64) {
+        // A handle of its own, so the cloth pass below can borrow the model.
+        let gl = &self.gl.clone().expect("GL Context not initialized!");
+        self.gpu_buffers.begin_frame();
 
         gl.viewport(0, 0, self.width, self.height);
 
-        let vertex_buffer = gl.create_buffer().unwrap();
+        let palette = &PALETTES[self.palette_index];
+        gl.clear_color(palette.clear[0], palette.clear[1], palette.clear[2], palette.clear[3]);
+        gl.clear(GL::COLOR_BUFFER_BIT);
 
+        let upload = profiling::scope("buffer upload", || "Buffer upload".to_string());
         let mut vertex_positions : Vec<f32> = vec![];
+        let mut depths : Vec<f32> = vec![];
         
-        self.current_positions.iter().for_each(|v| {vertex_positions.push(v.x); vertex_positions.push(v.y)});
+        // Between steps, blend from the positions before the last one, drawing a step behind.
+        let interpolate = self.interpolation_from.len() == self.current_positions.len();
+        let alpha = (((timestamp - self.prev_timestamp) / 1000.0) as f32 / self.params.dt).max(0.0).min(1.0);
+        let shear = self.view_shear;
+        // A replay shows its own positions, as the solve had them at the move being shown.
+        let replay = self.sweep_replay.as_ref().filter(|r| r.positions.len() == self.current_positions.len());
+        let interpolate = interpolate && replay.is_none();
+        replay.map_or(&self.current_positions, |r| &r.positions).iter().enumerate().for_each(|(i, &v)| {
+            let v = if interpolate {self.interpolation_from[i].lerp(v, alpha)} else {v};
+            vertex_positions.push(v.x + v.z * shear.x);
+            vertex_positions.push(v.y + v.z * shear.y);
+            if self.anaglyph {
+                depths.push(v.z);
+            }
+        });
 
         let verts = js_sys::Float32Array::from(vertex_positions.as_slice());
 
-        let mut edges : Vec<i32> = vec![];
-        self.constraints.iter().for_each(|c| {edges.push(c.p0 as i32); edges.push(c.p1 as i32)});
-
-        let index_buffer = gl.create_buffer().unwrap();
-        let indices = js_sys::Int32Array::from(edges.as_slice());
-
+        // Every buffer goes through the registry so it is reused across frames rather than leaked.
+        let vertex_buffer = self.gpu_buffers.get_or_create(gl, "cloth_vertices", vertex_positions.len() * 4);
 
         gl.bind_buffer(GL::ARRAY_BUFFER, Some(&vertex_buffer));
         gl.buffer_data_with_array_buffer_view(GL::ARRAY_BUFFER, &verts, GL::STATIC_DRAW);
-        
-        gl.bind_buffer(GL::ELEMENT_ARRAY_BUFFER, Some(&index_buffer));
-        gl.buffer_data_with_array_buffer_view(GL::ELEMENT_ARRAY_BUFFER, &indices, GL::STATIC_DRAW);
-
+        drop(upload);
 
-        let vert_shader = gl.create_shader(GL::VERTEX_SHADER).unwrap();
-        gl.shader_source(&vert_shader, &vert_code);
-        gl.compile_shader(&vert_shader);
 
-        let frag_shader = gl.create_shader(GL::FRAGMENT_SHADER).unwrap();
-        gl.shader_source(&frag_shader, &frag_code);
-        gl.compile_shader(&frag_shader);
-
-        let shader_program = gl.create_program().unwrap();
-        gl.attach_shader(&shader_program, &vert_shader);
-        gl.attach_shader(&shader_program, &frag_shader);
-        gl.link_program(&shader_program);
+        // Compiled once, and again only for a new or lost context.
+        let shaders = profiling::scope("shaders", || "Shader program".to_string());
+        let usable = self.line_program.as_ref().map_or(false, |p| p.is_for(gl));
+        let failed = self.line_program_failure.as_ref().map_or(false, |f| f.is_for(gl));
+        if !usable && !failed {
+            self.line_program = LineProgram::new(gl);
+            self.line_program_failure = if self.line_program.is_none() {Some(BuildFailure::new(gl))} else {None};
+        }
+        let (position, time, aspect_ratio_uniform, view_center_uniform, view_scale_uniform, color_uniform) = match &self.line_program {
+            Some(p) => {
+                gl.use_program(Some(&p.program));
+                (p.position, p.time.clone(), p.aspect_ratio.clone(), p.view_center.clone(), p.view_scale.clone(), p.color.clone())
+            }
+            _ => {
+                // Nothing can be drawn, but this frame's buffers still count towards freeing the
+                // ones no longer used.
+                self.gpu_buffers.collect(gl);
+                return;
+            }
+        };
+        drop(shaders);
 
-        gl.use_program(Some(&shader_program));
+        let _draw = profiling::scope("draw", || "Draw calls".to_string());
 
         // Attach the position vector as an attribute for the GL context.
-        let position = gl.get_attrib_location(&shader_program, "a_position") as u32;
         gl.vertex_attrib_pointer_with_i32(position, 2, GL::FLOAT, false, 0, 0);
         gl.enable_vertex_attrib_array(position);
 
         // Attach the time as a uniform for the GL context.
-        let time = gl.get_uniform_location(&shader_program, "u_time");
         gl.uniform1f(time.as_ref(), timestamp as f32);
 
-        let aspect_ratio = self.width as f32 / self.height as f32;
-        let aspect_ratio_uniform = gl.get_uniform_location(&shader_program, "u_aspect_ratio");
+        let aspect_ratio = self.aspect_ratio();
         gl.uniform1f(aspect_ratio_uniform.as_ref(), aspect_ratio);
 
+        gl.uniform2f(view_center_uniform.as_ref(), self.view_transform.center.x, self.view_transform.center.y);
+        gl.uniform1f(view_scale_uniform.as_ref(), self.view_transform.scale);
+
         let vcolor = vec![1.0f32, 0.0f32, 0.0f32];
-        let lcolor = vec![0.0f32, 0.0f32, 0.0f32];
 
-        let color_uniform = gl.get_uniform_location(&shader_program, "u_color");
 
-        gl.uniform3f(color_uniform.as_ref(), lcolor[0], lcolor[1], lcolor[2]);
+        // The ghost goes first, so the live cloth reads over it where the two overlap.
+        if let Some(ghost_positions) = self.ghost_vertices(interpolate, alpha) {
+            let ghost_array = js_sys::Float32Array::from(ghost_positions.as_slice());
+            let ghost_buffer = self.gpu_buffers.get_or_create(gl, "ghost_vertices", ghost_positions.len() * 4);
+            gl.bind_buffer(GL::ARRAY_BUFFER, Some(&ghost_buffer));
+            gl.buffer_data_with_array_buffer_view(GL::ARRAY_BUFFER, &ghost_array, GL::STATIC_DRAW);
+            gl.vertex_attrib_pointer_with_i32(position, 2, GL::FLOAT, false, 0, 0);
+
+            let edges = edge_indices(&self.constraints, 0..self.constraints.len(), self.show_seams());
+            gl.uniform3f(color_uniform.as_ref(), palette.ghost[0], palette.ghost[1], palette.ghost[2]);
+            draw_indexed(gl, &mut self.gpu_buffers, self.index_mode, "ghost_edges", GL::LINES, &edges, &ghost_positions, &ghost_buffer, position);
 
-        gl.draw_elements_with_i32(GL::LINES, line_count, GL::UNSIGNED_INT, 0);
+            gl.bind_buffer(GL::ARRAY_BUFFER, Some(&vertex_buffer));
+            gl.vertex_attrib_pointer_with_i32(position, 2, GL::FLOAT, false, 0, 0);
+        }
+
+        if self.anaglyph {
+            // Once per eye through its channels. Nothing is depth tested, so there is no buffer
+            // to clear between the two.
+            let stereo_buffer = self.gpu_buffers.get_or_create(gl, "stereo_vertices", vertex_positions.len() * 4);
+            for eye in stereo::eyes(self.interocular).iter() {
+                let eye_positions : Vec<f32> = vertex_positions.chunks(2).zip(depths.iter()).flat_map(|(v, &z)| vec![v[0] + eye.shift * z, v[1]]).collect();
+                gl.bind_buffer(GL::ARRAY_BUFFER, Some(&stereo_buffer));
+                gl.buffer_data_with_array_buffer_view(GL::ARRAY_BUFFER, &js_sys::Float32Array::from(eye_positions.as_slice()), GL::STATIC_DRAW);
+                gl.vertex_attrib_pointer_with_i32(position, 2, GL::FLOAT, false, 0, 0);
+                gl.color_mask(eye.mask[0], eye.mask[1], eye.mask[2], true);
+                self.draw_cloth(gl, &color_uniform, &eye_positions, &stereo_buffer, position, stereo::grey);
+            }
+            gl.color_mask(true, true, true, true);
+            // The overlays after this are drawn once, at the unshifted positions.
+            gl.bind_buffer(GL::ARRAY_BUFFER, Some(&vertex_buffer));
+            gl.vertex_attrib_pointer_with_i32(position, 2, GL::FLOAT, false, 0, 0);
+        } else {
+            self.draw_cloth(gl, &color_uniform, &vertex_positions, &vertex_buffer, position, |color| color);
+        }
 
         //gl.uniform3f(color_uniform.as_ref(), vcolor[0], vcolor[1], vcolor[2]);
 
         //gl.draw_arrays(GL::POINTS, 0, particle_count);
 
+        if self.show_valence {
+            // One draw per distinct valence, each colored from the palette ramp.
+            let min_valence = self.topology.min_valence();
+            let max_valence = self.topology.max_valence();
+            let range = (max_valence - min_valence).max(1) as f32;
+            for valence in min_valence..=max_valence {
+                let points : Vec<u32> = (0..self.num_particles).filter(|&i| self.topology.valence[i] == valence).map(|i| i as u32).collect();
+                if points.is_empty() {
+                    continue;
+                }
+
+                let color = self.color_scales.color(ScaleUse::Valence, palette, (valence - min_valence) as f32 / range);
+                gl.uniform3f(color_uniform.as_ref(), color[0], color[1], color[2]);
+                draw_indexed(gl, &mut self.gpu_buffers, self.index_mode, "valence_points", GL::POINTS, &points, &vertex_positions, &vertex_buffer, position);
+            }
+        }
+
+        if self.freeze.num_frozen() > 0 {
+            let points : Vec<u32> = (0..self.num_particles).filter(|&i| self.freeze.frozen[i]).map(|i| i as u32).collect();
+
+            gl.uniform3f(color_uniform.as_ref(), palette.frozen[0], palette.frozen[1], palette.frozen[2]);
+            draw_indexed(gl, &mut self.gpu_buffers, self.index_mode, "frozen_points", GL::POINTS, &points, &vertex_positions, &vertex_buffer, position);
+        }
+
+        // Weighted pins are boxed, larger the more they give. Pins held still aren't marked.
+        let weighted_pins : Vec<(Vec2, f32)> = (0..self.num_particles)
+            .filter(|&i| self.is_fixed[i] && self.pin_modes[i] == PinMode::Fixed && self.pin_weights[i] > 0.0)
+            .map(|i| (self.on_screen(self.current_positions[i]), self.pin_weights[i]))
+            .collect();
+        if !weighted_pins.is_empty() {
+            let pixel = 2.0 / (self.height as f32 * self.view_transform.scale);
+            let mut markers : Vec<f32> = vec![];
+            for (at, weight) in weighted_pins {
+                let r = (3.0 + 12.0 * weight.sqrt()) * pixel;
+                let corners = [at + vec2(-r, -r), at + vec2(r, -r), at + vec2(r, r), at + vec2(-r, r)];
+                for k in 0..4 {
+                    let (a, b) = (corners[k], corners[(k + 1) % 4]);
+                    markers.extend_from_slice(&[a.x, a.y, b.x, b.y]);
+                }
+            }
+            let marker_array = js_sys::Float32Array::from(markers.as_slice());
+            let marker_buffer = self.gpu_buffers.get_or_create(gl, "pin_markers", markers.len() * 4);
+            gl.bind_buffer(GL::ARRAY_BUFFER, Some(&marker_buffer));
+            gl.buffer_data_with_array_buffer_view(GL::ARRAY_BUFFER, &marker_array, GL::STATIC_DRAW);
+            gl.vertex_attrib_pointer_with_i32(position, 2, GL::FLOAT, false, 0, 0);
+
+            gl.uniform3f(color_uniform.as_ref(), palette.weight[0], palette.weight[1], palette.weight[2]);
+            gl.draw_arrays(GL::LINES, 0, markers.len() as i32 / 2);
+        }
+
+        if !self.selection.is_empty() {
+            let points : Vec<u32> = self.selection.particles.iter().map(|&i| i as u32).collect();
+
+            gl.uniform3f(color_uniform.as_ref(), palette.selected[0], palette.selected[1], palette.selected[2]);
+            draw_indexed(gl, &mut self.gpu_buffers, self.index_mode, "selected_points", GL::POINTS, &points, &vertex_positions, &vertex_buffer, position);
+        }
+
+        if let Some((start, end)) = self.select_box {
+            let outline = [start.x, start.y, end.x, start.y, end.x, end.y, start.x, end.y];
+            let outline_array = js_sys::Float32Array::from(&outline[..]);
+            let outline_buffer = self.gpu_buffers.get_or_create(gl, "select_box", outline.len() * 4);
+            gl.bind_buffer(GL::ARRAY_BUFFER, Some(&outline_buffer));
+            gl.buffer_data_with_array_buffer_view(GL::ARRAY_BUFFER, &outline_array, GL::STATIC_DRAW);
+            gl.vertex_attrib_pointer_with_i32(position, 2, GL::FLOAT, false, 0, 0);
+
+            gl.uniform3f(color_uniform.as_ref(), palette.selected[0], palette.selected[1], palette.selected[2]);
+            gl.draw_arrays(GL::LINE_LOOP, 0, 4);
+        }
+
+        let mut guides : Vec<f32> = vec![];
+        for m in self.measurements.iter() {
+            for end in [m.start, m.end].iter() {
+                let p = self.on_screen(end.position(&self.current_positions));
+                guides.extend_from_slice(&[p.x, p.y]);
+            }
+        }
+        if let (Some(start), Some(cursor)) = (self.measure_start, self.cursor_history.latest()) {
+            let p = self.on_screen(start.position(&self.current_positions));
+            guides.extend_from_slice(&[p.x, p.y, cursor.x, cursor.y]);
+        }
+        if self.show_rulers {
+            let (min, max) = self.visible_bounds();
+            let pixel = 2.0 / (self.height.max(1) as f32 * self.view_transform.scale);
+            for (x, labelled) in measure::ruler_ticks(min.x, max.x) {
+                let length = if labelled {12.0} else {6.0} * pixel;
+                guides.extend_from_slice(&[x, min.y, x, min.y + length]);
+            }
+            for (y, labelled) in measure::ruler_ticks(min.y, max.y) {
+                let length = if labelled {12.0} else {6.0} * pixel;
+                guides.extend_from_slice(&[min.x, y, min.x + length, y]);
+            }
+        }
+        if !guides.is_empty() {
+            let guide_array = js_sys::Float32Array::from(guides.as_slice());
+            let guide_buffer = self.gpu_buffers.get_or_create(gl, "measure_guides", guides.len() * 4);
+            gl.bind_buffer(GL::ARRAY_BUFFER, Some(&guide_buffer));
+            gl.buffer_data_with_array_buffer_view(GL::ARRAY_BUFFER, &guide_array, GL::STATIC_DRAW);
+            gl.vertex_attrib_pointer_with_i32(position, 2, GL::FLOAT, false, 0, 0);
+
+            gl.uniform3f(color_uniform.as_ref(), palette.probe[0], palette.probe[1], palette.probe[2]);
+            gl.draw_arrays(GL::LINES, 0, (guides.len() / 2) as i32);
+        }
+
+        if let (true, Some(cursor)) = (self.brush.active, self.cursor_history.latest()) {
+            const NUM_SEGMENTS : usize = 32;
+            let outline = self.brush.outline(cursor, NUM_SEGMENTS);
+            let outline_array = js_sys::Float32Array::from(&outline[..]);
+            let outline_buffer = self.gpu_buffers.get_or_create(gl, "brush_outline", outline.len() * 4);
+            gl.bind_buffer(GL::ARRAY_BUFFER, Some(&outline_buffer));
+            gl.buffer_data_with_array_buffer_view(GL::ARRAY_BUFFER, &outline_array, GL::STATIC_DRAW);
+            gl.vertex_attrib_pointer_with_i32(position, 2, GL::FLOAT, false, 0, 0);
+
+            gl.uniform3f(color_uniform.as_ref(), palette.probe[0], palette.probe[1], palette.probe[2]);
+            gl.draw_arrays(GL::LINE_LOOP, 0, NUM_SEGMENTS as i32);
+        }
+
+        if let Some((start, end)) = self.freeze_box {
+            let outline = [start.x, start.y, end.x, start.y, end.x, end.y, start.x, end.y];
+            let outline_array = js_sys::Float32Array::from(&outline[..]);
+            let outline_buffer = self.gpu_buffers.get_or_create(gl, "freeze_box", outline.len() * 4);
+            gl.bind_buffer(GL::ARRAY_BUFFER, Some(&outline_buffer));
+            gl.buffer_data_with_array_buffer_view(GL::ARRAY_BUFFER, &outline_array, GL::STATIC_DRAW);
+            gl.vertex_attrib_pointer_with_i32(position, 2, GL::FLOAT, false, 0, 0);
+
+            gl.uniform3f(color_uniform.as_ref(), palette.frozen[0], palette.frozen[1], palette.frozen[2]);
+            gl.draw_arrays(GL::LINE_LOOP, 0, 4);
+        }
+
+        if let Some(run) = &self.reversal_run {
+            // A progress bar along the bottom of the canvas, placed in simulation space so the
+            // regular shader draws it.
+            let corner = |x : f32, y : f32| self.view_transform.to_sim(vec2(x * aspect_ratio, y));
+            let (a, b) = (corner(-1.0, -1.0), corner(-1.0 + 2.0 * run.progress(), -0.97));
+            let bar = [a.x, a.y, b.x, a.y, a.x, b.y, b.x, b.y];
+            let bar_array = js_sys::Float32Array::from(&bar[..]);
+            let bar_buffer = self.gpu_buffers.get_or_create(gl, "reversal_progress", bar.len() * 4);
+            gl.bind_buffer(GL::ARRAY_BUFFER, Some(&bar_buffer));
+            gl.buffer_data_with_array_buffer_view(GL::ARRAY_BUFFER, &bar_array, GL::STATIC_DRAW);
+            gl.vertex_attrib_pointer_with_i32(position, 2, GL::FLOAT, false, 0, 0);
+
+            gl.uniform3f(color_uniform.as_ref(), palette.probe[0], palette.probe[1], palette.probe[2]);
+            gl.draw_arrays(GL::TRIANGLE_STRIP, 0, 4);
+        }
+
+        if let Some(w) = &self.weight {
+            let lines = w.line_vertices(self.current_positions[w.attached_particle]);
+            let weight_verts = js_sys::Float32Array::from(lines.as_slice());
+            let weight_buffer = self.gpu_buffers.get_or_create(gl, "weight_lines", lines.len() * 4);
+            gl.bind_buffer(GL::ARRAY_BUFFER, Some(&weight_buffer));
+            gl.buffer_data_with_array_buffer_view(GL::ARRAY_BUFFER, &weight_verts, GL::STATIC_DRAW);
+            gl.vertex_attrib_pointer_with_i32(position, 2, GL::FLOAT, false, 0, 0);
+
+            gl.uniform3f(color_uniform.as_ref(), palette.weight[0], palette.weight[1], palette.weight[2]);
+            gl.draw_arrays(GL::LINES, 0, lines.len() as i32 / 2);
+        }
+
+        if !self.sdf_contour.is_empty() {
+            let contour = js_sys::Float32Array::from(self.sdf_contour.as_slice());
+            let contour_buffer = self.gpu_buffers.get_or_create(gl, "sdf_contour", self.sdf_contour.len() * 4);
+            gl.bind_buffer(GL::ARRAY_BUFFER, Some(&contour_buffer));
+            gl.buffer_data_with_array_buffer_view(GL::ARRAY_BUFFER, &contour, GL::STATIC_DRAW);
+            gl.vertex_attrib_pointer_with_i32(position, 2, GL::FLOAT, false, 0, 0);
+
+            gl.uniform3f(color_uniform.as_ref(), palette.collider[0], palette.collider[1], palette.collider[2]);
+            gl.draw_arrays(GL::LINES, 0, self.sdf_contour.len() as i32 / 2);
+        }
+
+        for (k, curve) in self.curves.iter().enumerate() {
+            let strip = curve.line_strip(64);
+            let strip_array = js_sys::Float32Array::from(strip.as_slice());
+            let strip_buffer = self.gpu_buffers.get_or_create(gl, &format!("rail_{}", k), strip.len() * 4);
+            gl.bind_buffer(GL::ARRAY_BUFFER, Some(&strip_buffer));
+            gl.buffer_data_with_array_buffer_view(GL::ARRAY_BUFFER, &strip_array, GL::STATIC_DRAW);
+            gl.vertex_attrib_pointer_with_i32(position, 2, GL::FLOAT, false, 0, 0);
+
+            gl.uniform3f(color_uniform.as_ref(), palette.collider[0], palette.collider[1], palette.collider[2]);
+            gl.draw_arrays(GL::LINE_STRIP, 0, strip.len() as i32 / 2);
+        }
+
+        if !self.spheres.is_empty() {
+            let outlines : Vec<f32> = self.spheres.iter().flat_map(|s| s.outline(48)).collect();
+            let outline_array = js_sys::Float32Array::from(outlines.as_slice());
+            let outline_buffer = self.gpu_buffers.get_or_create(gl, "sphere_outlines", outlines.len() * 4);
+            gl.bind_buffer(GL::ARRAY_BUFFER, Some(&outline_buffer));
+            gl.buffer_data_with_array_buffer_view(GL::ARRAY_BUFFER, &outline_array, GL::STATIC_DRAW);
+            gl.vertex_attrib_pointer_with_i32(position, 2, GL::FLOAT, false, 0, 0);
+
+            gl.uniform3f(color_uniform.as_ref(), palette.collider[0], palette.collider[1], palette.collider[2]);
+            gl.draw_arrays(GL::LINES, 0, outlines.len() as i32 / 2);
+        }
+
+        if self.floor {
+            let floor = js_sys::Float32Array::from(&[-100.0, FLOOR_HEIGHT, 100.0, FLOOR_HEIGHT][..]);
+            let floor_buffer = self.gpu_buffers.get_or_create(gl, "floor_line", 4 * 4);
+            gl.bind_buffer(GL::ARRAY_BUFFER, Some(&floor_buffer));
+            gl.buffer_data_with_array_buffer_view(GL::ARRAY_BUFFER, &floor, GL::STATIC_DRAW);
+            gl.vertex_attrib_pointer_with_i32(position, 2, GL::FLOAT, false, 0, 0);
+
+            gl.uniform3f(color_uniform.as_ref(), palette.collider[0], palette.collider[1], palette.collider[2]);
+            gl.draw_arrays(GL::LINES, 0, 2);
+        }
+
+        let uniforms = ChartUniforms {
+            aspect_ratio : aspect_ratio_uniform,
+            view_center : view_center_uniform,
+            view_scale : view_scale_uniform,
+            color : color_uniform,
+        };
+        // The small multiples cover the main cloth, leaving the charts over them.
+        if let Some(grid) = &self.small_multiples {
+            grid.render(gl, &mut self.gpu_buffers, &uniforms, position, self.width, self.height, palette.cloth, palette.ramp[1], palette.clear);
+            gl.viewport(0, 0, self.width, self.height);
+        }
+
+        if self.charts_active() {
+            let rects : Vec<ChartRect> = (0..self.charts.len()).map(|k| self.chart_rect(k)).collect();
+            for (chart, &rect) in self.charts.iter().zip(rects.iter()) {
+                chart.render(gl, &mut self.gpu_buffers, &uniforms, position, rect, self.height, palette, &self.color_scales);
+            }
+            gl.viewport(0, 0, self.width, self.height);
+        }
+
+        self.gpu_buffers.collect(gl);
+    }
+
+    // The only place an animation frame is requested. Each Render schedules exactly one
+    // successor, so there is never more than one loop driving the physics.
+    fn schedule_next_frame(&mut self)
+    {
+        debug_assert!(self.render_loop.is_none(), "an animation frame is already pending");
+
+        // The callback to request animation frame is passed a time value which can be used for
+        // rendering motion independent of the framerate which may vary.
         let render_frame = self.link.callback(Msg::Render);
         let handle = RenderService::request_animation_frame(render_frame);
 
-        // A reference to the new handle must be retained for the next render to run.
+        // A reference to the handle must be stored, otherwise it is dropped and the render won't
+        // occur.
         self.render_loop = Some(handle);
     }
+
+    // Counts frames the browser dropped, judged against a running estimate of the display's
+    // refresh interval. Uses real timestamps so scripted time doesn't register as skips.
+    fn track_frame_timing(&mut self, real_timestamp : f64)
+    {
+        if self.last_real_timestamp > 0.0 {
+            let interval = real_timestamp - self.last_real_timestamp;
+            if interval > 1000.0 {
+                // A hidden tab stops animation frames altogether, which isn't a skip.
+            } else if interval > 2.0 * self.refresh_interval {
+                self.skipped_frames += (interval / self.refresh_interval).round() as u64 - 1;
+            } else {
+                self.refresh_interval += 0.05 * (interval - self.refresh_interval);
+            }
+            self.frame_pacing.add_interval(interval);
+        }
+        self.last_real_timestamp = real_timestamp;
+    }
+}
+
+// Embedded pages settle the cloth before showing it and the standalone page doesn't. A warmup=1
+// or warmup=0 query parameter overrides either.
+fn warm_up_by_default() -> bool
+{
+    let window = match web_sys::window() {
+        Some(window) => window,
+        None => return false,
+    };
+    let search = window.location().search().unwrap_or_default();
+    if search.contains("warmup=1") {
+        return true;
+    }
+    if search.contains("warmup=0") {
+        return false;
+    }
+    window.top().ok().flatten().map_or(false, |top| top != window)
+}
+
+// A fuzz=1 query parameter starts fuzzing as soon as the page loads.
+fn fuzz_requested() -> bool
+{
+    web_sys::window()
+        .and_then(|w| w.location().search().ok())
+        .map_or(false, |search| search.trim_start_matches('?').split('&').any(|pair| pair == "fuzz=1"))
+}
+
+// Frame pacing with any overrides from the query string, so tests can pin the refresh rate and
+// thresholds rather than depend on the display they run on.
+fn frame_pacing_from_url() -> FramePacing
+{
+    let mut pacing = FramePacing::new();
+    if let Some(search) = web_sys::window().and_then(|w| w.location().search().ok()) {
+        pacing.override_from_query(&search);
+    }
+    pacing
+}
+
+// Whether the canvas could be handed to a worker with transferControlToOffscreen. There is no
+// worker backend yet, so this is only reported; rendering always stays on the main thread.
+fn offscreen_canvas_supported() -> bool
+{
+    let global = js_sys::global();
+    let has_offscreen_canvas = js_sys::Reflect::has(&global, &"OffscreenCanvas".into()).unwrap_or(false);
+    let has_transfer = js_sys::Reflect::get(&global, &"HTMLCanvasElement".into())
+        .and_then(|canvas_class| js_sys::Reflect::get(&canvas_class, &"prototype".into()))
+        .and_then(|prototype| js_sys::Reflect::has(&prototype, &"transferControlToOffscreen".into()))
+        .unwrap_or(false);
+    has_offscreen_canvas && has_transfer
+}
+
+// Whether positions could be shared with a worker without copying. SharedArrayBuffer is only
+// exposed to cross-origin isolated pages. Like the OffscreenCanvas check this is only reported.
+fn shared_memory_supported() -> bool
+{
+    let global = js_sys::global();
+    let isolated = js_sys::Reflect::get(&global, &"crossOriginIsolated".into()).ok().and_then(|v| v.as_bool()).unwrap_or(false);
+    let has_shared_array_buffer = js_sys::Reflect::has(&global, &"SharedArrayBuffer".into()).unwrap_or(false);
+    let has_atomics = js_sys::Reflect::has(&global, &"Atomics".into()).unwrap_or(false);
+    isolated && has_shared_array_buffer && has_atomics
 }
 
 fn main() {
+    logging::init();
+    crash::install_panic_hook();
     yew::start_app::<Model>();
 }
@@ -0,0 +1,60 @@
+use glam::*;
+
+// Ruler tick spacing in simulation units. Every fifth tick is longer and labelled.
+pub const TICK_SPACING : f32 = 0.1;
+pub const LABEL_EVERY : i32 = 5;
+// Past this many ticks along an edge only the labelled ones are drawn.
+const MAX_TICKS : i32 = 200;
+
+// How close in pixels a click has to land to a particle to measure from the particle itself.
+pub const SNAP_PIXELS : f32 = 6.0;
+
+// One end of a measurement: a point fixed in simulation space, or a particle that it follows.
+#[derive(Clone, Copy, PartialEq)]
+pub enum MeasurePoint
+{
+    Fixed(Vec2),
+    Particle(usize),
+}
+
+impl MeasurePoint {
+    pub fn position(&self, positions : &[Vec3]) -> Vec3
+    {
+        match *self {
+            MeasurePoint::Fixed(p) => vec3(p.x, p.y, 0.0),
+            MeasurePoint::Particle(p) => positions[p],
+        }
+    }
+}
+
+// A distance between two points, kept until it is dismissed. Positions are in simulation space so
+// the line and its label follow the camera.
+pub struct Measurement
+{
+    pub start : MeasurePoint,
+    pub end : MeasurePoint,
+}
+
+impl Measurement {
+    // Between two particles the distance is the true one. A fixed end has no depth, so anything
+    // involving one is measured in the plane of the view.
+    pub fn length(&self, positions : &[Vec3]) -> f32
+    {
+        let (a, b) = (self.start.position(positions), self.end.position(positions));
+        match (self.start, self.end) {
+            (MeasurePoint::Particle(_), MeasurePoint::Particle(_)) => (a - b).length(),
+            _ => (vec2(a.x, a.y) - vec2(b.x, b.y)).length(),
+        }
+    }
+}
+
+// The ticks of a ruler covering [min, max], each with whether it gets a label.
+pub fn ruler_ticks(min : f32, max : f32) -> Vec<(f32, bool)>
+{
+    let (first, last) = ((min / TICK_SPACING).ceil() as i32, (max / TICK_SPACING).floor() as i32);
+    let sparse = last - first > MAX_TICKS;
+    (first..=last)
+        .map(|k| (k as f32 * TICK_SPACING, k % LABEL_EVERY == 0))
+        .filter(|&(_, labelled)| labelled || !sparse)
+        .collect()
+}
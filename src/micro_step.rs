@@ -0,0 +1,231 @@
+use crate::solver::SolverParams;
+
+// The parts of a step in the order they run. A whole step runs them all back to back; the phase
+// stepper stops after each one and draws.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StepPhase
+{
+    // Gravity and the brush, and the weight's fall.
+    Integration,
+    // Contact detection and, when set to go first, the collider push-out.
+    Detection,
+    // An iteration's constraint pass. Iteration 0's applies the warm start.
+    Project(i32),
+    // Whatever ends the iteration, which for Jacobi starts with moving the particles.
+    Apply(i32),
+    // Lambda decay, the newborn contact boost, the collider response after the constraints and
+    // the step's observers.
+    Finish,
+}
+
+impl StepPhase {
+    // The phase after this one, or None when this one ends the step.
+    pub fn next(&self, max_iterations : i32) -> Option<StepPhase>
+    {
+        match *self {
+            StepPhase::Integration => Some(StepPhase::Detection),
+            StepPhase::Detection if max_iterations > 0 => Some(StepPhase::Project(0)),
+            StepPhase::Detection => Some(StepPhase::Finish),
+            StepPhase::Project(k) => Some(StepPhase::Apply(k)),
+            StepPhase::Apply(k) if k + 1 < max_iterations => Some(StepPhase::Project(k + 1)),
+            StepPhase::Apply(_) => Some(StepPhase::Finish),
+            StepPhase::Finish => None,
+        }
+    }
+
+    // Whether the solve starts with this phase. A step with no iterations still opens one.
+    pub fn begins_solve(&self, max_iterations : i32) -> bool
+    {
+        match *self {
+            StepPhase::Project(0) => true,
+            StepPhase::Finish => max_iterations == 0,
+            _ => false,
+        }
+    }
+
+    pub fn label(&self, apply_label : &str) -> String
+    {
+        match *self {
+            StepPhase::Integration => "Integration".to_string(),
+            StepPhase::Detection => "Contact detection".to_string(),
+            StepPhase::Project(0) => "Iteration 0: constraint pass, warm started".to_string(),
+            StepPhase::Project(k) => format!("Iteration {}: constraint pass", k),
+            StepPhase::Apply(k) => format!("Iteration {}: {}", k, apply_label),
+            StepPhase::Finish => "Lambda decay and collider response".to_string(),
+        }
+    }
+}
+
+// A step stopped between phases. The solver settings are taken when it starts and kept for the
+// rest of it, like a whole step's.
+pub struct MicroStep
+{
+    // The phase to run next.
+    pub next : StepPhase,
+    pub params : SolverParams,
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::*;
+    use crate::cloth::{build_cloth, BendModel, ClothBuild, Connectivity, Scene};
+    use crate::rail::PinMode;
+    use crate::solver::{self, ClothState, Scratch, Solver};
+    use super::*;
+
+    #[test]
+    fn next_runs_every_iteration_in_order()
+    {
+        use StepPhase::*;
+        let table : [(i32, &[StepPhase]); 3] = [
+            (0, &[Integration, Detection, Finish]),
+            (1, &[Integration, Detection, Project(0), Apply(0), Finish]),
+            (3, &[Integration, Detection, Project(0), Apply(0), Project(1), Apply(1), Project(2), Apply(2), Finish]),
+        ];
+        for &(max_iterations, expected) in table.iter() {
+            let mut phases = vec![Integration];
+            while let Some(next) = phases.last().unwrap().next(max_iterations) {
+                phases.push(next);
+            }
+            assert_eq!(phases, expected, "{} iterations", max_iterations);
+            let openings : Vec<StepPhase> = phases.iter().cloned().filter(|phase| phase.begins_solve(max_iterations)).collect();
+            assert_eq!(openings.len(), 1, "{} iterations open the solve at {:?}", max_iterations, openings);
+        }
+    }
+
+    struct Sim
+    {
+        cloth : ClothBuild,
+        previous_positions : Vec<Vec3>,
+        inverse_masses : Vec<f32>,
+        pin_modes : Vec<PinMode>,
+        scratch : Scratch,
+    }
+
+    impl Sim {
+        fn new() -> Sim
+        {
+            let cloth = build_cloth(&Scene::Hanging, Connectivity::Eight, false, 6, 5);
+            let mut inverse_masses = vec![];
+            solver::fill_inverse_masses(&mut inverse_masses, &cloth.is_fixed, &[], &[]);
+            Sim {
+                previous_positions : cloth.positions.clone(),
+                pin_modes : cloth.is_fixed.iter().map(|&fixed| if fixed {PinMode::Fixed} else {PinMode::Free}).collect(),
+                inverse_masses : inverse_masses,
+                cloth : cloth,
+                scratch : Scratch::default(),
+            }
+        }
+
+        fn integrate(&mut self, params : &SolverParams)
+        {
+            for ((p, pm1), &inverse_mass) in self.cloth.positions.iter_mut().zip(self.previous_positions.iter_mut()).zip(self.inverse_masses.iter()) {
+                solver::integrate_particle(p, pm1, inverse_mass, vec3(0.3, -0.98, 0.1), 0.6, params.dt);
+            }
+        }
+
+        // A fresh view of the state for each phase, as the model builds one.
+        fn state(&mut self) -> ClothState<'_>
+        {
+            ClothState {
+                positions : &mut self.cloth.positions,
+                previous_positions : &mut self.previous_positions,
+                is_fixed : &self.cloth.is_fixed,
+                inverse_masses : &self.inverse_masses,
+                pin_modes : &self.pin_modes,
+                curves : &[],
+                sheet_of : &self.cloth.sheet_of,
+                constraints : &mut self.cloth.constraints,
+                active_constraints : None,
+                area_constraints : &mut self.cloth.area_constraints,
+                bend_constraints : &mut self.cloth.bend_constraints,
+                dihedral_constraints : &mut self.cloth.dihedral_constraints,
+                contacts : &mut [],
+                contact_distance : 0.0,
+                collider_contacts : &mut [],
+                colliders : None,
+                weight : None,
+                anchors : &mut [],
+                observers : &mut [],
+            }
+        }
+
+        fn whole_step(&mut self, solver : &mut dyn Solver, params : &SolverParams)
+        {
+            self.integrate(params);
+            let mut scratch = std::mem::take(&mut self.scratch);
+            scratch.resize(self.cloth.positions.len());
+            let mut state = self.state();
+            solver.solve(&mut state, params, &mut scratch);
+            solver::decay_idle_lambdas(&mut state, params);
+            self.scratch = scratch;
+        }
+
+        // The same step a phase at a time, as the phase stepper runs it.
+        fn phased_step(&mut self, solver : &mut dyn Solver, params : &SolverParams)
+        {
+            let mut phase = Some(StepPhase::Integration);
+            while let Some(current) = phase {
+                let mut scratch = std::mem::take(&mut self.scratch);
+                if current.begins_solve(params.max_iterations()) {
+                    scratch.resize(self.cloth.positions.len());
+                }
+                match current {
+                    StepPhase::Integration => self.integrate(params),
+                    StepPhase::Detection => {}
+                    StepPhase::Project(iteration) => solver.project(&mut self.state(), params, &mut scratch, iteration),
+                    StepPhase::Apply(iteration) => solver.apply(&mut self.state(), params, &mut scratch, iteration),
+                    StepPhase::Finish => solver::decay_idle_lambdas(&mut self.state(), params),
+                }
+                self.scratch = scratch;
+                phase = current.next(params.max_iterations());
+            }
+        }
+
+        fn bits(&self) -> Vec<u32>
+        {
+            let lambdas = self.cloth.constraints.iter().chain(self.cloth.bend_constraints.iter()).map(|c| c.lambda);
+            let scalars = self.cloth.area_constraints.iter().map(|c| c.lambda).chain(self.cloth.dihedral_constraints.iter().map(|c| c.lambda));
+            self.cloth.positions.iter().chain(self.previous_positions.iter()).cloned().chain(lambdas)
+                .flat_map(|v| vec![v.x, v.y, v.z]).chain(scalars).map(f32::to_bits).collect()
+        }
+    }
+
+    fn params(iterations : i32) -> SolverParams
+    {
+        SolverParams {
+            dt : 1.0 / 60.0,
+            num_iterations : iterations,
+            sheet_iterations : vec![iterations],
+            sheet_warm_start : vec![true],
+            warm_start : true,
+            eta : 0.9,
+            adaptive_eta : 0.5,
+            stiffness : 5000.0,
+            tension_only : false,
+            use_area_constraints : true,
+            area_stiffness : 2000.0,
+            bend_model : BendModel::Dihedral,
+            bend_stiffness : 50.0,
+            pin_stiffness : 5000.0,
+            lambda_limit : Some(0.5),
+        }
+    }
+
+    #[test]
+    fn a_step_run_phase_by_phase_matches_a_whole_step_bit_for_bit()
+    {
+        for &iterations in &[0, 1, 5] {
+            for (mut whole_solver, mut phased_solver) in solver::registry().into_iter().zip(solver::registry()) {
+                let (mut whole, mut phased) = (Sim::new(), Sim::new());
+                // Iterations drop to zero and come back partway, so the idle decay is crossed too.
+                for step in 0..20 {
+                    let params = params(if step % 7 == 6 {0} else {iterations});
+                    whole.whole_step(whole_solver.as_mut(), &params);
+                    phased.phased_step(phased_solver.as_mut(), &params);
+                    assert!(whole.bits() == phased.bits(), "{} with {} iterations split at step {}", whole_solver.name(), iterations, step);
+                }
+            }
+        }
+    }
+}
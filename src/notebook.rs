@@ -0,0 +1,190 @@
+use log::error;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{IdbDatabase, IdbObjectStoreParameters, IdbOpenDbRequest, IdbRequest, IdbTransactionMode};
+use yew::Callback;
+use crate::params::Params;
+
+const DATABASE : &str = "warmstart";
+const STORE : &str = "notebook";
+
+// Past this many records the oldest are deleted as new ones come in.
+pub const MAX_ENTRIES : usize = 300;
+
+// A summary of how a run went, taken when it was logged.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct NotebookMetrics
+{
+    pub steps : i32,
+    pub kinetic_energy : f32,
+    pub potential_energy : f32,
+    pub pops : u32,
+    pub wrinkle_energy : Option<f32>,
+    // The mean distance residual after the first iteration over the last timeline run.
+    #[serde(default)]
+    pub first_iteration_residual : Option<f32>,
+}
+
+// One logged run: everything needed to set it up again, what came of it, and a note.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct NotebookEntry
+{
+    // Given by the database when the entry is stored.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id : Option<u32>,
+    // Milliseconds since the epoch.
+    pub timestamp : f64,
+    pub params : Params,
+    // The timeline that was loaded, if any.
+    pub scenario : Option<String>,
+    pub seed : u64,
+    pub metrics : NotebookMetrics,
+    pub note : String,
+}
+
+// The orders the notebook panel can list entries in.
+#[derive(Clone, Copy, PartialEq)]
+pub enum NotebookSort
+{
+    Newest,
+    Oldest,
+    Energy,
+    Pops,
+    Steps,
+}
+
+pub const NOTEBOOK_SORTS : [NotebookSort; 5] = [NotebookSort::Newest, NotebookSort::Oldest, NotebookSort::Energy, NotebookSort::Pops, NotebookSort::Steps];
+
+impl NotebookSort {
+    pub fn name(&self) -> &'static str
+    {
+        match self {
+            NotebookSort::Newest => "newest",
+            NotebookSort::Oldest => "oldest",
+            NotebookSort::Energy => "lowest energy",
+            NotebookSort::Pops => "fewest pops",
+            NotebookSort::Steps => "most steps",
+        }
+    }
+
+    pub fn from_name(name : &str) -> Option<NotebookSort>
+    {
+        NOTEBOOK_SORTS.iter().copied().find(|s| s.name() == name)
+    }
+
+    // The entries whose note or scenario contains filter, in this order.
+    pub fn sorted<'a>(&self, entries : &'a [NotebookEntry], filter : &str) -> Vec<&'a NotebookEntry>
+    {
+        let filter = filter.to_lowercase();
+        let mut sorted : Vec<&NotebookEntry> = entries.iter().filter(|e| {
+            filter.is_empty() || e.note.to_lowercase().contains(&filter) || e.scenario.as_ref().map_or(false, |s| s.to_lowercase().contains(&filter))
+        }).collect();
+        let energy = |e : &NotebookEntry| e.metrics.kinetic_energy + e.metrics.potential_energy;
+        match self {
+            NotebookSort::Newest => sorted.reverse(),
+            NotebookSort::Oldest => {}
+            NotebookSort::Energy => sorted.sort_by(|a, b| energy(a).partial_cmp(&energy(b)).unwrap_or(std::cmp::Ordering::Equal)),
+            NotebookSort::Pops => sorted.sort_by_key(|e| e.metrics.pops),
+            NotebookSort::Steps => sorted.sort_by_key(|e| std::cmp::Reverse(e.metrics.steps)),
+        }
+        sorted
+    }
+}
+
+fn describe(error : JsValue) -> String
+{
+    error.as_string().unwrap_or_else(|| format!("{:?}", error))
+}
+
+// Calls done once request finishes, whether it succeeded or not. A single closure serves both
+// events, so it is always called exactly once and freed after.
+fn on_finish(request : &IdbRequest, done : impl FnOnce(Result<JsValue, String>) + 'static)
+{
+    let handle = request.clone();
+    let handler = Closure::once_into_js(move |_event : JsValue| {
+        let result = match handle.error() {
+            Ok(Some(error)) => Err(error.message()),
+            _ => handle.result().map_err(describe),
+        };
+        done(result);
+    });
+    request.set_onsuccess(Some(handler.unchecked_ref()));
+    request.set_onerror(Some(handler.unchecked_ref()));
+}
+
+// Opens the notebook's database, creating its store on first use.
+pub fn open(done : Callback<Result<IdbDatabase, String>>)
+{
+    let request : Result<IdbOpenDbRequest, String> = (|| {
+        let factory = web_sys::window().ok_or("no window")?.indexed_db().map_err(describe)?.ok_or("IndexedDB is unavailable")?;
+        factory.open_with_u32(DATABASE, 1).map_err(describe)
+    })();
+    let request = match request {
+        Ok(request) => request,
+        Err(e) => return done.emit(Err(e)),
+    };
+
+    let handle = request.clone();
+    let upgrade = Closure::once_into_js(move |_event : JsValue| {
+        if let Ok(db) = handle.result().and_then(|db| db.dyn_into::<IdbDatabase>().map_err(JsValue::from)) {
+            let parameters = IdbObjectStoreParameters::new();
+            parameters.set_key_path(&JsValue::from_str("id"));
+            parameters.set_auto_increment(true);
+            if let Err(e) = db.create_object_store_with_optional_parameters(STORE, &parameters) {
+                error!("Failed to create the notebook store: {}", describe(e));
+            }
+        }
+    });
+    request.set_onupgradeneeded(Some(upgrade.unchecked_ref()));
+
+    on_finish(&request, move |result| {
+        done.emit(result.and_then(|db| db.dyn_into::<IdbDatabase>().map_err(describe)));
+    });
+}
+
+fn store(db : &IdbDatabase, mode : IdbTransactionMode) -> Result<web_sys::IdbObjectStore, String>
+{
+    db.transaction_with_str_and_mode(STORE, mode).and_then(|t| t.object_store(STORE)).map_err(describe)
+}
+
+pub fn add(db : &IdbDatabase, entry : &NotebookEntry, done : Callback<Result<(), String>>)
+{
+    let request = serde_json::to_string(entry).map_err(|e| e.to_string())
+        .and_then(|json| js_sys::JSON::parse(&json).map_err(describe))
+        .and_then(|value| store(db, IdbTransactionMode::Readwrite)?.add(&value).map_err(describe));
+    match request {
+        Ok(request) => on_finish(&request, move |result| done.emit(result.map(|_| ()))),
+        Err(e) => done.emit(Err(e)),
+    }
+}
+
+pub fn delete(db : &IdbDatabase, id : u32, done : Callback<Result<(), String>>)
+{
+    match store(db, IdbTransactionMode::Readwrite).and_then(|s| s.delete(&JsValue::from(id)).map_err(describe)) {
+        Ok(request) => on_finish(&request, move |result| done.emit(result.map(|_| ()))),
+        Err(e) => done.emit(Err(e)),
+    }
+}
+
+// Every stored entry, oldest first. Entries that no longer parse are skipped.
+pub fn load_all(db : &IdbDatabase, done : Callback<Result<Vec<NotebookEntry>, String>>)
+{
+    match store(db, IdbTransactionMode::Readonly).and_then(|s| s.get_all().map_err(describe)) {
+        Ok(request) => on_finish(&request, move |result| {
+            done.emit(result.map(|values| {
+                js_sys::Array::from(&values).iter()
+                    .filter_map(|value| js_sys::JSON::stringify(&value).ok()?.as_string())
+                    .filter_map(|json| serde_json::from_str(&json).ok())
+                    .collect()
+            }));
+        }),
+        Err(e) => done.emit(Err(e)),
+    }
+}
+
+// The whole notebook as one JSON array, for download.
+pub fn to_json(entries : &[NotebookEntry]) -> String
+{
+    serde_json::to_string_pretty(entries).unwrap_or_else(|_| "[]".to_string())
+}
@@ -0,0 +1,231 @@
+use glam::*;
+use std::collections::{HashMap, HashSet};
+
+// More than this and every step would take longer than a frame.
+pub const MAX_VERTICES : usize = 20000;
+
+// A polygon mesh read from a Wavefront OBJ file. Only vertex positions and faces are read;
+// texture coordinates, normals, groups and materials are skipped.
+pub struct ObjMesh
+{
+    pub positions : Vec<Vec3>,
+    // Each face's vertices in order, three or more of them.
+    pub faces : Vec<Vec<usize>>,
+}
+
+// A face vertex is v, v/vt, v//vn or v/vt/vn. Indices count from 1, or back from the latest
+// vertex when negative.
+fn vertex_index(token : &str, num_vertices : usize) -> Result<usize, String>
+{
+    let index : i64 = token.split('/').next().unwrap_or("").parse().map_err(|_| format!("bad vertex reference {}", token))?;
+    let resolved = if index < 0 {num_vertices as i64 + index} else {index - 1};
+    if index == 0 || resolved < 0 || resolved >= num_vertices as i64 {
+        return Err(format!("vertex {} doesn't exist, there are {}", index, num_vertices));
+    }
+    Ok(resolved as usize)
+}
+
+impl ObjMesh {
+    pub fn parse(text : &str) -> Result<ObjMesh, String>
+    {
+        let mut mesh = ObjMesh { positions : vec![], faces : vec![] };
+        for (number, line) in text.lines().enumerate() {
+            let error = |e : String| format!("line {}: {}", number + 1, e);
+            let mut tokens = line.split('#').next().unwrap_or("").split_whitespace();
+            match tokens.next() {
+                Some("v") => {
+                    let coordinates = tokens.take(3).map(|t| t.parse::<f32>().ok().filter(|x| x.is_finite())).collect::<Option<Vec<f32>>>();
+                    match coordinates {
+                        Some(c) if c.len() == 3 => mesh.positions.push(vec3(c[0], c[1], c[2])),
+                        _ => return Err(error("a vertex needs three finite coordinates".to_string())),
+                    }
+                    if mesh.positions.len() > MAX_VERTICES {
+                        return Err(format!("more than {} vertices", MAX_VERTICES));
+                    }
+                }
+                Some("f") => {
+                    let face = tokens.map(|t| vertex_index(t, mesh.positions.len())).collect::<Result<Vec<usize>, String>>().map_err(error)?;
+                    if face.len() < 3 {
+                        return Err(error("a face needs at least three vertices".to_string()));
+                    }
+                    if (0..face.len()).any(|k| face[k + 1..].contains(&face[k])) {
+                        return Err(error("a face uses the same vertex twice".to_string()));
+                    }
+                    mesh.faces.push(face);
+                }
+                _ => {}
+            }
+        }
+        if mesh.faces.is_empty() {
+            return Err("no faces".to_string());
+        }
+
+        // Vertices no face uses would only fall away on their own, so they are dropped.
+        let mut remap = vec![None; mesh.positions.len()];
+        let mut positions = vec![];
+        for face in mesh.faces.iter_mut() {
+            for v in face.iter_mut() {
+                let old = *v;
+                *v = match remap[old] {
+                    Some(new) => new,
+                    None => {
+                        positions.push(mesh.positions[old]);
+                        remap[old] = Some(positions.len() - 1);
+                        positions.len() - 1
+                    }
+                };
+            }
+        }
+        mesh.positions = positions;
+
+        if let Some((a, b)) = mesh.edges().into_iter().find(|&(a, b)| (mesh.positions[a] - mesh.positions[b]).length() < 1e-9) {
+            return Err(format!("vertices {} and {} of an edge are in the same place", a + 1, b + 1));
+        }
+        Ok(mesh)
+    }
+
+    // Every face split into triangles fanning out from its first vertex.
+    pub fn triangles(&self) -> Vec<[usize; 3]>
+    {
+        self.faces.iter().flat_map(|f| (1..f.len() - 1).map(move |k| [f[0], f[k], f[k + 1]])).collect()
+    }
+
+    // Each edge of the triangles once, lowest index first, in the order first met.
+    pub fn edges(&self) -> Vec<(usize, usize)>
+    {
+        let mut seen = HashSet::new();
+        let mut edges = vec![];
+        for t in self.triangles() {
+            for k in 0..3 {
+                let edge = (t[k].min(t[(k + 1) % 3]), t[k].max(t[(k + 1) % 3]));
+                if seen.insert(edge) {
+                    edges.push(edge);
+                }
+            }
+        }
+        edges
+    }
+
+    // Vertices on an edge that only one triangle has.
+    pub fn boundary(&self) -> Vec<bool>
+    {
+        let mut uses = HashMap::new();
+        for t in self.triangles() {
+            for k in 0..3 {
+                *uses.entry((t[k].min(t[(k + 1) % 3]), t[k].max(t[(k + 1) % 3]))).or_insert(0) += 1;
+            }
+        }
+        let mut boundary = vec![false; self.positions.len()];
+        for ((a, b), count) in uses {
+            if count == 1 {
+                boundary[a] = true;
+                boundary[b] = true;
+            }
+        }
+        boundary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SQUARE : &str = "# a unit square\nv 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3 4\n";
+
+    // A hexagon fanned into six triangles around a centre vertex.
+    fn hexagon() -> String
+    {
+        let mut text = String::from("v 0 0 0\n");
+        for k in 0..6 {
+            let angle = k as f32 * std::f32::consts::PI / 3.0;
+            text.push_str(&format!("v {} {} 0\n", angle.cos(), angle.sin()));
+        }
+        for k in 0..6 {
+            text.push_str(&format!("f 1 {} {}\n", k + 2, (k + 1) % 6 + 2));
+        }
+        text
+    }
+
+    #[test]
+    fn polygons_are_fanned_into_triangles()
+    {
+        let mesh = ObjMesh::parse(SQUARE).unwrap();
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.triangles(), vec![[0, 1, 2], [0, 2, 3]]);
+        // Four sides and the fan diagonal.
+        assert_eq!(mesh.edges(), vec![(0, 1), (1, 2), (0, 2), (2, 3), (0, 3)]);
+    }
+
+    #[test]
+    fn face_vertex_forms_and_negative_indices()
+    {
+        let text = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvn 0 0 1\nf 1/1 2/1/1 3//1\nf -3 -2 -1\n";
+        let mesh = ObjMesh::parse(text).unwrap();
+        assert_eq!(mesh.faces, vec![vec![0, 1, 2], vec![0, 1, 2]]);
+    }
+
+    #[test]
+    fn comments_and_other_statements_are_skipped()
+    {
+        let text = "o curtain\ng front\nusemtl cloth\nv 0 0 0 # origin\nv 1 0 0\nv 0 1 0\ns off\nf 1 2 3 # the only face\n";
+        let mesh = ObjMesh::parse(text).unwrap();
+        assert_eq!(mesh.positions, vec![vec3(0.0, 0.0, 0.0), vec3(1.0, 0.0, 0.0), vec3(0.0, 1.0, 0.0)]);
+        assert_eq!(mesh.faces.len(), 1);
+    }
+
+    #[test]
+    fn unused_vertices_are_dropped_and_faces_renumbered()
+    {
+        let mesh = ObjMesh::parse("v 9 9 9\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 2 3 4\n").unwrap();
+        assert_eq!(mesh.positions.len(), 3);
+        assert_eq!(mesh.faces, vec![vec![0, 1, 2]]);
+        assert_eq!(mesh.positions[0], vec3(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn only_the_rim_is_boundary()
+    {
+        let mesh = ObjMesh::parse(&hexagon()).unwrap();
+        let boundary = mesh.boundary();
+        assert!(!boundary[0]);
+        assert!(boundary[1..].iter().all(|&b| b));
+        assert_eq!(mesh.edges().len(), 12);
+    }
+
+    #[test]
+    fn malformed_files_are_rejected()
+    {
+        let bad = [
+            ("v 0 0\nf 1 1 1\n", "three finite coordinates"),
+            ("v 0 0 nan\n", "three finite coordinates"),
+            ("v 0 0 0\nv 1 0 0\nf 1 2\n", "at least three"),
+            ("v 0 0 0\nv 1 0 0\nf 1 2 3\n", "doesn't exist"),
+            ("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 0 1 2\n", "doesn't exist"),
+            ("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 x\n", "bad vertex reference"),
+            ("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 1\n", "same vertex twice"),
+            ("v 0 0 0\nv 1 0 0\n", "no faces"),
+            ("", "no faces"),
+            ("v 0 0 0\nv 0 0 0\nv 0 1 0\nf 1 2 3\n", "same place"),
+        ];
+        for &(text, message) in bad.iter() {
+            match ObjMesh::parse(text) {
+                Ok(_) => panic!("{:?} was accepted", text),
+                Err(e) => assert!(e.contains(message), "{:?} gave {:?}", text, e),
+            }
+        }
+    }
+
+    #[test]
+    fn errors_name_the_line()
+    {
+        let e = ObjMesh::parse("v 0 0 0\nv 1 0 0\n\nf 1 2 7\n").err().unwrap();
+        assert!(e.starts_with("line 4:"), "{}", e);
+    }
+
+    #[test]
+    fn too_many_vertices_are_rejected()
+    {
+        let text = "v 0 0 0\n".repeat(MAX_VERTICES + 1);
+        assert!(ObjMesh::parse(&text).err().unwrap().contains("vertices"));
+    }
+}
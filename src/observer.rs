@@ -0,0 +1,62 @@
+use glam::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+use crate::cloth::Constraint;
+use crate::solver::SolverParams;
+
+// The cloth as a step leaves it, collider response included.
+pub struct StepStats<'a>
+{
+    pub positions : &'a [Vec3],
+    pub constraints : &'a [Constraint],
+    pub params : &'a SolverParams,
+}
+
+// Hooks into fixed points of a step, so diagnostics can watch the simulation without code of
+// their own in the solver. Every method does nothing by default. The model keeps a list of
+// observers, and the step and solver skip the calls altogether while it is empty.
+pub trait SimulationObserver
+{
+    fn on_step_begin(&mut self, _step : i32) {}
+
+    // Positions as the solver gets them, after prediction and collisions.
+    fn on_solve_begin(&mut self, _positions : &[Vec3]) {}
+
+    // A structural distance constraint whose correction was applied straight to positions, as
+    // Gauss-Seidel does. Corrections gathered into a workspace are not reported.
+    fn on_constraint_projected(&mut self, _iteration : i32, _index : usize, _constraint : &Constraint, _d0 : Vec3, _d1 : Vec3) {}
+
+    // Positions and constraints as the iteration left them.
+    fn on_iteration_end(&mut self, _iteration : i32, _positions : &[Vec3], _constraints : &[Constraint]) {}
+
+    fn on_step_end(&mut self, _stats : &StepStats) {}
+}
+
+// A shared observer, for ones the model also reads from, like the recorder it exports.
+impl<T : SimulationObserver> SimulationObserver for Rc<RefCell<T>>
+{
+    fn on_step_begin(&mut self, step : i32)
+    {
+        self.borrow_mut().on_step_begin(step);
+    }
+
+    fn on_solve_begin(&mut self, positions : &[Vec3])
+    {
+        self.borrow_mut().on_solve_begin(positions);
+    }
+
+    fn on_constraint_projected(&mut self, iteration : i32, index : usize, constraint : &Constraint, d0 : Vec3, d1 : Vec3)
+    {
+        self.borrow_mut().on_constraint_projected(iteration, index, constraint, d0, d1);
+    }
+
+    fn on_iteration_end(&mut self, iteration : i32, positions : &[Vec3], constraints : &[Constraint])
+    {
+        self.borrow_mut().on_iteration_end(iteration, positions, constraints);
+    }
+
+    fn on_step_end(&mut self, stats : &StepStats)
+    {
+        self.borrow_mut().on_step_end(stats);
+    }
+}
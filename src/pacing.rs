@@ -0,0 +1,182 @@
+use std::collections::{HashMap, VecDeque};
+
+// What to do on a display that refreshes faster than the physics steps, where most frames have no
+// new step to show.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum HighRefreshPolicy
+{
+    // Draw every frame, blending between the last two steps.
+    Interpolate,
+    // Only draw frames that have something new to show.
+    SkipDraws,
+    // Redraw the latest step every frame.
+    Off,
+}
+
+pub const HIGH_REFRESH_POLICIES : [HighRefreshPolicy; 3] = [HighRefreshPolicy::Interpolate, HighRefreshPolicy::SkipDraws, HighRefreshPolicy::Off];
+
+impl HighRefreshPolicy {
+    pub fn name(&self) -> &'static str
+    {
+        match self {
+            HighRefreshPolicy::Interpolate => "interpolate",
+            HighRefreshPolicy::SkipDraws => "skip",
+            HighRefreshPolicy::Off => "off",
+        }
+    }
+
+    pub fn from_name(name : &str) -> Option<HighRefreshPolicy>
+    {
+        HIGH_REFRESH_POLICIES.iter().copied().find(|p| p.name() == name)
+    }
+}
+
+// Frame intervals kept for the histogram, and how many it needs before it trusts the mode.
+const NUM_INTERVALS : usize = 120;
+const MIN_INTERVALS : usize = 30;
+// Histogram bucket width in milliseconds.
+const BUCKET_MS : f64 = 0.5;
+// Longer gaps are a hidden tab or a breakpoint rather than the display.
+const MAX_INTERVAL_MS : f64 = 250.0;
+pub const MAX_STEPS_PER_FRAME : u32 = 8;
+
+// Works out the display's refresh rate from recent frame intervals, and from that how physics steps
+// and draws are paced. The thresholds are public so they can be overridden for testing.
+pub struct FramePacing
+{
+    intervals : VecDeque<f64>,
+    detected_hz : Option<f32>,
+    pub policy : HighRefreshPolicy,
+    // The display counts as high refresh once it outpaces the physics steps by this factor.
+    pub high_refresh_ratio : f32,
+    // Displays slower than this run several steps a frame to keep physics at this rate.
+    pub min_physics_hz : f32,
+    // Used instead of the detected rate when set.
+    pub forced_hz : Option<f32>,
+}
+
+impl FramePacing {
+    pub fn new() -> FramePacing
+    {
+        FramePacing {
+            intervals : VecDeque::with_capacity(NUM_INTERVALS),
+            detected_hz : None,
+            policy : HighRefreshPolicy::Interpolate,
+            high_refresh_ratio : 1.5,
+            min_physics_hz : 60.0,
+            forced_hz : None,
+        }
+    }
+
+    // Applies pacing=, refresh_hz=, high_refresh_ratio= and min_physics_hz= query parameters.
+    // Unrecognised values are ignored.
+    pub fn override_from_query(&mut self, search : &str)
+    {
+        for pair in search.trim_start_matches('?').split('&') {
+            let mut parts = pair.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some("pacing"), Some(value)) => {
+                    if let Some(policy) = HighRefreshPolicy::from_name(value) {
+                        self.policy = policy;
+                    }
+                }
+                (Some("refresh_hz"), Some(value)) => self.forced_hz = value.parse().ok().filter(|&hz : &f32| hz > 0.0),
+                (Some("high_refresh_ratio"), Some(value)) => {
+                    if let Ok(ratio) = value.parse() {
+                        self.high_refresh_ratio = ratio;
+                    }
+                }
+                (Some("min_physics_hz"), Some(value)) => {
+                    if let Ok(hz) = value.parse() {
+                        self.min_physics_hz = hz;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub fn add_interval(&mut self, ms : f64)
+    {
+        if ms <= 0.0 || ms > MAX_INTERVAL_MS {
+            return;
+        }
+        if self.intervals.len() == NUM_INTERVALS {
+            self.intervals.pop_front();
+        }
+        self.intervals.push_back(ms);
+        self.detected_hz = self.detect();
+    }
+
+    // The rate of the most common interval. Dropped frames land at multiples of it, so they don't
+    // drag the estimate down the way they would a mean. Ties go to the shorter interval.
+    fn detect(&self) -> Option<f32>
+    {
+        if self.intervals.len() < MIN_INTERVALS {
+            return None;
+        }
+        let bucket = |ms : f64| (ms / BUCKET_MS).round() as i64;
+        let mut counts : HashMap<i64, usize> = HashMap::new();
+        for &ms in self.intervals.iter() {
+            *counts.entry(bucket(ms)).or_insert(0) += 1;
+        }
+        let mode = counts.iter().max_by_key(|&(&b, &count)| (count, -b)).map(|(&b, _)| b)?;
+
+        // Average the intervals in and next to the mode's bucket for a finer estimate.
+        let near : Vec<f64> = self.intervals.iter().copied().filter(|&ms| (bucket(ms) - mode).abs() <= 1).collect();
+        let mean = near.iter().sum::<f64>() / near.len() as f64;
+        Some((1000.0 / mean) as f32)
+    }
+
+    pub fn refresh_hz(&self) -> Option<f32>
+    {
+        self.forced_hz.or(self.detected_hz)
+    }
+
+    // Whether most frames fall between physics steps of length dt.
+    pub fn is_high_refresh(&self, dt : f32) -> bool
+    {
+        self.refresh_hz().map_or(false, |hz| hz * dt > self.high_refresh_ratio)
+    }
+
+    pub fn interpolates(&self, dt : f32) -> bool
+    {
+        self.policy == HighRefreshPolicy::Interpolate && self.is_high_refresh(dt)
+    }
+
+    pub fn skips_draws(&self, dt : f32) -> bool
+    {
+        self.policy == HighRefreshPolicy::SkipDraws && self.is_high_refresh(dt)
+    }
+
+    // Steps to run on a frame that steps at all.
+    pub fn steps_per_frame(&self) -> u32
+    {
+        match self.refresh_hz() {
+            Some(hz) if hz < self.min_physics_hz => ((self.min_physics_hz / hz).ceil() as u32).min(MAX_STEPS_PER_FRAME),
+            _ => 1,
+        }
+    }
+
+    // The detected rate and what is being done about it, for the stats panel.
+    pub fn summary(&self, dt : f32) -> String
+    {
+        let hz = match self.refresh_hz() {
+            Some(hz) => hz,
+            None => return "Display: measuring refresh rate".to_string(),
+        };
+        let policy = if self.is_high_refresh(dt) {
+            match self.policy {
+                HighRefreshPolicy::Interpolate => "interpolating between steps".to_string(),
+                HighRefreshPolicy::SkipDraws => "skipping draws between steps".to_string(),
+                HighRefreshPolicy::Off => "redrawing every frame".to_string(),
+            }
+        } else {
+            match self.steps_per_frame() {
+                1 => "one step per frame".to_string(),
+                n => format!("{} steps per frame", n),
+            }
+        };
+        format!("Display: {:.0} Hz{}, {}", hz, if self.forced_hz.is_some() {" (forced)"} else {""}, policy)
+    }
+}
@@ -0,0 +1,105 @@
+use crate::color_scale;
+
+// Every color drawn on the canvas. Anything that renders should take its colors from the active
+// palette rather than hardcoding them, so switching palettes recolors the whole view.
+pub struct Palette
+{
+    pub name : &'static str,
+    pub clear : [f32; 4],
+    pub cloth : [f32; 3],
+    pub weight : [f32; 3],
+    pub collider : [f32; 3],
+    // Edges that tripped the strain alarm.
+    pub alarm : [f32; 3],
+    // The constraint picked in the inspector.
+    pub probe : [f32; 3],
+    // Frozen particles and the box being dragged to freeze more.
+    pub frozen : [f32; 3],
+    // Selected particles and the rubber band being dragged to select them.
+    pub selected : [f32; 3],
+    // A recorded run laid over the live one, pale against the background as if seen through.
+    pub ghost : [f32; 3],
+    // Low, middle and high stops of the ramp used for per-particle and per-constraint values.
+    pub ramp : [[f32; 3]; 3],
+}
+
+impl Palette {
+    // The ramp at t in [0, 1], blended between the stops as any color scale is.
+    pub fn ramp_color(&self, t : f32) -> [f32; 3]
+    {
+        color_scale::evaluate(&color_scale::palette_stops(self), t)
+    }
+}
+
+pub const PALETTES : [Palette; 3] = [
+    Palette {
+        name : "Default",
+        clear : [1.0, 1.0, 1.0, 1.0],
+        cloth : [0.0, 0.0, 0.0],
+        weight : [0.3, 0.3, 0.3],
+        collider : [0.0, 0.3, 0.8],
+        alarm : [1.0, 0.0, 1.0],
+        probe : [0.0, 0.7, 0.0],
+        frozen : [0.4, 0.75, 1.0],
+        selected : [1.0, 0.5, 0.0],
+        ghost : [0.6, 0.75, 0.95],
+        ramp : [[0.0, 0.2, 0.9], [0.6, 0.6, 0.6], [0.9, 0.1, 0.0]],
+    },
+    // Okabe-Ito colors, which stay distinguishable under the common color vision deficiencies.
+    Palette {
+        name : "Colorblind Safe",
+        clear : [1.0, 1.0, 1.0, 1.0],
+        cloth : [0.0, 0.45, 0.7],
+        weight : [0.8, 0.4, 0.0],
+        collider : [0.9, 0.6, 0.0],
+        // Vermilion.
+        alarm : [0.835, 0.369, 0.0],
+        // Bluish green.
+        probe : [0.0, 0.62, 0.45],
+        // Sky blue.
+        frozen : [0.337, 0.706, 0.914],
+        // Reddish purple.
+        selected : [0.8, 0.475, 0.655],
+        // Orange, paled.
+        ghost : [0.98, 0.85, 0.6],
+        // Viridis end and middle points.
+        ramp : [[0.267, 0.005, 0.329], [0.128, 0.567, 0.551], [0.993, 0.906, 0.144]],
+    },
+    // Light on black, for projectors that wash out thin dark lines.
+    Palette {
+        name : "High Contrast",
+        clear : [0.0, 0.0, 0.0, 1.0],
+        cloth : [1.0, 1.0, 1.0],
+        weight : [1.0, 1.0, 0.0],
+        collider : [0.0, 1.0, 1.0],
+        alarm : [1.0, 0.0, 1.0],
+        probe : [0.0, 1.0, 0.0],
+        frozen : [0.6, 0.8, 1.0],
+        selected : [1.0, 0.5, 0.0],
+        ghost : [0.45, 0.4, 0.1],
+        ramp : [[0.0, 0.6, 1.0], [1.0, 1.0, 1.0], [1.0, 0.8, 0.0]],
+    },
+];
+
+const STORAGE_KEY : &str = "warmstart.palette";
+
+fn storage() -> Option<web_sys::Storage>
+{
+    web_sys::window()?.local_storage().ok()?
+}
+
+// The palette chosen in an earlier session, or the default one.
+pub fn saved_index() -> usize
+{
+    storage()
+        .and_then(|s| s.get_item(STORAGE_KEY).ok().flatten())
+        .and_then(|name| PALETTES.iter().position(|p| p.name == name))
+        .unwrap_or(0)
+}
+
+pub fn save(index : usize)
+{
+    if let Some(s) = storage() {
+        let _ = s.set_item(STORAGE_KEY, PALETTES[index].name);
+    }
+}
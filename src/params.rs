@@ -0,0 +1,502 @@
+use log::warn;
+use serde::{Deserialize, Serialize};
+use crate::cloth::{BendModel, Connectivity, Scene};
+use crate::pacing::MAX_STEPS_PER_FRAME;
+
+// Every setting that shapes the simulation, so presets and scripts can set any number of them
+// at once through Params::apply.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Params
+{
+    pub dt : f32,
+    pub num_iterations : i32,
+    pub solver_index : usize,
+    pub eta : f32,
+    // How quickly eta falls off for constraints whose particles moved fast in the step, per unit
+    // of motion. 0 uses eta everywhere.
+    #[serde(default)]
+    pub adaptive_eta : f32,
+    // How far each stored distance lambda is smoothed towards its neighbours' before the warm
+    // start. 0 leaves them as stored.
+    #[serde(default)]
+    pub lambda_diffusion : f32,
+    pub nu : f32,
+    pub stiffness : f32,
+    // Which way gravity pulls, in degrees anticlockwise from straight down, and how many seconds
+    // it takes to turn a full circle from there. A period of 0 holds it still.
+    #[serde(default)]
+    pub gravity_angle : f32,
+    #[serde(default)]
+    pub gravity_period : f32,
+    pub warm_start : bool,
+    // Start a hanging sheet's vertical constraints from the tension they settle to on reset.
+    pub analytic_seed : bool,
+    pub tension_only : bool,
+    pub use_area_constraints : bool,
+    pub area_stiffness : f32,
+    pub bend_model : BendModel,
+    pub bend_stiffness : f32,
+    // Multiplies the stiffness of constraints with both ends on the boundary.
+    pub stiffen_perimeter : bool,
+    pub perimeter_stiffness : f32,
+    pub weight_mass : f32,
+    // Hold pinned particles by stiff springs rather than fixing them.
+    pub soft_pins : bool,
+    pub pin_stiffness : f32,
+    // The inverse mass each fixed pin is given, as a fraction of a free particle's. 0 holds pins
+    // still; a little more lets them give under extreme loads, like a curtain's heavy rings.
+    #[serde(default)]
+    pub pin_inverse_mass : f32,
+    // Cap every distance constraint's stored impulse at what could plausibly stop the whole
+    // cloth in one step, times the safety factor, to catch a single constraint running away.
+    #[serde(default)]
+    pub lambda_clamp : bool,
+    #[serde(default = "default_clamp_safety")]
+    pub clamp_safety : f32,
+    // Solve contacts between sheets, where the scene has more than one.
+    #[serde(default = "default_self_collision")]
+    pub self_collision : bool,
+    // The most steps a slow display may take in a frame to keep physics up to speed.
+    #[serde(default = "default_max_substeps")]
+    pub max_substeps : u32,
+    pub scene : Scene,
+    pub connectivity : Connectivity,
+    // Joins the hanging sheet's side edges into a tube.
+    pub wrap_x : bool,
+    pub num_particles_x : i32,
+    pub num_particles_y : i32,
+}
+
+// The fields to change, leaving the rest as they are.
+#[derive(Clone, Default)]
+pub struct ParamsDelta
+{
+    pub dt : Option<f32>,
+    pub num_iterations : Option<i32>,
+    pub solver_index : Option<usize>,
+    pub eta : Option<f32>,
+    pub adaptive_eta : Option<f32>,
+    pub lambda_diffusion : Option<f32>,
+    pub nu : Option<f32>,
+    pub stiffness : Option<f32>,
+    pub gravity_angle : Option<f32>,
+    pub gravity_period : Option<f32>,
+    pub warm_start : Option<bool>,
+    pub analytic_seed : Option<bool>,
+    pub tension_only : Option<bool>,
+    pub use_area_constraints : Option<bool>,
+    pub area_stiffness : Option<f32>,
+    pub bend_model : Option<BendModel>,
+    pub bend_stiffness : Option<f32>,
+    pub stiffen_perimeter : Option<bool>,
+    pub perimeter_stiffness : Option<f32>,
+    pub weight_mass : Option<f32>,
+    pub soft_pins : Option<bool>,
+    pub pin_stiffness : Option<f32>,
+    pub pin_inverse_mass : Option<f32>,
+    pub lambda_clamp : Option<bool>,
+    pub clamp_safety : Option<f32>,
+    pub self_collision : Option<bool>,
+    pub max_substeps : Option<u32>,
+    pub scene : Option<Scene>,
+    pub connectivity : Option<Connectivity>,
+    pub wrap_x : Option<bool>,
+    pub num_particles_x : Option<i32>,
+    pub num_particles_y : Option<i32>,
+}
+
+// What the model has to do after a field changes.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Effect
+{
+    // Read afresh every step. Stiffness changes belong here: a stored lambda is a force times
+    // dt squared, and the force a constraint carries doesn't depend on how stiff it is.
+    Nothing,
+    // Stored lambdas stay but are rescaled to the new time step.
+    RescaleLambda,
+    // A constraint family was switched on or off, so its lambdas are dropped and the valences
+    // recounted.
+    Topology,
+    // Stored lambdas no longer mean the same thing and are all dropped.
+    CleanLambda,
+    // The grid changes size, keeping the running state if resizes preserve it.
+    Resize,
+    // The cloth is rebuilt from scratch.
+    Reset,
+}
+
+fn set<T : PartialEq + Copy>(field : &mut T, value : Option<T>, name : &'static str, effect : Effect, changes : &mut Vec<(&'static str, Effect)>)
+{
+    if let Some(value) = value {
+        if *field != value {
+            *field = value;
+            changes.push((name, effect));
+        }
+    }
+}
+
+// The settings the page starts with.
+impl Default for Params {
+    fn default() -> Params
+    {
+        Params {
+            dt : 1.0 / 60.0,
+            num_iterations : 2,
+            solver_index : 1,
+            eta : 1.0f32,
+            adaptive_eta : 0.0,
+            lambda_diffusion : 0.0,
+            nu : 0.6f32,
+            stiffness : 5000.0f32,
+            gravity_angle : 0.0,
+            gravity_period : 0.0,
+            warm_start : true,
+            analytic_seed : false,
+            tension_only : false,
+            use_area_constraints : false,
+            area_stiffness : 5000.0f32,
+            bend_model : BendModel::None,
+            bend_stiffness : 100.0f32,
+            stiffen_perimeter : false,
+            perimeter_stiffness : 2.0f32,
+            weight_mass : 1.0f32,
+            soft_pins : false,
+            pin_stiffness : 1e6f32,
+            pin_inverse_mass : 0.0,
+            lambda_clamp : false,
+            clamp_safety : default_clamp_safety(),
+            self_collision : default_self_collision(),
+            max_substeps : default_max_substeps(),
+            scene : Scene::Hanging,
+            connectivity : Connectivity::Eight,
+            wrap_x : false,
+            num_particles_x : 10,
+            num_particles_y : 10,
+        }
+    }
+}
+
+impl Params {
+    // Every field, for putting these params back wholesale through apply.
+    pub fn delta(&self) -> ParamsDelta
+    {
+        ParamsDelta {
+            dt : Some(self.dt),
+            num_iterations : Some(self.num_iterations),
+            solver_index : Some(self.solver_index),
+            eta : Some(self.eta),
+            adaptive_eta : Some(self.adaptive_eta),
+            lambda_diffusion : Some(self.lambda_diffusion),
+            nu : Some(self.nu),
+            stiffness : Some(self.stiffness),
+            gravity_angle : Some(self.gravity_angle),
+            gravity_period : Some(self.gravity_period),
+            warm_start : Some(self.warm_start),
+            analytic_seed : Some(self.analytic_seed),
+            tension_only : Some(self.tension_only),
+            use_area_constraints : Some(self.use_area_constraints),
+            area_stiffness : Some(self.area_stiffness),
+            bend_model : Some(self.bend_model),
+            bend_stiffness : Some(self.bend_stiffness),
+            stiffen_perimeter : Some(self.stiffen_perimeter),
+            perimeter_stiffness : Some(self.perimeter_stiffness),
+            weight_mass : Some(self.weight_mass),
+            soft_pins : Some(self.soft_pins),
+            pin_stiffness : Some(self.pin_stiffness),
+            pin_inverse_mass : Some(self.pin_inverse_mass),
+            lambda_clamp : Some(self.lambda_clamp),
+            clamp_safety : Some(self.clamp_safety),
+            self_collision : Some(self.self_collision),
+            max_substeps : Some(self.max_substeps),
+            scene : Some(self.scene),
+            connectivity : Some(self.connectivity),
+            wrap_x : Some(self.wrap_x),
+            num_particles_x : Some(self.num_particles_x),
+            num_particles_y : Some(self.num_particles_y),
+        }
+    }
+
+    // Applies delta and returns the fields that actually changed, each with its effect. This is
+    // the one place a field's effect is decided.
+    pub fn apply(&mut self, delta : &ParamsDelta) -> Vec<(&'static str, Effect)>
+    {
+        let mut changes = vec![];
+        set(&mut self.dt, delta.dt, "dt", Effect::RescaleLambda, &mut changes);
+        set(&mut self.num_iterations, delta.num_iterations.map(|n| n.max(0).min(MAX_ITERATIONS)), "num_iterations", Effect::Nothing, &mut changes);
+        set(&mut self.solver_index, delta.solver_index, "solver_index", Effect::CleanLambda, &mut changes);
+        set(&mut self.eta, delta.eta, "eta", Effect::Nothing, &mut changes);
+        set(&mut self.adaptive_eta, delta.adaptive_eta.map(|f| f.max(0.0)), "adaptive_eta", Effect::Nothing, &mut changes);
+        set(&mut self.lambda_diffusion, delta.lambda_diffusion.map(|f| f.max(0.0).min(1.0)), "lambda_diffusion", Effect::Nothing, &mut changes);
+        set(&mut self.nu, delta.nu, "nu", Effect::Nothing, &mut changes);
+        // No rescale for any stiffness: a stored lambda is the constraint's force times dt squared,
+        // and at rest that force balances the load whatever the compliance. Each iteration solves
+        // the new compliance against it, so a stiffer cloth only closes the remaining error faster.
+        set(&mut self.stiffness, delta.stiffness, "stiffness", Effect::Nothing, &mut changes);
+        set(&mut self.gravity_angle, delta.gravity_angle, "gravity_angle", Effect::Nothing, &mut changes);
+        set(&mut self.gravity_period, delta.gravity_period.map(|f| f.max(0.0)), "gravity_period", Effect::Nothing, &mut changes);
+        set(&mut self.warm_start, delta.warm_start, "warm_start", Effect::CleanLambda, &mut changes);
+        set(&mut self.analytic_seed, delta.analytic_seed, "analytic_seed", Effect::Reset, &mut changes);
+        set(&mut self.tension_only, delta.tension_only, "tension_only", Effect::CleanLambda, &mut changes);
+        set(&mut self.use_area_constraints, delta.use_area_constraints, "use_area_constraints", Effect::Topology, &mut changes);
+        set(&mut self.area_stiffness, delta.area_stiffness, "area_stiffness", Effect::Nothing, &mut changes);
+        set(&mut self.bend_model, delta.bend_model, "bend_model", Effect::Topology, &mut changes);
+        set(&mut self.bend_stiffness, delta.bend_stiffness, "bend_stiffness", Effect::Nothing, &mut changes);
+        set(&mut self.stiffen_perimeter, delta.stiffen_perimeter, "stiffen_perimeter", Effect::Topology, &mut changes);
+        set(&mut self.perimeter_stiffness, delta.perimeter_stiffness.map(|f| f.max(1.0)), "perimeter_stiffness", Effect::Topology, &mut changes);
+        set(&mut self.weight_mass, delta.weight_mass, "weight_mass", Effect::Nothing, &mut changes);
+        set(&mut self.soft_pins, delta.soft_pins, "soft_pins", Effect::Reset, &mut changes);
+        set(&mut self.pin_stiffness, delta.pin_stiffness, "pin_stiffness", Effect::Nothing, &mut changes);
+        set(&mut self.pin_inverse_mass, delta.pin_inverse_mass.map(|f| f.max(0.0).min(1.0)), "pin_inverse_mass", Effect::Nothing, &mut changes);
+        set(&mut self.lambda_clamp, delta.lambda_clamp, "lambda_clamp", Effect::Nothing, &mut changes);
+        set(&mut self.clamp_safety, delta.clamp_safety.map(|f| f.max(MIN_CLAMP_SAFETY)), "clamp_safety", Effect::Nothing, &mut changes);
+        set(&mut self.self_collision, delta.self_collision, "self_collision", Effect::Nothing, &mut changes);
+        set(&mut self.max_substeps, delta.max_substeps.map(|n| n.max(1).min(MAX_STEPS_PER_FRAME)), "max_substeps", Effect::Nothing, &mut changes);
+        set(&mut self.scene, delta.scene, "scene", Effect::Reset, &mut changes);
+        set(&mut self.connectivity, delta.connectivity, "connectivity", Effect::Reset, &mut changes);
+        set(&mut self.wrap_x, delta.wrap_x, "wrap_x", Effect::Reset, &mut changes);
+        set(&mut self.num_particles_x, delta.num_particles_x, "num_particles_x", Effect::Resize, &mut changes);
+        set(&mut self.num_particles_y, delta.num_particles_y, "num_particles_y", Effect::Resize, &mut changes);
+        changes
+    }
+}
+
+// The slider parameters that can also be typed in exactly.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Param
+{
+    Iterations,
+    Eta,
+    Nu,
+    Stiffness,
+    // An extra parameter of the selected solver, by index.
+    SolverParam(usize),
+    AreaStiffness,
+    BendStiffness,
+    WeightMass,
+    PinStiffness,
+}
+
+// A paper-like sheet in the cantilever: dihedral bending with very little compliance, and
+// structural constraints stiff enough to barely stretch. Bending couples four particles and
+// passes error along slowly, so this is where the warm start matters most.
+pub fn stiff_sheet() -> ParamsDelta
+{
+    ParamsDelta {
+        scene : Some(Scene::Cantilever),
+        wrap_x : Some(false),
+        bend_model : Some(BendModel::Dihedral),
+        bend_stiffness : Some(1e6),
+        stiffness : Some(1e8),
+        warm_start : Some(true),
+        ..ParamsDelta::default()
+    }
+}
+
+// Shared by the sliders and the numeric entry boxes, so both reject the same inputs. Anything
+// Rust's float parsing takes is accepted, including scientific notation like 2.5e6.
+pub fn parse_param(name : &str, text : &str) -> Option<f32>
+{
+    match text.trim().parse::<f32>()
+    {
+        Ok(f) if f.is_finite() => Some(f),
+        _ => {
+            warn!("Ignoring unparsable {} value {:?}", name, text);
+            None
+        }
+    }
+}
+
+// The lambda clamp's safety factor never goes below this, so the clamp can't hold constraints
+// at less than the impulse that stops the cloth.
+pub const MIN_CLAMP_SAFETY : f32 = 1.0;
+
+fn default_clamp_safety() -> f32
+{
+    10.0
+}
+
+fn default_self_collision() -> bool
+{
+    true
+}
+
+fn default_max_substeps() -> u32
+{
+    MAX_STEPS_PER_FRAME
+}
+
+// Iteration counts are clamped to this wherever they come from. Zero iterations is allowed and
+// means integrate only.
+pub const MAX_ITERATIONS : i32 = 100;
+
+pub fn parse_iterations(name : &str, text : &str) -> Option<i32>
+{
+    match text.trim().parse::<i32>()
+    {
+        Ok(n) if n >= 0 => Some(n.min(MAX_ITERATIONS)),
+        _ => {
+            warn!("Ignoring unparsable {} value {:?}", name, text);
+            None
+        }
+    }
+}
+
+pub fn parse_count(name : &str, text : &str) -> Option<i32>
+{
+    match text.trim().parse::<i32>()
+    {
+        Ok(n) if n >= 1 => Some(n),
+        _ => {
+            warn!("Ignoring unparsable {} value {:?}", name, text);
+            None
+        }
+    }
+}
+
+pub fn parse_seed(text : &str) -> Option<u64>
+{
+    match text.trim().parse::<u32>()
+    {
+        Ok(n) => Some(n as u64),
+        _ => {
+            warn!("Ignoring unparsable seed {:?}, seeds are whole numbers below 2^32", text);
+            None
+        }
+    }
+}
+
+// Stiffness sliders are logarithmic and send the exponent. A typed value containing an e is
+// taken as the full stiffness instead, so "1e6" means exactly 1e6 rather than 10^(1e6).
+pub fn parse_stiffness(name : &str, text : &str) -> Option<f32>
+{
+    let is_full_value = text.contains(|c| c == 'e' || c == 'E');
+    let f = parse_param(name, text)?;
+    let stiffness = if is_full_value {f} else {10.0f32.powf(f)};
+    if stiffness.is_finite() && stiffness > 0.0 {
+        Some(stiffness)
+    } else {
+        warn!("Ignoring out of range {} value {:?}", name, text);
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn only(set : impl FnOnce(&mut ParamsDelta)) -> ParamsDelta
+    {
+        let mut delta = ParamsDelta::default();
+        set(&mut delta);
+        delta
+    }
+
+    // Every field moved off its default, with the one effect that change must have.
+    fn effects() -> Vec<(ParamsDelta, &'static str, Effect)>
+    {
+        vec![
+            (only(|d| d.dt = Some(1.0 / 120.0)), "dt", Effect::RescaleLambda),
+            (only(|d| d.num_iterations = Some(8)), "num_iterations", Effect::Nothing),
+            (only(|d| d.solver_index = Some(0)), "solver_index", Effect::CleanLambda),
+            (only(|d| d.eta = Some(0.5)), "eta", Effect::Nothing),
+            (only(|d| d.adaptive_eta = Some(2.0)), "adaptive_eta", Effect::Nothing),
+            (only(|d| d.lambda_diffusion = Some(0.5)), "lambda_diffusion", Effect::Nothing),
+            (only(|d| d.nu = Some(0.9)), "nu", Effect::Nothing),
+            (only(|d| d.stiffness = Some(1e6)), "stiffness", Effect::Nothing),
+            (only(|d| d.gravity_angle = Some(90.0)), "gravity_angle", Effect::Nothing),
+            (only(|d| d.gravity_period = Some(10.0)), "gravity_period", Effect::Nothing),
+            (only(|d| d.warm_start = Some(false)), "warm_start", Effect::CleanLambda),
+            (only(|d| d.analytic_seed = Some(true)), "analytic_seed", Effect::Reset),
+            (only(|d| d.tension_only = Some(true)), "tension_only", Effect::CleanLambda),
+            (only(|d| d.use_area_constraints = Some(true)), "use_area_constraints", Effect::Topology),
+            (only(|d| d.area_stiffness = Some(1e4)), "area_stiffness", Effect::Nothing),
+            (only(|d| d.bend_model = Some(BendModel::Dihedral)), "bend_model", Effect::Topology),
+            (only(|d| d.bend_stiffness = Some(1e3)), "bend_stiffness", Effect::Nothing),
+            (only(|d| d.stiffen_perimeter = Some(true)), "stiffen_perimeter", Effect::Topology),
+            (only(|d| d.perimeter_stiffness = Some(4.0)), "perimeter_stiffness", Effect::Topology),
+            (only(|d| d.weight_mass = Some(3.0)), "weight_mass", Effect::Nothing),
+            (only(|d| d.soft_pins = Some(true)), "soft_pins", Effect::Reset),
+            (only(|d| d.pin_stiffness = Some(1e4)), "pin_stiffness", Effect::Nothing),
+            (only(|d| d.pin_inverse_mass = Some(0.1)), "pin_inverse_mass", Effect::Nothing),
+            (only(|d| d.lambda_clamp = Some(true)), "lambda_clamp", Effect::Nothing),
+            (only(|d| d.clamp_safety = Some(20.0)), "clamp_safety", Effect::Nothing),
+            (only(|d| d.self_collision = Some(false)), "self_collision", Effect::Nothing),
+            (only(|d| d.max_substeps = Some(1)), "max_substeps", Effect::Nothing),
+            (only(|d| d.scene = Some(Scene::Stacked)), "scene", Effect::Reset),
+            (only(|d| d.connectivity = Some(Connectivity::Four)), "connectivity", Effect::Reset),
+            (only(|d| d.wrap_x = Some(true)), "wrap_x", Effect::Reset),
+            (only(|d| d.num_particles_x = Some(20)), "num_particles_x", Effect::Resize),
+            (only(|d| d.num_particles_y = Some(20)), "num_particles_y", Effect::Resize),
+        ]
+    }
+
+    #[test]
+    fn each_field_has_exactly_its_effect()
+    {
+        for (delta, name, effect) in effects() {
+            let mut params = Params::default();
+            assert_eq!(params.apply(&delta), vec![(name, effect)], "changing {}", name);
+        }
+    }
+
+    #[test]
+    fn every_field_is_in_the_table()
+    {
+        let value = serde_json::to_value(&Params::default()).unwrap();
+        let fields = value.as_object().unwrap();
+        let table = effects();
+        assert_eq!(fields.len(), table.len());
+        for (_, name, _) in table.iter() {
+            assert!(fields.contains_key(*name), "{} is not a field", name);
+        }
+    }
+
+    #[test]
+    fn setting_the_current_values_changes_nothing()
+    {
+        let mut params = Params::default();
+        let delta = params.delta();
+        assert!(params.apply(&delta).is_empty());
+    }
+
+    #[test]
+    fn values_are_clamped_before_they_are_compared()
+    {
+        let mut params = Params::default();
+        params.apply(&ParamsDelta { num_iterations : Some(1000), max_substeps : Some(0), lambda_diffusion : Some(2.0), ..ParamsDelta::default() });
+        assert_eq!(params.num_iterations, MAX_ITERATIONS);
+        assert_eq!(params.max_substeps, 1);
+        assert_eq!(params.lambda_diffusion, 1.0);
+
+        // Asking for what a clamp already gave is no change at all.
+        assert!(params.apply(&ParamsDelta { num_iterations : Some(2000), ..ParamsDelta::default() }).is_empty());
+
+        // Zero iterations is allowed, and is as low as it goes.
+        params.apply(&ParamsDelta { num_iterations : Some(-3), ..ParamsDelta::default() });
+        assert_eq!(params.num_iterations, 0);
+    }
+
+    #[test]
+    fn parse_param_takes_finite_numbers_only()
+    {
+        assert_eq!(parse_param("eta", "0.5"), Some(0.5));
+        assert_eq!(parse_param("eta", " 2.5e6 "), Some(2.5e6));
+        assert_eq!(parse_param("eta", "-3"), Some(-3.0));
+        for &text in &["", "  ", "abc", "1.2.3", "NaN", "nan", "inf", "-inf", "1e39"] {
+            assert_eq!(parse_param("eta", text), None, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn parse_stiffness_reads_exponents_and_full_values()
+    {
+        assert_eq!(parse_stiffness("stiffness", "6"), Some(1e6));
+        assert_eq!(parse_stiffness("stiffness", "3.5"), Some(10.0f32.powf(3.5)));
+        assert_eq!(parse_stiffness("stiffness", "1e6"), Some(1e6));
+        assert_eq!(parse_stiffness("stiffness", "2.5E3"), Some(2500.0));
+        assert_eq!(parse_stiffness("stiffness", "-2"), Some(0.01));
+        for &text in &["", "abc", "NaN", "inf", "-1e6", "0e0", "40"] {
+            assert_eq!(parse_stiffness("stiffness", text), None, "{:?}", text);
+        }
+    }
+}
@@ -0,0 +1,7 @@
+precision mediump float;
+
+varying vec3 v_id_color;
+
+void main() {
+    gl_FragColor = vec4(v_id_color, 1.0);
+}
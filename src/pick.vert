@@ -0,0 +1,23 @@
+precision highp float;
+
+attribute vec2 a_position;
+attribute vec3 a_id_color;
+uniform float u_aspect_ratio;
+uniform vec2 u_view_center;
+uniform float u_view_scale;
+uniform vec2 u_cursor;
+uniform vec2 u_canvas_size;
+uniform float u_point_size;
+varying vec3 v_id_color;
+
+// Draws into a single pixel that stands for the one under the cursor. Each particle is offset by
+// its distance from the cursor in canvas pixels, and nearer particles get a smaller depth so the
+// nearest one covering the cursor wins.
+void main() {
+    gl_PointSize = u_point_size;
+    vec2 p = (a_position - u_view_center) * u_view_scale;
+    vec2 offset = (vec2(p.x / u_aspect_ratio, p.y) - u_cursor) * u_canvas_size * 0.5;
+    float depth = min(length(offset) / u_point_size, 1.0);
+    gl_Position = vec4(offset * 2.0, depth * 2.0 - 1.0, 1.0);
+    v_id_color = a_id_color;
+}
@@ -0,0 +1,161 @@
+use glam::*;
+use log::error;
+use web_sys::{WebGlFramebuffer, WebGlProgram, WebGlRenderbuffer, WebGlTexture, WebGlRenderingContext as GL};
+use crate::gpu_buffers::GpuBuffers;
+use crate::view::ViewTransform;
+
+// Grids with fewer particles than this are picked on the CPU, where a loop over them is cheaper
+// than a draw and a readback.
+pub const GPU_PICK_MIN_PARTICLES : usize = 10000;
+
+// A plain drag grabs the particle nearest the cursor only within this many pixels of it, so
+// pressing on empty canvas grabs nothing.
+pub const GRAB_PIXELS : f32 = 12.0;
+
+// Particles further than half this many pixels from the cursor along either axis can't be picked
+// on the GPU.
+const POINT_SIZE : f32 = 17.0;
+
+// Indices are stored plus one in the RGB bytes, so black means nothing was under the cursor.
+fn encode_id(index : usize) -> [f32; 3]
+{
+    let n = index + 1;
+    [(n & 255) as f32 / 255.0, ((n >> 8) & 255) as f32 / 255.0, ((n >> 16) & 255) as f32 / 255.0]
+}
+
+fn decode_id(pixel : [u8; 4]) -> Option<usize>
+{
+    let n = pixel[0] as usize | (pixel[1] as usize) << 8 | (pixel[2] as usize) << 16;
+    n.checked_sub(1)
+}
+
+fn compile(gl : &GL, kind : u32, source : &str) -> Option<web_sys::WebGlShader>
+{
+    let shader = gl.create_shader(kind)?;
+    gl.shader_source(&shader, source);
+    gl.compile_shader(&shader);
+    if !gl.get_shader_parameter(&shader, GL::COMPILE_STATUS).as_bool().unwrap_or(false) {
+        error!("Picking shader failed to compile: {}", gl.get_shader_info_log(&shader).unwrap_or_default());
+        return None;
+    }
+    Some(shader)
+}
+
+// Picks particles by drawing their indices as colors into a one pixel framebuffer standing for
+// the pixel under the cursor and reading it back.
+pub struct GpuPicker
+{
+    program : WebGlProgram,
+    framebuffer : WebGlFramebuffer,
+    _color : WebGlTexture,
+    _depth : WebGlRenderbuffer,
+    // Encoded index colors, rebuilt when the particle count changes.
+    id_colors : Vec<f32>,
+}
+
+impl GpuPicker {
+    pub fn new(gl : &GL) -> Option<GpuPicker>
+    {
+        let vert_shader = compile(gl, GL::VERTEX_SHADER, include_str!("./pick.vert"))?;
+        let frag_shader = compile(gl, GL::FRAGMENT_SHADER, include_str!("./pick.frag"))?;
+        let program = gl.create_program()?;
+        gl.attach_shader(&program, &vert_shader);
+        gl.attach_shader(&program, &frag_shader);
+        gl.link_program(&program);
+        if !gl.get_program_parameter(&program, GL::LINK_STATUS).as_bool().unwrap_or(false) {
+            error!("Picking program failed to link: {}", gl.get_program_info_log(&program).unwrap_or_default());
+            return None;
+        }
+
+        // WebGL 1 can only render RGBA8 to a texture, not a renderbuffer.
+        let color = gl.create_texture()?;
+        gl.bind_texture(GL::TEXTURE_2D, Some(&color));
+        gl.tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(GL::TEXTURE_2D, 0, GL::RGBA as i32, 1, 1, 0, GL::RGBA, GL::UNSIGNED_BYTE, None).ok()?;
+        gl.tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_MIN_FILTER, GL::NEAREST as i32);
+        gl.bind_texture(GL::TEXTURE_2D, None);
+
+        let depth = gl.create_renderbuffer()?;
+        gl.bind_renderbuffer(GL::RENDERBUFFER, Some(&depth));
+        gl.renderbuffer_storage(GL::RENDERBUFFER, GL::DEPTH_COMPONENT16, 1, 1);
+        gl.bind_renderbuffer(GL::RENDERBUFFER, None);
+
+        let framebuffer = gl.create_framebuffer()?;
+        gl.bind_framebuffer(GL::FRAMEBUFFER, Some(&framebuffer));
+        gl.framebuffer_texture_2d(GL::FRAMEBUFFER, GL::COLOR_ATTACHMENT0, GL::TEXTURE_2D, Some(&color), 0);
+        gl.framebuffer_renderbuffer(GL::FRAMEBUFFER, GL::DEPTH_ATTACHMENT, GL::RENDERBUFFER, Some(&depth));
+        let complete = gl.check_framebuffer_status(GL::FRAMEBUFFER) == GL::FRAMEBUFFER_COMPLETE;
+        gl.bind_framebuffer(GL::FRAMEBUFFER, None);
+        if !complete {
+            error!("Picking framebuffer is incomplete");
+            return None;
+        }
+
+        Some(GpuPicker {
+            program : program,
+            framebuffer : framebuffer,
+            _color : color,
+            _depth : depth,
+            id_colors : vec![],
+        })
+    }
+
+    // The particle nearest the cursor among those drawn over it, or None if none are. positions
+    // are x, y pairs in simulation space and cursor is in clip space. Leaves the default
+    // framebuffer bound and depth testing off.
+    pub fn pick(&mut self, gl : &GL, buffers : &mut GpuBuffers, positions : &[f32], view : &ViewTransform, canvas_size : Vec2, cursor : Vec2) -> Option<usize>
+    {
+        let num_particles = positions.len() / 2;
+        if self.id_colors.len() != num_particles * 3 {
+            self.id_colors = (0..num_particles).flat_map(|i| encode_id(i).to_vec()).collect();
+        }
+
+        gl.bind_framebuffer(GL::FRAMEBUFFER, Some(&self.framebuffer));
+        gl.viewport(0, 0, 1, 1);
+        gl.clear_color(0.0, 0.0, 0.0, 0.0);
+        gl.clear_depth(1.0);
+        gl.clear(GL::COLOR_BUFFER_BIT | GL::DEPTH_BUFFER_BIT);
+        gl.enable(GL::DEPTH_TEST);
+        gl.depth_func(GL::LESS);
+
+        gl.use_program(Some(&self.program));
+        let uniform = |name : &str| gl.get_uniform_location(&self.program, name);
+        gl.uniform1f(uniform("u_aspect_ratio").as_ref(), canvas_size.x / canvas_size.y);
+        gl.uniform2f(uniform("u_view_center").as_ref(), view.center.x, view.center.y);
+        gl.uniform1f(uniform("u_view_scale").as_ref(), view.scale);
+        gl.uniform2f(uniform("u_cursor").as_ref(), cursor.x, cursor.y);
+        gl.uniform2f(uniform("u_canvas_size").as_ref(), canvas_size.x, canvas_size.y);
+        gl.uniform1f(uniform("u_point_size").as_ref(), POINT_SIZE);
+
+        let position = gl.get_attrib_location(&self.program, "a_position") as u32;
+        let position_buffer = buffers.get_or_create(gl, "pick_positions", positions.len() * 4);
+        gl.bind_buffer(GL::ARRAY_BUFFER, Some(&position_buffer));
+        gl.buffer_data_with_array_buffer_view(GL::ARRAY_BUFFER, &js_sys::Float32Array::from(positions), GL::STATIC_DRAW);
+        gl.vertex_attrib_pointer_with_i32(position, 2, GL::FLOAT, false, 0, 0);
+        gl.enable_vertex_attrib_array(position);
+
+        let id_color = gl.get_attrib_location(&self.program, "a_id_color") as u32;
+        let id_buffer = buffers.get_or_create(gl, "pick_ids", self.id_colors.len() * 4);
+        gl.bind_buffer(GL::ARRAY_BUFFER, Some(&id_buffer));
+        gl.buffer_data_with_array_buffer_view(GL::ARRAY_BUFFER, &js_sys::Float32Array::from(self.id_colors.as_slice()), GL::STATIC_DRAW);
+        gl.vertex_attrib_pointer_with_i32(id_color, 3, GL::FLOAT, false, 0, 0);
+        gl.enable_vertex_attrib_array(id_color);
+
+        gl.draw_arrays(GL::POINTS, 0, num_particles as i32);
+
+        let mut pixel = [0u8; 4];
+        let read = gl.read_pixels_with_opt_u8_array(0, 0, 1, 1, GL::RGBA, GL::UNSIGNED_BYTE, Some(&mut pixel));
+
+        // The regular draw doesn't use the id attribute and would trip over it left enabled.
+        gl.disable_vertex_attrib_array(id_color);
+        gl.disable(GL::DEPTH_TEST);
+        gl.bind_framebuffer(GL::FRAMEBUFFER, None);
+
+        match read {
+            Ok(()) => decode_id(pixel).filter(|&i| i < num_particles),
+            Err(e) => {
+                error!("Failed to read back the picking pixel: {:?}", e);
+                None
+            }
+        }
+    }
+}
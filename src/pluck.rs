@@ -0,0 +1,104 @@
+use glam::*;
+
+// A ring-down measurement: a particle pulled away from where it rested and let go, with its
+// displacement along the pull sampled once per simulation step. Steps are always target_dt
+// apart whatever the frame timing, so the sample times are exact.
+pub struct Pluck
+{
+    pub particle : usize,
+    rest : Vec3,
+    direction : Vec3,
+    amplitude : f32,
+    dt : f32,
+    num_steps : usize,
+    samples : Vec<f32>,
+}
+
+// The decaying oscillation fitted to a finished capture.
+pub struct RingDown
+{
+    // Per second, from amplitude ~ exp(-decay_rate * t).
+    pub decay_rate : f32,
+    pub period : f32,
+    pub num_peaks : usize,
+}
+
+impl Pluck {
+    // None if the particle was let go too close to where it started to ring.
+    pub fn new(particle : usize, rest : Vec3, release : Vec3, dt : f32, num_steps : usize) -> Option<Pluck>
+    {
+        let offset = release - rest;
+        let amplitude = offset.length();
+        if amplitude < 1e-4 {
+            return None;
+        }
+        Some(Pluck {
+            particle : particle,
+            rest : rest,
+            direction : offset / amplitude,
+            amplitude : amplitude,
+            dt : dt,
+            num_steps : num_steps.max(1),
+            samples : Vec::with_capacity(num_steps),
+        })
+    }
+
+    pub fn is_complete(&self) -> bool
+    {
+        self.samples.len() >= self.num_steps
+    }
+
+    pub fn progress(&self) -> (usize, usize)
+    {
+        (self.samples.len(), self.num_steps)
+    }
+
+    pub fn record(&mut self, positions : &[Vec3])
+    {
+        if !self.is_complete() {
+            self.samples.push((positions[self.particle] - self.rest).dot(self.direction));
+        }
+    }
+
+    // Fits ln(peak) against time over the positive peaks of the signed displacement. Peaks under
+    // 1% of the release amplitude are taken as solver noise. Needs at least three peaks.
+    pub fn analyze(&self) -> Option<RingDown>
+    {
+        let floor = 0.01 * self.amplitude;
+        let s = &self.samples;
+        let peaks : Vec<(f32, f32)> = (1..s.len().saturating_sub(1))
+            .filter(|&k| s[k] > floor && s[k] > s[k - 1] && s[k] >= s[k + 1])
+            .map(|k| ((k + 1) as f32 * self.dt, s[k].ln()))
+            .collect();
+        if peaks.len() < 3 {
+            return None;
+        }
+
+        let n = peaks.len() as f32;
+        let mean_t = peaks.iter().map(|p| p.0).sum::<f32>() / n;
+        let mean_y = peaks.iter().map(|p| p.1).sum::<f32>() / n;
+        let covariance : f32 = peaks.iter().map(|p| (p.0 - mean_t) * (p.1 - mean_y)).sum();
+        let variance : f32 = peaks.iter().map(|p| (p.0 - mean_t) * (p.0 - mean_t)).sum();
+
+        Some(RingDown {
+            decay_rate : -covariance / variance,
+            period : (peaks[peaks.len() - 1].0 - peaks[0].0) / (n - 1.0),
+            num_peaks : peaks.len(),
+        })
+    }
+
+    // The samples as CSV, with the fit and the settings it was taken under as leading comments.
+    pub fn to_csv(&self, settings : &str) -> String
+    {
+        let mut csv = format!("# particle {}, amplitude {}, dt {}\n# {}\n", self.particle, self.amplitude, self.dt, settings);
+        match self.analyze() {
+            Some(fit) => csv.push_str(&format!("# decay_rate_per_s {}, period_s {}, peaks {}\n", fit.decay_rate, fit.period, fit.num_peaks)),
+            None => csv.push_str("# too few peaks to fit\n"),
+        }
+        csv.push_str("step,time_s,displacement\n");
+        for (k, d) in self.samples.iter().enumerate() {
+            csv.push_str(&format!("{},{},{}\n", k, (k + 1) as f32 * self.dt, d));
+        }
+        csv
+    }
+}
@@ -0,0 +1,123 @@
+use std::cell::RefCell;
+use web_sys::Performance;
+
+// Phase timing for DevTools and the stats panel. While enabled, every scope becomes a
+// performance.measure in the browser's trace and its duration is added to its category's total
+// for the frame. Disabled scopes cost a flag check.
+struct Profiler
+{
+    enabled : bool,
+    // Looked up when profiling is first switched on, so code that only opens scopes never touches
+    // the window and runs natively too.
+    performance : Option<Performance>,
+    // Milliseconds per category this frame, and smoothed over frames, in first-seen order.
+    frame : Vec<(&'static str, f64)>,
+    smoothed : Vec<(&'static str, f64)>,
+}
+
+thread_local! {
+    static PROFILER : RefCell<Profiler> = RefCell::new(Profiler {
+        enabled : false,
+        performance : None,
+        frame : vec![],
+        smoothed : vec![],
+    });
+}
+
+fn add(totals : &mut Vec<(&'static str, f64)>, category : &'static str, ms : f64)
+{
+    match totals.iter_mut().find(|(c, _)| *c == category) {
+        Some(total) => total.1 += ms,
+        None => totals.push((category, ms)),
+    }
+}
+
+pub fn set_enabled(enabled : bool)
+{
+    PROFILER.with(|p| {
+        let mut p = p.borrow_mut();
+        p.enabled = enabled;
+        if enabled && p.performance.is_none() {
+            p.performance = web_sys::window().and_then(|w| w.performance());
+        }
+        p.frame.clear();
+        p.smoothed.clear();
+    });
+}
+
+// Ends the measure when dropped.
+pub struct Scope
+{
+    category : &'static str,
+    // The measure name and start time, or None when profiling was off.
+    measure : Option<(String, f64)>,
+}
+
+// Starts a measure named by name(), which is only called when profiling is on. Scopes opened
+// inside another show up nested under it in the trace.
+pub fn scope(category : &'static str, name : impl FnOnce() -> String) -> Scope
+{
+    let measure = PROFILER.with(|p| {
+        let p = p.borrow();
+        match (&p.performance, p.enabled) {
+            (Some(performance), true) => {
+                let name = name();
+                let _ = performance.mark(&format!("{} start", name));
+                Some((name, performance.now()))
+            }
+            _ => None,
+        }
+    });
+    Scope { category : category, measure : measure }
+}
+
+impl Drop for Scope {
+    fn drop(&mut self)
+    {
+        if let Some((name, start)) = self.measure.take() {
+            PROFILER.with(|p| {
+                let mut guard = p.borrow_mut();
+                let p = &mut *guard;
+                if let Some(performance) = &p.performance {
+                    let start_mark = format!("{} start", name);
+                    let _ = performance.measure_with_start_mark(&name, &start_mark);
+                    performance.clear_marks_with_mark_name(&start_mark);
+                    let elapsed = performance.now() - start;
+                    add(&mut p.frame, self.category, elapsed);
+                }
+            });
+        }
+    }
+}
+
+// Folds this frame's totals into the smoothed ones and drops the frame's measures so the
+// performance buffer doesn't grow without bound. Traces already captured keep them.
+pub fn end_frame()
+{
+    PROFILER.with(|p| {
+        let mut guard = p.borrow_mut();
+        let p = &mut *guard;
+        if !p.enabled {
+            return;
+        }
+        let frame : Vec<(&'static str, f64)> = p.frame.drain(..).collect();
+        for (category, ms) in p.smoothed.iter_mut() {
+            let this_frame = frame.iter().find(|(c, _)| c == category).map_or(0.0, |f| f.1);
+            *ms += 0.05 * (this_frame - *ms);
+        }
+        for (category, ms) in frame {
+            if !p.smoothed.iter().any(|(c, _)| *c == category) {
+                p.smoothed.push((category, ms));
+            }
+        }
+        if let Some(performance) = &p.performance {
+            performance.clear_measures();
+        }
+    });
+}
+
+// Smoothed milliseconds per frame for each category seen.
+pub fn summary() -> Vec<(&'static str, f64)>
+{
+    PROFILER.with(|p| p.borrow().smoothed.clone())
+}
@@ -0,0 +1,167 @@
+use glam::*;
+
+// A curve particles can be pinned to and slide along.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Curve
+{
+    Line { start : Vec3, end : Vec3 },
+    // In the plane z = center.z.
+    Circle { center : Vec3, radius : f32 },
+}
+
+impl Curve {
+    pub fn closest_point(&self, p : Vec3) -> Vec3
+    {
+        match *self {
+            Curve::Line { start, end } => {
+                let direction = end - start;
+                let length_squared = direction.length_squared();
+                if length_squared < 1e-12 {
+                    return start;
+                }
+                let t = ((p - start).dot(direction) / length_squared).max(0.0).min(1.0);
+                start + direction * t
+            }
+            Curve::Circle { center, radius } => {
+                let offset = vec2(p.x - center.x, p.y - center.y);
+                let length = offset.length();
+                // Every point of the circle is as close to its center, so pick the top.
+                let direction = if length > 1e-6 {offset / length} else {vec2(0.0, 1.0)};
+                vec3(center.x + direction.x * radius, center.y + direction.y * radius, center.z)
+            }
+        }
+    }
+
+    // Points along the curve as a flat list of x, y pairs ready to upload for GL::LINE_STRIP.
+    pub fn line_strip(&self, num_segments : usize) -> Vec<f32>
+    {
+        match *self {
+            Curve::Line { start, end } => vec![start.x, start.y, end.x, end.y],
+            Curve::Circle { center, radius } => (0..=num_segments).flat_map(|k| {
+                let angle = k as f32 / num_segments as f32 * std::f32::consts::PI * 2.0;
+                vec![center.x + radius * angle.cos(), center.y + radius * angle.sin()]
+            }).collect(),
+        }
+    }
+}
+
+// The rails a curtain's top row can hang from, sized by one number: the half-length of the line
+// or the radius of the circle.
+#[derive(Clone, Copy, PartialEq)]
+pub enum RailShape
+{
+    Line,
+    Circle,
+}
+
+impl RailShape {
+    // The rail through anchor, a horizontal line centred on it or a circle with it at the top.
+    pub fn curve(&self, anchor : Vec3, size : f32) -> Curve
+    {
+        match self {
+            RailShape::Line => Curve::Line { start : anchor - vec3(size, 0.0, 0.0), end : anchor + vec3(size, 0.0, 0.0) },
+            RailShape::Circle => Curve::Circle { center : anchor - vec3(0.0, size, 0.0), radius : size },
+        }
+    }
+}
+
+// How a particle is held. Fixed particles mirror is_fixed; particles on a curve are free to move
+// and are put back on it after every solver iteration. Anchored particles are free too, and held
+// by the spring of their anchor.
+#[derive(Clone, Copy, PartialEq)]
+pub enum PinMode
+{
+    Free,
+    Fixed,
+    OnCurve(usize),
+    Anchored,
+}
+
+// A soft pin: a stiff zero-length spring from a particle to the point it was pinned at, solved
+// with the other constraints so the pin gives a little under load. The lambda is a vector, as the
+// spring pulls the particle straight back to the point whichever way it has moved.
+pub struct Anchor
+{
+    pub particle : usize,
+    pub position : Vec3,
+    pub lambda : Vec3,
+}
+
+impl Anchor {
+    pub fn new(particle : usize, position : Vec3) -> Anchor
+    {
+        Anchor {
+            particle : particle,
+            position : position,
+            lambda : vec3(0.0, 0.0, 0.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual : Vec3, expected : Vec3)
+    {
+        assert!((actual - expected).length() < 1e-5, "{} is not {}", actual, expected);
+    }
+
+    #[test]
+    fn line_projects_onto_the_segment()
+    {
+        let line = Curve::Line { start : vec3(-1.0, 2.0, 0.0), end : vec3(1.0, 2.0, 0.0) };
+        assert_near(line.closest_point(vec3(0.25, 5.0, 3.0)), vec3(0.25, 2.0, 0.0));
+        assert_near(line.closest_point(vec3(-1.0, 2.0, 0.0)), vec3(-1.0, 2.0, 0.0));
+    }
+
+    #[test]
+    fn line_clamps_to_its_ends()
+    {
+        let line = Curve::Line { start : vec3(0.0, 0.0, 0.0), end : vec3(2.0, 2.0, 0.0) };
+        assert_near(line.closest_point(vec3(-3.0, -1.0, 0.0)), vec3(0.0, 0.0, 0.0));
+        assert_near(line.closest_point(vec3(5.0, 4.0, 1.0)), vec3(2.0, 2.0, 0.0));
+    }
+
+    #[test]
+    fn degenerate_line_is_its_start()
+    {
+        let point = Curve::Line { start : vec3(1.0, 1.0, 1.0), end : vec3(1.0, 1.0, 1.0) };
+        assert_near(point.closest_point(vec3(4.0, -2.0, 0.0)), vec3(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn circle_projects_radially_in_its_plane()
+    {
+        let circle = Curve::Circle { center : vec3(1.0, 1.0, 0.5), radius : 2.0 };
+        assert_near(circle.closest_point(vec3(4.0, 1.0, -3.0)), vec3(3.0, 1.0, 0.5));
+        assert_near(circle.closest_point(vec3(1.1, 1.1, 0.5)), vec3(1.0 + 2.0f32.sqrt(), 1.0 + 2.0f32.sqrt(), 0.5));
+        // The centre is equally close to everything, and goes to the top.
+        assert_near(circle.closest_point(vec3(1.0, 1.0, 7.0)), vec3(1.0, 3.0, 0.5));
+    }
+
+    #[test]
+    fn closest_points_are_on_the_curve_and_stay_put()
+    {
+        let curves = [
+            Curve::Line { start : vec3(-1.0, 0.0, 0.0), end : vec3(1.0, 0.5, 0.0) },
+            Curve::Circle { center : vec3(0.0, -0.5, 0.0), radius : 0.5 },
+        ];
+        for curve in curves.iter() {
+            for k in 0..20 {
+                let p = vec3((k as f32 * 0.7).sin() * 2.0, (k as f32 * 1.3).cos() * 2.0, k as f32 * 0.1);
+                let on = curve.closest_point(p);
+                assert_near(curve.closest_point(on), on);
+            }
+        }
+    }
+
+    #[test]
+    fn rails_pass_through_their_anchor()
+    {
+        let anchor = vec3(0.0, 0.5, 0.0);
+        for shape in [RailShape::Line, RailShape::Circle].iter() {
+            assert_near(shape.curve(anchor, 0.3).closest_point(anchor), anchor);
+        }
+    }
+}
@@ -0,0 +1,608 @@
+use glam::*;
+use std::collections::VecDeque;
+use crate::observer::{SimulationObserver, StepStats};
+
+// Which particles a recording keeps.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ParticleSelection
+{
+    All,
+    Pinned,
+    // The indices typed into the probe field.
+    Probes,
+    // Every particle_stride-th particle.
+    Strided,
+}
+
+pub struct RecordingSettings
+{
+    pub selection : ParticleSelection,
+    pub probes : String,
+    pub particle_stride : usize,
+    pub step_stride : u32,
+    pub cap_megabytes : f32,
+    // Store a full frame every keyframe_interval frames and quantized differences in between.
+    pub compressed : bool,
+    pub keyframe_interval : u32,
+    // The step of the difference quantization in world units, which bounds the error of every
+    // reconstructed position to half of it.
+    pub quantum : f32,
+}
+
+impl RecordingSettings {
+    pub fn new() -> RecordingSettings
+    {
+        RecordingSettings {
+            selection : ParticleSelection::All,
+            probes : String::new(),
+            particle_stride : 10,
+            step_stride : 1,
+            cap_megabytes : 64.0,
+            compressed : false,
+            keyframe_interval : 30,
+            quantum : 1e-4,
+        }
+    }
+
+    // The particle indices this selection picks out of a cloth, in ascending order. Probe indices
+    // that are unparsable or out of range are dropped.
+    pub fn particles(&self, is_fixed : &[bool]) -> Vec<usize>
+    {
+        let n = is_fixed.len();
+        match self.selection {
+            ParticleSelection::All => (0..n).collect(),
+            ParticleSelection::Pinned => (0..n).filter(|&i| is_fixed[i]).collect(),
+            ParticleSelection::Probes => {
+                let mut probes : Vec<usize> = self.probes.split(|c : char| c == ',' || c.is_whitespace())
+                    .filter_map(|s| s.parse().ok())
+                    .filter(|&i| i < n)
+                    .collect();
+                probes.sort();
+                probes.dedup();
+                probes
+            }
+            ParticleSelection::Strided => (0..n).step_by(self.particle_stride.max(1)).collect(),
+        }
+    }
+}
+
+// A recorded frame, either every coordinate in full or packed differences from the frame before.
+#[derive(Clone)]
+enum StoredFrame
+{
+    Key(Vec<f32>),
+    Delta(Vec<u8>),
+}
+
+impl StoredFrame {
+    fn bytes(&self) -> usize
+    {
+        match self {
+            StoredFrame::Key(values) => values.len() * 4,
+            StoredFrame::Delta(packed) => packed.len(),
+        }
+    }
+}
+
+// Position trajectories of a fixed set of particles, one frame every step_stride steps. Once the
+// frames outgrow the cap the oldest are dropped; compressed recordings drop a keyframe and its
+// differences together, so the oldest frame kept is always a keyframe.
+#[derive(Clone)]
+pub struct Recorder
+{
+    particles : Vec<usize>,
+    step_stride : u32,
+    // The step the recording began at, whose positions are the first frame.
+    first_step : Option<i32>,
+    max_bytes : usize,
+    // The keyframe interval and quantum, for compressed recordings.
+    compression : Option<(u32, f32)>,
+    steps_seen : u32,
+    frames : VecDeque<StoredFrame>,
+    stored_bytes : usize,
+    // The last frame as it will be decoded, which the next difference is taken from.
+    last_frame : Vec<f32>,
+    frames_since_key : u32,
+    pub evicted : usize,
+}
+
+impl Recorder {
+    pub fn new(particles : Vec<usize>, settings : &RecordingSettings) -> Recorder
+    {
+        Recorder {
+            particles : particles,
+            step_stride : settings.step_stride.max(1),
+            first_step : None,
+            max_bytes : (settings.cap_megabytes.max(0.0) * 1024.0 * 1024.0) as usize,
+            compression : if settings.compressed {Some((settings.keyframe_interval.max(1), settings.quantum.max(1e-9)))} else {None},
+            steps_seen : 0,
+            frames : VecDeque::new(),
+            stored_bytes : 0,
+            last_frame : vec![],
+            frames_since_key : 0,
+            evicted : 0,
+        }
+    }
+
+    pub fn num_particles(&self) -> usize
+    {
+        self.particles.len()
+    }
+
+    pub fn num_frames(&self) -> usize
+    {
+        self.frames.len()
+    }
+
+    pub fn particles(&self) -> &[usize]
+    {
+        &self.particles
+    }
+
+    // The frame holding the positions after the given step, or the latest frame before it when
+    // the step stride skipped that step. None outside the frames kept.
+    pub fn frame_at_step(&self, step : i32) -> Option<usize>
+    {
+        let since = step - self.first_step?;
+        if since < 0 {
+            return None;
+        }
+        let k = (since / self.step_stride as i32) as usize;
+        k.checked_sub(self.evicted).filter(|&k| k < self.frames.len())
+    }
+
+    pub fn is_compressed(&self) -> bool
+    {
+        self.compression.is_some()
+    }
+
+    pub fn bytes(&self) -> usize
+    {
+        self.stored_bytes
+    }
+
+    // What the frames would take stored in full.
+    pub fn raw_bytes(&self) -> usize
+    {
+        self.frames.len() * self.particles.len() * 3 * 4
+    }
+
+    pub fn compression_ratio(&self) -> f32
+    {
+        self.raw_bytes() as f32 / self.stored_bytes.max(1) as f32
+    }
+
+    // Bytes a second of recording adds at the given step rate, going by the frames so far once
+    // there are any.
+    pub fn bytes_per_second(&self, steps_per_second : f32) -> f32
+    {
+        let frame_bytes = if self.frames.is_empty() {
+            (self.particles.len() * 3 * 4) as f32
+        } else {
+            self.stored_bytes as f32 / self.frames.len() as f32
+        };
+        frame_bytes * steps_per_second / self.step_stride as f32
+    }
+
+    pub fn record(&mut self, positions : &[Vec3])
+    {
+        self.steps_seen += 1;
+        if (self.steps_seen - 1) % self.step_stride != 0 {
+            return;
+        }
+
+        let mut frame = Vec::with_capacity(self.particles.len() * 3);
+        for &i in self.particles.iter() {
+            let p = positions[i];
+            frame.extend_from_slice(&[p.x, p.y, p.z]);
+        }
+
+        let stored = match self.compression {
+            Some((keyframe_interval, quantum)) if !self.frames.is_empty() && self.frames_since_key < keyframe_interval => {
+                self.frames_since_key += 1;
+                StoredFrame::Delta(encode_delta(&mut self.last_frame, &frame, quantum))
+            }
+            Some(_) => {
+                self.frames_since_key = 1;
+                self.last_frame = frame.clone();
+                StoredFrame::Key(frame)
+            }
+            None => StoredFrame::Key(frame),
+        };
+        self.stored_bytes += stored.bytes();
+        self.frames.push_back(stored);
+
+        while self.stored_bytes > self.max_bytes && self.frames.len() > 1 {
+            self.evict_oldest();
+        }
+    }
+
+    // Drops the oldest frame, and with it any differences that depended on it.
+    fn evict_oldest(&mut self)
+    {
+        if let Some(frame) = self.frames.pop_front() {
+            self.stored_bytes -= frame.bytes();
+            self.evicted += 1;
+        }
+        while let Some(StoredFrame::Delta(_)) = self.frames.front() {
+            let frame = self.frames.pop_front().unwrap();
+            self.stored_bytes -= frame.bytes();
+            self.evicted += 1;
+        }
+        if self.frames.is_empty() {
+            // The next frame has nothing to be a difference from.
+            self.frames_since_key = 0;
+        }
+    }
+
+    // Decodes frame k into decoded, going on from the frame already there when it can, so
+    // reading frames in order decodes each difference once.
+    pub fn decode(&self, k : usize, decoded : &mut DecodedFrame) -> bool
+    {
+        if k >= self.frames.len() {
+            return false;
+        }
+        let key = (0..=k).rev().find(|&j| matches!(self.frames[j], StoredFrame::Key(_))).unwrap_or(0);
+        let mut j = match decoded.index {
+            Some(j) if j >= key && j <= k => j,
+            _ => {
+                match &self.frames[key] {
+                    StoredFrame::Key(values) => decoded.values.clone_from(values),
+                    StoredFrame::Delta(_) => return false,
+                }
+                key
+            }
+        };
+        let quantum = self.compression.map_or(0.0, |(_, quantum)| quantum);
+        while j < k {
+            j += 1;
+            if let StoredFrame::Delta(packed) = &self.frames[j] {
+                decode_delta(&mut decoded.values, packed, quantum);
+            }
+        }
+        decoded.index = Some(k);
+        true
+    }
+
+    // A recording read back from a .npy file as to_npy writes them, every frame a keyframe. The
+    // file doesn't say which particles or steps it kept, so it is taken to hold every particle
+    // from the first step after a reset, one frame a step.
+    pub fn from_npy(bytes : &[u8]) -> Result<Recorder, String>
+    {
+        if bytes.len() < 10 || &bytes[..8] != b"\x93NUMPY\x01\x00" {
+            return Err("not a version 1.0 .npy file".to_string());
+        }
+        let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        let header = bytes.get(10..10 + header_len).map(String::from_utf8_lossy).ok_or("the header is cut short")?;
+        if !header.contains("'descr': '<f4'") || !header.contains("'fortran_order': False") {
+            return Err("only little-endian f32 in C order can be read".to_string());
+        }
+        let shape : Vec<usize> = header.split("'shape': (").nth(1)
+            .and_then(|rest| rest.split(')').next())
+            .map(|dims| dims.split(',').filter_map(|d| d.trim().parse().ok()).collect())
+            .unwrap_or_default();
+        let (num_frames, num_particles) = match shape[..] {
+            [frames, particles, 3] => (frames, particles),
+            _ => return Err(format!("the shape is {:?}, not frames x particles x 3", shape)),
+        };
+        let data = &bytes[10 + header_len..];
+        let frame_len = num_particles * 3;
+        if data.len() < num_frames * frame_len * 4 {
+            return Err("the data is shorter than the shape".to_string());
+        }
+        let values : Vec<f32> = data.chunks_exact(4).take(num_frames * frame_len).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect();
+        let frames : VecDeque<StoredFrame> = values.chunks(frame_len.max(1)).map(|frame| StoredFrame::Key(frame.to_vec())).collect();
+        Ok(Recorder {
+            particles : (0..num_particles).collect(),
+            step_stride : 1,
+            first_step : Some(1),
+            max_bytes : usize::MAX,
+            compression : None,
+            steps_seen : 0,
+            stored_bytes : values.len() * 4,
+            frames : frames,
+            last_frame : vec![],
+            frames_since_key : 0,
+            evicted : 0,
+        })
+    }
+
+    // The recording as a NumPy .npy file of little-endian f32 with shape [frames, particles, 3],
+    // decoded if it was compressed.
+    pub fn to_npy(&self) -> Vec<u8>
+    {
+        let mut bytes = npy_header(&[self.frames.len(), self.particles.len(), 3]);
+        bytes.reserve(self.raw_bytes());
+        let quantum = self.compression.map_or(0.0, |(_, quantum)| quantum);
+        let mut frame : Vec<f32> = vec![];
+        for stored in self.frames.iter() {
+            match stored {
+                StoredFrame::Key(values) => frame.clone_from(values),
+                StoredFrame::Delta(packed) => decode_delta(&mut frame, packed, quantum),
+            }
+            for value in frame.iter() {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        bytes
+    }
+}
+
+// Quantizes the differences between frame and previous, the last frame as decoded, and packs them
+// as zigzag varints, so the small steps of a smooth trajectory take a byte or two each. previous
+// becomes what the decoder will rebuild, so rounding errors never accumulate.
+fn encode_delta(previous : &mut [f32], frame : &[f32], quantum : f32) -> Vec<u8>
+{
+    let mut packed = Vec::with_capacity(frame.len());
+    for (last, &value) in previous.iter_mut().zip(frame.iter()) {
+        let q = ((value - *last) / quantum).round() as i64;
+        *last += q as f32 * quantum;
+        let mut zigzag = ((q << 1) ^ (q >> 63)) as u64;
+        loop {
+            let byte = (zigzag & 0x7f) as u8;
+            zigzag >>= 7;
+            if zigzag == 0 {
+                packed.push(byte);
+                break;
+            }
+            packed.push(byte | 0x80);
+        }
+    }
+    packed
+}
+
+// Adds the differences packed by encode_delta onto frame.
+fn decode_delta(frame : &mut [f32], packed : &[u8], quantum : f32)
+{
+    let mut bytes = packed.iter();
+    for value in frame.iter_mut() {
+        let mut zigzag = 0u64;
+        let mut shift = 0;
+        while let Some(&byte) = bytes.next() {
+            zigzag |= ((byte & 0x7f) as u64) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        let q = (zigzag >> 1) as i64 ^ -((zigzag & 1) as i64);
+        *value += q as f32 * quantum;
+    }
+}
+
+// A frame as decode left it, and which frame that was.
+pub struct DecodedFrame
+{
+    pub index : Option<usize>,
+    pub values : Vec<f32>,
+}
+
+impl DecodedFrame {
+    pub fn new() -> DecodedFrame
+    {
+        DecodedFrame { index : None, values : vec![] }
+    }
+}
+
+impl SimulationObserver for Recorder {
+    fn on_step_begin(&mut self, step : i32)
+    {
+        if self.first_step.is_none() {
+            self.first_step = Some(step);
+        }
+    }
+
+    fn on_step_end(&mut self, stats : &StepStats)
+    {
+        self.record(stats.positions);
+    }
+}
+
+// Version 1.0 .npy header for a C-ordered little-endian f32 array, padded with spaces so the
+// data starts on a 64-byte boundary.
+pub fn npy_header(shape : &[usize]) -> Vec<u8>
+{
+    let dims : Vec<String> = shape.iter().map(|d| d.to_string()).collect();
+    let shape_text = if dims.len() == 1 {format!("({},)", dims[0])} else {format!("({})", dims.join(", "))};
+    let mut dict = format!("{{'descr': '<f4', 'fortran_order': False, 'shape': {}, }}", shape_text);
+
+    // Magic, version and the two-byte length come to 10 bytes, and the dict ends with a newline.
+    let unpadded = 10 + dict.len() + 1;
+    dict.push_str(&" ".repeat((64 - unpadded % 64) % 64));
+    dict.push('\n');
+
+    let mut header = b"\x93NUMPY\x01\x00".to_vec();
+    header.extend_from_slice(&(dict.len() as u16).to_le_bytes());
+    header.extend_from_slice(dict.as_bytes());
+    header
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A few particles wandering smoothly, one frame per step.
+    fn trajectory(num_frames : usize, num_particles : usize) -> Vec<Vec<Vec3>>
+    {
+        (0..num_frames).map(|f| {
+            (0..num_particles).map(|p| {
+                let t = f as f32 * 0.05 + p as f32;
+                vec3(t.sin(), t.cos() * 0.5, p as f32 * 0.1 + t * 0.01)
+            }).collect()
+        }).collect()
+    }
+
+    fn record_all(settings : &RecordingSettings, frames : &[Vec<Vec3>]) -> Recorder
+    {
+        let mut recorder = Recorder::new((0..frames[0].len()).collect(), settings);
+        for positions in frames.iter() {
+            recorder.record(positions);
+        }
+        recorder
+    }
+
+    // The header's length, dict and the data after it, read back independently of from_npy.
+    fn split_npy(bytes : &[u8]) -> (String, &[u8])
+    {
+        assert_eq!(&bytes[..8], b"\x93NUMPY\x01\x00");
+        let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        assert_eq!((10 + header_len) % 64, 0, "the data is not 64-byte aligned");
+        let header = std::str::from_utf8(&bytes[10..10 + header_len]).unwrap().to_string();
+        assert!(header.ends_with('\n'));
+        (header, &bytes[10 + header_len..])
+    }
+
+    #[test]
+    fn npy_header_describes_the_recorded_data()
+    {
+        let frames = trajectory(7, 5);
+        let recorder = record_all(&RecordingSettings::new(), &frames);
+        let bytes = recorder.to_npy();
+        let (header, data) = split_npy(&bytes);
+        assert!(header.contains("'descr': '<f4'"));
+        assert!(header.contains("'fortran_order': False"));
+        assert!(header.contains("'shape': (7, 5, 3)"));
+        assert_eq!(data.len(), 7 * 5 * 3 * 4);
+
+        let values : Vec<f32> = data.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect();
+        let expected : Vec<f32> = frames.iter().flat_map(|f| f.iter().flat_map(|p| vec![p.x, p.y, p.z])).collect();
+        assert_eq!(values, expected);
+    }
+
+    #[test]
+    fn npy_header_writes_one_dimensional_shapes_as_tuples()
+    {
+        let bytes = npy_header(&[4]);
+        let (header, data) = split_npy(&bytes);
+        assert!(header.contains("'shape': (4,)"));
+        assert!(data.is_empty());
+    }
+
+    #[test]
+    fn npy_files_read_back_as_written()
+    {
+        let recorder = record_all(&RecordingSettings::new(), &trajectory(6, 4));
+        let read = Recorder::from_npy(&recorder.to_npy()).unwrap();
+        assert_eq!(read.num_frames(), 6);
+        assert_eq!(read.num_particles(), 4);
+        assert_eq!(read.to_npy(), recorder.to_npy());
+        assert!(Recorder::from_npy(b"not numpy").is_err());
+    }
+
+    #[test]
+    fn strides_and_selections_pick_what_they_say()
+    {
+        let mut settings = RecordingSettings::new();
+        let is_fixed = [true, false, false, true, false];
+        settings.selection = ParticleSelection::Pinned;
+        assert_eq!(settings.particles(&is_fixed), vec![0, 3]);
+        settings.selection = ParticleSelection::Probes;
+        settings.probes = "4, 1 x 1,9".to_string();
+        assert_eq!(settings.particles(&is_fixed), vec![1, 4]);
+        settings.selection = ParticleSelection::Strided;
+        settings.particle_stride = 2;
+        assert_eq!(settings.particles(&is_fixed), vec![0, 2, 4]);
+
+        settings.step_stride = 3;
+        let recorder = record_all(&settings, &trajectory(10, 2));
+        assert_eq!(recorder.num_frames(), 4);
+    }
+
+    #[test]
+    fn the_cap_evicts_the_oldest_frames()
+    {
+        let mut settings = RecordingSettings::new();
+        // Room for three frames of four particles.
+        settings.cap_megabytes = (3 * 4 * 3 * 4) as f32 / (1024.0 * 1024.0);
+        let recorder = record_all(&settings, &trajectory(10, 4));
+        assert_eq!(recorder.num_frames(), 3);
+        assert_eq!(recorder.evicted, 7);
+        assert!(recorder.bytes() <= 3 * 4 * 3 * 4);
+    }
+
+    fn compressed(quantum : f32) -> RecordingSettings
+    {
+        let mut settings = RecordingSettings::new();
+        settings.compressed = true;
+        settings.keyframe_interval = 8;
+        settings.quantum = quantum;
+        settings
+    }
+
+    fn max_error(recorder : &Recorder, frames : &[Vec<Vec3>]) -> f32
+    {
+        let mut decoded = DecodedFrame::new();
+        let mut error = 0.0f32;
+        for (k, positions) in frames.iter().enumerate() {
+            assert!(recorder.decode(k, &mut decoded));
+            for (p, xyz) in positions.iter().zip(decoded.values.chunks(3)) {
+                error = error.max((p.x - xyz[0]).abs()).max((p.y - xyz[1]).abs()).max((p.z - xyz[2]).abs());
+            }
+        }
+        error
+    }
+
+    #[test]
+    fn compressed_round_trip_stays_within_half_a_quantum()
+    {
+        let frames = trajectory(100, 6);
+        for &quantum in [1e-4, 1e-3, 1e-2].iter() {
+            let recorder = record_all(&compressed(quantum), &frames);
+            assert_eq!(recorder.num_frames(), 100);
+            // A little over half for the rounding of the f32 sums themselves.
+            let error = max_error(&recorder, &frames);
+            assert!(error <= quantum * 0.5 + 1e-6, "quantum {}: error {}", quantum, error);
+            assert!(recorder.compression_ratio() > 1.0, "quantum {}: ratio {}", quantum, recorder.compression_ratio());
+        }
+    }
+
+    #[test]
+    fn decoding_out_of_order_matches_decoding_in_order()
+    {
+        let recorder = record_all(&compressed(1e-4), &trajectory(40, 3));
+        let mut in_order = DecodedFrame::new();
+        let mut frames = vec![];
+        for k in 0..40 {
+            recorder.decode(k, &mut in_order);
+            frames.push(in_order.values.clone());
+        }
+        let mut jumping = DecodedFrame::new();
+        for &k in [37, 3, 20, 21, 8, 0, 39].iter() {
+            assert!(recorder.decode(k, &mut jumping));
+            assert_eq!(jumping.values, frames[k], "frame {}", k);
+        }
+        assert!(!recorder.decode(40, &mut jumping));
+    }
+
+    #[test]
+    fn compressed_npy_export_is_the_decoded_recording()
+    {
+        let frames = trajectory(20, 4);
+        let recorder = record_all(&compressed(1e-3), &frames);
+        let read = Recorder::from_npy(&recorder.to_npy()).unwrap();
+        assert!(!read.is_compressed());
+        let mut expected = DecodedFrame::new();
+        let mut actual = DecodedFrame::new();
+        for k in 0..20 {
+            recorder.decode(k, &mut expected);
+            read.decode(k, &mut actual);
+            assert_eq!(actual.values, expected.values, "frame {}", k);
+        }
+    }
+
+    #[test]
+    fn compressed_eviction_keeps_a_keyframe_first()
+    {
+        let frames = trajectory(50, 4);
+        let mut settings = compressed(1e-4);
+        // Room for a couple of keyframes and their differences.
+        settings.cap_megabytes = 400.0 / (1024.0 * 1024.0);
+        let recorder = record_all(&settings, &frames);
+        assert!(recorder.evicted > 0);
+        // Whole keyframe groups go together.
+        assert_eq!(recorder.evicted % 8, 0);
+        assert!(recorder.bytes() <= 400);
+        let error = max_error(&recorder, &frames[recorder.evicted..]);
+        assert!(error <= 0.5e-4 + 1e-6, "error {}", error);
+    }
+}
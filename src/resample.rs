@@ -0,0 +1,167 @@
+use glam::*;
+use crate::cloth::{AreaConstraint, ClothBuild, Constraint, EdgeKind, SheetGrid};
+
+// The parts of a running cloth a resize carries over.
+pub struct ClothSample<'a>
+{
+    pub positions : &'a [Vec3],
+    pub previous_positions : &'a [Vec3],
+    pub is_fixed : &'a [bool],
+    pub constraints : &'a [Constraint],
+    pub area_constraints : &'a [AreaConstraint],
+    pub sheet_grids : &'a [SheetGrid],
+}
+
+const EDGE_KINDS : [EdgeKind; 4] = [EdgeKind::Vertical, EdgeKind::Horizontal, EdgeKind::Diagonal, EdgeKind::AntiDiagonal];
+
+// Bilinear interpolation over an lx by ly lattice, clamping to its border.
+fn bilinear(x : f32, y : f32, lx : i32, ly : i32, value : impl Fn(i32, i32) -> Vec3) -> Vec3
+{
+    let x = x.max(0.0).min((lx - 1) as f32);
+    let y = y.max(0.0).min((ly - 1) as f32);
+    let i0 = (x.floor() as i32).min((lx - 2).max(0));
+    let j0 = (y.floor() as i32).min((ly - 2).max(0));
+    let i1 = (i0 + 1).min(lx - 1);
+    let j1 = (j0 + 1).min(ly - 1);
+    let tx = x - i0 as f32;
+    let ty = y - j0 as f32;
+
+    let bottom = value(i0, j0).lerp(value(i1, j0), tx);
+    let top = value(i0, j1).lerp(value(i1, j1), tx);
+    bottom.lerp(top, ty)
+}
+
+// Carries the state of old over to the freshly built cloth new, which must have the same sheets
+// at a different resolution. Positions, previous positions and lambdas are interpolated across
+// each sheet in grid space, lambdas scaled by the change in rest length, and every old pin moves
+// to the nearest new particle. Returns the new previous positions, or None if the sheets don't
+// correspond.
+pub fn resample(old : &ClothSample, new : &mut ClothBuild) -> Option<Vec<Vec3>>
+{
+    if old.sheet_grids.len() != new.sheet_grids.len() {
+        return None;
+    }
+    // A wrapped sheet would have to be interpolated around its seam, so it is rebuilt instead.
+    if old.sheet_grids.iter().chain(new.sheet_grids.iter()).any(|grid| grid.wrap_x) {
+        return None;
+    }
+
+    let mut previous_positions = new.positions.clone();
+    let resample_area = !old.area_constraints.is_empty() && !new.area_constraints.is_empty();
+
+    for (from, to) in old.sheet_grids.iter().zip(new.sheet_grids.iter()) {
+        // Grid coordinates of the new sheet scaled into the old one.
+        let scale = vec2(
+            (from.num_particles_x - 1) as f32 / (to.num_particles_x - 1) as f32,
+            (from.num_particles_y - 1) as f32 / (to.num_particles_y - 1) as f32,
+        );
+        let (fx, fy) = (from.num_particles_x, from.num_particles_y);
+
+        for i in 0..to.num_particles_x {
+            for j in 0..to.num_particles_y {
+                let p = vec2(i as f32, j as f32) * scale;
+                let index = to.particle(i, j);
+                new.positions[index] = bilinear(p.x, p.y, fx, fy, |a, b| old.positions[from.particle(a, b)]);
+                previous_positions[index] = bilinear(p.x, p.y, fx, fy, |a, b| old.previous_positions[from.particle(a, b)]);
+                new.is_fixed[index] = false;
+            }
+        }
+
+        for i in 0..fx {
+            for j in 0..fy {
+                if old.is_fixed[from.particle(i, j)] {
+                    let p = vec2(i as f32, j as f32) / scale;
+                    let ni = (p.x.round() as i32).max(0).min(to.num_particles_x - 1);
+                    let nj = (p.y.round() as i32).max(0).min(to.num_particles_y - 1);
+                    new.is_fixed[to.particle(ni, nj)] = true;
+                }
+            }
+        }
+
+        for &kind in EDGE_KINDS.iter() {
+            // Alternating diagonals have gaps to interpolate across, so theirs start from zero.
+            if !from.has_full_lattice(kind) || !to.has_full_lattice(kind) {
+                continue;
+            }
+            let (lx, ly, offset) = to.lattice(kind);
+            let (old_lx, old_ly, old_offset) = from.lattice(kind);
+            for i in 0..lx {
+                for j in 0..ly {
+                    let p = (vec2(i as f32, j as f32) + offset) * scale - old_offset;
+                    let old_constraint = |a, b| &old.constraints[from.constraint(kind, a, b).unwrap()];
+                    let lambda = bilinear(p.x, p.y, old_lx, old_ly, |a, b| old_constraint(a, b).lambda);
+                    let old_length = bilinear(p.x, p.y, old_lx, old_ly, |a, b| vec3(old_constraint(a, b).length, 0.0, 0.0)).x;
+
+                    let c = &mut new.constraints[to.constraint(kind, i, j).unwrap()];
+                    c.lambda = lambda * (c.length / old_length);
+                }
+            }
+        }
+
+        if resample_area {
+            let (lx, ly, offset) = to.lattice(EdgeKind::Diagonal);
+            let (old_lx, old_ly, old_offset) = from.lattice(EdgeKind::Diagonal);
+            for i in 0..lx {
+                for j in 0..ly {
+                    let p = (vec2(i as f32, j as f32) + offset) * scale - old_offset;
+                    let old_quad = |a, b| &old.area_constraints[from.area_constraint(a, b)];
+                    let sample = bilinear(p.x, p.y, old_lx, old_ly, |a, b| vec3(old_quad(a, b).lambda, old_quad(a, b).area, 0.0));
+
+                    let c = &mut new.area_constraints[to.area_constraint(i, j)];
+                    c.lambda = sample.x * (c.area / sample.y);
+                }
+            }
+        }
+    }
+
+    Some(previous_positions)
+}
+
+// Copies the state of old into new wherever their sheets overlap in grid coordinates, for growing
+// a cloth without disturbing the part already there. Whatever only new has keeps its built
+// position with no velocity and zero lambdas, as do the bending constraints, which have no grid
+// lookup. Returns the new previous positions, or None if the sheets don't correspond.
+pub fn carry_over(old : &ClothSample, new : &mut ClothBuild) -> Option<Vec<Vec3>>
+{
+    if old.sheet_grids.len() != new.sheet_grids.len() {
+        return None;
+    }
+
+    let mut previous_positions = new.positions.clone();
+    let carry_area = !old.area_constraints.is_empty() && !new.area_constraints.is_empty();
+
+    for (from, to) in old.sheet_grids.iter().zip(new.sheet_grids.iter()) {
+        for i in 0..from.num_particles_x.min(to.num_particles_x) {
+            for j in 0..from.num_particles_y.min(to.num_particles_y) {
+                let (a, b) = (from.particle(i, j), to.particle(i, j));
+                new.positions[b] = old.positions[a];
+                previous_positions[b] = old.previous_positions[a];
+                new.is_fixed[b] = old.is_fixed[a];
+            }
+        }
+
+        for &kind in EDGE_KINDS.iter() {
+            let (lx, ly, _) = to.lattice(kind);
+            let (old_lx, old_ly, _) = from.lattice(kind);
+            for i in 0..lx.min(old_lx) {
+                for j in 0..ly.min(old_ly) {
+                    if let (Some(a), Some(b)) = (from.constraint(kind, i, j), to.constraint(kind, i, j)) {
+                        new.constraints[b].lambda = old.constraints[a].lambda;
+                    }
+                }
+            }
+        }
+
+        if carry_area {
+            let (lx, ly, _) = to.lattice(EdgeKind::Diagonal);
+            let (old_lx, old_ly, _) = from.lattice(EdgeKind::Diagonal);
+            for i in 0..lx.min(old_lx) {
+                for j in 0..ly.min(old_ly) {
+                    new.area_constraints[to.area_constraint(i, j)].lambda = old.area_constraints[from.area_constraint(i, j)].lambda;
+                }
+            }
+        }
+    }
+
+    Some(previous_positions)
+}
@@ -0,0 +1,81 @@
+use glam::*;
+
+// The outcome of running the cloth forward, reversing time and running it back the same number
+// of steps. A lossless integrator and solver would land exactly where it started, so the error
+// measures the dissipation the constraint projection adds.
+pub struct ReversalResult
+{
+    pub steps : i32,
+    // Root mean square and largest distance of the free particles from where they started.
+    pub rms_error : f32,
+    pub max_error : f32,
+    pub settings : String,
+    // Features that were on and make the run irreversible regardless of the solver.
+    pub irreversible : Vec<&'static str>,
+}
+
+impl ReversalResult {
+    pub fn new(steps : i32, start : &[Vec3], end : &[Vec3], is_fixed : &[bool], settings : String, irreversible : Vec<&'static str>) -> ReversalResult
+    {
+        let mut sum = 0.0;
+        let mut max_error = 0.0f32;
+        let mut count = 0;
+        for i in (0..start.len()).filter(|&i| !is_fixed[i]) {
+            let error = (end[i] - start[i]).length();
+            sum += error * error;
+            max_error = max_error.max(error);
+            count += 1;
+        }
+        ReversalResult {
+            steps : steps,
+            rms_error : if count > 0 {(sum / count as f32).sqrt()} else {0.0},
+            max_error : max_error,
+            settings : settings,
+            irreversible : irreversible,
+        }
+    }
+}
+
+// A check in progress. It runs a time budget of steps per frame rather than all at once, so the
+// canvas keeps showing the cloth and the tab stays responsive through long checks.
+pub struct ReversalRun
+{
+    pub steps : i32,
+    // Forward steps and then backward ones, 2 * steps in all.
+    pub steps_done : i32,
+    pub start : Vec<Vec3>,
+    pub settings : String,
+}
+
+impl ReversalRun {
+    pub fn new(steps : i32, start : Vec<Vec3>, settings : String) -> ReversalRun
+    {
+        ReversalRun { steps : steps, steps_done : 0, start : start, settings : settings }
+    }
+
+    pub fn is_complete(&self) -> bool
+    {
+        self.steps_done >= 2 * self.steps
+    }
+
+    // Whether time has been reversed for the way back.
+    pub fn is_reversed(&self) -> bool
+    {
+        self.steps_done >= self.steps
+    }
+
+    pub fn progress(&self) -> f32
+    {
+        self.steps_done as f32 / (2 * self.steps).max(1) as f32
+    }
+}
+
+// Every check run this session as CSV, one row each.
+pub fn to_csv(results : &[ReversalResult]) -> String
+{
+    let mut csv = "steps,rms_error,max_error,settings,irreversible\n".to_string();
+    for r in results.iter() {
+        csv.push_str(&format!("{},{},{},\"{}\",\"{}\"\n", r.steps, r.rms_error, r.max_error, r.settings, r.irreversible.join("; ")));
+    }
+    csv
+}
@@ -0,0 +1,49 @@
+// Stream ids for Pcg32::new, one per feature that draws random numbers. Each feature's sequence
+// then depends only on the session seed, never on how much the others have drawn. Ids must not
+// change once assigned, or old seeds stop reproducing old runs.
+pub const FRAME_TIMES_STREAM : u64 = 0;
+pub const FUZZ_STREAM : u64 = 1;
+
+// PCG32 (XSH RR variant). Small, self-contained and bit-identical on every platform, which is
+// all the simulation needs from a random number generator.
+pub struct Pcg32
+{
+    state : u64,
+    increment : u64,
+}
+
+impl Pcg32 {
+    const MULTIPLIER : u64 = 6364136223846793005;
+
+    pub fn new(seed : u64, stream : u64) -> Pcg32
+    {
+        let mut rng = Pcg32 {
+            state : 0,
+            increment : (stream << 1) | 1,
+        };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    pub fn next_u32(&mut self) -> u32
+    {
+        let old = self.state;
+        self.state = old.wrapping_mul(Self::MULTIPLIER).wrapping_add(self.increment);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rot = (old >> 59) as u32;
+        xorshifted.rotate_right(rot)
+    }
+
+    // Uniform in [0, 1).
+    pub fn next_f64(&mut self) -> f64
+    {
+        self.next_u32() as f64 / 4294967296.0
+    }
+
+    pub fn range(&mut self, min : f64, max : f64) -> f64
+    {
+        min + (max - min) * self.next_f64()
+    }
+}
@@ -0,0 +1,198 @@
+use serde::Serialize;
+use std::collections::VecDeque;
+use crate::observer::{SimulationObserver, StepStats};
+
+// Summaries of the run's health over each window of simulated time, for long unattended runs.
+// Everything is gathered into running totals as the steps go, so a window costs nothing to keep
+// however many steps it spans.
+
+pub const DEFAULT_WINDOW_SECONDS : f32 = 10.0;
+
+// The history panel and the export keep this many of the latest summaries.
+pub const HISTORY_LENGTH : usize = 50;
+
+#[derive(Clone, Serialize)]
+pub struct Rollup
+{
+    // Simulated seconds since summaries were switched on. Resets don't wind these back.
+    pub start_time : f32,
+    pub end_time : f32,
+    pub steps : u32,
+    // Distance residuals after each step, tension only where that is set.
+    pub mean_residual : f32,
+    pub max_residual : f32,
+    // Total energy at the end of the window less at its start.
+    pub energy_drift : f32,
+    pub relative_energy_drift : f32,
+    pub lambda_clamps : u64,
+    pub strain_alarms : u32,
+    // Milliseconds a step takes to solve and a frame to draw, on average.
+    pub mean_step_ms : f64,
+    pub mean_render_ms : f64,
+    pub mean_contacts : f32,
+    pub max_contacts : usize,
+}
+
+impl Rollup {
+    pub const TABLE_HEADER : &'static str = "     time  steps  mean res   max res  energy drift  clamps  alarms  step ms  draw ms  contacts";
+
+    pub fn table_row(&self) -> String
+    {
+        format!("{:>9.1} {:>6} {:>9.2e} {:>9.2e} {:>+13.3e} {:>7} {:>7} {:>8.3} {:>8.3} {:>5.0}/{:<4}",
+            self.end_time, self.steps, self.mean_residual, self.max_residual, self.energy_drift,
+            self.lambda_clamps, self.strain_alarms, self.mean_step_ms, self.mean_render_ms, self.mean_contacts, self.max_contacts)
+    }
+}
+
+// What the model knows of a step that the observer hooks don't carry. The clamp and alarm counts
+// are the model's running totals, which a reset sets back to zero.
+pub struct StepHealth
+{
+    pub dt : f32,
+    pub energy : f32,
+    pub contacts : usize,
+    pub total_lambda_clamps : u64,
+    pub total_strain_alarms : u32,
+}
+
+// The running totals for the window in progress.
+#[derive(Default)]
+struct Window
+{
+    start_time : f32,
+    steps : u32,
+    residual_sum : f64,
+    residual_count : u64,
+    max_residual : f32,
+    start_energy : Option<f32>,
+    end_energy : f32,
+    lambda_clamps : u64,
+    strain_alarms : u32,
+    step_ms : f64,
+    timed_steps : u32,
+    render_ms : f64,
+    frames : u32,
+    contacts_sum : u64,
+    max_contacts : usize,
+}
+
+pub struct Rollups
+{
+    pub window_seconds : f32,
+    pub history : VecDeque<Rollup>,
+    time : f32,
+    window : Window,
+    // The totals at the last step, to count the step's own from.
+    last_lambda_clamps : u64,
+    last_strain_alarms : u32,
+}
+
+impl Rollups {
+    pub fn new() -> Rollups
+    {
+        Rollups {
+            window_seconds : DEFAULT_WINDOW_SECONDS,
+            history : VecDeque::with_capacity(HISTORY_LENGTH),
+            time : 0.0,
+            window : Window::default(),
+            last_lambda_clamps : 0,
+            last_strain_alarms : 0,
+        }
+    }
+
+    pub fn clear(&mut self)
+    {
+        self.history.clear();
+        self.time = 0.0;
+        self.window = Window::default();
+    }
+
+    // Drops the window in progress, for a rebuilt cloth whose energy and residuals don't follow on
+    // from the old one's. The clock carries on.
+    pub fn restart_window(&mut self)
+    {
+        self.window = Window { start_time : self.time, ..Window::default() };
+    }
+
+    // Adds a step's totals, once the observer has had its positions. Returns the summary when the
+    // step closes a window.
+    pub fn end_step(&mut self, health : StepHealth) -> Option<&Rollup>
+    {
+        self.time += health.dt;
+        let w = &mut self.window;
+        w.steps += 1;
+        w.start_energy.get_or_insert(health.energy);
+        w.end_energy = health.energy;
+        w.lambda_clamps += health.total_lambda_clamps.saturating_sub(self.last_lambda_clamps);
+        w.strain_alarms += health.total_strain_alarms.saturating_sub(self.last_strain_alarms);
+        self.last_lambda_clamps = health.total_lambda_clamps;
+        self.last_strain_alarms = health.total_strain_alarms;
+        w.contacts_sum += health.contacts as u64;
+        w.max_contacts = w.max_contacts.max(health.contacts);
+
+        if self.time - w.start_time < self.window_seconds {
+            return None;
+        }
+        let start_energy = w.start_energy.unwrap_or(w.end_energy);
+        let rollup = Rollup {
+            start_time : w.start_time,
+            end_time : self.time,
+            steps : w.steps,
+            mean_residual : (w.residual_sum / w.residual_count.max(1) as f64) as f32,
+            max_residual : w.max_residual,
+            energy_drift : w.end_energy - start_energy,
+            relative_energy_drift : (w.end_energy - start_energy) / start_energy.abs().max(1e-6),
+            lambda_clamps : w.lambda_clamps,
+            strain_alarms : w.strain_alarms,
+            mean_step_ms : w.step_ms / w.timed_steps.max(1) as f64,
+            mean_render_ms : w.render_ms / w.frames.max(1) as f64,
+            mean_contacts : w.contacts_sum as f32 / w.steps as f32,
+            max_contacts : w.max_contacts,
+        };
+        if self.history.len() == HISTORY_LENGTH {
+            self.history.pop_front();
+        }
+        self.history.push_back(rollup);
+        self.restart_window();
+        self.history.back()
+    }
+
+    // The time a frame's steps took to solve, all together.
+    pub fn add_steps_time(&mut self, ms : f64, steps : u32)
+    {
+        self.window.step_ms += ms;
+        self.window.timed_steps += steps;
+    }
+
+    pub fn add_render_time(&mut self, ms : f64)
+    {
+        self.window.render_ms += ms;
+        self.window.frames += 1;
+    }
+
+    pub fn to_json(&self, settings : &str) -> String
+    {
+        #[derive(Serialize)]
+        struct Export<'a>
+        {
+            settings : &'a str,
+            window_seconds : f32,
+            summaries : &'a VecDeque<Rollup>,
+        }
+        serde_json::to_string_pretty(&Export { settings : settings, window_seconds : self.window_seconds, summaries : &self.history }).unwrap_or_default()
+    }
+}
+
+impl SimulationObserver for Rollups {
+    fn on_step_end(&mut self, stats : &StepStats)
+    {
+        let w = &mut self.window;
+        for c in stats.constraints.iter() {
+            let error = (stats.positions[c.p0] - stats.positions[c.p1]).length() - c.length;
+            let residual = if stats.params.tension_only {error.max(0.0)} else {error.abs()};
+            w.residual_sum += residual as f64;
+            w.max_residual = w.max_residual.max(residual);
+        }
+        w.residual_count += stats.constraints.len() as u64;
+    }
+}
@@ -0,0 +1,225 @@
+use glam::*;
+use serde::Deserialize;
+
+// A 2D signed distance field sampled on a regular grid of nodes spanning [min, max] in world
+// space. values is row-major (values[j * width + i] is the node at column i, row j) and is
+// negative inside the obstacle.
+#[derive(Deserialize)]
+pub struct SdfGrid
+{
+    width : usize,
+    height : usize,
+    min : [f32; 2],
+    max : [f32; 2],
+    values : Vec<f32>,
+}
+
+impl SdfGrid {
+    pub fn from_json(text : &str) -> Result<SdfGrid, String>
+    {
+        let grid : SdfGrid = serde_json::from_str(text).map_err(|e| format!("Invalid SDF JSON: {}", e))?;
+
+        if grid.width < 2 || grid.height < 2 {
+            return Err(format!("SDF grid must be at least 2x2, got {}x{}", grid.width, grid.height));
+        }
+        if grid.values.len() != grid.width * grid.height {
+            return Err(format!("SDF grid is {}x{} but has {} values", grid.width, grid.height, grid.values.len()));
+        }
+        if !(grid.max[0] > grid.min[0] && grid.max[1] > grid.min[1]) {
+            return Err("SDF bounds must have max > min on both axes".to_string());
+        }
+        if grid.values.iter().any(|v| !v.is_finite()) {
+            return Err("SDF values must be finite".to_string());
+        }
+
+        Ok(grid)
+    }
+
+    fn cell_size(&self) -> Vec2
+    {
+        vec2(
+            (self.max[0] - self.min[0]) / (self.width - 1) as f32,
+            (self.max[1] - self.min[1]) / (self.height - 1) as f32,
+        )
+    }
+
+    fn node_position(&self, i : usize, j : usize) -> Vec2
+    {
+        let cell = self.cell_size();
+        vec2(self.min[0] + i as f32 * cell.x, self.min[1] + j as f32 * cell.y)
+    }
+
+    fn value(&self, i : usize, j : usize) -> f32
+    {
+        self.values[j * self.width + i]
+    }
+
+    // Bilinearly interpolated distance at p. Points outside the bounds are clamped to the border.
+    pub fn sample(&self, p : Vec2) -> f32
+    {
+        let cell = self.cell_size();
+        let fx = ((p.x - self.min[0]) / cell.x).max(0.0).min((self.width - 1) as f32);
+        let fy = ((p.y - self.min[1]) / cell.y).max(0.0).min((self.height - 1) as f32);
+
+        let i0 = (fx.floor() as usize).min(self.width - 2);
+        let j0 = (fy.floor() as usize).min(self.height - 2);
+        let tx = fx - i0 as f32;
+        let ty = fy - j0 as f32;
+
+        let v00 = self.value(i0, j0);
+        let v10 = self.value(i0 + 1, j0);
+        let v01 = self.value(i0, j0 + 1);
+        let v11 = self.value(i0 + 1, j0 + 1);
+
+        let bottom = v00 + (v10 - v00) * tx;
+        let top = v01 + (v11 - v01) * tx;
+        bottom + (top - bottom) * ty
+    }
+
+    // Central-difference gradient of the sampled field, one cell wide in each axis.
+    pub fn gradient(&self, p : Vec2) -> Vec2
+    {
+        let cell = self.cell_size();
+        let dx = self.sample(p + vec2(cell.x, 0.0)) - self.sample(p - vec2(cell.x, 0.0));
+        let dy = self.sample(p + vec2(0.0, cell.y)) - self.sample(p - vec2(0.0, cell.y));
+        vec2(dx / (2.0 * cell.x), dy / (2.0 * cell.y))
+    }
+
+    // Approximates the zero isocontour with marching squares, returning line segments as a flat
+    // list of x, y pairs ready to upload for GL::LINES.
+    pub fn isocontour(&self) -> Vec<f32>
+    {
+        let mut segments : Vec<f32> = vec![];
+
+        for j in 0..self.height - 1
+        {
+            for i in 0..self.width - 1
+            {
+                // Corners in winding order, so edge k runs from corner k to corner k+1.
+                let corners = [(i, j), (i + 1, j), (i + 1, j + 1), (i, j + 1)];
+                let values : Vec<f32> = corners.iter().map(|&(ci, cj)| self.value(ci, cj)).collect();
+
+                let mut crossings : Vec<Vec2> = vec![];
+                for k in 0..4
+                {
+                    let a = values[k];
+                    let b = values[(k + 1) % 4];
+                    if (a < 0.0) != (b < 0.0) {
+                        let t = a / (a - b);
+                        let pa = self.node_position(corners[k].0, corners[k].1);
+                        let pb = self.node_position(corners[(k + 1) % 4].0, corners[(k + 1) % 4].1);
+                        crossings.push(pa + (pb - pa) * t);
+                    }
+                }
+
+                let mut push_segment = |a : Vec2, b : Vec2| {
+                    segments.push(a.x);
+                    segments.push(a.y);
+                    segments.push(b.x);
+                    segments.push(b.y);
+                };
+
+                if crossings.len() == 2 {
+                    push_segment(crossings[0], crossings[1]);
+                } else if crossings.len() == 4 {
+                    // Saddle: use the cell centre to decide which pair of opposite corners is
+                    // connected through the middle.
+                    let centre = values.iter().sum::<f32>() * 0.25;
+                    if (centre < 0.0) == (values[0] < 0.0) {
+                        push_segment(crossings[0], crossings[1]);
+                        push_segment(crossings[2], crossings[3]);
+                    } else {
+                        push_segment(crossings[3], crossings[0]);
+                        push_segment(crossings[1], crossings[2]);
+                    }
+                }
+            }
+        }
+
+        segments
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A field sampled from an analytic distance over [-1, 1] on both axes, a cell every 0.025.
+    fn grid(distance : impl Fn(Vec2) -> f32) -> SdfGrid
+    {
+        let n = 81;
+        let mut grid = SdfGrid { width : n, height : n, min : [-1.0, -1.0], max : [1.0, 1.0], values : vec![] };
+        for j in 0..n {
+            for i in 0..n {
+                let p = grid.node_position(i, j);
+                grid.values.push(distance(p));
+            }
+        }
+        grid
+    }
+
+    fn circle(p : Vec2) -> f32
+    {
+        p.length() - 0.5
+    }
+
+    fn rectangle(p : Vec2) -> f32
+    {
+        let q = p.abs() - vec2(0.4, 0.3);
+        q.max(Vec2::zero()).length() + q.x.max(q.y).min(0.0)
+    }
+
+    fn assert_near(actual : f32, expected : f32, tolerance : f32, what : &str)
+    {
+        assert!((actual - expected).abs() <= tolerance, "{}: {} is not within {} of {}", what, actual, tolerance, expected);
+    }
+
+    #[test]
+    fn samples_match_an_analytic_circle_inside_outside_and_on_it()
+    {
+        let grid = grid(circle);
+        for &p in &[vec2(0.0, 0.0), vec2(0.1, -0.2), vec2(0.5, 0.0), vec2(0.3, 0.4), vec2(-0.7, 0.6), vec2(0.01, 0.93)] {
+            assert_near(grid.sample(p), circle(p), 1e-3, &format!("{:?}", p));
+            // The distance has no gradient at the centre.
+            if p.length() > 0.1 {
+                let expected = p.normalize();
+                let gradient = grid.gradient(p);
+                assert!((gradient - expected).length() < 0.02, "{:?}: gradient {:?}, not {:?}", p, gradient, expected);
+            }
+        }
+        assert!(grid.sample(vec2(0.2, 0.1)) < 0.0);
+        assert!(grid.sample(vec2(0.6, 0.1)) > 0.0);
+    }
+
+    #[test]
+    fn samples_match_an_analytic_box_inside_outside_and_on_it()
+    {
+        let grid = grid(rectangle);
+        let points = [(vec2(0.0, 0.0), vec2(0.0, 0.0)), (vec2(0.4, 0.1), vec2(1.0, 0.0)), (vec2(-0.2, 0.3), vec2(0.0, 1.0)),
+            (vec2(0.7, 0.0), vec2(1.0, 0.0)), (vec2(0.0, -0.6), vec2(0.0, -1.0)), (vec2(0.1, -0.2), vec2(0.0, -1.0))];
+        for &(p, normal) in points.iter() {
+            assert_near(grid.sample(p), rectangle(p), 1e-4, &format!("{:?}", p));
+            if normal != Vec2::zero() {
+                let gradient = grid.gradient(p);
+                assert!((gradient - normal).length() < 1e-3, "{:?}: gradient {:?}, not {:?}", p, gradient, normal);
+            }
+        }
+        // Off a corner the distance is to the corner itself.
+        assert_near(grid.sample(vec2(0.7, 0.7)), 0.5, 1e-3, "corner");
+    }
+
+    #[test]
+    fn points_off_the_grid_take_the_border_value()
+    {
+        let grid = grid(circle);
+        assert_eq!(grid.sample(vec2(3.0, 0.2)), grid.sample(vec2(1.0, 0.2)));
+        assert_eq!(grid.sample(vec2(-5.0, -5.0)), grid.value(0, 0));
+        assert_eq!(grid.sample(vec2(0.25, 7.0)), grid.sample(vec2(0.25, 1.0)));
+        assert_eq!(grid.sample(vec2(9.0, 9.0)), grid.value(80, 80));
+
+        // The field is flat past the border, so the gradient has nothing along the clamped axis.
+        let gradient = grid.gradient(vec2(3.0, 0.2));
+        assert_eq!(gradient.x, 0.0);
+        assert!(gradient.y > 0.0);
+    }
+}
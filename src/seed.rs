@@ -0,0 +1,37 @@
+// The session seed every random stream is derived from. It is kept across page loads so a run
+// can be repeated, and shown in the UI so it can be shared and typed into another browser.
+const STORAGE_KEY : &str = "warmstart.seed";
+
+fn storage() -> Option<web_sys::Storage>
+{
+    web_sys::window()?.local_storage().ok()?
+}
+
+// Seeds stay below 2^32 so they survive the round trip through a JavaScript number input.
+pub fn random_seed() -> u64
+{
+    (js_sys::Math::random() * 4294967296.0) as u64
+}
+
+// The seed saved by an earlier session, or a fresh one on the first load.
+pub fn saved_or_random() -> u64
+{
+    let saved = storage()
+        .and_then(|s| s.get_item(STORAGE_KEY).ok().flatten())
+        .and_then(|text| text.parse().ok());
+    match saved {
+        Some(seed) => seed,
+        None => {
+            let seed = random_seed();
+            save(seed);
+            seed
+        }
+    }
+}
+
+pub fn save(seed : u64)
+{
+    if let Some(s) = storage() {
+        let _ = s.set_item(STORAGE_KEY, &seed.to_string());
+    }
+}
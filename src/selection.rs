@@ -0,0 +1,191 @@
+use glam::*;
+use crate::rail::Anchor;
+
+// The particles inside the rectangle with corners start and end, in simulation space.
+pub fn particles_in_box(positions : &[Vec3], start : Vec2, end : Vec2) -> Vec<usize>
+{
+    let (min, max) = (start.min(end), start.max(end));
+    (0..positions.len()).filter(|&i| {
+        let p = positions[i];
+        p.x >= min.x && p.x <= max.x && p.y >= min.y && p.y <= max.y
+    }).collect()
+}
+
+// A group of particles picked with a rubber band, for the tools to act on together. It lasts until
+// it is cleared or the cloth is rebuilt.
+pub struct Selection
+{
+    pub particles : Vec<usize>,
+    selected : Vec<bool>,
+}
+
+impl Selection {
+    pub fn new() -> Selection
+    {
+        Selection { particles : vec![], selected : vec![] }
+    }
+
+    pub fn reset(&mut self, num_particles : usize)
+    {
+        self.particles.clear();
+        self.selected = vec![false; num_particles];
+    }
+
+    pub fn set(&mut self, particles : Vec<usize>)
+    {
+        self.clear();
+        for &p in particles.iter() {
+            self.selected[p] = true;
+        }
+        self.particles = particles;
+    }
+
+    pub fn clear(&mut self)
+    {
+        for &p in self.particles.iter() {
+            self.selected[p] = false;
+        }
+        self.particles.clear();
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.particles.is_empty()
+    }
+
+    pub fn contains(&self, p : usize) -> bool
+    {
+        self.selected.get(p).cloned().unwrap_or(false)
+    }
+}
+
+// Moves a selection by the cursor's offset from where the drag began. Each particle is held by an
+// anchor at its starting point plus the offset rather than moved outright, so the solver spreads
+// the pull through the cloth. Pinned particles are left out and stay where they are.
+pub struct GroupDrag
+{
+    start : Vec2,
+    origins : Vec<Vec3>,
+    // Handed to the solver with the soft pins for each step.
+    pub anchors : Vec<Anchor>,
+}
+
+impl GroupDrag {
+    pub fn new(particles : &[usize], positions : &[Vec3], is_fixed : &[bool], cursor : Vec2) -> GroupDrag
+    {
+        let free : Vec<usize> = particles.iter().cloned().filter(|&p| !is_fixed[p]).collect();
+        GroupDrag {
+            start : cursor,
+            origins : free.iter().map(|&p| positions[p]).collect(),
+            anchors : free.iter().map(|&p| Anchor::new(p, positions[p])).collect(),
+        }
+    }
+
+    pub fn num_particles(&self) -> usize
+    {
+        self.origins.len()
+    }
+
+    pub fn move_to(&mut self, cursor : Vec2)
+    {
+        let offset = cursor - self.start;
+        for (a, origin) in self.anchors.iter_mut().zip(self.origins.iter()) {
+            a.position = *origin + vec3(offset.x, offset.y, 0.0);
+        }
+    }
+
+    // The particle that stands for the group when measuring how far the drag trails the cursor.
+    pub fn lead_particle(&self) -> Option<usize>
+    {
+        self.anchors.first().map(|a| a.particle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 3 by 3 grid with unit spacing from the origin.
+    fn grid() -> Vec<Vec3>
+    {
+        (0..9).map(|k| vec3((k / 3) as f32, (k % 3) as f32, 0.0)).collect()
+    }
+
+    #[test]
+    fn box_takes_the_particles_inside_it()
+    {
+        assert_eq!(particles_in_box(&grid(), vec2(-0.5, -0.5), vec2(1.5, 0.5)), vec![0, 3]);
+        // Edges count as inside.
+        assert_eq!(particles_in_box(&grid(), vec2(1.0, 1.0), vec2(2.0, 2.0)), vec![4, 5, 7, 8]);
+    }
+
+    #[test]
+    fn box_corners_can_come_in_any_order()
+    {
+        let forwards = particles_in_box(&grid(), vec2(0.5, 0.5), vec2(2.5, 1.5));
+        assert_eq!(particles_in_box(&grid(), vec2(2.5, 1.5), vec2(0.5, 0.5)), forwards);
+        assert_eq!(particles_in_box(&grid(), vec2(0.5, 1.5), vec2(2.5, 0.5)), forwards);
+        assert_eq!(forwards, vec![4, 7]);
+    }
+
+    #[test]
+    fn empty_box_takes_nothing()
+    {
+        assert!(particles_in_box(&grid(), vec2(0.2, 0.2), vec2(0.8, 0.8)).is_empty());
+    }
+
+    #[test]
+    fn selection_membership_follows_set_and_clear()
+    {
+        let mut selection = Selection::new();
+        selection.reset(9);
+        selection.set(vec![1, 4]);
+        assert!(selection.contains(4) && !selection.contains(2));
+        selection.set(vec![2]);
+        assert!(selection.contains(2) && !selection.contains(4));
+        selection.clear();
+        assert!(selection.is_empty() && !selection.contains(2));
+        // Out of range after a rebuild to fewer particles reads as not selected.
+        selection.set(vec![8]);
+        selection.reset(4);
+        assert!(selection.is_empty() && !selection.contains(8));
+    }
+
+    #[test]
+    fn group_drag_leaves_fixed_particles_out()
+    {
+        let positions = grid();
+        let mut is_fixed = vec![false; 9];
+        is_fixed[0] = true;
+        is_fixed[4] = true;
+        let drag = GroupDrag::new(&[0, 1, 4, 5], &positions, &is_fixed, vec2(0.0, 0.0));
+        assert_eq!(drag.num_particles(), 2);
+        let held : Vec<usize> = drag.anchors.iter().map(|a| a.particle).collect();
+        assert_eq!(held, vec![1, 5]);
+        assert_eq!(drag.lead_particle(), Some(1));
+    }
+
+    #[test]
+    fn all_fixed_selection_drags_nothing()
+    {
+        let drag = GroupDrag::new(&[0, 1], &grid(), &[true; 9], vec2(0.0, 0.0));
+        assert_eq!(drag.num_particles(), 0);
+        assert_eq!(drag.lead_particle(), None);
+    }
+
+    #[test]
+    fn group_drag_moves_every_anchor_by_the_same_offset()
+    {
+        let positions = grid();
+        let mut drag = GroupDrag::new(&[1, 2, 7], &positions, &[false; 9], vec2(1.0, 1.0));
+        drag.move_to(vec2(1.5, 0.75));
+        for a in drag.anchors.iter() {
+            assert_eq!(a.position, positions[a.particle] + vec3(0.5, -0.25, 0.0));
+        }
+        // Offsets are from where the drag began, not from the last move.
+        drag.move_to(vec2(1.0, 1.0));
+        for a in drag.anchors.iter() {
+            assert_eq!(a.position, positions[a.particle]);
+        }
+    }
+}
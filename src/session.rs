@@ -0,0 +1,123 @@
+use log::info;
+use serde::{Deserialize, Serialize};
+use glam::*;
+use crate::params::Params;
+
+// The running cloth kept in localStorage so a reload picks up where it left off, warm start and
+// all. Floats are stored as the hex of their bits so the resumed cloth is exactly the saved one.
+const STORAGE_KEY : &str = "warmstart.session";
+const VERSION : u32 = 1;
+
+// Larger sessions would crowd out everything else in the origin's quota, so past this many
+// characters only the parameters are kept.
+const MAX_CHARS : usize = 2_000_000;
+
+// Saved state older than this is dropped rather than resumed, parameters and all.
+pub const RESUME_WINDOW_MS : f64 = 10.0 * 60.0 * 1000.0;
+
+// How often a session is saved while the cloth runs. It is also saved when the page is hidden.
+pub const SAVE_INTERVAL_MS : f64 = 5000.0;
+
+// Everything that makes up the cloth between steps. Each list's length is checked against the
+// cloth the parameters rebuild, so state from a differently built cloth is thrown away.
+#[derive(Serialize, Deserialize)]
+pub struct SavedCloth
+{
+    pub time_step : i32,
+    pub positions : String,
+    pub previous_positions : String,
+    pub is_fixed : String,
+    // Each pin's inverse mass, in the particles' order.
+    #[serde(default)]
+    pub pin_weights : String,
+    pub lambdas : String,
+    pub bend_lambdas : String,
+    pub area_lambdas : String,
+    pub dihedral_lambdas : String,
+    pub anchor_lambdas : String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Session
+{
+    pub version : u32,
+    // Date.now() when it was saved.
+    pub saved_ms : f64,
+    pub params : Params,
+    // None when the cloth was too large to keep or isn't one the parameters can rebuild.
+    pub state : Option<SavedCloth>,
+}
+
+pub fn encode(values : impl Iterator<Item = f32>) -> String
+{
+    values.map(|v| format!("{:08x}", v.to_bits())).collect()
+}
+
+pub fn encode_vec3(values : &[Vec3]) -> String
+{
+    encode(values.iter().flat_map(|v| vec![v.x, v.y, v.z]))
+}
+
+pub fn decode(text : &str) -> Option<Vec<f32>>
+{
+    if text.len() % 8 != 0 || !text.is_ascii() {
+        return None;
+    }
+    (0..text.len() / 8).map(|k| u32::from_str_radix(&text[8 * k..8 * k + 8], 16).ok().map(f32::from_bits)).collect()
+}
+
+pub fn decode_vec3(text : &str) -> Option<Vec<Vec3>>
+{
+    let values = decode(text)?;
+    if values.len() % 3 != 0 {
+        return None;
+    }
+    Some(values.chunks(3).map(|c| vec3(c[0], c[1], c[2])).collect())
+}
+
+fn storage() -> Option<web_sys::Storage>
+{
+    web_sys::window()?.local_storage().ok()?
+}
+
+// The session saved by an earlier page load, if there is one recent enough and of this version.
+pub fn saved() -> Option<Session>
+{
+    let session = storage()
+        .and_then(|s| s.get_item(STORAGE_KEY).ok().flatten())
+        .and_then(|text| serde_json::from_str::<Session>(&text).ok())?;
+    let age = js_sys::Date::now() - session.saved_ms;
+    if session.version != VERSION || age > RESUME_WINDOW_MS {
+        info!("Dropped a saved session {:.0} s old", age / 1000.0);
+        clear();
+        return None;
+    }
+    Some(session)
+}
+
+// Saves the session, or only its parameters if the whole of it won't fit.
+pub fn save(params : &Params, state : Option<SavedCloth>)
+{
+    let storage = match storage() {
+        Some(storage) => storage,
+        None => return,
+    };
+    let mut session = Session { version : VERSION, saved_ms : js_sys::Date::now(), params : params.clone(), state : state };
+    if let Ok(text) = serde_json::to_string(&session) {
+        if text.len() <= MAX_CHARS && storage.set_item(STORAGE_KEY, &text).is_ok() {
+            return;
+        }
+        info!("The cloth is too large to keep across reloads ({} characters), so only the parameters are saved", text.len());
+    }
+    session.state = None;
+    if let Ok(text) = serde_json::to_string(&session) {
+        let _ = storage.set_item(STORAGE_KEY, &text);
+    }
+}
+
+pub fn clear()
+{
+    if let Some(s) = storage() {
+        let _ = s.remove_item(STORAGE_KEY);
+    }
+}
@@ -0,0 +1,275 @@
+use glam::*;
+use log::warn;
+use web_sys::{Performance, WebGlRenderingContext as GL};
+use crate::chart::{ChartRect, ChartUniforms};
+use crate::cloth::{build_cloth, BendModel, ClothBuild, Scene};
+use crate::diffusion;
+use crate::gpu_buffers::GpuBuffers;
+use crate::params::Params;
+use crate::rail::PinMode;
+use crate::solver::{self, ClothState, Scratch, Solver, SolverParams};
+
+// A grid of small hanging cloths, one for each pair of iteration count and eta, stepped alongside
+// the main cloth and pushed by the same gust so they can be compared by eye. Rows are iteration
+// counts and columns are etas. Clicking a cell hands its pair to the main cloth.
+
+pub const GRID_CELLS : usize = 3;
+pub const DEFAULT_ROWS : [i32; GRID_CELLS] = [1, 2, 4];
+pub const DEFAULT_COLUMNS : [f32; GRID_CELLS] = [0.0, 0.5, 0.95];
+
+// Particles along each side of a cell's cloth, and the smallest the budget guard shrinks it to.
+pub const DEFAULT_SIZE : i32 = 15;
+const MIN_SIZE : i32 = 5;
+
+// What the cells may take of a frame between them, and the frames in a row they may go over it
+// before their cloths are made smaller.
+const BUDGET_MS : f64 = 8.0;
+const OVER_BUDGET_FRAMES : usize = 3;
+
+// The gust blows sideways for a second out of every four, at half the strength of gravity.
+const GUST_PERIOD_S : f32 = 4.0;
+const GUST_LENGTH_S : f32 = 1.0;
+const GUST_STRENGTH : f32 = 0.5;
+
+// The push every cell gets after the given step.
+fn gust(step : i32, dt : f32, gravity : Vec3) -> Vec3
+{
+    let t = (step as f32 * dt) % GUST_PERIOD_S;
+    if t < GUST_LENGTH_S {
+        let rise = (std::f32::consts::PI * t / GUST_LENGTH_S).sin();
+        vec3(1.0, 0.0, 0.5) * gravity.length() * GUST_STRENGTH * rise
+    } else {
+        Vec3::zero()
+    }
+}
+
+// One cell's cloth, stepped the way the main cloth is but with only its distance constraints.
+pub struct MiniSim
+{
+    pub iterations : i32,
+    pub eta : f32,
+    cloth : ClothBuild,
+    previous_positions : Vec<Vec3>,
+    pin_modes : Vec<PinMode>,
+    inverse_masses : Vec<f32>,
+    solver : Box<dyn Solver>,
+    scratch : Scratch,
+    params : SolverParams,
+}
+
+impl MiniSim {
+    fn new(params : &Params, iterations : i32, eta : f32, size : i32) -> MiniSim
+    {
+        let cloth = build_cloth(&Scene::Hanging, params.connectivity, false, size, size);
+        let mut scratch = Scratch::default();
+        scratch.resize(cloth.positions.len());
+        let mut inverse_masses = vec![];
+        solver::fill_inverse_masses(&mut inverse_masses, &cloth.is_fixed, &[], &[]);
+        MiniSim {
+            iterations : iterations,
+            eta : eta,
+            previous_positions : cloth.positions.clone(),
+            inverse_masses : inverse_masses,
+            pin_modes : cloth.is_fixed.iter().map(|&fixed| if fixed {PinMode::Fixed} else {PinMode::Free}).collect(),
+            cloth : cloth,
+            solver : solver::registry().swap_remove(params.solver_index),
+            scratch : scratch,
+            params : SolverParams {
+                dt : params.dt,
+                num_iterations : iterations,
+                sheet_iterations : vec![iterations],
+                sheet_warm_start : vec![params.warm_start],
+                warm_start : params.warm_start,
+                eta : eta,
+                adaptive_eta : params.adaptive_eta,
+                lambda_limit : None,
+                stiffness : params.stiffness,
+                tension_only : params.tension_only,
+                use_area_constraints : false,
+                area_stiffness : params.area_stiffness,
+                bend_model : BendModel::None,
+                bend_stiffness : params.bend_stiffness,
+                pin_stiffness : params.pin_stiffness,
+            },
+        }
+    }
+
+    fn step(&mut self, acceleration : Vec3, nu : f32)
+    {
+        for ((p, pm1), &inverse_mass) in self.cloth.positions.iter_mut().zip(self.previous_positions.iter_mut()).zip(self.inverse_masses.iter()) {
+            solver::integrate_particle(p, pm1, inverse_mass, acceleration, nu, self.params.dt);
+        }
+        let mut state = ClothState {
+            positions : &mut self.cloth.positions,
+            previous_positions : &mut self.previous_positions,
+            is_fixed : &self.cloth.is_fixed,
+            inverse_masses : &self.inverse_masses,
+            pin_modes : &self.pin_modes,
+            curves : &[],
+            sheet_of : &self.cloth.sheet_of,
+            constraints : &mut self.cloth.constraints,
+            active_constraints : None,
+            area_constraints : &mut [],
+            bend_constraints : &mut [],
+            dihedral_constraints : &mut [],
+            contacts : &mut [],
+            contact_distance : 0.0,
+            collider_contacts : &mut [],
+            colliders : None,
+            weight : None,
+            anchors : &mut [],
+            observers : &mut [],
+        };
+        self.solver.solve(&mut state, &self.params, &mut self.scratch);
+    }
+
+    pub fn mean_residual(&self) -> f32
+    {
+        diffusion::mean_residual(&self.cloth.positions, &self.cloth.constraints)
+    }
+
+    // Every constraint as a line in the XY plane the hanging cloth faces.
+    fn line_vertices(&self) -> Vec<f32>
+    {
+        let positions = &self.cloth.positions;
+        self.cloth.constraints.iter().flat_map(|c| {
+            let (a, b) = (positions[c.p0], positions[c.p1]);
+            vec![a.x, a.y, b.x, b.y]
+        }).collect()
+    }
+
+    // The view center and scale that fit the cloth in a cell of the given aspect ratio.
+    fn framing(&self, aspect_ratio : f32) -> (Vec2, f32)
+    {
+        let (min, max) = self.cloth.positions.iter().fold((Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)), |(min, max), p| {
+            (min.min(p.truncate()), max.max(p.truncate()))
+        });
+        let extent = (max - min).max(Vec2::splat(1e-3));
+        let scale = (1.8 / extent.y).min(1.8 * aspect_ratio / extent.x);
+        ((min + max) * 0.5, scale)
+    }
+}
+
+pub struct SmallMultiples
+{
+    pub rows : [i32; GRID_CELLS],
+    pub columns : [f32; GRID_CELLS],
+    pub size : i32,
+    // Row by row.
+    pub cells : Vec<MiniSim>,
+    // Steps taken since the cells were built, which the gust is timed from.
+    step : i32,
+    frames_over_budget : usize,
+}
+
+impl SmallMultiples {
+    pub fn new(params : &Params, rows : [i32; GRID_CELLS], columns : [f32; GRID_CELLS]) -> SmallMultiples
+    {
+        let mut grid = SmallMultiples { rows : rows, columns : columns, size : DEFAULT_SIZE, cells : vec![], step : 0, frames_over_budget : 0 };
+        grid.rebuild(params);
+        grid
+    }
+
+    // Starts every cell again from rest, so they stay in step with each other.
+    pub fn rebuild(&mut self, params : &Params)
+    {
+        let size = self.size;
+        self.cells = self.rows.iter()
+            .flat_map(|&iterations| self.columns.iter().map(move |&eta| (iterations, eta)))
+            .map(|(iterations, eta)| MiniSim::new(params, iterations, eta, size))
+            .collect();
+        self.step = 0;
+        self.frames_over_budget = 0;
+    }
+
+    // Steps every cell as many times as the main cloth stepped this frame. Cells that keep going
+    // over the budget are rebuilt smaller.
+    pub fn advance(&mut self, performance : &Performance, params : &Params, gravity : Vec3, steps : u32)
+    {
+        let start = performance.now();
+        for _ in 0..steps {
+            self.step += 1;
+            let acceleration = gravity + gust(self.step, params.dt, gravity);
+            for cell in self.cells.iter_mut() {
+                cell.step(acceleration, params.nu);
+            }
+        }
+        let elapsed_ms = performance.now() - start;
+
+        self.frames_over_budget = if elapsed_ms > BUDGET_MS {self.frames_over_budget + 1} else {0};
+        if self.frames_over_budget >= OVER_BUDGET_FRAMES && self.size > MIN_SIZE {
+            self.size = (self.size * 3 / 4).max(MIN_SIZE);
+            warn!("Small multiples took {:.1}ms a frame, down to {}x{} particles", elapsed_ms, self.size, self.size);
+            self.rebuild(params);
+        }
+    }
+
+    pub fn cell_rect(k : usize, canvas_width : i32, canvas_height : i32) -> ChartRect
+    {
+        let (width, height) = (canvas_width / GRID_CELLS as i32, canvas_height / GRID_CELLS as i32);
+        ChartRect { x : (k % GRID_CELLS) as i32 * width, y : (k / GRID_CELLS) as i32 * height, width : width, height : height }
+    }
+
+    // The cell under a point in canvas pixels from the top left.
+    pub fn cell_at(&self, x : i32, y : i32, canvas_width : i32, canvas_height : i32) -> Option<&MiniSim>
+    {
+        (0..self.cells.len()).find(|&k| {
+            let rect = SmallMultiples::cell_rect(k, canvas_width, canvas_height);
+            x >= rect.x && x < rect.x + rect.width && y >= rect.y && y < rect.y + rect.height
+        }).map(|k| &self.cells[k])
+    }
+
+    // Draws every cell over the whole canvas with the line program already in use, leaving the
+    // viewport and view uniforms set for the last cell, as a chart does.
+    pub fn render(&self, gl : &GL, buffers : &mut GpuBuffers, uniforms : &ChartUniforms, position : u32, canvas_width : i32, canvas_height : i32, cloth_color : [f32; 3], frame_color : [f32; 3], clear : [f32; 4])
+    {
+        for (k, cell) in self.cells.iter().enumerate() {
+            let rect = SmallMultiples::cell_rect(k, canvas_width, canvas_height);
+            let bottom = canvas_height - rect.y - rect.height;
+            gl.viewport(rect.x, bottom, rect.width, rect.height);
+            gl.enable(GL::SCISSOR_TEST);
+            gl.scissor(rect.x, bottom, rect.width, rect.height);
+            gl.clear_color(clear[0], clear[1], clear[2], clear[3]);
+            gl.clear(GL::COLOR_BUFFER_BIT);
+
+            let mut draw = |name : String, vertices : &[f32], mode : u32, color : [f32; 3]| {
+                let array = js_sys::Float32Array::from(vertices);
+                let buffer = buffers.get_or_create(gl, &name, vertices.len() * 4);
+                gl.bind_buffer(GL::ARRAY_BUFFER, Some(&buffer));
+                gl.buffer_data_with_array_buffer_view(GL::ARRAY_BUFFER, &array, GL::STATIC_DRAW);
+                gl.vertex_attrib_pointer_with_i32(position, 2, GL::FLOAT, false, 0, 0);
+                gl.uniform3f(uniforms.color.as_ref(), color[0], color[1], color[2]);
+                gl.draw_arrays(mode, 0, vertices.len() as i32 / 2);
+            };
+
+            gl.uniform1f(uniforms.aspect_ratio.as_ref(), 1.0);
+            gl.uniform2f(uniforms.view_center.as_ref(), 0.0, 0.0);
+            gl.uniform1f(uniforms.view_scale.as_ref(), 1.0);
+            draw(format!("small_multiples_{}_frame", k), &[-0.995, -0.99, 0.995, -0.99, 0.995, 0.99, -0.995, 0.99], GL::LINE_LOOP, frame_color);
+
+            let aspect_ratio = rect.width as f32 / rect.height.max(1) as f32;
+            let (center, scale) = cell.framing(aspect_ratio);
+            gl.uniform1f(uniforms.aspect_ratio.as_ref(), aspect_ratio);
+            gl.uniform2f(uniforms.view_center.as_ref(), center.x, center.y);
+            gl.uniform1f(uniforms.view_scale.as_ref(), scale);
+            draw(format!("small_multiples_{}", k), &cell.line_vertices(), GL::LINES, cloth_color);
+        }
+        gl.disable(GL::SCISSOR_TEST);
+    }
+}
+
+// Exactly three comma separated values, as the axis text fields take them.
+pub fn parse_axis<T : std::str::FromStr>(text : &str) -> Option<[T; GRID_CELLS]>
+{
+    let mut values = text.split(',').map(|v| v.trim().parse::<T>().ok());
+    let axis = [values.next()??, values.next()??, values.next()??];
+    if values.next().is_some() {
+        return None;
+    }
+    Some(axis)
+}
+
+pub fn axis_text<T : std::fmt::Display>(axis : &[T; GRID_CELLS]) -> String
+{
+    axis.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(", ")
+}
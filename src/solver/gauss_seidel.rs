@@ -0,0 +1,22 @@
+use crate::profiling;
+use super::passes::{project_all, Apply};
+use super::{ClothState, Scratch, Solver, SolverParams};
+
+// Each constraint moves the particles straight away, so later constraints in the same sweep
+// already see the correction.
+pub struct GaussSeidel;
+
+impl Solver for GaussSeidel {
+    fn name(&self) -> &'static str
+    {
+        "Gauss-Seidel"
+    }
+
+    fn project(&mut self, state : &mut ClothState, params : &SolverParams, scratch : &mut Scratch, iteration : i32)
+    {
+        let effective_eta = 0.7*params.eta;
+
+        let _iteration = profiling::scope("iterations", || format!("{} iteration {}{}", self.name(), iteration, if iteration == 0 {" (warm start)"} else {""}));
+        project_all(state, params, scratch, iteration, effective_eta, Apply::Immediately);
+    }
+}
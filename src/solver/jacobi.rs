@@ -0,0 +1,74 @@
+use glam::*;
+use crate::profiling;
+use super::passes::{finish_iteration, project_all, Apply};
+use super::{ClothState, ParamSpec, Scratch, Solver, SolverParams};
+
+// Every constraint reads the positions from the start of the iteration and the summed
+// corrections are applied together. The passes scale them down by the relaxation factor, along
+// with what the lambdas take up.
+pub struct Jacobi
+{
+    relaxation : f32,
+}
+
+impl Jacobi {
+    const PARAMS : [ParamSpec; 1] = [
+        ParamSpec { name : "jacobi_relaxation", label : "Jacobi Relaxation", min : 0.0, max : 1.0, step : 0.01, default : 0.6 },
+    ];
+
+    pub fn new() -> Jacobi
+    {
+        Jacobi { relaxation : Self::PARAMS[0].default }
+    }
+}
+
+impl Solver for Jacobi {
+    fn name(&self) -> &'static str
+    {
+        "Jacobi"
+    }
+
+    fn project(&mut self, state : &mut ClothState, params : &SolverParams, scratch : &mut Scratch, iteration : i32)
+    {
+        let _iteration = profiling::scope("iterations", || format!("{} iteration {}{}", self.name(), iteration, if iteration == 0 {" (warm start)"} else {""}));
+        project_all(state, params, scratch, iteration, params.eta, Apply::ToWorkspace(self.relaxation));
+    }
+
+    fn apply(&mut self, state : &mut ClothState, _params : &SolverParams, scratch : &mut Scratch, iteration : i32)
+    {
+        let _apply = profiling::scope("jacobi apply", || format!("Jacobi apply {}", iteration));
+        for i in 0..state.positions.len() {
+            let impulse = scratch.workspace[i];
+            state.positions[i] += impulse;
+            scratch.workspace[i] = vec3(0.0, 0.0, 0.0);
+            let veloImpulse = scratch.velocity_workspace[i];
+            state.previous_positions[i] += veloImpulse;
+            scratch.velocity_workspace[i] = vec3(0.0, 0.0, 0.0);
+        }
+        if let Some(w) = &mut state.weight {
+            w.position += scratch.weight_workspace;
+            scratch.weight_workspace = vec3(0.0, 0.0, 0.0);
+        }
+        finish_iteration(state, iteration);
+    }
+
+    fn apply_label(&self) -> &'static str
+    {
+        "Jacobi apply, collisions and pins"
+    }
+
+    fn param_specs(&self) -> &'static [ParamSpec]
+    {
+        &Self::PARAMS
+    }
+
+    fn param(&self, _index : usize) -> f32
+    {
+        self.relaxation
+    }
+
+    fn set_param(&mut self, _index : usize, value : f32)
+    {
+        self.relaxation = value;
+    }
+}
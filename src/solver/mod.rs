@@ -0,0 +1,222 @@
+use glam::*;
+use crate::cloth::{AreaConstraint, BendModel, Constraint, DihedralConstraint};
+use crate::collision::{ColliderContact, Colliders};
+use crate::contacts::Contact;
+use crate::observer::SimulationObserver;
+use crate::rail::{Anchor, Curve, PinMode};
+use crate::weight::Weight;
+
+mod gauss_seidel;
+mod jacobi;
+mod passes;
+
+pub use gauss_seidel::GaussSeidel;
+pub use passes::{boost_newborn_contacts, decay_idle_lambdas, finish_iteration};
+pub use jacobi::Jacobi;
+
+// Everything a solver may read or move during the constraint iterations of one step. The
+// integration before and the collider push-out after stay with the caller, unless the push-out
+// is interleaved with the iterations.
+pub struct ClothState<'a>
+{
+    pub positions : &'a mut Vec<Vec3>,
+    pub previous_positions : &'a mut Vec<Vec3>,
+    pub is_fixed : &'a [bool],
+    // Each particle's inverse mass, from fill_inverse_masses.
+    pub inverse_masses : &'a [f32],
+    // Particles pinned to one of the curves are projected back onto it after every iteration.
+    pub pin_modes : &'a [PinMode],
+    pub curves : &'a [Curve],
+    pub sheet_of : &'a [usize],
+    pub constraints : &'a mut [Constraint],
+    // When set, only these distance constraints are solved and the rest keep their lambdas.
+    pub active_constraints : Option<&'a [usize]>,
+    pub area_constraints : &'a mut [AreaConstraint],
+    pub bend_constraints : &'a mut [Constraint],
+    pub dihedral_constraints : &'a mut [DihedralConstraint],
+    pub contacts : &'a mut [Contact],
+    pub contact_distance : f32,
+    // Static collider contacts, only filled in when they are solved as constraints.
+    pub collider_contacts : &'a mut [ColliderContact],
+    // Colliders to push particles out of at the end of every iteration, when collisions are
+    // interleaved with the constraints.
+    pub colliders : Option<Colliders<'a>>,
+    pub weight : Option<&'a mut Weight>,
+    // Soft pins, each holding an anchored particle.
+    pub anchors : &'a mut [Anchor],
+    pub observers : &'a mut [Box<dyn SimulationObserver>],
+}
+
+// 1 for a free particle and 0 for a held one, except that fixed pins take their pin weight. Only
+// pins are weighted: a particle held by a tool, such as the pluck or the freeze, never moves.
+pub fn fill_inverse_masses(inverse_masses : &mut Vec<f32>, is_fixed : &[bool], pin_modes : &[PinMode], pin_weights : &[f32])
+{
+    inverse_masses.clear();
+    inverse_masses.extend(is_fixed.iter().enumerate().map(|(p, &fixed)| match (fixed, pin_modes.get(p)) {
+        (false, _) => 1.0,
+        (true, Some(PinMode::Fixed)) => pin_weights.get(p).cloned().unwrap_or(0.0),
+        (true, _) => 0.0,
+    }));
+}
+
+// One Verlet step of a particle before the constraints see it. nu damps the motion carried over
+// from the last step, and a weighted pin moves a fraction of what a free particle would, so it
+// creeps rather than falls.
+pub fn integrate_particle(position : &mut Vec3, previous_position : &mut Vec3, inverse_mass : f32, acceleration : Vec3, nu : f32, dt : f32)
+{
+    let p0 = *position;
+    if inverse_mass > 0.0 {
+        let d = (p0 - *previous_position) * nu + acceleration * dt;
+        *position = p0 + d * inverse_mass;
+    }
+    *previous_position = p0;
+}
+
+impl ClothState<'_> {
+    // Called once before a solve starts.
+    pub fn notify_solve_begin(&mut self)
+    {
+        for observer in self.observers.iter_mut() {
+            observer.on_solve_begin(self.positions);
+        }
+    }
+
+    // Solvers call this once at the end of every iteration.
+    pub fn notify_iteration_end(&mut self, iteration : i32)
+    {
+        if self.observers.is_empty() {
+            return;
+        }
+        for observer in self.observers.iter_mut() {
+            observer.on_iteration_end(iteration, self.positions, self.constraints);
+        }
+    }
+}
+
+// The global settings for a step, with the per-sheet overrides already resolved.
+pub struct SolverParams
+{
+    pub dt : f32,
+    pub num_iterations : i32,
+    pub sheet_iterations : Vec<i32>,
+    pub sheet_warm_start : Vec<bool>,
+    pub warm_start : bool,
+    pub eta : f32,
+    pub adaptive_eta : f32,
+    pub stiffness : f32,
+    pub tension_only : bool,
+    pub use_area_constraints : bool,
+    pub area_stiffness : f32,
+    pub bend_model : BendModel,
+    pub bend_stiffness : f32,
+    pub pin_stiffness : f32,
+    // The largest stored impulse a distance constraint may hold, when clamping is on.
+    pub lambda_limit : Option<f32>,
+}
+
+impl SolverParams {
+    // The loop runs long enough for the sheet wanting the most iterations and the others sit out
+    // the rest.
+    pub fn max_iterations(&self) -> i32
+    {
+        self.sheet_iterations.iter().cloned().fold(self.num_iterations, i32::max)
+    }
+}
+
+// Buffers kept between steps so the solvers don't allocate every frame.
+#[derive(Default)]
+pub struct Scratch
+{
+    pub workspace : Vec<Vec3>,
+    pub velocity_workspace : Vec<Vec3>,
+    pub weight_workspace : Vec3,
+    // Times the lambda clamp engaged since the model last collected the count.
+    pub lambda_clamps : u32,
+}
+
+impl Scratch {
+    pub fn resize(&mut self, num_particles : usize)
+    {
+        self.workspace.clear();
+        self.workspace.resize(num_particles, vec3(0.0, 0.0, 0.0));
+        self.velocity_workspace.clear();
+        self.velocity_workspace.resize(num_particles, vec3(0.0, 0.0, 0.0));
+        self.weight_workspace = vec3(0.0, 0.0, 0.0);
+    }
+}
+
+// The most any particle's velocity can plausibly change in one step, in units per second.
+const MAX_VELOCITY_CHANGE : f32 = 10.0;
+
+// The clamp on a stored distance impulse: the position-level impulse, momentum times dt, that
+// would take MAX_VELOCITY_CHANGE out of every particle of the cloth at once, times safety.
+// Particles have unit mass.
+pub fn lambda_limit(num_particles : usize, dt : f32, safety : f32) -> f32
+{
+    safety * num_particles as f32 * MAX_VELOCITY_CHANGE * dt
+}
+
+// An extra tunable a solver exposes, rendered as a slider under the solver selector.
+pub struct ParamSpec
+{
+    pub name : &'static str,
+    pub label : &'static str,
+    pub min : f32,
+    pub max : f32,
+    pub step : f32,
+    pub default : f32,
+}
+
+pub trait Solver
+{
+    fn name(&self) -> &'static str;
+
+    // One iteration in two halves, so the phase stepper can stop between them: the constraint
+    // pass, which warm starts on iteration 0, then whatever moves the particles by the pass's
+    // corrections and ends the iteration.
+    fn project(&mut self, state : &mut ClothState, params : &SolverParams, scratch : &mut Scratch, iteration : i32);
+
+    fn apply(&mut self, state : &mut ClothState, _params : &SolverParams, _scratch : &mut Scratch, iteration : i32)
+    {
+        finish_iteration(state, iteration);
+    }
+
+    // What the second half does, for the phase stepper's label.
+    fn apply_label(&self) -> &'static str
+    {
+        "collisions and pins"
+    }
+
+    fn solve(&mut self, state : &mut ClothState, params : &SolverParams, scratch : &mut Scratch)
+    {
+        for iteration in 0..params.max_iterations()
+        {
+            self.project(state, params, scratch, iteration);
+            self.apply(state, params, scratch, iteration);
+        }
+    }
+
+    fn param_specs(&self) -> &'static [ParamSpec]
+    {
+        &[]
+    }
+
+    fn param(&self, _index : usize) -> f32
+    {
+        0.0
+    }
+
+    fn set_param(&mut self, _index : usize, _value : f32) {}
+}
+
+// Every solver offered in the selector, in display order. New solvers only need adding here.
+pub fn registry() -> Vec<Box<dyn Solver>>
+{
+    vec![
+        Box::new(Jacobi::new()),
+        Box::new(GaussSeidel),
+    ]
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,579 @@
+use glam::*;
+use crate::cloth::BendModel;
+use crate::contacts::Contact;
+use crate::profiling;
+use crate::rail::PinMode;
+use super::{ClothState, Scratch, SolverParams};
+
+// Fraction of a tension-only constraint's stored impulse kept per step while it is slack.
+pub(super) const SLACK_LAMBDA_DECAY : f32 = 0.5;
+
+// Where a pass puts its position corrections: straight into the positions, so later constraints
+// see them, or into the scratch workspace for the solver to apply at the end of the iteration.
+// Workspace corrections are scaled by the solver's relaxation, and so is what the lambdas take up,
+// so a stored impulse only ever holds what the positions were actually moved by.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Apply
+{
+    Immediately,
+    ToWorkspace(f32),
+}
+
+impl Apply {
+    fn relaxation(self) -> f32
+    {
+        match self {
+            Apply::Immediately => 1.0,
+            Apply::ToWorkspace(relaxation) => relaxation,
+        }
+    }
+}
+
+// The distance constraint families, which share one pass.
+#[derive(Clone, Copy, PartialEq)]
+pub enum DistanceSet
+{
+    Structural,
+    Bending,
+}
+
+// One pass of every constraint kind in the usual order. effective_eta is the warm start factor
+// after any solver-specific scaling.
+pub fn project_all(state : &mut ClothState, params : &SolverParams, scratch : &mut Scratch, iteration : i32, effective_eta : f32, apply : Apply)
+{
+    project_distance_constraints(state, params, scratch, iteration, effective_eta, apply, DistanceSet::Structural);
+
+    // Bending after stretching, so it works on edges that are already close to length.
+    match params.bend_model {
+        BendModel::None => {}
+        BendModel::Distance => project_distance_constraints(state, params, scratch, iteration, effective_eta, apply, DistanceSet::Bending),
+        BendModel::Dihedral => project_dihedral_constraints(state, params, scratch, iteration, effective_eta, apply),
+    }
+
+    if params.use_area_constraints {
+        project_area_constraints(state, params, scratch, iteration, effective_eta, apply);
+    }
+
+    if !state.contacts.is_empty() {
+        project_contacts(state, params, scratch, iteration, effective_eta, apply);
+    }
+
+    if !state.collider_contacts.is_empty() {
+        project_collider_contacts(state, params, scratch, iteration, effective_eta, apply);
+    }
+
+    if state.weight.is_some() && iteration < params.num_iterations {
+        project_weight_attachment(state, params, scratch, iteration, effective_eta, apply);
+    }
+
+    if !state.anchors.is_empty() {
+        project_anchors(state, params, scratch, iteration, effective_eta, apply);
+    }
+}
+
+// The end of every iteration, once its corrections are in the positions.
+pub fn finish_iteration(state : &mut ClothState, iteration : i32)
+{
+    project_colliders(state);
+    project_curve_pins(state);
+    state.notify_iteration_end(iteration);
+}
+
+// The interleaved collider push-out. It moves the positions directly even under Jacobi, after
+// the iteration's corrections are applied, since a particle left inside a collider by the
+// relaxation would only be pushed part of the way out.
+pub fn project_colliders(state : &mut ClothState)
+{
+    if let Some(colliders) = state.colliders {
+        let _collision = profiling::scope("collision", || "Interleaved collider response".to_string());
+        colliders.push_out(state.positions, state.previous_positions, state.is_fixed);
+    }
+}
+
+// Puts every particle pinned to a curve back on its nearest point, keeping whatever it slid along
+// the curve. Particles that are held, such as by the pluck tool, are left alone.
+pub fn project_curve_pins(state : &mut ClothState)
+{
+    for (i, mode) in state.pin_modes.iter().enumerate() {
+        if let PinMode::OnCurve(curve) = *mode {
+            if !state.is_fixed[i] {
+                state.positions[i] = state.curves[curve].closest_point(state.positions[i]);
+            }
+        }
+    }
+}
+
+// The warm start factor for a constraint, eased off by how far its fastest particle has moved
+// from where the step started, so impulses stored where the cloth is being thrown around don't
+// kick it further while the settled regions keep all of theirs.
+pub(super) fn local_eta(positions : &[Vec3], previous_positions : &[Vec3], particles : &[usize], effective_eta : f32, adaptive_eta : f32) -> f32
+{
+    if adaptive_eta <= 0.0 {
+        return effective_eta;
+    }
+    let motion = particles.iter().map(|&p| (positions[p] - previous_positions[p]).length()).fold(0.0f32, f32::max);
+    let x = adaptive_eta * motion;
+    effective_eta / (1.0 + x * x)
+}
+
+// Bending distance constraints use the bend stiffness, are always solved in full and may push
+// as well as pull.
+pub fn project_distance_constraints(state : &mut ClothState, params : &SolverParams, scratch : &mut Scratch, iteration : i32, effective_eta : f32, apply : Apply, set : DistanceSet)
+{
+    let (constraints, active_constraints, stiffness, tension_only) = match set {
+        DistanceSet::Structural => (&mut *state.constraints, state.active_constraints, params.stiffness, params.tension_only),
+        DistanceSet::Bending => (&mut *state.bend_constraints, None, params.bend_stiffness, false),
+    };
+    let baseATilde = 1.0f32 / (stiffness * params.dt * params.dt);
+
+    let count = active_constraints.map_or(constraints.len(), |active| active.len());
+    let relaxation = apply.relaxation();
+    let observed = set == DistanceSet::Structural && !state.observers.is_empty();
+
+    for k in 0..count
+    {
+        let index = active_constraints.map_or(k, |active| active[k]);
+        let c = &mut constraints[index];
+        let aTilde = baseATilde / c.stiffness_scale;
+
+        let sheet = state.sheet_of[c.p0];
+        if iteration >= params.sheet_iterations[sheet] {
+            continue;
+        }
+
+        let p0InvMass = state.inverse_masses[c.p0];
+        let p1InvMass = state.inverse_masses[c.p1];
+        let totalInvMass = p0InvMass + p1InvMass;
+        if totalInvMass == 0.0 {
+            // Neither end can move, so the stored impulse is kept for when one can again.
+            continue;
+        }
+        let mut p0 = state.positions[c.p0];
+        let mut p1 = state.positions[c.p1];
+
+        let len = (p0-p1).length();
+        let normal = (p0-p1)/len;
+
+        let residual = len - c.length;
+
+        if tension_only && residual <= 0.0 {
+            // Slack constraints exert nothing, and their stored impulse fades rather than
+            // lingering to warm start a constraint that is no longer active.
+            if iteration == 0 {
+                c.lambda *= SLACK_LAMBDA_DECAY;
+            }
+            continue;
+        }
+
+        let mut velocityCorrection = vec3(0.0, 0.0, 0.0);
+
+        let mut deltaLambda = -(residual * normal + aTilde*if iteration == 0 {vec3(0.0, 0.0, 0.0)} else {c.lambda}) / (totalInvMass + aTilde);
+        if iteration == 0 && params.sheet_warm_start[sheet]{
+            let eta = local_eta(state.positions, state.previous_positions, &[c.p0, c.p1], effective_eta, params.adaptive_eta);
+            deltaLambda += eta*c.lambda;
+            velocityCorrection +=  eta*c.lambda;
+        }
+        deltaLambda *= relaxation;
+        velocityCorrection *= relaxation;
+
+        if iteration == 0
+        {
+            c.lambda = vec3(0.0, 0.0, 0.0);
+        }
+
+        c.lambda += deltaLambda;
+
+        if tension_only {
+            // A thread can only pull, so the accumulated impulse may never push the ends apart.
+            let push = c.lambda.dot(normal);
+            if push > 0.0 {
+                c.lambda -= normal * push;
+                deltaLambda -= normal * push;
+            }
+        }
+
+        if let Some(limit) = params.lambda_limit {
+            let magnitude = c.lambda.length();
+            if magnitude > limit {
+                // The step's correction gives up whatever took the impulse past the limit.
+                let excess = c.lambda * (1.0 - limit / magnitude);
+                c.lambda -= excess;
+                deltaLambda -= excess;
+                scratch.lambda_clamps += 1;
+            }
+        }
+
+        // Each end moves along the gradient by its own inverse mass, as XPBD has it. The
+        // denominator above already holds both ends, so splitting the correction by their share
+        // of it as well would leave a constraint between two free particles half as stiff.
+        let p0Correction = deltaLambda * p0InvMass;
+        let p1Correction = -deltaLambda * p1InvMass;
+
+        let p0VeloCorrection = velocityCorrection*p0InvMass;
+        let p1VeloCorrection = -velocityCorrection*p1InvMass;
+
+        if let Apply::ToWorkspace(_) = apply
+        {
+            scratch.workspace[c.p0] += p0Correction;
+            scratch.workspace[c.p1] += p1Correction;
+
+            //scratch.velocity_workspace[c.p0] += p0VeloCorrection;
+            //scratch.velocity_workspace[c.p1] += p1VeloCorrection;
+        }
+        else
+        {
+            p0 += p0Correction;
+            p1 += p1Correction;
+
+            state.positions[c.p0] = p0;
+            state.positions[c.p1] = p1;
+
+            if observed {
+                for observer in state.observers.iter_mut() {
+                    observer.on_constraint_projected(iteration, index, c, p0Correction, p1Correction);
+                }
+            }
+
+            //state.previous_positions[c.p0] += p0VeloCorrection;
+            //state.previous_positions[c.p1] += p1VeloCorrection;
+        }
+    }
+}
+
+// Sheets given no iterations never reach the iteration 0 reset, so instead of keeping a stale
+// value to warm start from whenever iterations resume, their stored impulses fade each step the
+// way a slack constraint's do.
+pub fn decay_idle_lambdas(state : &mut ClothState, params : &SolverParams)
+{
+    let idle = |sheet : usize| params.sheet_iterations[sheet] <= 0;
+    if params.num_iterations > 0 && !params.sheet_iterations.iter().any(|&n| n <= 0) {
+        return;
+    }
+
+    for c in state.constraints.iter_mut().chain(state.bend_constraints.iter_mut()) {
+        if idle(state.sheet_of[c.p0]) {
+            c.lambda *= SLACK_LAMBDA_DECAY;
+        }
+    }
+    for c in state.area_constraints.iter_mut() {
+        if idle(state.sheet_of[c.particles[0]]) {
+            c.lambda *= SLACK_LAMBDA_DECAY;
+        }
+    }
+    for c in state.dihedral_constraints.iter_mut() {
+        if idle(state.sheet_of[c.particles[0]]) {
+            c.lambda *= SLACK_LAMBDA_DECAY;
+        }
+    }
+    for c in state.contacts.iter_mut() {
+        if idle(c.key.sheets.0) && idle(c.key.sheets.1) {
+            c.lambda *= SLACK_LAMBDA_DECAY;
+        }
+    }
+    for c in state.collider_contacts.iter_mut() {
+        if idle(state.sheet_of[c.particle]) {
+            c.lambda *= SLACK_LAMBDA_DECAY;
+        }
+    }
+    // The attachment follows the global count.
+    if params.num_iterations <= 0 {
+        if let Some(w) = &mut state.weight {
+            w.lambda *= SLACK_LAMBDA_DECAY;
+        }
+    }
+}
+
+// Settings shared by the constraints of one N-body pass.
+#[derive(Clone, Copy)]
+struct NBodyStep
+{
+    aTilde : f32,
+    iteration : i32,
+    warm_start : bool,
+    effective_eta : f32,
+    relaxation : f32,
+}
+
+// The XPBD update of one scalar constraint over any number of particles, used by the area and
+// dihedral passes. lambda accumulates under the same warm start rules as the distance
+// constraints, and each particle moves along its gradient in target, which is either the
+// positions or the workspace.
+fn project_n_body(target : &mut [Vec3], inverse_masses : &[f32], particles : &[usize], gradients : &[Vec3], residual : f32, lambda : &mut f32, step : NBodyStep)
+{
+    let invMass = |p : usize| inverse_masses[p];
+    if particles.iter().all(|&p| invMass(p) == 0.0) {
+        return;
+    }
+
+    let denominator : f32 = particles.iter().zip(gradients).map(|(&p, g)| invMass(p) * g.length_squared()).sum::<f32>() + step.aTilde;
+    if denominator < 1e-12 {
+        return;
+    }
+
+    let mut deltaLambda = -(residual + step.aTilde * if step.iteration == 0 {0.0} else {*lambda}) / denominator;
+    if step.iteration == 0 && step.warm_start {
+        deltaLambda += step.effective_eta * *lambda;
+    }
+    deltaLambda *= step.relaxation;
+
+    if step.iteration == 0 {
+        *lambda = 0.0;
+    }
+    *lambda += deltaLambda;
+
+    for (&p, g) in particles.iter().zip(gradients) {
+        target[p] += *g * invMass(p) * deltaLambda;
+    }
+}
+
+// One pass over the quad area constraints.
+pub fn project_area_constraints(state : &mut ClothState, params : &SolverParams, scratch : &mut Scratch, iteration : i32, effective_eta : f32, apply : Apply)
+{
+    let aTilde = 1.0f32 / (params.area_stiffness * params.dt * params.dt);
+
+    for c in state.area_constraints.iter_mut()
+    {
+        let sheet = state.sheet_of[c.particles[0]];
+        if iteration >= params.sheet_iterations[sheet] {
+            continue;
+        }
+
+        let gradients = c.gradients(state.positions);
+        let residual = c.current_area(state.positions) - c.area;
+
+        let step = NBodyStep { aTilde : aTilde, iteration : iteration, warm_start : params.sheet_warm_start[sheet], effective_eta : local_eta(state.positions, state.previous_positions, &c.particles, effective_eta, params.adaptive_eta), relaxation : apply.relaxation() };
+        let target = match apply {
+            Apply::ToWorkspace(_) => &mut scratch.workspace[..],
+            Apply::Immediately => &mut state.positions[..],
+        };
+        project_n_body(target, state.inverse_masses, &c.particles, &gradients, residual, &mut c.lambda, step);
+    }
+}
+
+// One pass over the dihedral bending constraints, with the bend stiffness as compliance.
+pub fn project_dihedral_constraints(state : &mut ClothState, params : &SolverParams, scratch : &mut Scratch, iteration : i32, effective_eta : f32, apply : Apply)
+{
+    let aTilde = 1.0f32 / (params.bend_stiffness * params.dt * params.dt);
+
+    for c in state.dihedral_constraints.iter_mut()
+    {
+        let sheet = state.sheet_of[c.particles[0]];
+        if iteration >= params.sheet_iterations[sheet] {
+            continue;
+        }
+
+        let (angle, gradients) = match c.angle_and_gradients(state.positions) {
+            Some(found) => found,
+            None => {
+                // A collapsed triangle has no fold to hold, and its old impulse would warm start
+                // against whatever shape it opens back up into.
+                if iteration == 0 {
+                    c.lambda = 0.0;
+                }
+                continue;
+            }
+        };
+        let residual = angle - c.angle;
+
+        let step = NBodyStep { aTilde : aTilde, iteration : iteration, warm_start : params.sheet_warm_start[sheet], effective_eta : local_eta(state.positions, state.previous_positions, &c.particles, effective_eta, params.adaptive_eta), relaxation : apply.relaxation() };
+        let target = match apply {
+            Apply::ToWorkspace(_) => &mut scratch.workspace[..],
+            Apply::Immediately => &mut state.positions[..],
+        };
+        project_n_body(target, state.inverse_masses, &c.particles, &gradients, residual, &mut c.lambda, step);
+    }
+}
+
+// The correction for one contact, updating its lambda. first_iteration_eta is the warm start
+// factor on a step's first iteration and None on later ones.
+fn contact_correction(c : &mut Contact, positions : &[Vec3], inverse_masses : &[f32], contact_distance : f32, first_iteration_eta : Option<f32>, relaxation : f32) -> Option<(Vec3, Vec3)>
+{
+    let (a, b) = c.key.particles;
+    let aInvMass = inverse_masses[a];
+    let bInvMass = inverse_masses[b];
+    let totalInvMass = aInvMass + bInvMass;
+    if totalInvMass == 0.0 {
+        return None;
+    }
+
+    let delta = positions[a] - positions[b];
+    let len = delta.length();
+    if len < 1e-6 {
+        return None;
+    }
+    let normal = delta / len;
+    let residual = len - contact_distance;
+
+    let mut deltaLambda = -residual / totalInvMass;
+    if let Some(eta) = first_iteration_eta {
+        deltaLambda += eta * c.lambda;
+        c.lambda = 0.0;
+    }
+    deltaLambda *= relaxation;
+
+    let lambda = (c.lambda + deltaLambda).max(0.0);
+    deltaLambda = lambda - c.lambda;
+    c.lambda = lambda;
+
+    Some((normal * deltaLambda * aInvMass, -normal * deltaLambda * bInvMass))
+}
+
+// Pushes apart contacting particles from different sheets. The accumulated lambda is clamped
+// so the contact can only ever push, and warm starting only applies when both sheets use it.
+pub fn project_contacts(state : &mut ClothState, params : &SolverParams, scratch : &mut Scratch, iteration : i32, effective_eta : f32, apply : Apply)
+{
+    for c in state.contacts.iter_mut()
+    {
+        let (a, b) = c.key.particles;
+        let (sa, sb) = c.key.sheets;
+        if iteration >= params.sheet_iterations[sa].max(params.sheet_iterations[sb]) {
+            continue;
+        }
+
+        let first_iteration_eta = if iteration == 0 {
+            Some(if params.sheet_warm_start[sa] && params.sheet_warm_start[sb] {effective_eta} else {0.0})
+        } else {
+            None
+        };
+        let (aCorrection, bCorrection) = match contact_correction(c, state.positions, state.inverse_masses, state.contact_distance, first_iteration_eta, apply.relaxation()) {
+            Some(corrections) => corrections,
+            None => continue,
+        };
+        match apply {
+            Apply::ToWorkspace(_) => {
+                scratch.workspace[a] += aCorrection;
+                scratch.workspace[b] += bCorrection;
+            }
+            Apply::Immediately => {
+                state.positions[a] += aCorrection;
+                state.positions[b] += bCorrection;
+            }
+        }
+    }
+}
+
+// Extra Gauss-Seidel passes over the contacts younger than max_age steps, after the solver is
+// done, so fresh contacts catch up with the warm-started ones around them.
+pub fn boost_newborn_contacts(state : &mut ClothState, max_age : u32, passes : i32)
+{
+    for _ in 0..passes {
+        for c in state.contacts.iter_mut().filter(|c| c.age < max_age) {
+            let (a, b) = c.key.particles;
+            if let Some((aCorrection, bCorrection)) = contact_correction(c, state.positions, state.inverse_masses, state.contact_distance, None, 1.0) {
+                state.positions[a] += aCorrection;
+                state.positions[b] += bCorrection;
+            }
+        }
+    }
+}
+
+pub fn project_collider_contacts(state : &mut ClothState, params : &SolverParams, scratch : &mut Scratch, iteration : i32, effective_eta : f32, apply : Apply)
+{
+    for c in state.collider_contacts.iter_mut()
+    {
+        let sheet = state.sheet_of[c.particle];
+        if iteration >= params.sheet_iterations[sheet] || state.is_fixed[c.particle] {
+            continue;
+        }
+
+        let residual = state.positions[c.particle].dot(c.normal) - c.offset;
+
+        let mut deltaLambda = -residual;
+        if iteration == 0 {
+            if params.sheet_warm_start[sheet] {
+                deltaLambda += effective_eta * c.lambda;
+            }
+            c.lambda = 0.0;
+        }
+        deltaLambda *= apply.relaxation();
+
+        let lambda = (c.lambda + deltaLambda).max(0.0);
+        deltaLambda = lambda - c.lambda;
+        c.lambda = lambda;
+
+        let correction = c.normal * deltaLambda;
+        match apply {
+            Apply::ToWorkspace(_) => scratch.workspace[c.particle] += correction,
+            Apply::Immediately => state.positions[c.particle] += correction,
+        }
+    }
+}
+
+// The attachment between the weight and its particle, solved like a distance constraint but
+// with the corrections split by the two very different inverse masses.
+pub fn project_weight_attachment(state : &mut ClothState, params : &SolverParams, scratch : &mut Scratch, iteration : i32, effective_eta : f32, apply : Apply)
+{
+    let aTilde = 1.0f32 / (params.stiffness * params.dt * params.dt);
+    let w = state.weight.as_mut().unwrap();
+    let p = w.attached_particle;
+
+    let particleInvMass = state.inverse_masses[p];
+    let weightInvMass = w.inverse_mass();
+    let totalInvMass = particleInvMass + weightInvMass;
+    if totalInvMass == 0.0 {
+        return;
+    }
+
+    let delta = state.positions[p] - w.position;
+    let len = delta.length();
+    if len < 1e-6 {
+        return;
+    }
+    let normal = delta / len;
+    let residual = len - w.length;
+
+    let mut deltaLambda = -(residual * normal + aTilde*if iteration == 0 {vec3(0.0, 0.0, 0.0)} else {w.lambda}) / (totalInvMass + aTilde);
+    if iteration == 0 && params.warm_start {
+        deltaLambda += effective_eta*w.lambda;
+    }
+    deltaLambda *= apply.relaxation();
+    if iteration == 0 {
+        w.lambda = vec3(0.0, 0.0, 0.0);
+    }
+    w.lambda += deltaLambda;
+
+    let particleCorrection = deltaLambda * particleInvMass;
+    let weightCorrection = -deltaLambda * weightInvMass;
+
+    match apply {
+        Apply::ToWorkspace(_) => {
+            scratch.workspace[p] += particleCorrection;
+            scratch.weight_workspace += weightCorrection;
+        }
+        Apply::Immediately => {
+            state.positions[p] += particleCorrection;
+            w.position += weightCorrection;
+        }
+    }
+}
+
+// The constraint is the particle's offset from its anchor point, so unlike a distance constraint
+// it never needs a direction and is well defined at zero length. Particles held by a tool are left
+// to it.
+pub fn project_anchors(state : &mut ClothState, params : &SolverParams, scratch : &mut Scratch, iteration : i32, effective_eta : f32, apply : Apply)
+{
+    let aTilde = 1.0f32 / (params.pin_stiffness * params.dt * params.dt);
+    for a in state.anchors.iter_mut() {
+        let p = a.particle;
+        let sheet = state.sheet_of[p];
+        if iteration >= params.sheet_iterations[sheet] || state.is_fixed[p] {
+            continue;
+        }
+
+        let residual = state.positions[p] - a.position;
+        let mut deltaLambda = -(residual + aTilde*if iteration == 0 {vec3(0.0, 0.0, 0.0)} else {a.lambda}) / (1.0 + aTilde);
+        if iteration == 0 {
+            if params.sheet_warm_start[sheet] {
+                deltaLambda += effective_eta*a.lambda;
+            }
+            a.lambda = vec3(0.0, 0.0, 0.0);
+        }
+        deltaLambda *= apply.relaxation();
+        a.lambda += deltaLambda;
+
+        match apply {
+            Apply::ToWorkspace(_) => scratch.workspace[p] += deltaLambda,
+            Apply::Immediately => state.positions[p] += deltaLambda,
+        }
+    }
+}
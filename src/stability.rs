@@ -0,0 +1,159 @@
+use crate::params::Params;
+
+// Quantities derived from the parameters that say whether the step can resolve the constraints.
+// A distance constraint on its own is a spring between unit masses, whose natural frequency is
+// sqrt(k/m). The step samples it at 1/dt, so it can only follow frequencies up to 1/(2 dt). Past
+// that XPBD still converges, but the constraint acts as a rigid link rather than the stiffness set.
+
+// Every particle has unit mass. Only the weight is heavier, and it has an attachment of its own.
+pub const PARTICLE_MASS : f32 = 1.0;
+
+// Ratios of the constraint frequency to the Nyquist frequency where the readout turns yellow
+// and red.
+pub const MARGINAL_RATIO : f32 = 0.5;
+pub const UNRESOLVED_RATIO : f32 = 1.0;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Margin
+{
+    Resolved,
+    Marginal,
+    Unresolved,
+}
+
+impl Margin {
+    pub fn color(&self) -> &'static str
+    {
+        match self {
+            Margin::Resolved => "#1B7F2A",
+            Margin::Marginal => "#B07A00",
+            Margin::Unresolved => "#C62828",
+        }
+    }
+}
+
+pub struct StiffnessResolution
+{
+    // The constraint's natural frequency and the step's Nyquist frequency, in Hz.
+    pub frequency : f32,
+    pub nyquist : f32,
+}
+
+impl StiffnessResolution {
+    pub fn of(params : &Params) -> StiffnessResolution
+    {
+        StiffnessResolution {
+            frequency : (params.stiffness / PARTICLE_MASS).sqrt() / (2.0 * std::f32::consts::PI),
+            nyquist : 0.5 / params.dt,
+        }
+    }
+
+    pub fn ratio(&self) -> f32
+    {
+        self.frequency / self.nyquist
+    }
+
+    pub fn margin(&self) -> Margin
+    {
+        match self.ratio() {
+            r if r < MARGINAL_RATIO => Margin::Resolved,
+            r if r < UNRESOLVED_RATIO => Margin::Marginal,
+            _ => Margin::Unresolved,
+        }
+    }
+}
+
+// The stiffness whose frequency is ratio times the Nyquist frequency at dt.
+pub fn stiffness_at_ratio(dt : f32, ratio : f32) -> f32
+{
+    let omega = ratio * std::f32::consts::PI / dt;
+    PARTICLE_MASS * omega * omega
+}
+
+// The time step at which the stiffness's frequency is ratio times the Nyquist frequency.
+pub fn dt_at_ratio(stiffness : f32, ratio : f32) -> f32
+{
+    ratio * std::f32::consts::PI / (stiffness / PARTICLE_MASS).sqrt()
+}
+
+// What would bring an unresolved stiffness back under the limit, or None if it is resolved.
+pub fn suggestion(params : &Params) -> Option<String>
+{
+    if StiffnessResolution::of(params).margin() != Margin::Unresolved {
+        return None;
+    }
+    // Just inside the limit, so following the suggestion clears the warning.
+    let stiffness = stiffness_at_ratio(params.dt, 0.99 * UNRESOLVED_RATIO);
+    let steps_per_second = (1.0 / dt_at_ratio(params.stiffness, 0.99 * UNRESOLVED_RATIO)).ceil();
+    let exponent = (stiffness.log10() * 10.0).floor() / 10.0;
+    Some(format!("Lower the stiffness below 10^{:.1}, or take a time step of 1/{} s or less.", exponent, steps_per_second))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(stiffness : f32, dt : f32) -> Params
+    {
+        Params { stiffness : stiffness, dt : dt, ..Params::default() }
+    }
+
+    fn ratio(stiffness : f32, dt : f32) -> f32
+    {
+        StiffnessResolution::of(&params(stiffness, dt)).ratio()
+    }
+
+    #[test]
+    fn stiffness_and_dt_at_a_ratio_give_that_ratio_back()
+    {
+        for &dt in &[1.0 / 30.0, 1.0 / 60.0, 1.0 / 240.0] {
+            for &r in &[0.1, 0.5, 0.99, 1.0, 3.0] {
+                let stiffness = stiffness_at_ratio(dt, r);
+                assert!((ratio(stiffness, dt) - r).abs() < 1e-5 * r, "stiffness {} at dt {}", stiffness, dt);
+                let dt = dt_at_ratio(stiffness, r);
+                assert!((ratio(stiffness, dt) - r).abs() < 1e-5 * r, "dt {} at stiffness {}", dt, stiffness);
+            }
+        }
+    }
+
+    #[test]
+    fn the_margin_changes_at_its_thresholds()
+    {
+        let dt = 1.0 / 60.0;
+        let margin = |r : f32| StiffnessResolution::of(&params(stiffness_at_ratio(dt, r), dt)).margin();
+        assert!(margin(0.0) == Margin::Resolved);
+        assert!(margin(0.999 * MARGINAL_RATIO) == Margin::Resolved);
+        assert!(margin(1.001 * MARGINAL_RATIO) == Margin::Marginal);
+        assert!(margin(0.999 * UNRESOLVED_RATIO) == Margin::Marginal);
+        assert!(margin(1.001 * UNRESOLVED_RATIO) == Margin::Unresolved);
+        assert!(margin(100.0) == Margin::Unresolved);
+    }
+
+    // Reads the stiffness exponent and the steps per second back out of a suggestion.
+    fn suggested(text : &str) -> (f32, f32)
+    {
+        let exponent = text.split("10^").nth(1).unwrap().split(',').next().unwrap();
+        let steps = text.split("1/").nth(1).unwrap().split(' ').next().unwrap();
+        (exponent.parse().unwrap(), steps.parse().unwrap())
+    }
+
+    #[test]
+    fn following_either_suggestion_clears_unresolved()
+    {
+        for &(stiffness, dt) in &[(1e8, 1.0 / 60.0), (4e5, 1.0 / 60.0), (1e6, 1.0 / 120.0), (1e4, 1.0 / 10.0)] {
+            let text = suggestion(&params(stiffness, dt)).unwrap();
+            let (exponent, steps_per_second) = suggested(&text);
+            let margin = |p : Params| StiffnessResolution::of(&p).margin();
+            assert!(margin(params(10.0f32.powf(exponent), dt)) != Margin::Unresolved, "{}", text);
+            assert!(margin(params(stiffness, 1.0 / steps_per_second)) != Margin::Unresolved, "{}", text);
+        }
+    }
+
+    #[test]
+    fn there_is_no_suggestion_short_of_unresolved()
+    {
+        let dt = 1.0 / 60.0;
+        assert!(suggestion(&params(5000.0, dt)).is_none());
+        assert!(suggestion(&params(stiffness_at_ratio(dt, 0.9), dt)).is_none());
+    }
+}
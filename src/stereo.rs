@@ -0,0 +1,56 @@
+// Red-cyan anaglyph drawing. The view has no perspective camera, only the depth shear, so each
+// eye's picture is the same view with particles moved sideways in proportion to their depth.
+// Positive depth moves right in the left eye's picture and left in the right's, which puts it in
+// front of the screen through the glasses.
+
+const STORAGE_KEY : &str = "warmstart.interocular";
+
+// Sideways shift per unit of depth between the two eyes' pictures.
+pub const DEFAULT_INTEROCULAR : f32 = 0.08;
+pub const MAX_INTEROCULAR : f32 = 0.3;
+
+pub struct Eye
+{
+    // Added to x per unit of depth.
+    pub shift : f32,
+    // Which of red, green and blue the eye's pass writes.
+    pub mask : [bool; 3],
+}
+
+pub fn eyes(interocular : f32) -> [Eye; 2]
+{
+    [
+        Eye { shift : 0.5 * interocular, mask : [true, false, false] },
+        Eye { shift : -0.5 * interocular, mask : [false, true, true] },
+    ]
+}
+
+// Each channel filter passes only part of a color, so both eyes draw in grey of the same
+// brightness to see the same picture.
+pub fn grey(color : [f32; 3]) -> [f32; 3]
+{
+    let luminance = 0.299 * color[0] + 0.587 * color[1] + 0.114 * color[2];
+    [luminance; 3]
+}
+
+fn storage() -> Option<web_sys::Storage>
+{
+    web_sys::window()?.local_storage().ok()?
+}
+
+// The interocular distance set in an earlier session, or the default.
+pub fn saved_interocular() -> f32
+{
+    storage()
+        .and_then(|s| s.get_item(STORAGE_KEY).ok().flatten())
+        .and_then(|text| text.parse::<f32>().ok())
+        .filter(|f| f.is_finite() && *f >= 0.0 && *f <= MAX_INTEROCULAR)
+        .unwrap_or(DEFAULT_INTEROCULAR)
+}
+
+pub fn save(interocular : f32)
+{
+    if let Some(s) = storage() {
+        let _ = s.set_item(STORAGE_KEY, &interocular.to_string());
+    }
+}
@@ -0,0 +1,181 @@
+use glam::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+use crate::cloth::Constraint;
+use crate::observer::SimulationObserver;
+
+// One distance constraint's correction, as the Gauss-Seidel sweep applied it.
+pub struct SweepMove
+{
+    pub iteration : i32,
+    pub constraint : u32,
+    pub p0 : u32,
+    pub p1 : u32,
+    pub d0 : Vec3,
+    pub d1 : Vec3,
+}
+
+// Every correction of one solve in the order it was made, to play back without solving again.
+// Positions are also kept at the start of the solve and at the end of every iteration, which
+// takes in whatever the other passes moved, so seeking only replays moves from the nearest
+// iteration boundary. Recording stops at the capacity given to restart, and the log keeps its
+// allocations from one recording to the next.
+pub struct SweepLog
+{
+    start : Vec<Vec3>,
+    moves : Vec<SweepMove>,
+    // The number of moves made by the end of each iteration, and the positions then.
+    iteration_ends : Vec<(usize, Vec<Vec3>)>,
+    capacity : usize,
+    pub truncated : bool,
+    recording : bool,
+}
+
+impl SweepLog {
+    pub fn new() -> SweepLog
+    {
+        SweepLog {
+            start : vec![],
+            moves : vec![],
+            iteration_ends : vec![],
+            capacity : 0,
+            truncated : false,
+            recording : false,
+        }
+    }
+
+    // Empties the log to record the next solve, which can make at most capacity moves.
+    pub fn restart(&mut self, capacity : usize)
+    {
+        self.start.clear();
+        self.moves.clear();
+        self.moves.reserve(capacity);
+        self.iteration_ends.clear();
+        self.capacity = capacity;
+        self.truncated = false;
+        self.recording = false;
+    }
+
+    pub fn num_moves(&self) -> usize
+    {
+        self.moves.len()
+    }
+}
+
+impl SimulationObserver for SweepLog {
+    fn on_solve_begin(&mut self, positions : &[Vec3])
+    {
+        // Only the first solve after a restart is recorded.
+        if self.start.is_empty() {
+            self.start.extend_from_slice(positions);
+            self.recording = true;
+        }
+    }
+
+    fn on_constraint_projected(&mut self, iteration : i32, index : usize, c : &Constraint, d0 : Vec3, d1 : Vec3)
+    {
+        if !self.recording {
+            return;
+        }
+        if self.moves.len() == self.capacity {
+            self.truncated = true;
+            return;
+        }
+        self.moves.push(SweepMove { iteration : iteration, constraint : index as u32, p0 : c.p0 as u32, p1 : c.p1 as u32, d0 : d0, d1 : d1 });
+    }
+
+    fn on_iteration_end(&mut self, _iteration : i32, positions : &[Vec3], _constraints : &[Constraint])
+    {
+        if self.recording {
+            self.iteration_ends.push((self.moves.len(), positions.to_vec()));
+        }
+    }
+
+    fn on_step_end(&mut self, _stats : &crate::observer::StepStats)
+    {
+        self.recording = false;
+    }
+}
+
+// Plays a recorded solve back one constraint at a time over a cloth of its own.
+pub struct SweepReplay
+{
+    log : Rc<RefCell<SweepLog>>,
+    // How many moves have been applied to positions.
+    pub cursor : usize,
+    pub positions : Vec<Vec3>,
+    pub playing : bool,
+    // Moves per second.
+    pub speed : f32,
+    last_ms : Option<f64>,
+    carry : f64,
+}
+
+impl SweepReplay {
+    pub fn new(log : Rc<RefCell<SweepLog>>) -> SweepReplay
+    {
+        let positions = log.borrow().start.clone();
+        SweepReplay { log : log, cursor : 0, positions : positions, playing : false, speed : 200.0, last_ms : None, carry : 0.0 }
+    }
+
+    pub fn len(&self) -> usize
+    {
+        self.log.borrow().num_moves()
+    }
+
+    pub fn is_truncated(&self) -> bool
+    {
+        self.log.borrow().truncated
+    }
+
+    // The constraint and iteration of the move last applied, to be highlighted.
+    pub fn current(&self) -> Option<(usize, i32)>
+    {
+        let log = self.log.borrow();
+        self.cursor.checked_sub(1).and_then(|k| log.moves.get(k)).map(|m| (m.constraint as usize, m.iteration))
+    }
+
+    // Puts positions where the solve had them after cursor moves. At the very end that is where
+    // the last iteration left them, the other passes included.
+    pub fn seek(&mut self, cursor : usize)
+    {
+        let log = self.log.borrow();
+        let len = log.moves.len();
+        let cursor = cursor.min(len);
+        let checkpoint = if cursor == len && len > 0 {
+            log.iteration_ends.last()
+        } else {
+            log.iteration_ends.iter().rev().find(|(count, _)| *count < cursor)
+        };
+        let (base, from) = match checkpoint {
+            Some((count, positions)) => (positions, *count),
+            None => (&log.start, 0),
+        };
+        self.positions.clone_from(base);
+        for m in log.moves[from..cursor].iter() {
+            self.positions[m.p0 as usize] += m.d0;
+            self.positions[m.p1 as usize] += m.d1;
+        }
+        self.cursor = cursor;
+    }
+
+    // Moves forward by however many moves the time since the last frame is worth at the current
+    // speed, stopping at the end.
+    pub fn advance(&mut self, timestamp_ms : f64)
+    {
+        let last = self.last_ms.replace(timestamp_ms);
+        if !self.playing {
+            return;
+        }
+        let elapsed = last.map_or(0.0, |last| (timestamp_ms - last) / 1000.0);
+        self.carry += elapsed * self.speed as f64;
+        let steps = self.carry.floor() as usize;
+        self.carry -= steps as f64;
+        if steps > 0 {
+            self.seek(self.cursor + steps);
+        }
+        if self.cursor == self.len() {
+            self.playing = false;
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use serde::{Deserialize, Serialize};
+use crate::rng::{Pcg32, FRAME_TIMES_STREAM};
+
+// Where the simulation gets its frame timestamps from. The render loop passes in the real
+// requestAnimationFrame time and uses whatever comes back.
+pub trait TimeSource
+{
+    fn now(&mut self, real_timestamp : f64) -> f64;
+
+    // The frame intervals handed out so far, for sources that can be replayed.
+    fn intervals(&self) -> Option<&[f64]>
+    {
+        None
+    }
+}
+
+pub struct RealTime;
+
+impl TimeSource for RealTime {
+    fn now(&mut self, real_timestamp : f64) -> f64
+    {
+        real_timestamp
+    }
+}
+
+// A virtual clock starting at zero that advances by a scripted interval per frame, either drawn
+// from a seeded uniform distribution or read back from a recorded trace. Every interval used is
+// kept so a run can be exported and replayed exactly.
+pub struct ScriptedTime
+{
+    clock : f64,
+    intervals : Vec<f64>,
+    frame : usize,
+    rng : Option<Pcg32>,
+}
+
+impl ScriptedTime {
+    pub const MIN_INTERVAL_MS : f64 = 8.0;
+    pub const MAX_INTERVAL_MS : f64 = 40.0;
+
+    // Used once a replayed trace runs out.
+    const FALLBACK_INTERVAL_MS : f64 = 1000.0 / 60.0;
+
+    pub fn random(seed : u64) -> ScriptedTime
+    {
+        ScriptedTime {
+            clock : 0.0,
+            intervals : vec![],
+            frame : 0,
+            rng : Some(Pcg32::new(seed, FRAME_TIMES_STREAM)),
+        }
+    }
+
+    pub fn from_trace(intervals : Vec<f64>) -> ScriptedTime
+    {
+        ScriptedTime {
+            clock : 0.0,
+            intervals : intervals,
+            frame : 0,
+            rng : None,
+        }
+    }
+}
+
+impl TimeSource for ScriptedTime {
+    fn now(&mut self, _real_timestamp : f64) -> f64
+    {
+        // The first frame is the reset frame and sits at time zero; every later frame consumes
+        // one interval.
+        if self.frame > 0 {
+            let index = self.frame - 1;
+            if index == self.intervals.len() {
+                let interval = match &mut self.rng {
+                    Some(rng) => rng.range(Self::MIN_INTERVAL_MS, Self::MAX_INTERVAL_MS),
+                    None => Self::FALLBACK_INTERVAL_MS,
+                };
+                self.intervals.push(interval);
+            }
+            self.clock += self.intervals[index];
+        }
+        self.frame += 1;
+        self.clock
+    }
+
+    fn intervals(&self) -> Option<&[f64]>
+    {
+        Some(&self.intervals)
+    }
+}
+
+// The on-disk form of a ScriptedTime run, so a trace that produced a pop can be replayed.
+#[derive(Serialize, Deserialize)]
+pub struct FrameTrace
+{
+    pub intervals_ms : Vec<f64>,
+    // The session seed the trace was recorded under. Older traces don't have one.
+    #[serde(default)]
+    pub seed : Option<u64>,
+}
+
+impl FrameTrace {
+    pub fn from_json(bytes : &[u8]) -> Result<FrameTrace, String>
+    {
+        let trace : FrameTrace = serde_json::from_slice(bytes).map_err(|e| format!("Invalid frame trace JSON: {}", e))?;
+
+        // A negative or infinite interval would run the clock backwards or off the end, and a
+        // NaN would poison every time after it.
+        for (k, interval) in trace.intervals_ms.iter().enumerate() {
+            if !interval.is_finite() || *interval < 0.0 {
+                return Err(format!("Interval {} is {}ms; intervals must be finite and not negative", k, interval));
+            }
+        }
+
+        Ok(trace)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(source : &mut dyn TimeSource, frames : usize) -> Vec<f64>
+    {
+        (0..frames).map(|frame| source.now(1000.0 + frame as f64 * 7.0)).collect()
+    }
+
+    #[test]
+    fn scripted_time_ignores_the_real_timestamps()
+    {
+        let times = run(&mut ScriptedTime::random(3), 20);
+        assert_eq!(times[0], 0.0);
+        for pair in times.windows(2) {
+            let interval = pair[1] - pair[0];
+            assert!(interval >= ScriptedTime::MIN_INTERVAL_MS && interval <= ScriptedTime::MAX_INTERVAL_MS, "interval {}", interval);
+        }
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_frames()
+    {
+        assert_eq!(run(&mut ScriptedTime::random(7), 50), run(&mut ScriptedTime::random(7), 50));
+        assert_ne!(run(&mut ScriptedTime::random(7), 50), run(&mut ScriptedTime::random(8), 50));
+    }
+
+    #[test]
+    fn a_recorded_trace_replays_the_run_then_falls_back()
+    {
+        let mut recorded = ScriptedTime::random(11);
+        let times = run(&mut recorded, 30);
+        let mut replayed = ScriptedTime::from_trace(recorded.intervals().unwrap().to_vec());
+        let replayed_times = run(&mut replayed, 31);
+        assert_eq!(&replayed_times[..30], &times[..]);
+        assert!((replayed_times[30] - replayed_times[29] - 1000.0 / 60.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn a_trace_with_a_negative_or_non_finite_interval_is_rejected()
+    {
+        let trace = FrameTrace::from_json(br#"{"intervals_ms": [16.0, 0.0, 33.5], "seed": 4}"#).unwrap();
+        assert_eq!(trace.intervals_ms, vec![16.0, 0.0, 33.5]);
+        assert_eq!(trace.seed, Some(4));
+        assert!(FrameTrace::from_json(br#"{"intervals_ms": [16.0, -1.0]}"#).err().map_or(false, |e| e.contains("Interval 1")));
+        // JSON has no NaN or infinity, but 1e999 overflows to infinity when parsed.
+        assert!(FrameTrace::from_json(br#"{"intervals_ms": [1e999]}"#).is_err());
+        assert!(FrameTrace::from_json(b"not json").is_err());
+    }
+
+    #[test]
+    fn real_time_passes_the_timestamp_through()
+    {
+        assert_eq!(RealTime.now(1234.5), 1234.5);
+        assert!(RealTime.intervals().is_none());
+    }
+}
@@ -0,0 +1,203 @@
+use glam::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use crate::collision::Sphere;
+use crate::view::ViewTransform;
+
+// How a parameter moves between two keyframes that set it.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Interpolation
+{
+    Linear,
+    Step,
+}
+
+// Every parameter a timeline may set. Counts and switches jump at the keyframe, the rest are
+// interpolated. Switches are written as 0 or 1.
+pub const PARAMETERS : [(&str, Interpolation); 12] = [
+    ("eta", Interpolation::Linear),
+    ("lambda_diffusion", Interpolation::Linear),
+    ("nu", Interpolation::Linear),
+    ("stiffness", Interpolation::Linear),
+    ("area_stiffness", Interpolation::Linear),
+    ("weight_mass", Interpolation::Linear),
+    ("gravity_angle", Interpolation::Linear),
+    ("num_iterations", Interpolation::Step),
+    ("gravity_period", Interpolation::Step),
+    ("warm_start", Interpolation::Step),
+    ("tension_only", Interpolation::Step),
+    ("area_constraints", Interpolation::Step),
+];
+
+#[derive(Deserialize, Clone, Copy)]
+pub struct CameraPose
+{
+    pub center : [f32; 2],
+    pub scale : f32,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TimelineEvent
+{
+    Reset,
+    // Unpins one particle, or every pinned particle when none is given.
+    ReleasePin { particle : Option<usize> },
+    // Pins one particle where it is, as a click with the pin tool would.
+    Pin { particle : usize },
+    // Displaces every free particle, which the integrator turns into a velocity kick.
+    Nudge { offset : [f32; 3] },
+    // Displaces one particle if it is free, like a quick drag.
+    Pull { particle : usize, offset : [f32; 3] },
+}
+
+#[derive(Deserialize)]
+pub struct Keyframe
+{
+    // Simulation seconds since playback started.
+    pub time : f32,
+    #[serde(default)]
+    pub camera : Option<CameraPose>,
+    // Where the pinned particles are, as an offset from where they were when playback started.
+    #[serde(default)]
+    pub pins : Option<[f32; 3]>,
+    #[serde(default)]
+    pub params : HashMap<String, f32>,
+    #[serde(default)]
+    pub events : Vec<TimelineEvent>,
+}
+
+// A scripted run for recording demos and benchmarks: keyframes of camera poses, pin offsets,
+// parameter values and one-off events, played back against the fixed-step simulation clock so
+// every playback is identical. A timeline may also bring its own colliders, and a kinetic energy
+// the run should stay under.
+#[derive(Deserialize)]
+pub struct Timeline
+{
+    #[serde(default)]
+    pub colliders : Vec<Sphere>,
+    #[serde(default)]
+    pub max_kinetic_energy : Option<f32>,
+    keyframes : Vec<Keyframe>,
+}
+
+impl Timeline {
+    pub fn from_json(text : &str) -> Result<Timeline, String>
+    {
+        let timeline : Timeline = serde_json::from_str(text).map_err(|e| format!("Invalid timeline JSON: {}", e))?;
+
+        if timeline.keyframes.is_empty() {
+            return Err("Timeline has no keyframes".to_string());
+        }
+        for (k, sphere) in timeline.colliders.iter().enumerate() {
+            if !(sphere.radius > 0.0) || sphere.center.iter().any(|c| !c.is_finite()) {
+                return Err(format!("Collider {} needs a finite center and a positive radius", k));
+            }
+        }
+        for (k, keyframe) in timeline.keyframes.iter().enumerate() {
+            if !keyframe.time.is_finite() || keyframe.time < 0.0 {
+                return Err(format!("Keyframe {} has invalid time {}", k, keyframe.time));
+            }
+            if k > 0 && keyframe.time < timeline.keyframes[k - 1].time {
+                return Err(format!("Keyframe {} at {}s comes before keyframe {} at {}s; keyframes must be sorted by time",
+                    k, keyframe.time, k - 1, timeline.keyframes[k - 1].time));
+            }
+            for (name, value) in keyframe.params.iter() {
+                if !PARAMETERS.iter().any(|(known, _)| known == name) {
+                    let known : Vec<&str> = PARAMETERS.iter().map(|(known, _)| *known).collect();
+                    return Err(format!("Keyframe {} sets unknown parameter {:?}; expected one of {}", k, name, known.join(", ")));
+                }
+                if !value.is_finite() {
+                    return Err(format!("Keyframe {} sets {} to non-finite value {}", k, name, value));
+                }
+            }
+            if let Some(camera) = &keyframe.camera {
+                if !(camera.scale > 0.0) {
+                    return Err(format!("Keyframe {} has camera scale {}; it must be positive", k, camera.scale));
+                }
+            }
+            if keyframe.pins.map_or(false, |pins| pins.iter().any(|p| !p.is_finite())) {
+                return Err(format!("Keyframe {} has a non-finite pin offset", k));
+            }
+        }
+
+        Ok(timeline)
+    }
+
+    pub fn duration(&self) -> f32
+    {
+        self.keyframes.last().map_or(0.0, |k| k.time)
+    }
+
+    // Value of a parameter at time t, or None before the first keyframe that sets it. After the
+    // last such keyframe the value holds.
+    pub fn value_at(&self, name : &str, interpolation : Interpolation, t : f32) -> Option<f32>
+    {
+        let mut previous : Option<(f32, f32)> = None;
+        for keyframe in self.keyframes.iter() {
+            if let Some(&value) = keyframe.params.get(name) {
+                if keyframe.time > t {
+                    return match previous {
+                        Some((t0, v0)) if interpolation == Interpolation::Linear && keyframe.time > t0 => {
+                            Some(v0 + (value - v0) * (t - t0) / (keyframe.time - t0))
+                        }
+                        Some((_, v0)) => Some(v0),
+                        None => None,
+                    };
+                }
+                previous = Some((keyframe.time, value));
+            }
+        }
+        previous.map(|(_, v)| v)
+    }
+
+    // Camera pose at time t, interpolated like a continuous parameter.
+    pub fn camera_at(&self, t : f32) -> Option<ViewTransform>
+    {
+        let to_view = |c : &CameraPose| ViewTransform { center : vec2(c.center[0], c.center[1]), scale : c.scale };
+
+        let mut previous : Option<(f32, ViewTransform)> = None;
+        for keyframe in self.keyframes.iter() {
+            if let Some(camera) = &keyframe.camera {
+                let pose = to_view(camera);
+                if keyframe.time > t {
+                    return previous.map(|(t0, mut view)| {
+                        if keyframe.time > t0 {
+                            view.lerp_towards(&pose, (t - t0) / (keyframe.time - t0));
+                        }
+                        view
+                    });
+                }
+                previous = Some((keyframe.time, pose));
+            }
+        }
+        previous.map(|(_, view)| view)
+    }
+
+    // Pin offset at time t, interpolated linearly between the keyframes that set it.
+    pub fn pins_at(&self, t : f32) -> Option<Vec3>
+    {
+        let mut previous : Option<(f32, Vec3)> = None;
+        for keyframe in self.keyframes.iter() {
+            if let Some(pins) = keyframe.pins {
+                let offset = vec3(pins[0], pins[1], pins[2]);
+                if keyframe.time > t {
+                    return previous.map(|(t0, o0)| {
+                        if keyframe.time > t0 {o0.lerp(offset, (t - t0) / (keyframe.time - t0))} else {o0}
+                    });
+                }
+                previous = Some((keyframe.time, offset));
+            }
+        }
+        previous.map(|(_, offset)| offset)
+    }
+
+    // Events of keyframes with start <= time < end, in order.
+    pub fn events_in(&self, start : f32, end : f32) -> Vec<TimelineEvent>
+    {
+        self.keyframes.iter()
+            .filter(|k| k.time >= start && k.time < end)
+            .flat_map(|k| k.events.iter().cloned())
+            .collect()
+    }
+}
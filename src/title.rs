@@ -0,0 +1,131 @@
+use crate::params::Params;
+
+// The tab's title, kept to a short summary of the running configuration so several tabs of the
+// demo can be told apart, such as "GS i4 η0.9 WS 40×40 ● rec". Switching it off is remembered
+// across page loads.
+const STORAGE_KEY : &str = "warmstart.title_summary";
+
+// How often the title is checked for changes that don't come through the parameters, such as a
+// recording starting.
+pub const CHECK_INTERVAL_MS : f64 = 1000.0;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Activity
+{
+    Recording,
+    Experiment,
+}
+
+// The first letter of each word of a solver's name, so "Gauss-Seidel" is "GS".
+fn initials(name : &str) -> String
+{
+    name.split(|c : char| c == '-' || c.is_whitespace()).filter_map(|word| word.chars().next()).collect()
+}
+
+pub fn summary(params : &Params, solver_name : &str, activity : Option<Activity>) -> String
+{
+    let mut title = format!("{} i{} η{} {} {}×{}",
+        initials(solver_name), params.num_iterations, params.eta, if params.warm_start {"WS"} else {"cold"},
+        params.num_particles_x, params.num_particles_y);
+    match activity {
+        Some(Activity::Recording) => title.push_str(" ● rec"),
+        Some(Activity::Experiment) => title.push_str(" ● exp"),
+        None => {}
+    }
+    title
+}
+
+fn document() -> Option<web_sys::Document>
+{
+    web_sys::window()?.document()
+}
+
+// The title the page was loaded with, to put back when the summary is switched off.
+pub fn current() -> String
+{
+    document().map(|d| d.title()).unwrap_or_default()
+}
+
+pub fn set(title : &str)
+{
+    if let Some(d) = document() {
+        d.set_title(title);
+    }
+}
+
+fn storage() -> Option<web_sys::Storage>
+{
+    web_sys::window()?.local_storage().ok()?
+}
+
+pub fn saved_enabled() -> bool
+{
+    storage().and_then(|s| s.get_item(STORAGE_KEY).ok().flatten()).map_or(true, |value| value != "off")
+}
+
+pub fn save_enabled(enabled : bool)
+{
+    if let Some(s) = storage() {
+        let _ = s.set_item(STORAGE_KEY, if enabled {"on"} else {"off"});
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(num_iterations : i32, eta : f32, warm_start : bool, size : (i32, i32)) -> Params
+    {
+        let mut params = Params::default();
+        params.num_iterations = num_iterations;
+        params.eta = eta;
+        params.warm_start = warm_start;
+        params.num_particles_x = size.0;
+        params.num_particles_y = size.1;
+        params
+    }
+
+    #[test]
+    fn summary_encodes_the_configuration()
+    {
+        assert_eq!(summary(&params(4, 0.9, true, (40, 40)), "Gauss-Seidel", None), "GS i4 η0.9 WS 40×40");
+        assert_eq!(summary(&params(12, 1.0, false, (10, 24)), "Jacobi", None), "J i12 η1 cold 10×24");
+    }
+
+    #[test]
+    fn summary_marks_what_is_running()
+    {
+        let p = params(4, 0.9, true, (40, 40));
+        assert_eq!(summary(&p, "Gauss-Seidel", Some(Activity::Recording)), "GS i4 η0.9 WS 40×40 ● rec");
+        assert_eq!(summary(&p, "Gauss-Seidel", Some(Activity::Experiment)), "GS i4 η0.9 WS 40×40 ● exp");
+    }
+
+    #[test]
+    fn summary_names_every_registered_solver()
+    {
+        let p = Params::default();
+        let names : Vec<String> = crate::solver::registry().iter().map(|s| summary(&p, s.name(), None)).collect();
+        assert!(names.iter().all(|n| !n.starts_with(' ')), "{:?}", names);
+        assert_ne!(names[0].split(' ').next(), names[1].split(' ').next());
+    }
+
+    #[test]
+    fn initials_split_on_hyphens_and_spaces()
+    {
+        assert_eq!(initials("Gauss-Seidel"), "GS");
+        assert_eq!(initials("red black  Gauss-Seidel"), "rbGS");
+        assert_eq!(initials(""), "");
+    }
+
+    #[test]
+    fn summary_changes_only_with_the_fields_it_shows()
+    {
+        let p = params(4, 0.9, true, (40, 40));
+        let mut other = p.clone();
+        other.stiffness *= 2.0;
+        other.nu = 0.5;
+        assert_eq!(summary(&p, "Jacobi", None), summary(&other, "Jacobi", None));
+        other.eta = 0.8;
+        assert_ne!(summary(&p, "Jacobi", None), summary(&other, "Jacobi", None));
+    }
+}
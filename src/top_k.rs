@@ -0,0 +1,58 @@
+// The k items with the largest keys, largest first. Only a buffer of k is kept sorted, so picking
+// a handful out of many never sorts or copies the whole input. Ties keep their input order.
+pub fn top_k<T>(items : impl Iterator<Item = T>, k : usize, key : impl Fn(&T) -> f32) -> Vec<T>
+{
+    let mut best : Vec<(f32, T)> = Vec::with_capacity(k + 1);
+    if k == 0 {
+        return vec![];
+    }
+    for item in items {
+        let value = key(&item);
+        if best.len() == k && !(value > best[k - 1].0) {
+            continue;
+        }
+        let at = best.iter().position(|(v, _)| value > *v).unwrap_or(best.len());
+        best.insert(at, (value, item));
+        best.truncate(k);
+    }
+    best.into_iter().map(|(_, item)| item).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_the_largest_in_order()
+    {
+        let values = [3.0, 9.0, 1.0, 7.0, 5.0, 8.0];
+        assert_eq!(top_k(values.iter().copied(), 3, |&v| v), vec![9.0, 8.0, 7.0]);
+    }
+
+    #[test]
+    fn matches_a_full_sort()
+    {
+        let values : Vec<f32> = (0..200).map(|i| ((i * 37) % 101) as f32 - 50.0).collect();
+        let mut sorted = values.clone();
+        sorted.sort_by(|a, b| b.partial_cmp(a).unwrap());
+        for &k in [1, 10, 50].iter() {
+            assert_eq!(top_k(values.iter().copied(), k, |&v| v), sorted[..k].to_vec());
+        }
+    }
+
+    #[test]
+    fn ties_keep_their_input_order()
+    {
+        let items = [(0, 2.0), (1, 5.0), (2, 2.0), (3, 5.0), (4, 2.0)];
+        let picked : Vec<usize> = top_k(items.iter(), 4, |item| item.1).iter().map(|item| item.0).collect();
+        assert_eq!(picked, vec![1, 3, 0, 2]);
+    }
+
+    #[test]
+    fn short_inputs_and_zero_k()
+    {
+        assert_eq!(top_k([2.0, 4.0].iter().copied(), 10, |&v| v), vec![4.0, 2.0]);
+        assert!(top_k([2.0, 4.0].iter().copied(), 0, |&v| v).is_empty());
+        assert!(top_k(std::iter::empty::<f32>(), 3, |&v| v).is_empty());
+    }
+}
@@ -0,0 +1,151 @@
+// Structure of the constraint graph, rebuilt whenever constraints are added or removed. Anything
+// that needs per-particle constraint counts should read them from here rather than recounting.
+pub struct Topology
+{
+    // Number of active constraints touching each particle.
+    pub valence : Vec<u32>,
+    // Number of horizontal and vertical neighbours, four everywhere but the boundary.
+    structural_valence : Vec<u32>,
+    // The boundary of a cloth with no grid, such as an imported mesh, which structural
+    // neighbours can't tell.
+    boundary : Option<Vec<bool>>,
+}
+
+impl Topology {
+    pub fn empty() -> Topology
+    {
+        Topology { valence : vec![], structural_valence : vec![], boundary : None }
+    }
+
+    // No constraints yet; add each with add_constraint.
+    pub fn new(num_particles : usize) -> Topology
+    {
+        Topology { valence : vec![0u32; num_particles], structural_valence : vec![0u32; num_particles], boundary : None }
+    }
+
+    pub fn add_constraint(&mut self, particles : &[usize])
+    {
+        for &p in particles {
+            self.valence[p] += 1;
+        }
+    }
+
+    // A horizontal or vertical grid edge, which is also added with add_constraint.
+    pub fn add_structural_edge(&mut self, p0 : usize, p1 : usize)
+    {
+        self.structural_valence[p0] += 1;
+        self.structural_valence[p1] += 1;
+    }
+
+    pub fn set_boundary(&mut self, boundary : Vec<bool>)
+    {
+        self.boundary = Some(boundary);
+    }
+
+    // Whether a particle lacks one of its four structural neighbours. That holds along the grid's
+    // edges and along any edge opened up inside it by removing constraints.
+    pub fn is_boundary(&self, p : usize) -> bool
+    {
+        match &self.boundary {
+            Some(boundary) => boundary[p],
+            None => self.structural_valence[p] < 4,
+        }
+    }
+
+    pub fn min_valence(&self) -> u32
+    {
+        self.valence.iter().cloned().min().unwrap_or(0)
+    }
+
+    pub fn max_valence(&self) -> u32
+    {
+        self.valence.iter().cloned().max().unwrap_or(0)
+    }
+
+    pub fn mean_valence(&self) -> f32
+    {
+        self.valence.iter().sum::<u32>() as f32 / self.valence.len().max(1) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cloth::{build_cloth, Connectivity, EdgeKind, Scene};
+
+    // Built the way the model builds it, from the distance constraints of a fresh grid.
+    fn grid_topology(connectivity : Connectivity, nx : i32, ny : i32) -> Topology
+    {
+        let cloth = build_cloth(&Scene::Hanging, connectivity, false, nx, ny);
+        let mut topology = Topology::new(cloth.positions.len());
+        for c in cloth.constraints.iter() {
+            topology.add_constraint(&[c.p0, c.p1]);
+            match cloth.sheet_grids[0].edge_kind(c.p0, c.p1) {
+                Some(EdgeKind::Horizontal) | Some(EdgeKind::Vertical) => topology.add_structural_edge(c.p0, c.p1),
+                _ => {}
+            }
+        }
+        topology
+    }
+
+    #[test]
+    fn eight_connected_grid_valences()
+    {
+        let (nx, ny) = (5, 4);
+        let topology = grid_topology(Connectivity::Eight, nx, ny);
+        for i in 0..nx {
+            for j in 0..ny {
+                let on_x_edge = i == 0 || i == nx - 1;
+                let on_y_edge = j == 0 || j == ny - 1;
+                let expected = match (on_x_edge, on_y_edge) {
+                    (true, true) => 3,
+                    (true, false) | (false, true) => 5,
+                    (false, false) => 8,
+                };
+                assert_eq!(topology.valence[(i * ny + j) as usize], expected, "particle ({}, {})", i, j);
+            }
+        }
+        assert_eq!(topology.min_valence(), 3);
+        assert_eq!(topology.max_valence(), 8);
+    }
+
+    #[test]
+    fn four_connected_grid_valences()
+    {
+        let topology = grid_topology(Connectivity::Four, 3, 3);
+        assert_eq!(topology.valence, vec![2, 3, 2, 3, 4, 3, 2, 3, 2]);
+        // Each constraint adds one to both of its ends.
+        assert!((topology.mean_valence() - 24.0 / 9.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn boundary_follows_the_structural_neighbours()
+    {
+        let (nx, ny) = (4, 4);
+        let topology = grid_topology(Connectivity::Eight, nx, ny);
+        for i in 0..nx {
+            for j in 0..ny {
+                let expected = i == 0 || j == 0 || i == nx - 1 || j == ny - 1;
+                assert_eq!(topology.is_boundary((i * ny + j) as usize), expected, "particle ({}, {})", i, j);
+            }
+        }
+    }
+
+    #[test]
+    fn a_set_boundary_overrides_the_grid()
+    {
+        let mut topology = grid_topology(Connectivity::Four, 3, 3);
+        topology.set_boundary(vec![false, false, false, false, true, false, false, false, false]);
+        assert!(topology.is_boundary(4));
+        assert!(!topology.is_boundary(0));
+    }
+
+    #[test]
+    fn empty_topology_reads_as_zero()
+    {
+        let topology = Topology::empty();
+        assert_eq!(topology.min_valence(), 0);
+        assert_eq!(topology.max_valence(), 0);
+        assert_eq!(topology.mean_valence(), 0.0);
+    }
+}
@@ -0,0 +1,82 @@
+use crate::cloth::Scene;
+use crate::params::{Params, ParamsDelta};
+
+// One step of the guided tour. Say shows its text with the control it is about outlined, and moves
+// on after its time is up or when Next is clicked; the other steps take effect at once.
+pub enum TutorialStep
+{
+    Say { text : &'static str, highlight : Option<&'static str>, seconds : f32 },
+    Change(fn() -> ParamsDelta),
+    Reset,
+}
+
+pub static TOUR : [TutorialStep; 11] = [
+    TutorialStep::Change(|| ParamsDelta {
+        scene : Some(Scene::Hanging),
+        num_iterations : Some(2),
+        warm_start : Some(false),
+        eta : Some(1.0),
+        ..ParamsDelta::default()
+    }),
+    TutorialStep::Reset,
+    TutorialStep::Say {
+        text : "This cloth gets two solver iterations a step, which isn't nearly enough to hold it together. Watch it stretch and jitter as it hangs.",
+        highlight : Some("iterations"),
+        seconds : 6.0,
+    },
+    TutorialStep::Say {
+        text : "Every step starts its constraints from nothing, so the little the iterations achieve is thrown away at the end of it.",
+        highlight : Some("warm_start"),
+        seconds : 6.0,
+    },
+    TutorialStep::Change(|| ParamsDelta { warm_start : Some(true), ..ParamsDelta::default() }),
+    TutorialStep::Say {
+        text : "Warm start is on. Each step now begins from the impulses the last one ended with, so the same two iterations pull the cloth taut.",
+        highlight : Some("warm_start"),
+        seconds : 8.0,
+    },
+    TutorialStep::Change(|| ParamsDelta { eta : Some(0.5), ..ParamsDelta::default() }),
+    TutorialStep::Say {
+        text : "η is how much of the stored impulse is carried over. At 0.5 only half is, and the cloth sags back part of the way.",
+        highlight : Some("eta"),
+        seconds : 6.0,
+    },
+    TutorialStep::Change(|| ParamsDelta { eta : Some(1.0), ..ParamsDelta::default() }),
+    TutorialStep::Say {
+        text : "Back at 1, the full impulse is reused and the cloth firms up again.",
+        highlight : Some("eta"),
+        seconds : 5.0,
+    },
+    TutorialStep::Say {
+        text : "That's the tour. Your own settings come back when it closes.",
+        highlight : None,
+        seconds : 4.0,
+    },
+];
+
+// Where the tour is, and the settings to put back when it ends or is abandoned.
+pub struct Tutorial
+{
+    pub step : usize,
+    // Simulated seconds spent on the current Say step.
+    pub elapsed : f32,
+    pub saved : Params,
+}
+
+impl Tutorial {
+    pub fn new(saved : Params) -> Tutorial
+    {
+        Tutorial { step : 0, elapsed : 0.0, saved : saved }
+    }
+
+    pub fn current(&self) -> Option<&'static TutorialStep>
+    {
+        TOUR.get(self.step)
+    }
+
+    pub fn advance(&mut self)
+    {
+        self.step += 1;
+        self.elapsed = 0.0;
+    }
+}
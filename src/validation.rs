@@ -0,0 +1,197 @@
+use crate::cloth::Connectivity;
+use crate::params::{Params, ParamsDelta};
+use crate::stability::{Margin, StiffnessResolution};
+
+// Combinations of settings known to blow the cloth up, checked every time parameters are applied
+// wherever they come from. A warning is only shown; a blocked combination is moved to the
+// nearest setting that isn't.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Severity
+{
+    Warn,
+    Block,
+}
+
+// What the rules look at. The Jacobi relaxation lives on the solver rather than in Params, and is
+// None while another solver is selected.
+pub struct Settings<'a>
+{
+    pub params : &'a Params,
+    pub jacobi_relaxation : Option<f32>,
+}
+
+// The nearest valid setting for a blocked combination.
+pub enum Fix
+{
+    Params(ParamsDelta),
+    JacobiRelaxation(f32),
+}
+
+pub struct Rule
+{
+    pub name : &'static str,
+    pub severity : Severity,
+    pub message : &'static str,
+    violated : fn(&Settings) -> bool,
+    // Only blocking rules have one.
+    fix : Option<fn(&Settings) -> Fix>,
+}
+
+impl Rule {
+    pub fn is_violated(&self, settings : &Settings) -> bool
+    {
+        (self.violated)(settings)
+    }
+
+    pub fn fix(&self, settings : &Settings) -> Option<Fix>
+    {
+        self.fix.map(|fix| fix(settings))
+    }
+}
+
+// Above this time step stiffness from STIFFNESS_LIMIT up can't be held.
+const MAX_STIFF_DT : f32 = 1.0 / 30.0;
+const STIFFNESS_LIMIT : f32 = 1e7;
+
+pub static RULES : [Rule; 4] = [
+    Rule {
+        name : "undamped_full_warm_start",
+        severity : Severity::Warn,
+        message : "A full warm start (η = 1) with no damping (𝜈 = 1) and one iteration carries every step's error into the next, so the cloth rings up. Lower η or 𝜈, or add iterations.",
+        violated : |s| s.params.warm_start && s.params.eta >= 1.0 && s.params.nu >= 1.0 && s.params.num_iterations <= 1,
+        fix : None,
+    },
+    Rule {
+        name : "jacobi_overrelaxed",
+        severity : Severity::Block,
+        message : "Jacobi relaxation of 1 or more overshoots on an 8-connected grid, so it was set to 0.99.",
+        violated : |s| s.params.connectivity == Connectivity::Eight && s.jacobi_relaxation.map_or(false, |r| r >= 1.0),
+        fix : Some(|_| Fix::JacobiRelaxation(0.99)),
+    },
+    Rule {
+        name : "stiff_long_step",
+        severity : Severity::Block,
+        message : "Time steps over 1/30 s can't hold a stiffness of 1e7 or more, so the time step was set to 1/30 s.",
+        violated : |s| s.params.dt > MAX_STIFF_DT && s.params.stiffness >= STIFFNESS_LIMIT,
+        fix : Some(|_| Fix::Params(ParamsDelta { dt : Some(MAX_STIFF_DT), ..ParamsDelta::default() })),
+    },
+    Rule {
+        name : "unresolved_stiffness",
+        severity : Severity::Warn,
+        message : "The constraints vibrate faster than the time step can follow, so the cloth behaves as if it were stiffer than set and stiffness changes have little effect. See the readout under the stiffness slider for what would fix it.",
+        violated : |s| StiffnessResolution::of(s.params).margin() == Margin::Unresolved,
+        fix : None,
+    },
+];
+
+pub fn violations<'a>(settings : &'a Settings) -> impl Iterator<Item = &'static Rule> + 'a
+{
+    RULES.iter().filter(move |rule| rule.is_violated(settings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stability::{stiffness_at_ratio, UNRESOLVED_RATIO};
+
+    fn violated(params : &Params, jacobi_relaxation : Option<f32>) -> Vec<&'static str>
+    {
+        violations(&Settings { params : params, jacobi_relaxation : jacobi_relaxation }).map(|rule| rule.name).collect()
+    }
+
+    fn rule(name : &str) -> &'static Rule
+    {
+        RULES.iter().find(|rule| rule.name == name).unwrap()
+    }
+
+    // A resolvable stiffness, so only the rule under test can fire.
+    fn resolved() -> Params
+    {
+        let mut params = Params::default();
+        params.stiffness = stiffness_at_ratio(params.dt, 0.25);
+        params
+    }
+
+    #[test]
+    fn the_starting_settings_break_no_rule()
+    {
+        assert!(violated(&Params::default(), Some(0.6)).is_empty());
+        assert!(violated(&Params::default(), None).is_empty());
+    }
+
+    #[test]
+    fn undamped_full_warm_start_warns()
+    {
+        let mut params = resolved();
+        params.warm_start = true;
+        params.eta = 1.0;
+        params.nu = 1.0;
+        params.num_iterations = 1;
+        assert_eq!(violated(&params, None), vec!["undamped_full_warm_start"]);
+        assert_eq!(rule("undamped_full_warm_start").severity, Severity::Warn);
+
+        for change in [|p : &mut Params| p.eta = 0.9, |p : &mut Params| p.nu = 0.9, |p : &mut Params| p.num_iterations = 2, |p : &mut Params| p.warm_start = false].iter() {
+            let mut fixed = params.clone();
+            change(&mut fixed);
+            assert!(violated(&fixed, None).is_empty());
+        }
+    }
+
+    #[test]
+    fn jacobi_overrelaxed_blocks_and_fixes_the_relaxation()
+    {
+        let mut params = resolved();
+        params.connectivity = Connectivity::Eight;
+        assert_eq!(violated(&params, Some(1.0)), vec!["jacobi_overrelaxed"]);
+        assert!(violated(&params, Some(0.99)).is_empty());
+        // Only the Jacobi solver has a relaxation to overdo.
+        assert!(violated(&params, None).is_empty());
+        params.connectivity = Connectivity::Six;
+        assert!(violated(&params, Some(1.2)).is_empty());
+
+        let rule = rule("jacobi_overrelaxed");
+        assert_eq!(rule.severity, Severity::Block);
+        match rule.fix(&Settings { params : &params, jacobi_relaxation : Some(1.2) }) {
+            Some(Fix::JacobiRelaxation(r)) => assert!(r < 1.0),
+            _ => panic!("no relaxation fix"),
+        }
+    }
+
+    #[test]
+    fn stiff_long_step_blocks_and_fixes_the_time_step()
+    {
+        let mut params = Params::default();
+        params.stiffness = 1e7;
+        params.dt = 1.0 / 20.0;
+        let names = violated(&params, None);
+        assert!(names.contains(&"stiff_long_step"), "{:?}", names);
+
+        let rule = rule("stiff_long_step");
+        assert_eq!(rule.severity, Severity::Block);
+        match rule.fix(&Settings { params : &params, jacobi_relaxation : None }) {
+            Some(Fix::Params(delta)) => { params.apply(&delta); }
+            _ => panic!("no time step fix"),
+        }
+        assert!(!violated(&params, None).contains(&"stiff_long_step"));
+    }
+
+    #[test]
+    fn unresolved_stiffness_warns()
+    {
+        let mut params = Params::default();
+        params.stiffness = stiffness_at_ratio(params.dt, 1.5 * UNRESOLVED_RATIO);
+        assert_eq!(violated(&params, None), vec!["unresolved_stiffness"]);
+        assert_eq!(rule("unresolved_stiffness").severity, Severity::Warn);
+        assert!(rule("unresolved_stiffness").fix(&Settings { params : &params, jacobi_relaxation : None }).is_none());
+    }
+
+    #[test]
+    fn every_blocking_rule_has_a_fix_and_no_warning_does()
+    {
+        let params = Params::default();
+        let settings = Settings { params : &params, jacobi_relaxation : None };
+        for rule in RULES.iter() {
+            assert_eq!(rule.fix(&settings).is_some(), rule.severity == Severity::Block, "{}", rule.name);
+        }
+    }
+}
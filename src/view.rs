@@ -0,0 +1,50 @@
+use glam::*;
+
+// Maps simulation xy onto the canvas: positions are recentred on center and scaled before the
+// aspect ratio correction in basic.vert. The identity transform is the original fixed framing.
+#[derive(Clone, Copy)]
+pub struct ViewTransform
+{
+    pub center : Vec2,
+    pub scale : f32,
+}
+
+impl ViewTransform {
+    // Fraction of the visible half-height left empty around the fitted bounds.
+    const MARGIN : f32 = 0.1;
+
+    pub fn identity() -> ViewTransform
+    {
+        ViewTransform { center : vec2(0.0, 0.0), scale : 1.0 }
+    }
+
+    // The transform that fits the box [min, max] plus a margin into a canvas with the given
+    // aspect ratio, whose visible area is [-aspect, aspect] x [-1, 1] before scaling.
+    pub fn fit(min : Vec2, max : Vec2, aspect_ratio : f32) -> ViewTransform
+    {
+        let half_extent = ((max - min) * 0.5).max(vec2(1e-3, 1e-3));
+        let scale = (aspect_ratio / half_extent.x).min(1.0 / half_extent.y) * (1.0 - Self::MARGIN);
+        ViewTransform { center : (min + max) * 0.5, scale : scale }
+    }
+
+    // Moves a fraction t of the way towards target. Scale is blended in log space so zooming in
+    // and out ease at the same rate.
+    pub fn lerp_towards(&mut self, target : &ViewTransform, t : f32)
+    {
+        self.center = self.center + (target.center - self.center) * t;
+        self.scale = (self.scale.ln() + (target.scale.ln() - self.scale.ln()) * t).exp();
+    }
+
+    // Where a simulation position lands in aspect-corrected canvas coordinates.
+    pub fn to_view(&self, sim_position : Vec2) -> Vec2
+    {
+        (sim_position - self.center) * self.scale
+    }
+
+    // Inverse of the transform, for taking aspect-corrected canvas coordinates back to the
+    // simulation.
+    pub fn to_sim(&self, view_position : Vec2) -> Vec2
+    {
+        view_position / self.scale + self.center
+    }
+}
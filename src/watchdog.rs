@@ -0,0 +1,211 @@
+use crate::params::{Effect, Params, ParamsDelta};
+
+// Catches physics steps that run far past the frame budget, which can otherwise freeze the tab.
+// A step over the ceiling ends the frame's steps there, and the most expensive optional feature is
+// turned down for the frames after, one a trip, in this order:
+//
+// 1. Contacts between sheets are switched off, as the spatial hash and contact solve grow fastest.
+// 2. The steps a slow display may take a frame are halved, down to one.
+// 3. The iterations are halved, down to one.
+//
+// Each reduction is remembered so it can be put back from the banner, and everything is put back
+// when the grid is made smaller. A field the user sets themselves is theirs again.
+
+pub const DEFAULT_CEILING_MS : f64 = 100.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Reduction
+{
+    SelfCollision,
+    Substeps { from : u32, to : u32 },
+    Iterations { from : i32, to : i32 },
+}
+
+impl Reduction {
+    pub fn describe(&self) -> String
+    {
+        match self {
+            Reduction::SelfCollision => "sheet contacts off".to_string(),
+            Reduction::Substeps { from, to } => format!("steps per frame {} to {}", from, to),
+            Reduction::Iterations { from, to } => format!("iterations {} to {}", from, to),
+        }
+    }
+
+    pub fn delta(&self) -> ParamsDelta
+    {
+        match *self {
+            Reduction::SelfCollision => ParamsDelta { self_collision : Some(false), ..ParamsDelta::default() },
+            Reduction::Substeps { to, .. } => ParamsDelta { max_substeps : Some(to), ..ParamsDelta::default() },
+            Reduction::Iterations { to, .. } => ParamsDelta { num_iterations : Some(to), ..ParamsDelta::default() },
+        }
+    }
+
+    // The parameter it changes, as Params::apply names it.
+    fn field(&self) -> &'static str
+    {
+        match self {
+            Reduction::SelfCollision => "self_collision",
+            Reduction::Substeps { .. } => "max_substeps",
+            Reduction::Iterations { .. } => "num_iterations",
+        }
+    }
+}
+
+// What to turn down after a step that ran too long, or None when everything is already down as
+// far as it goes.
+pub fn next_reduction(params : &Params, has_contacts : bool) -> Option<Reduction>
+{
+    if has_contacts && params.self_collision {
+        Some(Reduction::SelfCollision)
+    } else if params.max_substeps > 1 {
+        Some(Reduction::Substeps { from : params.max_substeps, to : params.max_substeps / 2 })
+    } else if params.num_iterations > 1 {
+        Some(Reduction::Iterations { from : params.num_iterations, to : params.num_iterations / 2 })
+    } else {
+        None
+    }
+}
+
+pub struct Watchdog
+{
+    // 0 switches the watchdog off.
+    pub ceiling_ms : f64,
+    // In the order they were made.
+    pub reductions : Vec<Reduction>,
+    // The slowest step that tripped it since the last restore.
+    pub worst_step_ms : f64,
+}
+
+impl Watchdog {
+    pub fn new() -> Watchdog
+    {
+        Watchdog { ceiling_ms : DEFAULT_CEILING_MS, reductions : vec![], worst_step_ms : 0.0 }
+    }
+
+    pub fn is_tripped_by(&self, step_ms : f64) -> bool
+    {
+        self.ceiling_ms > 0.0 && step_ms > self.ceiling_ms
+    }
+
+    pub fn record(&mut self, reduction : Reduction, step_ms : f64)
+    {
+        self.reductions.push(reduction);
+        self.worst_step_ms = self.worst_step_ms.max(step_ms);
+    }
+
+    // Puts back every reduction and forgets them. A field reduced twice goes back to what it was
+    // before the first.
+    pub fn restore(&mut self) -> ParamsDelta
+    {
+        let mut delta = ParamsDelta::default();
+        for reduction in self.reductions.drain(..).rev() {
+            match reduction {
+                Reduction::SelfCollision => delta.self_collision = Some(true),
+                Reduction::Substeps { from, .. } => delta.max_substeps = Some(from),
+                Reduction::Iterations { from, .. } => delta.num_iterations = Some(from),
+            }
+        }
+        self.worst_step_ms = 0.0;
+        delta
+    }
+
+    // Forgets the reductions to parameters that have just been changed by something else.
+    pub fn forget_changed(&mut self, changes : &[(&'static str, Effect)])
+    {
+        self.reductions.retain(|r| !changes.iter().any(|(name, _)| *name == r.field()));
+        if self.reductions.is_empty() {
+            self.worst_step_ms = 0.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Trips the watchdog until it runs out, applying each reduction as the app does.
+    fn trip_until_spent(params : &mut Params, watchdog : &mut Watchdog, has_contacts : bool) -> Vec<Reduction>
+    {
+        let mut made = vec![];
+        while let Some(reduction) = next_reduction(params, has_contacts) {
+            params.apply(&reduction.delta());
+            watchdog.record(reduction, 150.0);
+            made.push(reduction);
+        }
+        made
+    }
+
+    fn params(self_collision : bool, max_substeps : u32, num_iterations : i32) -> Params
+    {
+        Params { self_collision : self_collision, max_substeps : max_substeps, num_iterations : num_iterations, ..Params::default() }
+    }
+
+    #[test]
+    fn contacts_go_first_then_substeps_then_iterations()
+    {
+        let mut params = params(true, 4, 8);
+        let made = trip_until_spent(&mut params, &mut Watchdog::new(), true);
+        assert_eq!(made, vec![
+            Reduction::SelfCollision,
+            Reduction::Substeps { from : 4, to : 2 },
+            Reduction::Substeps { from : 2, to : 1 },
+            Reduction::Iterations { from : 8, to : 4 },
+            Reduction::Iterations { from : 4, to : 2 },
+            Reduction::Iterations { from : 2, to : 1 },
+        ]);
+        assert_eq!(next_reduction(&params, true), None);
+    }
+
+    #[test]
+    fn contacts_are_skipped_when_no_sheet_has_any()
+    {
+        assert_eq!(next_reduction(&params(true, 2, 2), false), Some(Reduction::Substeps { from : 2, to : 1 }));
+        assert_eq!(next_reduction(&params(false, 1, 2), true), Some(Reduction::Iterations { from : 2, to : 1 }));
+        assert_eq!(next_reduction(&params(false, 1, 1), true), None);
+    }
+
+    #[test]
+    fn restore_puts_a_field_reduced_twice_back_to_its_first_value()
+    {
+        let before = params(true, 4, 8);
+        let mut params = before.clone();
+        let mut watchdog = Watchdog::new();
+        trip_until_spent(&mut params, &mut watchdog, true);
+
+        params.apply(&watchdog.restore());
+        assert_eq!((params.self_collision, params.max_substeps, params.num_iterations), (true, 4, 8));
+        assert!(watchdog.reductions.is_empty());
+        assert_eq!(watchdog.worst_step_ms, 0.0);
+    }
+
+    #[test]
+    fn forget_changed_drops_only_the_reductions_to_the_changed_field()
+    {
+        let mut params = params(true, 2, 4);
+        let mut watchdog = Watchdog::new();
+        trip_until_spent(&mut params, &mut watchdog, true);
+
+        let changes = params.apply(&ParamsDelta { num_iterations : Some(6), ..ParamsDelta::default() });
+        watchdog.forget_changed(&changes);
+        assert_eq!(watchdog.reductions, vec![Reduction::SelfCollision, Reduction::Substeps { from : 2, to : 1 }]);
+        assert_eq!(watchdog.worst_step_ms, 150.0);
+
+        // The user's iterations stay as they set them when the rest is put back.
+        params.apply(&watchdog.restore());
+        assert_eq!((params.self_collision, params.max_substeps, params.num_iterations), (true, 2, 6));
+    }
+
+    #[test]
+    fn forgetting_the_last_reduction_clears_the_worst_step()
+    {
+        let mut params = params(false, 1, 2);
+        let mut watchdog = Watchdog::new();
+        trip_until_spent(&mut params, &mut watchdog, false);
+
+        watchdog.forget_changed(&[("nu", Effect::Nothing)]);
+        assert_eq!(watchdog.reductions.len(), 1);
+        watchdog.forget_changed(&[("num_iterations", Effect::Nothing)]);
+        assert!(watchdog.reductions.is_empty());
+        assert_eq!(watchdog.worst_step_ms, 0.0);
+    }
+}
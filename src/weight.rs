@@ -0,0 +1,70 @@
+use glam::*;
+
+// A rigid point mass hanging off the cloth by a single attachment constraint. It is integrated
+// the same way as the cloth particles but with its own mass, so the attachment couples two
+// systems of very different inertia.
+pub struct Weight
+{
+    pub position : Vec3,
+    pub previous_position : Vec3,
+    pub mass : f32,
+    pub attached_particle : usize,
+    pub length : f32,
+    pub lambda : Vec3,
+    // Set while the mouse holds the weight; the weight then follows it kinematically.
+    pub drag_target : Option<Vec3>,
+}
+
+impl Weight {
+    pub const HALF_SIZE : f32 = 0.02;
+
+    pub fn new(attached_particle : usize, particle_position : Vec3, mass : f32) -> Weight
+    {
+        let length = 0.1f32;
+        let position = particle_position - vec3(0.0, length, 0.0);
+        Weight {
+            position : position,
+            previous_position : position,
+            mass : mass,
+            attached_particle : attached_particle,
+            length : length,
+            lambda : vec3(0.0, 0.0, 0.0),
+            drag_target : None,
+        }
+    }
+
+    pub fn inverse_mass(&self) -> f32
+    {
+        if self.drag_target.is_some() {0.0} else {1.0 / self.mass}
+    }
+
+    pub fn integrate(&mut self, gravity : Vec3, nu : f32, dt : f32)
+    {
+        let p0 = self.position;
+        match self.drag_target {
+            Some(target) => self.position = target,
+            None => {
+                let d = (self.position - self.previous_position) * nu + gravity * dt;
+                self.position += d;
+            }
+        }
+        self.previous_position = p0;
+    }
+
+    // Outline of the square plus the attachment line, as x, y pairs for GL::LINES.
+    pub fn line_vertices(&self, particle_position : Vec3) -> Vec<f32>
+    {
+        let h = Self::HALF_SIZE;
+        let p = self.position;
+        let corners = [vec2(p.x - h, p.y - h), vec2(p.x + h, p.y - h), vec2(p.x + h, p.y + h), vec2(p.x - h, p.y + h)];
+
+        let mut vertices = vec![];
+        for k in 0..4 {
+            let a = corners[k];
+            let b = corners[(k + 1) % 4];
+            vertices.extend_from_slice(&[a.x, a.y, b.x, b.y]);
+        }
+        vertices.extend_from_slice(&[p.x, p.y + h, particle_position.x, particle_position.y]);
+        vertices
+    }
+}
@@ -0,0 +1,97 @@
+use glam::*;
+use crate::cloth::{Constraint, EdgeKind, SheetGrid};
+
+// How far the quads of the cloth are stretched or squashed in their own plane, from the singular
+// values of each quad's deformation gradient. Residuals say how well the solver converged; this
+// says how the drape looks.
+#[derive(Clone, Copy)]
+pub struct WrinkleSample
+{
+    // Mean over quads of the squared distance of both singular values from 1.
+    pub energy : f32,
+    pub min_stretch : f32,
+    pub max_stretch : f32,
+}
+
+// The singular values of the gradient mapping a rest quad onto the quad at (i, j), smallest first.
+// Rest quads are rectangles whose sides are the rest lengths of the two edges leaving the (i, j)
+// corner. None if either edge is missing.
+fn stretches(grid : &SheetGrid, i : i32, j : i32, positions : &[Vec3], constraints : &[Constraint]) -> Option<(f32, f32)>
+{
+    let horizontal = &constraints[grid.constraint(EdgeKind::Horizontal, i, j)?];
+    let vertical = &constraints[grid.constraint(EdgeKind::Vertical, i, j)?];
+    let corner = positions[grid.particle(i, j)];
+    let fu = (positions[grid.particle(i + 1, j)] - corner) / horizontal.length.max(1e-6);
+    let fv = (positions[grid.particle(i, j + 1)] - corner) / vertical.length.max(1e-6);
+
+    // Eigenvalues of F^T F are the squared singular values.
+    let (a, b, c) = (fu.length_squared(), fu.dot(fv), fv.length_squared());
+    let mean = 0.5 * (a + c);
+    let spread = (0.25 * (a - c) * (a - c) + b * b).sqrt();
+    Some(((mean - spread).max(0.0).sqrt(), (mean + spread).sqrt()))
+}
+
+pub fn measure(positions : &[Vec3], constraints : &[Constraint], grids : &[SheetGrid]) -> Option<WrinkleSample>
+{
+    let mut energy = 0.0;
+    let mut min_stretch = f32::MAX;
+    let mut max_stretch = 0.0f32;
+    let mut num_quads = 0;
+    for grid in grids.iter() {
+        for i in 0..grid.num_particles_x - 1 {
+            for j in 0..grid.num_particles_y - 1 {
+                if let Some((low, high)) = stretches(grid, i, j, positions, constraints) {
+                    energy += (low - 1.0) * (low - 1.0) + (high - 1.0) * (high - 1.0);
+                    min_stretch = min_stretch.min(low);
+                    max_stretch = max_stretch.max(high);
+                    num_quads += 1;
+                }
+            }
+        }
+    }
+    if num_quads == 0 {
+        return None;
+    }
+    Some(WrinkleSample { energy : energy / num_quads as f32, min_stretch : min_stretch, max_stretch : max_stretch })
+}
+
+// Samples taken every step while logging, for export.
+pub struct WrinkleLog
+{
+    pub enabled : bool,
+    samples : Vec<(i32, WrinkleSample)>,
+}
+
+impl WrinkleLog {
+    pub fn new() -> WrinkleLog
+    {
+        WrinkleLog { enabled : false, samples : vec![] }
+    }
+
+    pub fn clear(&mut self)
+    {
+        self.samples.clear();
+    }
+
+    pub fn num_samples(&self) -> usize
+    {
+        self.samples.len()
+    }
+
+    pub fn record(&mut self, step : i32, sample : WrinkleSample)
+    {
+        if self.enabled {
+            self.samples.push((step, sample));
+        }
+    }
+
+    pub fn to_csv(&self, settings : &str) -> String
+    {
+        let mut csv = format!("# {}\n", settings);
+        csv.push_str("step,wrinkle_energy,min_stretch,max_stretch\n");
+        for (step, s) in self.samples.iter() {
+            csv.push_str(&format!("{},{},{},{}\n", step, s.energy, s.min_stretch, s.max_stretch));
+        }
+        csv
+    }
+}